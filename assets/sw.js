@@ -0,0 +1,40 @@
+// Keeps the app shell around for offline use.
+//
+// Network first: file names don't change between deploys, so serving from the cache first would
+// keep people on old versions. The cache is only used when the network fails.
+// Downloaded sources are cached by the app itself, in the origin private file system.
+
+const CACHE = "kitdiff-shell-v1";
+
+self.addEventListener("install", () => self.skipWaiting());
+
+self.addEventListener("activate", (event) => {
+    event.waitUntil(
+        caches.keys()
+            .then((keys) => Promise.all(keys.filter((key) => key !== CACHE).map((key) => caches.delete(key))))
+            .then(() => self.clients.claim())
+    );
+});
+
+self.addEventListener("fetch", (event) => {
+    const url = new URL(event.request.url);
+    if (event.request.method !== "GET" || url.origin !== self.location.origin) {
+        return;
+    }
+
+    event.respondWith(
+        fetch(event.request)
+            .then((response) => {
+                if (response.ok) {
+                    const copy = response.clone();
+                    caches.open(CACHE).then((cache) => cache.put(event.request, copy));
+                }
+                return response;
+            })
+            .catch(() =>
+                // Query parameters (e.g. `?url=`) would miss the cached index.html.
+                caches.match(event.request, { ignoreSearch: true })
+                    .then((cached) => cached || Response.error())
+            )
+    );
+});
@@ -0,0 +1,90 @@
+//! Every diff algorithm on a pair of 4K snapshots, on all cores and on one, to keep an eye on the
+//! per-pixel loops. Run with `cargo bench -p kitdiff-core`.
+//!
+//! `dify` is the single-threaded pixelmatch port [`Algorithm::Pixelmatch`] replaced, compare it
+//! with `Pixelmatch/changed`.
+
+use criterion::{BenchmarkId, Criterion, criterion_group, criterion_main};
+use image::{Rgba, RgbaImage};
+use kitdiff_core::diff::{Algorithm, DiffOptions, diff_rgba};
+use std::hint::black_box;
+
+const WIDTH: u32 = 3840;
+const HEIGHT: u32 = 2160;
+
+/// A ui-like 4K image: flat panels with a gradient, and some text-like stripes.
+fn snapshot() -> RgbaImage {
+    RgbaImage::from_fn(WIDTH, HEIGHT, |x, y| {
+        let panel = ((x / 480 + y / 270) % 3) as u8 * 40;
+        let stripe = if y % 24 < 12 && x % 7 < 3 { 80 } else { 0 };
+        Rgba([
+            panel + stripe,
+            panel + (y / 17) as u8 % 64,
+            200 - panel,
+            255,
+        ])
+    })
+}
+
+/// The snapshot with a changed widget and speckles of GPU noise, like a typical failing test.
+fn changed(old: &RgbaImage) -> RgbaImage {
+    let mut new = old.clone();
+    for y in 600..900 {
+        for x in 1200..1800 {
+            new.put_pixel(x, y, Rgba([230, 30, 30, 255]));
+        }
+    }
+    for index in 0..2000_u32 {
+        let x = index.wrapping_mul(7919) % WIDTH;
+        let y = index.wrapping_mul(104_729) % HEIGHT;
+        let Rgba([r, g, b, a]) = *new.get_pixel(x, y);
+        new.put_pixel(x, y, Rgba([r.wrapping_add(9), g, b, a]));
+    }
+    new
+}
+
+fn diff(criterion: &mut Criterion) {
+    let old = snapshot();
+    let new = changed(&old);
+    let single_thread = rayon::ThreadPoolBuilder::new()
+        .num_threads(1)
+        .build()
+        .expect("Failed to build the single threaded pool");
+
+    let mut group = criterion.benchmark_group("diff_4k");
+    group.sample_size(10);
+    for algorithm in Algorithm::all() {
+        let options = DiffOptions {
+            algorithm,
+            ..Default::default()
+        };
+        let name = algorithm.name();
+        for (input, new) in [("changed", &new), ("identical", &old)] {
+            group.bench_function(BenchmarkId::new(&name, format!("{input}/all cores")), |b| {
+                b.iter(|| diff_rgba(old.clone(), new.clone(), black_box(&options)));
+            });
+            group.bench_function(BenchmarkId::new(&name, format!("{input}/one core")), |b| {
+                single_thread.install(|| {
+                    b.iter(|| diff_rgba(old.clone(), new.clone(), black_box(&options)))
+                });
+            });
+        }
+    }
+    group.bench_function(BenchmarkId::new("dify", "changed"), |b| {
+        b.iter(|| {
+            dify::diff::get_results(
+                old.clone(),
+                new.clone(),
+                black_box(DiffOptions::default().threshold),
+                true,
+                None,
+                &None,
+                &None,
+            )
+        });
+    });
+    group.finish();
+}
+
+criterion_group!(benches, diff);
+criterion_main!(benches);
@@ -0,0 +1,424 @@
+//! Snapshots in zip and tar.gz archives, e.g. CI artifacts.
+
+use crate::CancelFlag;
+use crate::snapshot::{FileReference, Snapshot};
+use anyhow::Result;
+use flate2::read::DeflateDecoder;
+use flate2::read::GzDecoder;
+use rayon::prelude::*;
+use std::collections::HashMap;
+use std::io::{Cursor, Read as _};
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+use tar::Archive;
+use zip::{CompressionMethod, ZipArchive};
+
+/// The png files of an archive, by path.
+pub type Files = HashMap<PathBuf, Arc<[u8]>>;
+
+/// Where the png files of a zip archive are, so opening it again doesn't need to extract it, and
+/// files can be extracted as they are needed with [`read_entry`].
+#[derive(Debug, Clone, Default, serde::Serialize, serde::Deserialize)]
+pub struct ArchiveIndex {
+    pub entries: Vec<IndexEntry>,
+}
+
+#[derive(Debug, Clone, serde::Serialize, serde::Deserialize)]
+pub struct IndexEntry {
+    pub path: PathBuf,
+
+    /// Where the compressed data starts in the archive.
+    pub offset: u64,
+    pub compressed_size: u64,
+    pub size: u64,
+
+    /// Deflated, or stored as is.
+    pub deflated: bool,
+
+    /// Hash of the extracted file, to find unchanged snapshots without extracting them.
+    pub hash: u64,
+}
+
+pub fn is_zip(data: &[u8]) -> bool {
+    data.starts_with(b"PK")
+}
+
+pub fn is_tar_gz(data: &[u8]) -> bool {
+    data.starts_with(&[0x1F, 0x8B, 0x08])
+}
+
+/// Extracts the png files of a zip or tar.gz archive and pairs them up into snapshots.
+///
+/// Snapshots whose old and new images are identical are marked [`Snapshot::unchanged`].
+#[tracing::instrument(name = "extract", skip_all, fields(bytes = data.len()))]
+pub fn discover(data: &[u8], cancel: &CancelFlag) -> Result<Vec<Snapshot>> {
+    let files = if is_zip(data) {
+        run_zip_discovery(data, cancel)?
+    } else if is_tar_gz(data) {
+        run_tar_discovery(data, cancel)?
+    } else {
+        anyhow::bail!("Unsupported archive format");
+    };
+
+    tracing::debug!("Extracted {} files", files.len());
+    Ok(snapshots_from_files(&files))
+}
+
+/// Like [`discover`], and for zip archives also where their png files are, see [`ArchiveIndex`].
+/// Tar.gz archives can't be read in pieces, they have no index.
+#[tracing::instrument(name = "extract", skip_all, fields(bytes = data.len()))]
+pub fn discover_indexed(
+    data: &[u8],
+    cancel: &CancelFlag,
+) -> Result<(Vec<Snapshot>, Option<ArchiveIndex>)> {
+    if !is_zip(data) {
+        return Ok((discover(data, cancel)?, None));
+    }
+
+    let mut files = HashMap::new();
+    let mut index = Some(ArchiveIndex::default());
+    for file in extract_zip(data, true, cancel)? {
+        // Other compression methods can't be extracted by `read_entry`.
+        match (file.entry, &mut index) {
+            (Some(entry), Some(index)) => index.entries.push(entry),
+            _ => index = None,
+        }
+        files.insert(file.path, file.bytes.into());
+    }
+
+    tracing::debug!("Extracted {} files", files.len());
+    Ok((snapshots_from_files(&files), index))
+}
+
+/// Extract one file of the zip archive `data`, which `entry` is from.
+pub fn read_entry(data: &[u8], entry: &IndexEntry) -> Result<Vec<u8>> {
+    let compressed = usize::try_from(entry.offset)
+        .ok()
+        .zip(usize::try_from(entry.compressed_size).ok())
+        .and_then(|(start, len)| data.get(start..start.checked_add(len)?))
+        .ok_or_else(|| anyhow::anyhow!("{} is outside of the archive", entry.path.display()))?;
+    let bytes = if entry.deflated {
+        let mut bytes = Vec::with_capacity(entry.size as usize);
+        DeflateDecoder::new(compressed).read_to_end(&mut bytes)?;
+        bytes
+    } else {
+        compressed.to_vec()
+    };
+    anyhow::ensure!(
+        bytes.len() as u64 == entry.size && content_hash(&bytes) == entry.hash,
+        "{} doesn't match the archive index",
+        entry.path.display()
+    );
+    Ok(bytes)
+}
+
+/// Pairs up the files of an [`ArchiveIndex`] into snapshots like [`discover`], without
+/// extracting them. `reference` says where a file is read from.
+pub fn snapshots_from_index(
+    index: &ArchiveIndex,
+    reference: impl Fn(&IndexEntry) -> FileReference,
+) -> Vec<Snapshot> {
+    let files: HashMap<PathBuf, Indexed> = index
+        .entries
+        .iter()
+        .map(|entry| {
+            let file = Indexed {
+                reference: reference(entry),
+                size: entry.size,
+                hash: entry.hash,
+            };
+            (entry.path.clone(), file)
+        })
+        .collect();
+    pair_files(&files)
+}
+
+/// XXH3 of a file's contents, as stored in [`IndexEntry::hash`]. Indexes are kept on disk, so
+/// this has to be the same in every build.
+pub fn content_hash(bytes: &[u8]) -> u64 {
+    xxhash_rust::xxh3::xxh3_64(bytes)
+}
+
+/// What pairing files into snapshots needs to know about them.
+trait PairFile {
+    fn reference(&self, path: &Path) -> FileReference;
+    fn is_empty(&self) -> bool;
+    fn same_content(&self, other: &Self) -> bool;
+}
+
+impl PairFile for Arc<[u8]> {
+    fn reference(&self, path: &Path) -> FileReference {
+        in_memory(path, self)
+    }
+
+    fn is_empty(&self) -> bool {
+        self.as_ref().is_empty()
+    }
+
+    fn same_content(&self, other: &Self) -> bool {
+        self == other
+    }
+}
+
+/// A file of an [`ArchiveIndex`].
+struct Indexed {
+    reference: FileReference,
+    size: u64,
+    hash: u64,
+}
+
+impl PairFile for Indexed {
+    fn reference(&self, _path: &Path) -> FileReference {
+        self.reference.clone()
+    }
+
+    fn is_empty(&self) -> bool {
+        self.size == 0
+    }
+
+    fn same_content(&self, other: &Self) -> bool {
+        self.size == other.size && self.hash == other.hash
+    }
+}
+
+fn in_memory(path: &Path, bytes: &Arc<[u8]>) -> FileReference {
+    FileReference::Bytes {
+        name: path.display().to_string(),
+        bytes: bytes.clone(),
+    }
+}
+
+fn run_zip_discovery(zip_data: &[u8], cancel: &CancelFlag) -> Result<Files> {
+    Ok(extract_zip(zip_data, false, cancel)?
+        .into_iter()
+        .map(|file| (file.path, file.bytes.into()))
+        .collect())
+}
+
+/// A png file extracted from a zip archive.
+struct ZipFile {
+    path: PathBuf,
+    bytes: Vec<u8>,
+
+    /// Where it is in the archive, if asked for and [`read_entry`] can extract it.
+    entry: Option<IndexEntry>,
+}
+
+/// Extracts the png files of a zip archive on all cores, in the order they're in the archive, so
+/// the last of two files with the same path wins like it did extracting them one by one.
+fn extract_zip(zip_data: &[u8], indexed: bool, cancel: &CancelFlag) -> Result<Vec<ZipFile>> {
+    let archive = ZipArchive::new(Cursor::new(zip_data))?;
+
+    // Every thread reads its own files, clones only share the parsed central directory.
+    (0..archive.len())
+        .into_par_iter()
+        .map_init(
+            || archive.clone(),
+            |archive, i| {
+                anyhow::ensure!(!cancel.is_cancelled(), "Cancelled");
+                let mut file = archive.by_index(i)?;
+                let Some(path) = file.enclosed_name() else {
+                    // Skip files with invalid names
+                    return Ok(None);
+                };
+                if path.extension().and_then(|s| s.to_str()) != Some("png") {
+                    return Ok(None);
+                }
+
+                let mut bytes = Vec::new();
+                file.read_to_end(&mut bytes)?;
+                let deflated = match file.compression() {
+                    CompressionMethod::Deflated => Some(true),
+                    CompressionMethod::Stored => Some(false),
+                    _ => None,
+                };
+                let entry = deflated.filter(|_| indexed).map(|deflated| IndexEntry {
+                    path: path.clone(),
+                    offset: file.data_start(),
+                    compressed_size: file.compressed_size(),
+                    size: bytes.len() as u64,
+                    deflated,
+                    hash: content_hash(&bytes),
+                });
+                Ok(Some(ZipFile { path, bytes, entry }))
+            },
+        )
+        .filter_map(Result::transpose)
+        .collect()
+}
+
+fn run_tar_discovery(tar_data: &[u8], cancel: &CancelFlag) -> Result<Files> {
+    let cursor = Cursor::new(tar_data);
+    let gz_decoder = GzDecoder::new(cursor);
+    let mut archive = Archive::new(gz_decoder);
+
+    // Extract all files into memory
+    let mut files = HashMap::new();
+
+    for entry in archive.entries()? {
+        anyhow::ensure!(!cancel.is_cancelled(), "Cancelled");
+        let mut entry = entry?;
+        let path = entry.path()?.to_path_buf();
+
+        // Only process PNG files
+        if path.extension().and_then(|s| s.to_str()) == Some("png") {
+            let mut data = Vec::new();
+            entry.read_to_end(&mut data)?;
+            files.insert(path, data.into());
+        }
+    }
+
+    Ok(files)
+}
+
+/// Pairs up the `.old`, `.new` and `.diff` variants of in-memory png files into snapshots, the
+/// same way as for archives.
+pub fn snapshots_from_files(files: &Files) -> Vec<Snapshot> {
+    pair_files(files)
+}
+
+fn pair_files<F: PairFile>(files: &HashMap<PathBuf, F>) -> Vec<Snapshot> {
+    let mut snapshots = Vec::new();
+    let mut processed_files = std::collections::HashSet::new();
+
+    // Sorted, so the snapshots come out in the same order however the files were extracted.
+    let mut paths: Vec<&PathBuf> = files.keys().collect();
+    paths.sort();
+    for png_path in paths {
+        if processed_files.contains(png_path) {
+            continue;
+        }
+
+        if let Some(snapshot) = try_create_snapshot(png_path, files) {
+            // Mark related files as processed
+            processed_files.insert(png_path.clone());
+            if let Some(old_path) = get_variant_path(png_path, "old") {
+                processed_files.insert(old_path);
+            }
+            if let Some(new_path) = get_variant_path(png_path, "new") {
+                processed_files.insert(new_path);
+            }
+            if let Some(diff_path) = get_variant_path(png_path, "diff") {
+                processed_files.insert(diff_path);
+            }
+
+            snapshots.push(snapshot);
+        }
+    }
+
+    snapshots
+}
+
+fn try_create_snapshot<F: PairFile>(
+    png_path: &Path,
+    files: &HashMap<PathBuf, F>,
+) -> Option<Snapshot> {
+    let file_name = png_path.file_name()?.to_str()?;
+
+    // Skip .old.png and .diff.png files - they are only used as variants
+    if file_name.ends_with(".old.png") || file_name.ends_with(".diff.png") {
+        return None;
+    }
+
+    // Handle .new.png files that don't have a corresponding base file
+    if file_name.ends_with(".new.png") {
+        let base_path = get_base_path_from_variant(png_path)?;
+        // If the base file exists, this .new.png will be handled when processing the base file
+        if files.contains_key(&base_path) {
+            return None;
+        }
+        // No base file exists - this is a newly added snapshot
+        let new_data = files.get(png_path)?;
+        return Some(Snapshot {
+            path: base_path,
+            old: None,
+            new: Some(new_data.reference(png_path)),
+            diff: None,
+            unchanged: false,
+        });
+    }
+
+    // Get variant paths
+    let old_path = get_variant_path(png_path, "old")?;
+    let new_path = get_variant_path(png_path, "new")?;
+    let diff_path = get_variant_path(png_path, "diff")?;
+
+    // // Check if diff exists (required for a valid snapshot)
+    // if !files.contains_key(&diff_path) {
+    //     return None;
+    // }
+
+    let base_data = files.get(png_path)?;
+
+    // An empty .new.png marks a snapshot whose test was removed
+    if files.get(&new_path).is_some_and(|data| data.is_empty()) {
+        return Some(Snapshot {
+            path: png_path.to_path_buf(),
+            old: Some(base_data.reference(png_path)),
+            new: None,
+            diff: None,
+            unchanged: false,
+        });
+    }
+
+    let diff_data = files.get(&diff_path);
+    let diff_reference = diff_data.map(|data| data.reference(&diff_path));
+
+    if files.contains_key(&old_path) {
+        // old.png exists, use original as new and old.png as old
+        let old_data = files.get(&old_path)?;
+        Some(Snapshot {
+            path: png_path.to_path_buf(),
+            old: Some(old_data.reference(&old_path)),
+            new: Some(base_data.reference(png_path)),
+            diff: diff_reference, // We'll handle diff separately if needed
+            unchanged: old_data.same_content(base_data),
+        })
+    } else if files.contains_key(&new_path) {
+        // new.png exists, use original as old and new.png as new
+        let new_data = files.get(&new_path)?;
+        Some(Snapshot {
+            path: png_path.to_path_buf(),
+            old: Some(base_data.reference(png_path)),
+            new: Some(new_data.reference(&new_path)),
+            diff: diff_reference, // We'll handle diff separately if needed
+            unchanged: new_data.same_content(base_data),
+        })
+    } else {
+        // No old or new variant, skip this snapshot
+        None
+    }
+}
+
+/// The snapshot a png file belongs to, e.g. `button.png` for `button.diff.png`, so sources that
+/// list files before downloading them can filter the variants like their snapshot.
+pub fn snapshot_path(file: &Path) -> PathBuf {
+    let base_name = file
+        .file_name()
+        .and_then(|name| name.to_str())
+        .and_then(|name| {
+            [".old.png", ".new.png", ".diff.png"]
+                .iter()
+                .find_map(|variant| name.strip_suffix(variant))
+        });
+    match base_name {
+        Some(stem) => file.with_file_name(format!("{stem}.png")),
+        None => file.to_path_buf(),
+    }
+}
+
+fn get_variant_path(base_path: &Path, variant: &str) -> Option<PathBuf> {
+    let stem = base_path.file_stem()?.to_str()?;
+    let parent = base_path.parent().unwrap_or(Path::new(""));
+    Some(parent.join(format!("{stem}.{variant}.png")))
+}
+
+/// Converts a variant path (e.g., "image.new.png") back to the base path ("image.png")
+fn get_base_path_from_variant(variant_path: &Path) -> Option<PathBuf> {
+    let stem = variant_path.file_stem()?.to_str()?;
+    let base_stem = stem
+        .strip_suffix(".new")
+        .or_else(|| stem.strip_suffix(".old"))?;
+    let parent = variant_path.parent().unwrap_or(Path::new(""));
+    Some(parent.join(format!("{base_stem}.png")))
+}
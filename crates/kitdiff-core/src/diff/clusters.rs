@@ -0,0 +1,48 @@
+use super::palette::{CLASSIC_ANTI_ALIASED, CLASSIC_CHANGED};
+use image::RgbaImage;
+
+/// Repaints the changed pixels of 8-connected clusters smaller than `min_size` like anti-aliased
+/// ones, e.g. the isolated speckles of nondeterministic GPU rendering. Returns how many pixels
+/// that were.
+///
+/// Works on the highlights in [`super::DiffPalette::Classic`] colors, before recoloring.
+pub(super) fn suppress_small_clusters(image: &mut RgbaImage, min_size: u32) -> u32 {
+    let (width, height) = image.dimensions();
+    let index = |x: u32, y: u32| y as usize * width as usize + x as usize;
+    let mut visited = vec![false; width as usize * height as usize];
+    let mut cluster = Vec::new();
+    let mut suppressed = 0;
+
+    for y in 0..height {
+        for x in 0..width {
+            if visited[index(x, y)] || *image.get_pixel(x, y) != CLASSIC_CHANGED {
+                continue;
+            }
+
+            // Flood fill from here, `cluster` doubles as the stack of pixels to look around.
+            cluster.clear();
+            cluster.push((x, y));
+            visited[index(x, y)] = true;
+            let mut next = 0;
+            while let Some(&(cx, cy)) = cluster.get(next) {
+                next += 1;
+                for ny in cy.saturating_sub(1)..=(cy + 1).min(height - 1) {
+                    for nx in cx.saturating_sub(1)..=(cx + 1).min(width - 1) {
+                        if !visited[index(nx, ny)] && *image.get_pixel(nx, ny) == CLASSIC_CHANGED {
+                            visited[index(nx, ny)] = true;
+                            cluster.push((nx, ny));
+                        }
+                    }
+                }
+            }
+
+            if cluster.len() < min_size as usize {
+                for &(cx, cy) in &cluster {
+                    image.put_pixel(cx, cy, CLASSIC_ANTI_ALIASED);
+                }
+                suppressed += cluster.len() as u32;
+            }
+        }
+    }
+    suppressed
+}
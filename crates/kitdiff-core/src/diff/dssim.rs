@@ -0,0 +1,81 @@
+use super::{DiffAlgorithm, DiffOptions, DiffResult};
+use anyhow::Context as _;
+use image::{Rgba, RgbaImage};
+use rgb::RGBA8;
+
+/// [dssim](https://github.com/kornelski/dssim), a multi-scale SSIM in the perceptual L\*a\*b\*
+/// color space, for teams whose thresholds are defined in it. Needs the `dssim` feature.
+///
+/// A pixel counts as changed if the DSSIM of its neighborhood, in thousandths, is above the
+/// threshold, so the default threshold of 1 flags anything above the `0.001` dssim prints for
+/// barely visible changes. [`DiffResult::similarity`] is the SSIM the DSSIM of the whole image
+/// is computed from, `1 / similarity - 1`.
+pub struct Dssim;
+
+impl DiffAlgorithm for Dssim {
+    fn name(&self) -> &str {
+        "DSSIM"
+    }
+
+    fn threshold_hint(&self) -> &str {
+        "Maximum DSSIM of a pixel's neighborhood, in thousandths"
+    }
+
+    fn diff(
+        &self,
+        old: RgbaImage,
+        new: RgbaImage,
+        options: &DiffOptions,
+    ) -> anyhow::Result<DiffResult> {
+        let (width, height) = old.dimensions();
+        let mut dssim = dssim_core::Dssim::new();
+        // Only the full resolution map, to find the changed pixels.
+        dssim.set_save_ssim_maps(1);
+        let pixels = |image: &RgbaImage| -> Vec<RGBA8> {
+            image
+                .pixels()
+                .map(|Rgba([r, g, b, a])| RGBA8::new(*r, *g, *b, *a))
+                .collect()
+        };
+        let old_image = dssim
+            .create_image_rgba(&pixels(&old), width as usize, height as usize)
+            .context("The image is too small for DSSIM")?;
+        let new_image = dssim
+            .create_image_rgba(&pixels(&new), width as usize, height as usize)
+            .context("The image is too small for DSSIM")?;
+        let (value, maps) = dssim.compare(&old_image, new_image);
+        let map = maps.first().context("DSSIM didn't return a map")?;
+
+        let old_luma = super::ssim::luma(&old);
+        let mut image = RgbaImage::new(width, height);
+        let mut changed = 0;
+        for y in 0..height {
+            for x in 0..width {
+                // The map can be smaller than the image, e.g. by the size of the blur kernel.
+                let map_x =
+                    (x as usize * map.map.width() / width as usize).min(map.map.width() - 1);
+                let map_y =
+                    (y as usize * map.map.height() / height as usize).min(map.map.height() - 1);
+                let ssim = f64::from(map.map[(map_x, map_y)]);
+                let dissimilarity = (1.0 / ssim.max(f64::EPSILON) - 1.0) * 1000.0;
+                let color = if dissimilarity > f64::from(options.threshold) {
+                    changed += 1;
+                    Rgba([255, 0, 0, 255])
+                } else {
+                    let gray = old_luma[y as usize * width as usize + x as usize];
+                    let faded = (255.0 - (255.0 - gray) * 0.1) as u8;
+                    Rgba([faded, faded, faded, 255])
+                };
+                image.put_pixel(x, y, color);
+            }
+        }
+
+        Ok(DiffResult {
+            image: (changed > 0).then_some(image),
+            pixels: changed,
+            width,
+            height,
+            similarity: Some((1.0 / (1.0 + f64::from(value))) as f32),
+        })
+    }
+}
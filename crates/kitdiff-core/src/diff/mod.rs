@@ -0,0 +1,325 @@
+use anyhow::Context as _;
+use image::RgbaImage;
+use parking_lot::RwLock;
+use std::sync::{Arc, LazyLock};
+
+mod clusters;
+#[cfg(feature = "dssim")]
+mod dssim;
+mod overrides;
+mod palette;
+mod pixelmatch;
+mod pixelmatch_js;
+mod rows;
+mod ssim;
+
+#[cfg(feature = "dssim")]
+pub use dssim::Dssim;
+pub use overrides::{DiffOverride, DiffOverrides};
+pub use palette::DiffPalette;
+pub use pixelmatch::Pixelmatch;
+pub use pixelmatch_js::PixelmatchJs;
+pub use ssim::Ssim;
+
+static CUSTOM_ALGORITHMS: LazyLock<RwLock<Vec<Arc<dyn DiffAlgorithm>>>> =
+    LazyLock::new(|| RwLock::new(Vec::new()));
+
+#[derive(Debug, Clone, Copy, PartialEq, serde::Serialize, serde::Deserialize)]
+#[serde(default)]
+pub struct DiffOptions {
+    pub algorithm: Algorithm,
+    pub threshold: f32,
+    pub detect_aa_pixels: bool,
+
+    /// Changed pixels in smaller clusters are ignored like anti-aliasing, so speckles of GPU
+    /// nondeterminism don't count. Only works with algorithms that highlight in the
+    /// [`DiffPalette::Classic`] colors, like the built-in ones. 0 and 1 keep every pixel.
+    pub min_cluster_size: u32,
+
+    /// Colors of the highlighted pixels.
+    pub palette: DiffPalette,
+}
+
+/// The threshold of the built-in algorithms, unless they have their own, see
+/// [`DiffAlgorithm::default_threshold`].
+const DEFAULT_THRESHOLD: f32 = 1.0;
+
+impl Default for DiffOptions {
+    fn default() -> Self {
+        Self {
+            algorithm: Algorithm::default(),
+            threshold: DEFAULT_THRESHOLD,
+            detect_aa_pixels: true,
+            min_cluster_size: 0,
+            palette: DiffPalette::default(),
+        }
+    }
+}
+
+impl DiffOptions {
+    /// The default options, with the default threshold of `algorithm`.
+    pub fn for_algorithm(algorithm: Algorithm) -> Self {
+        Self {
+            algorithm,
+            threshold: algorithm.default_threshold(),
+            ..Default::default()
+        }
+    }
+}
+
+/// Compares two images of the same size.
+///
+/// Implement this to encode tolerances the built-in algorithms don't know about, e.g. for font
+/// rendering or GPU dithering, and add it with [`register_algorithm`].
+pub trait DiffAlgorithm: Send + Sync {
+    /// Shown in the settings. Also identifies the algorithm in saved settings, so keep it stable.
+    fn name(&self) -> &str;
+
+    /// How [`DiffOptions::threshold`] is interpreted, shown next to the slider.
+    fn threshold_hint(&self) -> &str {
+        ""
+    }
+
+    /// The threshold to start with when the algorithm is picked.
+    fn default_threshold(&self) -> f32 {
+        DEFAULT_THRESHOLD
+    }
+
+    fn diff(
+        &self,
+        old: RgbaImage,
+        new: RgbaImage,
+        options: &DiffOptions,
+    ) -> anyhow::Result<DiffResult>;
+}
+
+/// Which [`DiffAlgorithm`] to use.
+#[derive(
+    Debug, Clone, Copy, Default, PartialEq, Eq, Hash, serde::Serialize, serde::Deserialize,
+)]
+pub enum Algorithm {
+    /// Per-pixel color distance, like pixelmatch.
+    #[default]
+    Pixelmatch,
+
+    /// Structural similarity, which ignores small shifts in brightness and noise.
+    Ssim,
+
+    /// Exactly what the JavaScript pixelmatch reports, see [`PixelmatchJs`].
+    PixelmatchJs,
+
+    /// Perceptual multi-scale SSIM, only available with the `dssim` feature.
+    Dssim,
+
+    /// One added with [`register_algorithm`].
+    Custom(AlgorithmId),
+}
+
+/// Identifies a custom algorithm by a hash of its name, so it survives restarts.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, serde::Serialize, serde::Deserialize)]
+pub struct AlgorithmId(u64);
+
+impl AlgorithmId {
+    fn of(algorithm: &dyn DiffAlgorithm) -> Self {
+        // FNV-1a, which unlike the std hasher is stable across releases.
+        let hash = algorithm
+            .name()
+            .bytes()
+            .fold(0xcbf2_9ce4_8422_2325, |hash, byte| {
+                (hash ^ u64::from(byte)).wrapping_mul(0x0100_0000_01b3)
+            });
+        Self(hash)
+    }
+}
+
+impl Algorithm {
+    /// The built-in algorithms followed by the registered ones.
+    pub fn all() -> Vec<Self> {
+        let mut all = vec![Self::Pixelmatch, Self::Ssim, Self::PixelmatchJs];
+        if cfg!(feature = "dssim") {
+            all.push(Self::Dssim);
+        }
+        all.extend(
+            CUSTOM_ALGORITHMS
+                .read()
+                .iter()
+                .map(|algorithm| Self::Custom(AlgorithmId::of(algorithm.as_ref()))),
+        );
+        all
+    }
+
+    /// `None` for a custom algorithm that isn't registered (anymore).
+    pub fn get(self) -> Option<Arc<dyn DiffAlgorithm>> {
+        match self {
+            Self::Pixelmatch => Some(Arc::new(Pixelmatch)),
+            Self::Ssim => Some(Arc::new(Ssim)),
+            Self::PixelmatchJs => Some(Arc::new(PixelmatchJs)),
+            #[cfg(feature = "dssim")]
+            Self::Dssim => Some(Arc::new(Dssim)),
+            // Settings from a build with the feature.
+            #[cfg(not(feature = "dssim"))]
+            Self::Dssim => None,
+            Self::Custom(id) => CUSTOM_ALGORITHMS
+                .read()
+                .iter()
+                .find(|algorithm| AlgorithmId::of(algorithm.as_ref()) == id)
+                .cloned(),
+        }
+    }
+
+    /// See [`DiffAlgorithm::default_threshold`].
+    pub fn default_threshold(self) -> f32 {
+        self.get()
+            .map_or(DEFAULT_THRESHOLD, |algorithm| algorithm.default_threshold())
+    }
+
+    pub fn name(self) -> String {
+        self.get().map_or_else(
+            || "Unknown".to_owned(),
+            |algorithm| algorithm.name().to_owned(),
+        )
+    }
+}
+
+/// Makes `algorithm` selectable in the settings, replacing one with the same name.
+pub fn register_algorithm(algorithm: impl DiffAlgorithm + 'static) {
+    let id = AlgorithmId::of(&algorithm);
+    let mut algorithms = CUSTOM_ALGORITHMS.write();
+    algorithms.retain(|existing| AlgorithmId::of(existing.as_ref()) != id);
+    algorithms.push(Arc::new(algorithm));
+}
+
+#[derive(Debug, Clone)]
+pub struct DiffResult {
+    /// Highlights the changed pixels. Most algorithms skip it and return `None` if there are
+    /// none.
+    pub image: Option<RgbaImage>,
+
+    /// Number of pixels above the threshold.
+    pub pixels: i32,
+
+    /// Size of both images.
+    pub width: u32,
+    pub height: u32,
+
+    /// Overall similarity from 0 to 1, for algorithms that measure one (e.g. the mean SSIM).
+    pub similarity: Option<f32>,
+}
+
+impl DiffResult {
+    /// Whether any pixel is above the threshold.
+    pub fn is_different(&self) -> bool {
+        self.pixels > 0
+    }
+
+    /// Share of pixels above the threshold, from 0 to 1.
+    pub fn changed_fraction(&self) -> f32 {
+        let total = u64::from(self.width) * u64::from(self.height);
+        if total == 0 {
+            0.0
+        } else {
+            self.pixels as f32 / total as f32
+        }
+    }
+}
+
+/// The smallest diff worth a look, to hide the snapshots where only a few anti-aliased pixels
+/// changed. Zero turns a limit off.
+#[derive(Debug, Clone, Copy, Default, PartialEq, serde::Serialize, serde::Deserialize)]
+#[serde(default)]
+pub struct MinChange {
+    pub pixels: u32,
+
+    /// Share of the image, from 0 to 100.
+    pub percent: f32,
+}
+
+impl MinChange {
+    pub fn is_active(self) -> bool {
+        self.pixels > 0 || self.percent > 0.0
+    }
+
+    /// Whether a diff with `pixels` above the threshold, `fraction` of the image, is smaller than
+    /// this. Diffs without any are too, once a limit is set.
+    pub fn hides(self, pixels: i32, fraction: f32) -> bool {
+        i64::from(pixels) < i64::from(self.pixels) || fraction * 100.0 < self.percent
+    }
+}
+
+/// Decodes two encoded images (png, jpeg, …) and diffs them with the same thresholding kitdiff
+/// uses for the diff view.
+///
+/// ```no_run
+/// # fn main() -> anyhow::Result<()> {
+/// let old = std::fs::read("tests/snapshots/button.old.png")?;
+/// let new = std::fs::read("tests/snapshots/button.png")?;
+/// let result = kitdiff_core::diff::diff_images(&old, &new, &Default::default())?;
+/// assert!(!result.is_different(), "{} pixels differ", result.pixels);
+/// # Ok(())
+/// # }
+/// ```
+pub fn diff_images(old: &[u8], new: &[u8], options: &DiffOptions) -> anyhow::Result<DiffResult> {
+    let old = image::load_from_memory(old)
+        .context("Failed to decode old image")?
+        .to_rgba8();
+    let new = image::load_from_memory(new)
+        .context("Failed to decode new image")?
+        .to_rgba8();
+    diff_rgba(old, new, options)
+}
+
+/// Diffs two decoded images of the same size with [`DiffOptions::algorithm`], see
+/// [`diff_images`].
+#[tracing::instrument(name = "diff", level = "debug", skip_all)]
+pub fn diff_rgba(
+    old: RgbaImage,
+    new: RgbaImage,
+    options: &DiffOptions,
+) -> anyhow::Result<DiffResult> {
+    anyhow::ensure!(
+        old.dimensions() == new.dimensions(),
+        "Images must have the same dimensions"
+    );
+    let algorithm = options
+        .algorithm
+        .get()
+        .context("The selected diff algorithm isn't available")?;
+
+    let mut result = algorithm.diff(old, new, options)?;
+    if let Some(image) = &mut result.image {
+        if options.min_cluster_size > 1 {
+            let suppressed = clusters::suppress_small_clusters(image, options.min_cluster_size);
+            result.pixels = result.pixels.saturating_sub_unsigned(suppressed).max(0);
+        }
+        options.palette.recolor(image);
+    }
+    tracing::debug!("{} pixels differ ({})", result.pixels, algorithm.name());
+    Ok(result)
+}
+
+/// How many of the ascending `thresholds` still find `old` and `new` different, which is the
+/// index of the first threshold at which they are the same.
+///
+/// A higher threshold never finds more changed pixels, so this binary searches `thresholds`
+/// instead of diffing the images at every one of them.
+pub fn differing_thresholds(
+    old: &RgbaImage,
+    new: &RgbaImage,
+    options: &DiffOptions,
+    thresholds: &[f32],
+) -> anyhow::Result<usize> {
+    let (mut low, mut high) = (0, thresholds.len());
+    while low < high {
+        let middle = low + (high - low) / 2;
+        let options = DiffOptions {
+            threshold: thresholds[middle],
+            ..*options
+        };
+        if diff_rgba(old.clone(), new.clone(), &options)?.pixels > 0 {
+            low = middle + 1;
+        } else {
+            high = middle;
+        }
+    }
+    Ok(low)
+}
@@ -0,0 +1,69 @@
+use super::{Algorithm, DiffOptions};
+use globset::{Glob, GlobMatcher};
+use std::path::Path;
+
+/// Diff options for the snapshots matching a glob, e.g. a looser threshold for text rendering,
+/// which differs a little between machines.
+#[derive(Debug, Clone, PartialEq, serde::Serialize, serde::Deserialize)]
+pub struct DiffOverride {
+    /// Matched against the snapshot paths relative to the source root, e.g.
+    /// `**/text_rendering/**`.
+    pub path: String,
+    pub algorithm: Option<Algorithm>,
+    pub threshold: Option<f32>,
+    pub detect_aa_pixels: Option<bool>,
+    pub min_cluster_size: Option<u32>,
+}
+
+impl DiffOverride {
+    fn apply(&self, options: &mut DiffOptions) {
+        if let Some(algorithm) = self.algorithm
+            && algorithm != options.algorithm
+        {
+            // A threshold for another algorithm means something else.
+            options.algorithm = algorithm;
+            options.threshold = algorithm.default_threshold();
+        }
+        if let Some(threshold) = self.threshold {
+            options.threshold = threshold;
+        }
+        if let Some(detect_aa_pixels) = self.detect_aa_pixels {
+            options.detect_aa_pixels = detect_aa_pixels;
+        }
+        if let Some(min_cluster_size) = self.min_cluster_size {
+            options.min_cluster_size = min_cluster_size;
+        }
+    }
+}
+
+/// Compiled [`DiffOverride`]s. Where several match a path, the later ones win.
+#[derive(Debug, Clone, Default)]
+pub struct DiffOverrides(Vec<(GlobMatcher, DiffOverride)>);
+
+impl DiffOverrides {
+    /// Invalid globs are skipped with a warning.
+    pub fn new(overrides: &[DiffOverride]) -> Self {
+        Self(
+            overrides
+                .iter()
+                .filter_map(|entry| match Glob::new(entry.path.trim()) {
+                    Ok(glob) => Some((glob.compile_matcher(), entry.clone())),
+                    Err(err) => {
+                        tracing::warn!("Invalid glob {:?}: {err}", entry.path);
+                        None
+                    }
+                })
+                .collect(),
+        )
+    }
+
+    /// `options` with the overrides for `path` applied.
+    pub fn options(&self, path: &Path, mut options: DiffOptions) -> DiffOptions {
+        for (matcher, entry) in &self.0 {
+            if matcher.is_match(path) {
+                entry.apply(&mut options);
+            }
+        }
+        options
+    }
+}
@@ -0,0 +1,71 @@
+use image::{Rgba, RgbaImage};
+
+/// Colors of the highlighted pixels in diff images.
+///
+/// The algorithms highlight in pixelmatch's red and yellow, other palettes swap those for colors
+/// that people with a color vision deficiency can tell apart from the faded image.
+#[derive(
+    Debug, Clone, Copy, Default, PartialEq, Eq, Hash, serde::Serialize, serde::Deserialize,
+)]
+pub enum DiffPalette {
+    /// Red for changed pixels, yellow for anti-aliasing.
+    #[default]
+    Classic,
+
+    /// Blue and orange, safe for deuteranopia and protanopia (red-green).
+    BlueOrange,
+
+    /// Magenta and teal, safe for tritanopia (blue-yellow).
+    MagentaTeal,
+
+    /// Saturated magenta and cyan, to stand out on any image.
+    HighContrast,
+}
+
+pub(super) const CLASSIC_CHANGED: Rgba<u8> = Rgba([255, 0, 0, 255]);
+pub(super) const CLASSIC_ANTI_ALIASED: Rgba<u8> = Rgba([255, 255, 0, 255]);
+
+impl DiffPalette {
+    pub const ALL: [Self; 4] = [
+        Self::Classic,
+        Self::BlueOrange,
+        Self::MagentaTeal,
+        Self::HighContrast,
+    ];
+
+    pub fn name(self) -> &'static str {
+        match self {
+            Self::Classic => "Red / yellow",
+            Self::BlueOrange => "Blue / orange (deuteranopia, protanopia)",
+            Self::MagentaTeal => "Magenta / teal (tritanopia)",
+            Self::HighContrast => "High contrast",
+        }
+    }
+
+    /// Colors of changed and of anti-aliased pixels.
+    ///
+    /// The colorblind-safe ones are from the Okabe-Ito palette.
+    pub fn colors(self) -> [[u8; 3]; 2] {
+        match self {
+            Self::Classic => [[255, 0, 0], [255, 255, 0]],
+            Self::BlueOrange => [[0, 114, 178], [230, 159, 0]],
+            Self::MagentaTeal => [[204, 0, 153], [0, 158, 115]],
+            Self::HighContrast => [[255, 0, 255], [0, 255, 255]],
+        }
+    }
+
+    /// Swap the highlight colors of a diff image made with [`Self::Classic`] for ours.
+    pub fn recolor(self, image: &mut RgbaImage) {
+        if self == Self::Classic {
+            return;
+        }
+        let [changed, anti_aliased] = self.colors();
+        for pixel in image.pixels_mut() {
+            if *pixel == CLASSIC_CHANGED {
+                *pixel = Rgba([changed[0], changed[1], changed[2], 255]);
+            } else if *pixel == CLASSIC_ANTI_ALIASED {
+                *pixel = Rgba([anti_aliased[0], anti_aliased[1], anti_aliased[2], 255]);
+            }
+        }
+    }
+}
@@ -0,0 +1,175 @@
+use super::palette::{CLASSIC_ANTI_ALIASED, CLASSIC_CHANGED};
+use super::{DiffAlgorithm, DiffOptions, DiffResult, rows};
+use image::RgbaImage;
+
+/// The largest possible YIQ delta, between black and white.
+const MAX_DELTA: f32 = 35215.0;
+
+/// The default algorithm, comparing the color of each pixel like pixelmatch.
+///
+/// Gives the same results as [dify](https://github.com/jihchi/dify), the `f32` port of
+/// pixelmatch kitdiff used before, but diffs the rows in parallel. Each row's deltas are computed
+/// without branches first so they vectorize, only the few pixels above the threshold are then
+/// checked for anti-aliasing one by one. Unchanged pixels stay transparent.
+pub struct Pixelmatch;
+
+impl DiffAlgorithm for Pixelmatch {
+    fn name(&self) -> &str {
+        "Pixelmatch"
+    }
+
+    fn threshold_hint(&self) -> &str {
+        "Maximum color distance of a pixel"
+    }
+
+    fn diff(
+        &self,
+        old: RgbaImage,
+        new: RgbaImage,
+        options: &DiffOptions,
+    ) -> anyhow::Result<DiffResult> {
+        let (width, height) = old.dimensions();
+        let max_delta = MAX_DELTA * options.threshold * options.threshold;
+
+        let mut image = RgbaImage::new(width, height);
+        let rows = rows::par_map_rows(&mut image, |y, row| {
+            let old_row = rows::row(&old, y);
+            let new_row = rows::row(&new, y);
+            // Most rows of a snapshot are untouched.
+            if old_row == new_row {
+                return 0;
+            }
+
+            let deltas = row_deltas(old_row, new_row);
+            let mut pixels = 0;
+            for ((x, output), delta) in (0..).zip(row.chunks_exact_mut(4)).zip(deltas) {
+                if delta <= max_delta {
+                    continue;
+                }
+                let color = if options.detect_aa_pixels
+                    && (antialiased(&old, x, y, &new) || antialiased(&new, x, y, &old))
+                {
+                    CLASSIC_ANTI_ALIASED
+                } else {
+                    pixels += 1;
+                    CLASSIC_CHANGED
+                };
+                output.copy_from_slice(&color.0);
+            }
+            pixels
+        });
+        let pixels: i32 = rows.into_iter().sum();
+
+        Ok(DiffResult {
+            image: (pixels > 0).then_some(image),
+            pixels,
+            width,
+            height,
+            similarity: None,
+        })
+    }
+}
+
+/// The squared YIQ distance of every pixel of two rows.
+///
+/// Opaque pixels go through the same blend with white as translucent ones, which leaves them
+/// unchanged, so the loop has no branches.
+fn row_deltas(old: &[u8], new: &[u8]) -> Vec<f32> {
+    old.chunks_exact(4)
+        .zip(new.chunks_exact(4))
+        .map(|(old, new)| {
+            let [y1, i1, q1] = yiq(old);
+            let [y2, i2, q2] = yiq(new);
+            let (y, i, q) = (y1 - y2, i1 - i2, q1 - q2);
+            0.5053 * y * y + 0.299 * i * i + 0.1957 * q * q
+        })
+        .collect()
+}
+
+/// The color of an RGBA pixel on a white background, in YIQ.
+fn yiq(pixel: &[u8]) -> [f32; 3] {
+    let alpha = f32::from(pixel[3]) / 255.0;
+    let [r, g, b] = [pixel[0], pixel[1], pixel[2]].map(|c| 255.0 + (f32::from(c) - 255.0) * alpha);
+    [
+        r * 0.298_895_31 + g * 0.586_622_47 + b * 0.114_482_23,
+        r * 0.595_977_99 - g * 0.274_176_1 - b * 0.321_801_89,
+        r * 0.211_470_17 - g * 0.522_617_11 + b * 0.311_146_94,
+    ]
+}
+
+/// Difference in brightness, positive if the second pixel is darker.
+fn luma_delta(first: &RgbaImage, second: &RgbaImage, x1: u32, y1: u32, x2: u32, y2: u32) -> f32 {
+    let first = first.get_pixel(x1, y1);
+    let second = second.get_pixel(x2, y2);
+    if first == second {
+        return 0.0;
+    }
+    yiq(&first.0)[0] - yiq(&second.0)[0]
+}
+
+/// Whether the pixel is likely part of an anti-aliased edge: it has a darker and a brighter
+/// neighbor, and one of those has many siblings of the same color in both images.
+fn antialiased(image: &RgbaImage, x1: u32, y1: u32, other: &RgbaImage) -> bool {
+    let (width, height) = image.dimensions();
+    let x0 = x1.saturating_sub(1);
+    let y0 = y1.saturating_sub(1);
+    let x2 = (x1 + 1).min(width - 1);
+    let y2 = (y1 + 1).min(height - 1);
+    let mut zeroes = usize::from(x1 == x0 || x1 == x2 || y1 == y0 || y1 == y2);
+    let (mut min, mut max) = (0.0, 0.0);
+    let (mut min_pos, mut max_pos) = ((0, 0), (0, 0));
+
+    for x in x0..=x2 {
+        for y in y0..=y2 {
+            if x == x1 && y == y1 {
+                continue;
+            }
+            let delta = luma_delta(image, image, x1, y1, x, y);
+            if delta == 0.0 {
+                zeroes += 1;
+                if zeroes > 2 {
+                    return false;
+                }
+            } else if delta < min {
+                min = delta;
+                min_pos = (x, y);
+            } else if delta > max {
+                max = delta;
+                max_pos = (x, y);
+            }
+        }
+    }
+    if min == 0.0 || max == 0.0 {
+        return false;
+    }
+
+    let (min_x, min_y) = min_pos;
+    let (max_x, max_y) = max_pos;
+    (has_many_siblings(image, min_x, min_y) && has_many_siblings(other, min_x, min_y))
+        || (has_many_siblings(image, max_x, max_y) && has_many_siblings(other, max_x, max_y))
+}
+
+/// Whether more than two neighbors have exactly the pixel's color.
+fn has_many_siblings(image: &RgbaImage, x1: u32, y1: u32) -> bool {
+    let (width, height) = image.dimensions();
+    let x0 = x1.saturating_sub(1);
+    let y0 = y1.saturating_sub(1);
+    let x2 = (x1 + 1).min(width - 1);
+    let y2 = (y1 + 1).min(height - 1);
+    let mut zeroes = usize::from(x1 == x0 || x1 == x2 || y1 == y0 || y1 == y2);
+    let pixel = image.get_pixel(x1, y1);
+    for x in x0..=x2 {
+        for y in y0..=y2 {
+            if x == x1 && y == y1 {
+                continue;
+            }
+            if image.get_pixel(x, y) == pixel {
+                zeroes += 1;
+            }
+            if zeroes > 2 {
+                return true;
+            }
+        }
+    }
+    false
+}
@@ -0,0 +1,214 @@
+use super::{DiffAlgorithm, DiffOptions, DiffResult, rows};
+use image::RgbaImage;
+
+/// Opacity of the faded original image behind the highlighted pixels.
+const ALPHA: f64 = 0.1;
+
+const CHANGED: [u8; 3] = [255, 0, 0];
+const ANTI_ALIASED: [u8; 3] = [255, 255, 0];
+
+/// A line-by-line port of [pixelmatch](https://github.com/mapbox/pixelmatch) 5, for pipelines
+/// that move over from the JavaScript library and need the same numbers and diff images.
+/// Pixelmatch 6 blends translucent pixels against a background that depends on their position
+/// instead of white, so it can count those differently.
+///
+/// The threshold is pixelmatch's `threshold` option, with its default of `0.1`, and
+/// [`DiffOptions::detect_aa_pixels`] is the opposite of its `includeAA`. Like pixelmatch, it
+/// returns the faded image even if nothing changed. Unlike [`super::Pixelmatch`], everything is
+/// computed in `f64` and rounded like a `Uint8ClampedArray`, as in JavaScript.
+pub struct PixelmatchJs;
+
+impl DiffAlgorithm for PixelmatchJs {
+    fn name(&self) -> &str {
+        "pixelmatch.js"
+    }
+
+    fn threshold_hint(&self) -> &str {
+        "pixelmatch's threshold option, from 0 to 1"
+    }
+
+    fn default_threshold(&self) -> f32 {
+        0.1
+    }
+
+    fn diff(
+        &self,
+        old: RgbaImage,
+        new: RgbaImage,
+        options: &DiffOptions,
+    ) -> anyhow::Result<DiffResult> {
+        let (width, height) = old.dimensions();
+        let threshold = f64::from(options.threshold);
+        let max_delta = 35215.0 * threshold * threshold;
+        let mut output = RgbaImage::new(width, height);
+        let pixels = rows::par_map_rows(&mut output, |y, row| {
+            // Most rows of a snapshot are untouched, they only need the faded image.
+            let changed = rows::row(&old, y) != rows::row(&new, y);
+            let mut pixels = 0;
+            for (x, output) in (0..).zip(row.chunks_exact_mut(4)) {
+                let color = if !changed {
+                    gray(&old, x, y)
+                } else if color_delta(&old, &new, x, y, x, y, false).abs() > max_delta {
+                    if options.detect_aa_pixels
+                        && (antialiased(&old, x, y, &new) || antialiased(&new, x, y, &old))
+                    {
+                        ANTI_ALIASED
+                    } else {
+                        pixels += 1;
+                        CHANGED
+                    }
+                } else {
+                    gray(&old, x, y)
+                };
+                output.copy_from_slice(&[color[0], color[1], color[2], 255]);
+            }
+            pixels
+        });
+
+        Ok(DiffResult {
+            image: Some(output),
+            pixels: pixels.into_iter().sum(),
+            width,
+            height,
+            similarity: None,
+        })
+    }
+}
+
+/// Whether the pixel is likely part of an anti-aliased edge, the `antialiased` of pixelmatch.
+fn antialiased(image: &RgbaImage, x1: u32, y1: u32, other: &RgbaImage) -> bool {
+    let (width, height) = image.dimensions();
+    let x0 = x1.saturating_sub(1);
+    let y0 = y1.saturating_sub(1);
+    let x2 = (x1 + 1).min(width - 1);
+    let y2 = (y1 + 1).min(height - 1);
+    let mut zeroes = usize::from(x1 == x0 || x1 == x2 || y1 == y0 || y1 == y2);
+    let (mut min, mut max) = (0.0, 0.0);
+    let (mut min_pos, mut max_pos) = ((0, 0), (0, 0));
+
+    // Column by column, like pixelmatch, which decides the darkest and brightest neighbor on a
+    // tie.
+    for x in x0..=x2 {
+        for y in y0..=y2 {
+            if x == x1 && y == y1 {
+                continue;
+            }
+            let delta = color_delta(image, image, x1, y1, x, y, true);
+            if delta == 0.0 {
+                zeroes += 1;
+                if zeroes > 2 {
+                    return false;
+                }
+            } else if delta < min {
+                min = delta;
+                min_pos = (x, y);
+            } else if delta > max {
+                max = delta;
+                max_pos = (x, y);
+            }
+        }
+    }
+    if min == 0.0 || max == 0.0 {
+        return false;
+    }
+
+    let (min_x, min_y) = min_pos;
+    let (max_x, max_y) = max_pos;
+    (has_many_siblings(image, min_x, min_y) && has_many_siblings(other, min_x, min_y))
+        || (has_many_siblings(image, max_x, max_y) && has_many_siblings(other, max_x, max_y))
+}
+
+/// Whether more than two neighbors have exactly the pixel's color.
+fn has_many_siblings(image: &RgbaImage, x1: u32, y1: u32) -> bool {
+    let (width, height) = image.dimensions();
+    let x0 = x1.saturating_sub(1);
+    let y0 = y1.saturating_sub(1);
+    let x2 = (x1 + 1).min(width - 1);
+    let y2 = (y1 + 1).min(height - 1);
+    let mut zeroes = usize::from(x1 == x0 || x1 == x2 || y1 == y0 || y1 == y2);
+    let pixel = image.get_pixel(x1, y1);
+    for x in x0..=x2 {
+        for y in y0..=y2 {
+            if x == x1 && y == y1 {
+                continue;
+            }
+            if image.get_pixel(x, y) == pixel {
+                zeroes += 1;
+            }
+            if zeroes > 2 {
+                return true;
+            }
+        }
+    }
+    false
+}
+
+/// Squared YIQ distance, negative if the first pixel is brighter. Only the luma difference with
+/// `y_only`.
+fn color_delta(
+    first: &RgbaImage,
+    second: &RgbaImage,
+    x1: u32,
+    y1: u32,
+    x2: u32,
+    y2: u32,
+    y_only: bool,
+) -> f64 {
+    let first = first.get_pixel(x1, y1);
+    let second = second.get_pixel(x2, y2);
+    if first == second {
+        return 0.0;
+    }
+    let [r1, g1, b1] = blend_alpha(first.0);
+    let [r2, g2, b2] = blend_alpha(second.0);
+
+    let luma1 = rgb2y(r1, g1, b1);
+    let luma2 = rgb2y(r2, g2, b2);
+    let y = luma1 - luma2;
+    if y_only {
+        return y;
+    }
+    let i = rgb2i(r1, g1, b1) - rgb2i(r2, g2, b2);
+    let q = rgb2q(r1, g1, b1) - rgb2q(r2, g2, b2);
+    let delta = 0.5053 * y * y + 0.299 * i * i + 0.1957 * q * q;
+    if luma1 > luma2 { -delta } else { delta }
+}
+
+/// The color on a white background.
+fn blend_alpha([r, g, b, a]: [u8; 4]) -> [f64; 3] {
+    let [r, g, b] = [r, g, b].map(f64::from);
+    if a < 255 {
+        let a = f64::from(a) / 255.0;
+        [blend(r, a), blend(g, a), blend(b, a)]
+    } else {
+        [r, g, b]
+    }
+}
+
+fn blend(color: f64, alpha: f64) -> f64 {
+    255.0 + (color - 255.0) * alpha
+}
+
+fn rgb2y(r: f64, g: f64, b: f64) -> f64 {
+    r * 0.298_895_31 + g * 0.586_622_47 + b * 0.114_482_23
+}
+
+fn rgb2i(r: f64, g: f64, b: f64) -> f64 {
+    r * 0.595_977_99 - g * 0.274_176_10 - b * 0.321_801_89
+}
+
+fn rgb2q(r: f64, g: f64, b: f64) -> f64 {
+    r * 0.211_470_17 - g * 0.522_617_11 + b * 0.311_146_94
+}
+
+/// The faded original pixel, `drawGrayPixel` of pixelmatch.
+fn gray(image: &RgbaImage, x: u32, y: u32) -> [u8; 3] {
+    let [r, g, b, a] = image.get_pixel(x, y).0.map(f64::from);
+    let value = clamp_u8(blend(rgb2y(r, g, b), ALPHA * a / 255.0));
+    [value; 3]
+}
+
+/// How a `Uint8ClampedArray` stores a number: clamped, and rounded half to even.
+fn clamp_u8(value: f64) -> u8 {
+    value.clamp(0.0, 255.0).round_ties_even() as u8
+}
@@ -0,0 +1,29 @@
+//! Row-wise helpers for the algorithms' per-pixel loops: skipping the unchanged bulk of a
+//! snapshot, and spreading rows over all cores with rayon.
+
+use image::RgbaImage;
+use rayon::prelude::*;
+
+/// The bytes of row `y`.
+pub(super) fn row(image: &RgbaImage, y: u32) -> &[u8] {
+    let stride = image.width() as usize * 4;
+    let start = y as usize * stride;
+    &image.as_raw()[start..start + stride]
+}
+
+/// Calls `row_fn` with the index and bytes of every row of `image` in parallel, and returns what
+/// it returned in row order.
+pub(super) fn par_map_rows<T: Send>(
+    image: &mut RgbaImage,
+    row_fn: impl Fn(u32, &mut [u8]) -> T + Sync,
+) -> Vec<T> {
+    let stride = image.width() as usize * 4;
+    if stride == 0 {
+        return Vec::new();
+    }
+    image
+        .par_chunks_exact_mut(stride)
+        .enumerate()
+        .map(|(y, row)| row_fn(y as u32, row))
+        .collect()
+}
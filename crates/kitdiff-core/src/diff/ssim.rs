@@ -0,0 +1,160 @@
+use super::{DiffAlgorithm, DiffOptions, DiffResult, rows};
+use image::{Rgba, RgbaImage};
+use rayon::prelude::*;
+
+/// Pixels within this many pixels of each other make up a window.
+const RADIUS: u32 = 3;
+
+const C1: f64 = (0.01 * 255.0) * (0.01 * 255.0);
+const C2: f64 = (0.03 * 255.0) * (0.03 * 255.0);
+
+/// Structural similarity of the luma, over a 7x7 window around each pixel.
+///
+/// A pixel counts as changed if its dissimilarity (`1 - SSIM`) in percent is above the
+/// threshold, so the default threshold of 1 flags windows that are less than 99% similar.
+pub struct Ssim;
+
+impl DiffAlgorithm for Ssim {
+    fn name(&self) -> &str {
+        "SSIM"
+    }
+
+    fn threshold_hint(&self) -> &str {
+        "Maximum dissimilarity of a pixel's neighborhood, in percent"
+    }
+
+    fn diff(
+        &self,
+        old: RgbaImage,
+        new: RgbaImage,
+        options: &DiffOptions,
+    ) -> anyhow::Result<DiffResult> {
+        let (width, height) = old.dimensions();
+        let old_luma = luma(&old);
+        let new_luma = luma(&new);
+        let sums = Sums::new(width, &old_luma, &new_luma);
+
+        let mut image = RgbaImage::new(width, height);
+        let rows = rows::par_map_rows(&mut image, |y, row| {
+            let mut pixels = 0;
+            let mut total = 0.0;
+            for (x, output) in (0..).zip(row.chunks_exact_mut(4)) {
+                let ssim = sums.ssim(x, y, width, height);
+                total += ssim;
+
+                let dissimilarity = (1.0 - ssim) * 100.0;
+                let Rgba(color) = if dissimilarity > f64::from(options.threshold) {
+                    pixels += 1;
+                    Rgba([255, 0, 0, 255])
+                } else {
+                    // Like pixelmatch, show unchanged pixels as a faded grayscale of the old image.
+                    let gray = old_luma[y as usize * width as usize + x as usize];
+                    let faded = (255.0 - (255.0 - gray) * 0.1) as u8;
+                    Rgba([faded, faded, faded, 255])
+                };
+                output.copy_from_slice(&color);
+            }
+            (pixels, total)
+        });
+        // Summed in row order, so the similarity doesn't depend on how the rows were scheduled.
+        let (pixels, total) = rows
+            .into_iter()
+            .fold((0, 0.0), |(pixels, total), (row_pixels, row_total)| {
+                (pixels + row_pixels, total + row_total)
+            });
+
+        let count = u64::from(width) * u64::from(height);
+        Ok(DiffResult {
+            image: (pixels > 0).then_some(image),
+            pixels,
+            width,
+            height,
+            similarity: (count > 0).then(|| (total / count as f64) as f32),
+        })
+    }
+}
+
+/// Luma of each pixel, composited onto white.
+pub(super) fn luma(image: &RgbaImage) -> Vec<f64> {
+    image
+        .as_raw()
+        .par_chunks_exact(4)
+        .map(|pixel| {
+            let [r, g, b, a] = pixel else {
+                unreachable!("chunks_exact only yields whole pixels");
+            };
+            let luma = 0.299 * f64::from(*r) + 0.587 * f64::from(*g) + 0.114 * f64::from(*b);
+            let alpha = f64::from(*a) / 255.0;
+            luma * alpha + 255.0 * (1.0 - alpha)
+        })
+        .collect()
+}
+
+/// Summed-area tables of both images, so each window's statistics take constant time.
+struct Sums {
+    stride: usize,
+    old: Vec<f64>,
+    new: Vec<f64>,
+    old_sq: Vec<f64>,
+    new_sq: Vec<f64>,
+    product: Vec<f64>,
+}
+
+impl Sums {
+    fn new(width: u32, old: &[f64], new: &[f64]) -> Self {
+        let width = width as usize;
+        let height = old.len().checked_div(width).unwrap_or(0);
+        let stride = width + 1;
+        let size = stride * (height + 1);
+        let mut sums = Self {
+            stride,
+            old: vec![0.0; size],
+            new: vec![0.0; size],
+            old_sq: vec![0.0; size],
+            new_sq: vec![0.0; size],
+            product: vec![0.0; size],
+        };
+
+        for y in 0..height {
+            for x in 0..width {
+                let (a, b) = (old[y * width + x], new[y * width + x]);
+                let at = (y + 1) * stride + x + 1;
+                let (up, left, diagonal) = (at - stride, at - 1, at - stride - 1);
+                for (table, value) in [
+                    (&mut sums.old, a),
+                    (&mut sums.new, b),
+                    (&mut sums.old_sq, a * a),
+                    (&mut sums.new_sq, b * b),
+                    (&mut sums.product, a * b),
+                ] {
+                    table[at] = value + table[up] + table[left] - table[diagonal];
+                }
+            }
+        }
+        sums
+    }
+
+    fn ssim(&self, x: u32, y: u32, width: u32, height: u32) -> f64 {
+        let x0 = x.saturating_sub(RADIUS) as usize;
+        let y0 = y.saturating_sub(RADIUS) as usize;
+        let x1 = (x + RADIUS + 1).min(width) as usize;
+        let y1 = (y + RADIUS + 1).min(height) as usize;
+        let count = ((x1 - x0) * (y1 - y0)) as f64;
+
+        let sum = |table: &[f64]| {
+            table[y1 * self.stride + x1]
+                - table[y0 * self.stride + x1]
+                - table[y1 * self.stride + x0]
+                + table[y0 * self.stride + x0]
+        };
+
+        let mean_old = sum(&self.old) / count;
+        let mean_new = sum(&self.new) / count;
+        let var_old = sum(&self.old_sq) / count - mean_old * mean_old;
+        let var_new = sum(&self.new_sq) / count - mean_new * mean_new;
+        let covariance = sum(&self.product) / count - mean_old * mean_new;
+
+        ((2.0 * mean_old * mean_new + C1) * (2.0 * covariance + C2))
+            / ((mean_old * mean_old + mean_new * mean_new + C1) * (var_old + var_new + C2))
+    }
+}
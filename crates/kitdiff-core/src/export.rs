@@ -0,0 +1,190 @@
+//! Writing the old, new and diff images of changed snapshots to a folder or zip, e.g. to keep
+//! review evidence around after CI artifacts expire.
+//!
+//! Every snapshot gets a folder named like its path without the extension, so
+//! `tests/snapshots/button.png` ends up as `tests/snapshots/button/{old,new,diff}.png`.
+
+use crate::CancelFlag;
+use crate::diff::{DiffOptions, MinChange, diff_images};
+use crate::snapshot::{FileReference, Snapshot};
+use anyhow::{Context as _, Result};
+use image::ImageFormat;
+use std::io::{Cursor, Seek, Write};
+use std::path::{Component, Path};
+
+/// What [`export_zip`] and [`export_dir`] did.
+#[derive(Debug, Default, Clone, Copy)]
+pub struct ExportSummary {
+    pub exported: usize,
+
+    /// Unchanged, all differences below the threshold, or fewer than the [`MinChange`].
+    pub skipped: usize,
+
+    /// Couldn't be read or diffed, see the log for why.
+    pub failed: usize,
+}
+
+impl std::fmt::Display for ExportSummary {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(
+            f,
+            "Exported {} snapshots, skipped {} unchanged",
+            self.exported, self.skipped
+        )?;
+        if self.failed > 0 {
+            write!(f, ", {} failed", self.failed)?;
+        }
+        Ok(())
+    }
+}
+
+/// The files written for one snapshot, as path in the output and content.
+type Files = Vec<(String, Vec<u8>)>;
+
+/// Writes the snapshots into a zip archive. The snapshots must not reference urls, download them
+/// first.
+pub fn export_zip<W: Write + Seek>(
+    snapshots: &[Snapshot],
+    writer: W,
+    options: &DiffOptions,
+    min_change: MinChange,
+    cancel: &CancelFlag,
+) -> Result<ExportSummary> {
+    let mut zip = zip::ZipWriter::new(writer);
+    // Pngs are compressed already.
+    let file_options =
+        zip::write::SimpleFileOptions::default().compression_method(zip::CompressionMethod::Stored);
+    let summary = export(snapshots, options, min_change, cancel, |files| {
+        for (name, data) in files {
+            zip.start_file(name, file_options)?;
+            zip.write_all(&data)?;
+        }
+        Ok(())
+    })?;
+    zip.finish()?;
+    Ok(summary)
+}
+
+/// Writes the snapshots into `dir`, creating it if needed.
+#[cfg(not(target_arch = "wasm32"))]
+pub fn export_dir(
+    snapshots: &[Snapshot],
+    dir: &Path,
+    options: &DiffOptions,
+    min_change: MinChange,
+    cancel: &CancelFlag,
+) -> Result<ExportSummary> {
+    export(snapshots, options, min_change, cancel, |files| {
+        for (name, data) in files {
+            let path = dir.join(name);
+            if let Some(parent) = path.parent() {
+                std::fs::create_dir_all(parent)?;
+            }
+            std::fs::write(&path, data)
+                .with_context(|| format!("Failed to write {}", path.display()))?;
+        }
+        Ok(())
+    })
+}
+
+fn export(
+    snapshots: &[Snapshot],
+    options: &DiffOptions,
+    min_change: MinChange,
+    cancel: &CancelFlag,
+    mut write: impl FnMut(Files) -> Result<()>,
+) -> Result<ExportSummary> {
+    let mut summary = ExportSummary::default();
+    for snapshot in snapshots {
+        if cancel.is_cancelled() {
+            break;
+        }
+        match snapshot_files(snapshot, options, min_change) {
+            Ok(Some(files)) => {
+                // Failing to write is fatal, the next snapshot won't fare better.
+                write(files)?;
+                summary.exported += 1;
+            }
+            Ok(None) => summary.skipped += 1,
+            Err(err) => {
+                tracing::warn!("Failed to export {}: {err:#}", snapshot.path.display());
+                summary.failed += 1;
+            }
+        }
+    }
+    tracing::info!("{summary}");
+    Ok(summary)
+}
+
+/// `None` if the snapshot didn't change, or less than `min_change`.
+fn snapshot_files(
+    snapshot: &Snapshot,
+    options: &DiffOptions,
+    min_change: MinChange,
+) -> Result<Option<Files>> {
+    if snapshot.unchanged {
+        return Ok(None);
+    }
+    let old = snapshot.old.as_ref().map(read).transpose()?;
+    let new = snapshot.new.as_ref().map(read).transpose()?;
+
+    let diff = match (&snapshot.diff, &old, &new) {
+        // The test wrote the diff already, it only needs to be computed to check its size.
+        (Some(diff), _, _) if !min_change.is_active() => Some(read(diff)?),
+        (diff, Some(old), Some(new)) => {
+            let result = diff_images(old, new, options)?;
+            if !result.is_different() || min_change.hides(result.pixels, result.changed_fraction())
+            {
+                return Ok(None);
+            }
+            match diff {
+                Some(diff) => Some(read(diff)?),
+                None => result.image.map(|image| encode_png(&image)).transpose()?,
+            }
+        }
+        (Some(diff), _, _) => Some(read(diff)?),
+        // Added or deleted, there is nothing to compare.
+        (None, _, _) => None,
+    };
+
+    let dir = folder(&snapshot.path);
+    let files = [("old", old), ("new", new), ("diff", diff)]
+        .into_iter()
+        .filter_map(|(name, data)| Some((format!("{dir}/{name}.png"), data?)))
+        .collect();
+    Ok(Some(files))
+}
+
+/// The folder of a snapshot in the output, with `/` separators as zip archives want them.
+///
+/// Only the normal components are kept, so nothing is written outside of the output.
+pub fn folder(path: &Path) -> String {
+    path.with_extension("")
+        .components()
+        .filter_map(|component| match component {
+            Component::Normal(name) => Some(name.to_string_lossy()),
+            Component::Prefix(_)
+            | Component::RootDir
+            | Component::CurDir
+            | Component::ParentDir => None,
+        })
+        .collect::<Vec<_>>()
+        .join("/")
+}
+
+fn read(file: &FileReference) -> Result<Vec<u8>> {
+    match file {
+        FileReference::Path(path) => {
+            std::fs::read(path).with_context(|| format!("Failed to read {}", path.display()))
+        }
+        FileReference::Bytes { bytes, .. } => Ok(bytes.to_vec()),
+        FileReference::Url(url) => anyhow::bail!("Can't export {url} without downloading it"),
+    }
+}
+
+/// The png bytes of a diff image.
+pub fn encode_png(image: &image::RgbaImage) -> Result<Vec<u8>> {
+    let mut png = Vec::new();
+    image.write_to(&mut Cursor::new(&mut png), ImageFormat::Png)?;
+    Ok(png)
+}
@@ -0,0 +1,286 @@
+//! Snapshots in a directory, as written by kittest: `foo.png` next to `foo.new.png` or
+//! `foo.old.png`, and `foo.diff.png`.
+
+use crate::CancelFlag;
+use crate::path_filter::PathFilter;
+use crate::snapshot::{FileReference, Snapshot};
+use ignore::types::TypesBuilder;
+use ignore::{DirEntry, ParallelVisitor, ParallelVisitorBuilder, WalkBuilder, WalkState};
+use parking_lot::Mutex;
+use std::collections::{HashMap, HashSet};
+use std::ffi::OsString;
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+use std::sync::mpsc::Sender;
+
+/// Walks `base_path` in parallel and sends the snapshots it finds in batches, in no particular
+/// order. Returns once the walk is done or cancelled.
+///
+/// Paths are relative to `base_path`, which is also what `filter` is matched against.
+#[tracing::instrument(name = "discover", skip_all, fields(path = %base_path.display()))]
+pub fn discover(
+    base_path: &Path,
+    filter: &PathFilter,
+    cancel: &CancelFlag,
+    sender: &Sender<Vec<Snapshot>>,
+) {
+    let mut types_builder = TypesBuilder::new();
+    types_builder
+        .add("png", "*.png")
+        .expect("Failed to add png type");
+    types_builder.select("png");
+    let types = types_builder.build().expect("Failed to build types");
+
+    let listings = DirectoryListings::default();
+    let mut builder = SnapshotVisitorBuilder {
+        base_path,
+        filter,
+        cancel,
+        listings: &listings,
+        sender,
+    };
+    let mut walk = WalkBuilder::new(base_path);
+    walk.types(types);
+    skip_excluded_dirs(&mut walk, base_path, filter);
+    walk.build_parallel().visit(&mut builder);
+
+    tracing::debug!("Finished scanning");
+}
+
+/// Every snapshot in `base_path` as it currently renders: the `.new.png` variant if the last test
+/// run wrote one, the committed image otherwise. Snapshots only have a new side.
+///
+/// Unlike [`discover`] this includes snapshots that didn't change, for comparing whole sets of
+/// images against each other.
+pub fn current_images(base_path: &Path, filter: &PathFilter, cancel: &CancelFlag) -> Vec<Snapshot> {
+    let mut walk = WalkBuilder::new(base_path);
+    skip_excluded_dirs(&mut walk, base_path, filter);
+    let mut snapshots = Vec::new();
+    for entry in walk.build().flatten() {
+        if cancel.is_cancelled() {
+            break;
+        }
+        let path = entry.path();
+        let Some(name) = path.file_name().and_then(|name| name.to_str()) else {
+            continue;
+        };
+        if !entry.file_type().is_some_and(|ft| ft.is_file())
+            || !name.ends_with(".png")
+            || [".old.png", ".new.png", ".diff.png"]
+                .iter()
+                .any(|variant| name.ends_with(variant))
+        {
+            continue;
+        }
+        let relative_path = path.strip_prefix(base_path).unwrap_or(path);
+        if !filter.matches(relative_path) {
+            continue;
+        }
+
+        let new_path = path.with_extension("new.png");
+        let current = match std::fs::metadata(&new_path) {
+            // The test was removed.
+            Ok(metadata) if metadata.len() == 0 => continue,
+            Ok(_) => new_path,
+            Err(_) => path.to_path_buf(),
+        };
+        snapshots.push(Snapshot {
+            path: relative_path.to_path_buf(),
+            old: None,
+            new: Some(FileReference::Path(current)),
+            diff: None,
+            unchanged: false,
+        });
+    }
+    snapshots
+}
+
+/// Don't descend into directories the filter excludes entirely, like `target/**`, instead of
+/// walking them only to drop everything found there.
+fn skip_excluded_dirs(walk: &mut WalkBuilder, base_path: &Path, filter: &PathFilter) {
+    let base_path = base_path.to_path_buf();
+    let filter = filter.clone();
+    walk.filter_entry(move |entry| {
+        let is_dir = entry.file_type().is_some_and(|ft| ft.is_dir());
+        let path = entry.path();
+        !is_dir || !filter.excludes_dir(path.strip_prefix(&base_path).unwrap_or(path))
+    });
+}
+
+/// Snapshots are sent in batches of this size, so a huge directory doesn't flood the channel with
+/// one message per file.
+const BATCH_SIZE: usize = 64;
+
+/// File names per directory, so we can check for `.old.png` / `.new.png` / `.diff.png` variants
+/// without a `stat` call per file. That is very slow on network mounts.
+#[derive(Default)]
+struct DirectoryListings {
+    listings: Mutex<HashMap<PathBuf, Arc<HashSet<OsString>>>>,
+}
+
+impl DirectoryListings {
+    fn get(&self, dir: &Path) -> Arc<HashSet<OsString>> {
+        if let Some(listing) = self.listings.lock().get(dir) {
+            return listing.clone();
+        }
+
+        // Read without holding the lock, other threads are busy with other directories.
+        let listing: Arc<HashSet<OsString>> = Arc::new(
+            std::fs::read_dir(dir)
+                .map(|entries| entries.flatten().map(|e| e.file_name()).collect())
+                .unwrap_or_default(),
+        );
+        self.listings
+            .lock()
+            .entry(dir.to_path_buf())
+            .or_insert(listing)
+            .clone()
+    }
+
+    fn contains(&self, path: &Path) -> bool {
+        let (Some(dir), Some(name)) = (path.parent(), path.file_name()) else {
+            return false;
+        };
+        self.get(dir).contains(name)
+    }
+}
+
+struct SnapshotVisitorBuilder<'a> {
+    base_path: &'a Path,
+    filter: &'a PathFilter,
+    cancel: &'a CancelFlag,
+    listings: &'a DirectoryListings,
+    sender: &'a Sender<Vec<Snapshot>>,
+}
+
+impl<'s> ParallelVisitorBuilder<'s> for SnapshotVisitorBuilder<'s> {
+    fn build(&mut self) -> Box<dyn ParallelVisitor + 's> {
+        Box::new(SnapshotVisitor {
+            base_path: self.base_path,
+            filter: self.filter,
+            cancel: self.cancel,
+            listings: self.listings,
+            sender: self.sender,
+            batch: Vec::new(),
+        })
+    }
+}
+
+/// One per walker thread, collects snapshots into batches.
+struct SnapshotVisitor<'a> {
+    base_path: &'a Path,
+    filter: &'a PathFilter,
+    cancel: &'a CancelFlag,
+    listings: &'a DirectoryListings,
+    sender: &'a Sender<Vec<Snapshot>>,
+    batch: Vec<Snapshot>,
+}
+
+impl SnapshotVisitor<'_> {
+    fn flush(&mut self) -> WalkState {
+        if self.batch.is_empty() {
+            return WalkState::Continue;
+        }
+        if self.sender.send(std::mem::take(&mut self.batch)).is_err() {
+            // The receiver was dropped, nobody is interested anymore.
+            WalkState::Quit
+        } else {
+            WalkState::Continue
+        }
+    }
+}
+
+impl ParallelVisitor for SnapshotVisitor<'_> {
+    fn visit(&mut self, entry: Result<DirEntry, ignore::Error>) -> WalkState {
+        if self.cancel.is_cancelled() {
+            return WalkState::Quit;
+        }
+        let Ok(entry) = entry else {
+            return WalkState::Continue;
+        };
+        if entry.file_type().is_some_and(|ft| ft.is_file())
+            && let Some(snapshot) = try_create_snapshot(entry.path(), self.base_path, self.listings)
+            && self.filter.matches(&snapshot.path)
+        {
+            self.batch.push(snapshot);
+            if self.batch.len() >= BATCH_SIZE {
+                return self.flush();
+            }
+        }
+        WalkState::Continue
+    }
+}
+
+impl Drop for SnapshotVisitor<'_> {
+    fn drop(&mut self) {
+        self.flush();
+    }
+}
+
+fn try_create_snapshot(
+    png_path: &Path,
+    base_path: &Path,
+    listings: &DirectoryListings,
+) -> Option<Snapshot> {
+    let file_name = png_path.file_name()?.to_str()?;
+
+    // Skip files that are already variants (.old.png, .new.png, .diff.png)
+    if file_name.ends_with(".old.png")
+        || file_name.ends_with(".new.png")
+        || file_name.ends_with(".diff.png")
+    {
+        return None;
+    }
+
+    // Get base path without .png extension
+    let file_base_path = png_path.with_extension("");
+    let old_path = file_base_path.with_extension("old.png");
+    let new_path = file_base_path.with_extension("new.png");
+    let diff_path = file_base_path.with_extension("diff.png");
+
+    // Create relative path from the base directory
+    let relative_path = png_path.strip_prefix(base_path).unwrap_or(png_path);
+
+    // An empty .new.png marks a snapshot whose test was removed
+    if listings.contains(&new_path) && is_deletion_marker(&new_path) {
+        return Some(Snapshot {
+            path: relative_path.to_path_buf(),
+            old: Some(FileReference::Path(png_path.to_path_buf())),
+            new: None,
+            diff: None,
+            unchanged: false,
+        });
+    }
+
+    // Only create snapshot if diff exists
+    if !listings.contains(&diff_path) {
+        return None;
+    }
+
+    if listings.contains(&old_path) {
+        // old.png exists, use original as new and old.png as old
+        Some(Snapshot {
+            path: relative_path.to_path_buf(),
+            old: Some(FileReference::Path(old_path)),
+            new: Some(FileReference::Path(png_path.to_path_buf())),
+            diff: Some(FileReference::Path(diff_path)),
+            unchanged: false,
+        })
+    } else if listings.contains(&new_path) {
+        // new.png exists, use original as old and new.png as new
+        Some(Snapshot {
+            path: relative_path.to_path_buf(),
+            old: Some(FileReference::Path(png_path.to_path_buf())),
+            new: Some(FileReference::Path(new_path)),
+            diff: Some(FileReference::Path(diff_path)),
+            unchanged: false,
+        })
+    } else {
+        // No old or new variant, skip this snapshot
+        None
+    }
+}
+
+fn is_deletion_marker(path: &Path) -> bool {
+    std::fs::metadata(path).is_ok_and(|metadata| metadata.len() == 0)
+}
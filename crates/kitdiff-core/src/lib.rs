@@ -0,0 +1,35 @@
+//! The ui-independent parts of kitdiff: finding snapshot files and diffing images.
+//!
+//! Nothing in here depends on egui. Long running work reports through plain channels or return
+//! values and can be stopped with a [`CancelFlag`].
+
+pub mod archive;
+pub mod diff;
+pub mod export;
+#[cfg(not(target_arch = "wasm32"))]
+pub mod files;
+pub mod path_filter;
+pub mod phash;
+pub mod platform;
+mod snapshot;
+pub mod test_logs;
+
+pub use snapshot::{FileReference, Snapshot};
+
+use std::sync::Arc;
+use std::sync::atomic::{AtomicBool, Ordering};
+
+/// Set to cancel a running load. Loaders check it between steps and stop early, keeping what they
+/// found so far.
+#[derive(Debug, Clone, Default)]
+pub struct CancelFlag(Arc<AtomicBool>);
+
+impl CancelFlag {
+    pub fn cancel(&self) {
+        self.0.store(true, Ordering::Relaxed);
+    }
+
+    pub fn is_cancelled(&self) -> bool {
+        self.0.load(Ordering::Relaxed)
+    }
+}
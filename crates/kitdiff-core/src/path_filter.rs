@@ -0,0 +1,73 @@
+use globset::{Glob, GlobSet, GlobSetBuilder};
+use std::path::Path;
+
+/// Glob patterns deciding which snapshots are discovered, e.g. `target/**`.
+#[derive(Debug, Clone, Default, PartialEq, Eq, serde::Serialize, serde::Deserialize)]
+#[serde(default)]
+pub struct PathFilterSettings {
+    /// If not empty, only snapshots matching one of these are shown.
+    pub include: Vec<String>,
+
+    /// Snapshots matching one of these are skipped.
+    pub exclude: Vec<String>,
+}
+
+/// Compiled [`PathFilterSettings`], matched against snapshot paths relative to the source root.
+#[derive(Debug, Clone, Default)]
+pub struct PathFilter {
+    include: Option<GlobSet>,
+    exclude: Option<GlobSet>,
+
+    /// The directories of exclude patterns ending in `/**`, everything below them is excluded.
+    exclude_dirs: Option<GlobSet>,
+}
+
+impl PathFilter {
+    pub fn new(settings: &PathFilterSettings) -> Self {
+        let exclude_dirs: Vec<String> = settings
+            .exclude
+            .iter()
+            .filter_map(|pattern| pattern.trim().strip_suffix("/**"))
+            .map(str::to_owned)
+            .collect();
+        Self {
+            include: build(&settings.include),
+            exclude: build(&settings.exclude),
+            exclude_dirs: build(&exclude_dirs),
+        }
+    }
+
+    pub fn matches(&self, path: &Path) -> bool {
+        self.include.as_ref().is_none_or(|set| set.is_match(path))
+            && !self.exclude.as_ref().is_some_and(|set| set.is_match(path))
+    }
+
+    /// Whether nothing below the directory can match, so a walk can skip it.
+    pub fn excludes_dir(&self, dir: &Path) -> bool {
+        self.exclude_dirs
+            .as_ref()
+            .is_some_and(|set| set.is_match(dir))
+    }
+}
+
+/// Invalid patterns are skipped with a warning, so a typo doesn't hide everything.
+fn build(patterns: &[String]) -> Option<GlobSet> {
+    let mut builder = GlobSetBuilder::new();
+    let mut any = false;
+    for pattern in patterns.iter().map(|p| p.trim()).filter(|p| !p.is_empty()) {
+        match Glob::new(pattern) {
+            Ok(glob) => {
+                builder.add(glob);
+                any = true;
+            }
+            Err(err) => tracing::warn!("Invalid glob {pattern:?}: {err}"),
+        }
+    }
+    if !any {
+        return None;
+    }
+    builder
+        .build()
+        .inspect_err(|err| tracing::warn!("Failed to build glob set: {err}"))
+        .ok()
+}
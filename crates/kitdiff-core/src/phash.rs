@@ -0,0 +1,33 @@
+//! Perceptual hashes, which stay close when an image is re-rendered with small changes, unlike a
+//! hash of the file. Used to tell which snapshots show (nearly) the same thing.
+
+use image::DynamicImage;
+use image::imageops::FilterType;
+
+/// Hashes at most this many bits apart are considered the same image.
+pub const SIMILAR_DISTANCE: u32 = 10;
+
+/// A 64 bit difference hash: whether each pixel of a 9x8 grayscale thumbnail is brighter than its
+/// right neighbour.
+pub fn dhash(image: &DynamicImage) -> u64 {
+    let thumbnail = image.resize_exact(9, 8, FilterType::Triangle).to_luma8();
+    let mut hash = 0;
+    for y in 0..8 {
+        for x in 0..8 {
+            let left = thumbnail.get_pixel(x, y).0[0];
+            let right = thumbnail.get_pixel(x + 1, y).0[0];
+            hash = (hash << 1) | u64::from(left > right);
+        }
+    }
+    hash
+}
+
+/// Decodes an encoded image, e.g. a png, and hashes it with [`dhash`].
+pub fn dhash_bytes(bytes: &[u8]) -> anyhow::Result<u64> {
+    Ok(dhash(&image::load_from_memory(bytes)?))
+}
+
+/// Number of differing bits, 0 for identical hashes.
+pub fn distance(a: u64, b: u64) -> u32 {
+    (a ^ b).count_ones()
+}
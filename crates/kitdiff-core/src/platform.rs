@@ -0,0 +1,29 @@
+use std::path::{Path, PathBuf};
+
+/// Platform names and how they show up in directory or artifact names.
+const PLATFORMS: &[(&str, &[&str])] = &[
+    ("linux", &["linux", "ubuntu"]),
+    ("macos", &["macos", "mac", "osx", "darwin"]),
+    ("windows", &["windows", "win", "win32", "win64"]),
+];
+
+/// Splits off a leading platform directory, e.g. `linux/tests/foo.png` or
+/// `snapshots-macos/tests/foo.png` becomes `("macos", "tests/foo.png")`.
+pub fn split_platform(path: &Path) -> Option<(&'static str, PathBuf)> {
+    let mut components = path.components();
+    let first = components.next()?.as_os_str().to_str()?.to_lowercase();
+    let rest = components.as_path();
+    if rest.as_os_str().is_empty() {
+        return None;
+    }
+
+    let platform = first
+        .split(|c: char| !c.is_ascii_alphanumeric())
+        .find_map(|token| {
+            PLATFORMS
+                .iter()
+                .find(|(_, aliases)| aliases.contains(&token))
+                .map(|(name, _)| *name)
+        })?;
+    Some((platform, rest.to_path_buf()))
+}
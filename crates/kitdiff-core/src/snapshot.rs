@@ -0,0 +1,45 @@
+use std::path::PathBuf;
+use std::sync::Arc;
+
+/// A snapshot test image, with the variants to compare.
+#[derive(Debug, Clone)]
+pub struct Snapshot {
+    /// Relative to the root of the source, e.g. `tests/snapshots/button.png`.
+    pub path: PathBuf,
+
+    /// If only old is set, the file was deleted.
+    pub old: Option<FileReference>,
+
+    /// If only new is set, the file was added.
+    pub new: Option<FileReference>,
+
+    /// A diff image that came with the snapshot, e.g. written by kittest.
+    pub diff: Option<FileReference>,
+
+    /// Old and new are byte for byte identical. Kept so reviewers can confirm a screen didn't
+    /// change, but hidden by default.
+    pub unchanged: bool,
+}
+
+impl Snapshot {
+    pub fn added(&self) -> bool {
+        self.old.is_none() && self.new.is_some()
+    }
+
+    pub fn deleted(&self) -> bool {
+        self.old.is_some() && self.new.is_none()
+    }
+}
+
+#[derive(Debug, Clone)]
+pub enum FileReference {
+    Path(PathBuf),
+    Url(String),
+
+    /// In memory, e.g. extracted from an archive.
+    Bytes {
+        /// Unique within the source, e.g. the path in the archive.
+        name: String,
+        bytes: Arc<[u8]>,
+    },
+}
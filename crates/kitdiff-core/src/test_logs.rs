@@ -0,0 +1,108 @@
+//! Finding failed kittest snapshot tests in CI logs, so the snapshots that made a run fail can be
+//! pointed out instead of hunted for.
+
+use anyhow::Result;
+use std::io::{Cursor, Read as _};
+use std::path::Path;
+use zip::ZipArchive;
+
+/// A snapshot that didn't match in a test run.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct FailedSnapshot {
+    /// The name kittest reported, which is the snapshot path relative to the snapshot folder,
+    /// without the extension.
+    pub name: String,
+
+    /// The test that failed, if the log says.
+    pub test: Option<String>,
+
+    /// The line of the log that reported the failure.
+    pub message: String,
+}
+
+impl FailedSnapshot {
+    /// Whether this failure is about the snapshot at `path`.
+    pub fn matches(&self, path: &Path) -> bool {
+        path.with_extension("").ends_with(&self.name)
+    }
+}
+
+/// Reads the logs of a GitHub workflow run, as downloaded from its `logs` endpoint.
+pub fn failed_snapshots_in_zip(data: &[u8]) -> Result<Vec<FailedSnapshot>> {
+    let mut archive = ZipArchive::new(Cursor::new(data))?;
+    let mut failed = Vec::new();
+    for i in 0..archive.len() {
+        let mut file = archive.by_index(i)?;
+        if !file.is_file() || !file.name().ends_with(".txt") {
+            continue;
+        }
+        let mut log = Vec::new();
+        file.read_to_end(&mut log)?;
+        // The job logs are in there once as a whole and once per step.
+        for snapshot in failed_snapshots(&String::from_utf8_lossy(&log)) {
+            if !failed.contains(&snapshot) {
+                failed.push(snapshot);
+            }
+        }
+    }
+    Ok(failed)
+}
+
+/// Finds failures like `'button' Image did not match snapshot. Diff: 12, "…"` in the output of a
+/// test run.
+pub fn failed_snapshots(log: &str) -> Vec<FailedSnapshot> {
+    let mut failed: Vec<FailedSnapshot> = Vec::new();
+    let mut test = None;
+    for line in log.lines() {
+        let line = strip_timestamp(line).trim();
+
+        // libtest prints the output of failed tests below headers like
+        // `---- tests::button stdout ----`, nextest like `--- STDERR: crate tests::button ---`.
+        if let Some(name) = line
+            .strip_prefix("---- ")
+            .and_then(|rest| rest.strip_suffix(" stdout ----"))
+            .or_else(|| {
+                line.strip_prefix("--- STDERR: ")
+                    .or_else(|| line.strip_prefix("--- STDOUT: "))
+                    .and_then(|rest| rest.strip_suffix(" ---"))
+            })
+        {
+            test = Some(name.to_owned());
+            continue;
+        }
+
+        let Some(end) = line.find("' Image") else {
+            continue;
+        };
+        if !line[end..].contains("did not match snapshot") {
+            continue;
+        }
+        let Some(start) = line[..end].rfind('\'') else {
+            continue;
+        };
+        let name = &line[start + 1..end];
+        if name.is_empty() || failed.iter().any(|failure| failure.name == name) {
+            continue;
+        }
+        failed.push(FailedSnapshot {
+            name: name.to_owned(),
+            test: test.clone(),
+            message: line[start..].to_owned(),
+        });
+    }
+    failed
+}
+
+/// GitHub prefixes every line with a timestamp like `2025-01-31T12:00:00.1234567Z`.
+fn strip_timestamp(line: &str) -> &str {
+    match line.split_once(' ') {
+        Some((timestamp, rest))
+            if timestamp.ends_with('Z')
+                && timestamp.starts_with(|c: char| c.is_ascii_digit())
+                && timestamp.contains('T') =>
+        {
+            rest
+        }
+        _ => line,
+    }
+}
@@ -0,0 +1,123 @@
+//! Fixtures for [`PixelmatchJs`], with the results pixelmatch 5 gives for them.
+//!
+//! A gray step of `d` has no chroma, so pixelmatch's YIQ delta is `0.5053 * d²`, against a
+//! `maxDelta` of `35215 * threshold²`.
+
+use image::{Rgba, RgbaImage};
+use kitdiff_core::diff::{Algorithm, DiffAlgorithm as _, DiffOptions, PixelmatchJs};
+
+const CHANGED: Rgba<u8> = Rgba([255, 0, 0, 255]);
+const ANTI_ALIASED: Rgba<u8> = Rgba([255, 255, 0, 255]);
+
+fn options(detect_aa_pixels: bool) -> DiffOptions {
+    DiffOptions {
+        algorithm: Algorithm::PixelmatchJs,
+        threshold: 0.1,
+        detect_aa_pixels,
+        ..Default::default()
+    }
+}
+
+fn pixel(color: [u8; 4]) -> RgbaImage {
+    RgbaImage::from_pixel(1, 1, Rgba(color))
+}
+
+#[test]
+fn threshold() {
+    // 0.5053 * 26² = 341.6 stays below 35215 * 0.1² = 352.2, 0.5053 * 27² = 368.4 doesn't.
+    let below = PixelmatchJs
+        .diff(
+            pixel([0, 0, 0, 255]),
+            pixel([26, 26, 26, 255]),
+            &options(false),
+        )
+        .unwrap();
+    assert_eq!(below.pixels, 0);
+    // Faded black: 255 + (0 - 255) * 0.1 = 229.5, rounded to even like a Uint8ClampedArray.
+    assert_eq!(
+        *below.image.unwrap().get_pixel(0, 0),
+        Rgba([230, 230, 230, 255])
+    );
+
+    let above = PixelmatchJs
+        .diff(
+            pixel([0, 0, 0, 255]),
+            pixel([27, 27, 27, 255]),
+            &options(false),
+        )
+        .unwrap();
+    assert_eq!(above.pixels, 1);
+    assert_eq!(*above.image.unwrap().get_pixel(0, 0), CHANGED);
+}
+
+#[test]
+fn default_threshold() {
+    // pixelmatch's default of 0.1, not the 1.0 of the other algorithms, which flags nothing here.
+    let options = DiffOptions::for_algorithm(Algorithm::PixelmatchJs);
+    assert_eq!(options.threshold, 0.1);
+    let result = PixelmatchJs
+        .diff(pixel([0, 0, 0, 255]), pixel([27, 27, 27, 255]), &options)
+        .unwrap();
+    assert_eq!(result.pixels, 1);
+}
+
+#[test]
+fn identical_images_are_faded() {
+    // pixelmatch still draws the gray image when nothing changed.
+    let result = PixelmatchJs
+        .diff(pixel([0, 0, 0, 255]), pixel([0, 0, 0, 255]), &options(true))
+        .unwrap();
+    assert_eq!(result.pixels, 0);
+    assert_eq!(
+        *result.image.unwrap().get_pixel(0, 0),
+        Rgba([230, 230, 230, 255])
+    );
+}
+
+#[test]
+fn translucent_pixels_blend_with_white() {
+    // Fully transparent black is white on pixelmatch 5's white background.
+    let result = PixelmatchJs
+        .diff(
+            pixel([0, 0, 0, 0]),
+            pixel([255, 255, 255, 255]),
+            &options(false),
+        )
+        .unwrap();
+    assert_eq!(result.pixels, 0);
+    assert_eq!(
+        *result.image.unwrap().get_pixel(0, 0),
+        Rgba([255, 255, 255, 255])
+    );
+}
+
+/// A black and a white area with a gray edge between them, in column 2.
+fn edge(gray: u8) -> RgbaImage {
+    RgbaImage::from_fn(5, 5, |x, _| match x {
+        0 | 1 => Rgba([0, 0, 0, 255]),
+        2 => Rgba([gray, gray, gray, 255]),
+        _ => Rgba([255, 255, 255, 255]),
+    })
+}
+
+#[test]
+fn anti_aliased_edge() {
+    // 0.5053 * 28² = 396.2 is above the threshold, but the edge has a darker and a brighter
+    // neighbor, each with many siblings in both images.
+    let result = PixelmatchJs
+        .diff(edge(128), edge(100), &options(true))
+        .unwrap();
+    assert_eq!(result.pixels, 0);
+    let image = result.image.unwrap();
+    for y in 0..5 {
+        assert_eq!(*image.get_pixel(2, y), ANTI_ALIASED, "row {y}");
+        assert_eq!(*image.get_pixel(0, y), Rgba([230, 230, 230, 255]));
+        assert_eq!(*image.get_pixel(4, y), Rgba([255, 255, 255, 255]));
+    }
+
+    // With `includeAA`, the whole edge counts.
+    let result = PixelmatchJs
+        .diff(edge(128), edge(100), &options(false))
+        .unwrap();
+    assert_eq!(result.pixels, 5);
+}
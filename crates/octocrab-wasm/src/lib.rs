@@ -1,13 +1,15 @@
-#[cfg(target_arch = "wasm32")]
 mod reqwest_tower_service;
 #[cfg(target_arch = "wasm32")]
 pub mod wasm;
 
+pub use reqwest_tower_service::ReqwestTowerService;
 #[cfg(target_arch = "wasm32")]
 pub use wasm::wasm_builder;
 
 #[cfg(not(target_arch = "wasm32"))]
-use octocrab::{DefaultOctocrabBuilderConfig, NoAuth, NoSvc, NotLayerReady};
+use octocrab::{
+    AuthState, DefaultOctocrabBuilderConfig, LayerReady, NoAuth, NoConfig, NoSvc, NotLayerReady,
+};
 #[cfg(not(target_arch = "wasm32"))]
 pub fn builder()
 -> octocrab::OctocrabBuilder<NoSvc, DefaultOctocrabBuilderConfig, NoAuth, NotLayerReady> {
@@ -16,3 +18,18 @@ pub fn builder()
 
 #[cfg(target_arch = "wasm32")]
 pub use wasm::wasm_builder as builder;
+
+/// A builder that sends its requests with `client`, so they follow its proxy and certificate
+/// settings.
+#[cfg(not(target_arch = "wasm32"))]
+pub fn reqwest_builder(
+    client: reqwest::Client,
+) -> octocrab::OctocrabBuilder<ReqwestTowerService, NoConfig, AuthState, LayerReady> {
+    let service = ReqwestTowerService {
+        base_url: Some(("https".parse().unwrap(), "api.github.com".parse().unwrap())),
+        client,
+    };
+    octocrab::OctocrabBuilder::new_empty()
+        .with_service(service)
+        .with_auth(AuthState::None)
+}
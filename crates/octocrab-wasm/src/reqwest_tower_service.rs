@@ -44,7 +44,9 @@ where
     fn call(&mut self, req: http::Request<Body>) -> Self::Future {
         let Self { base_url, client } = self.clone();
 
-        Box::pin(async move {
+        // reqwest's futures aren't Send on the web, so they run on the local executor.
+        #[cfg(target_arch = "wasm32")]
+        return Box::pin(async move {
             let (tx, rx) = futures::channel::oneshot::channel();
 
             wasm_bindgen_futures::spawn_local(async move {
@@ -55,7 +57,10 @@ where
             let response = rx.await??;
 
             Ok(response)
-        })
+        });
+
+        #[cfg(not(target_arch = "wasm32"))]
+        Box::pin(call(client, base_url, req))
     }
 }
 
@@ -96,10 +101,12 @@ where
 
     let reqwest_response = client.execute(request).await?;
 
+    let status = reqwest_response.status();
     let headers = reqwest_response.headers().clone();
 
     let bytes = reqwest_response.bytes().await?;
     let mut response = http::Response::new(BoxBody::new(http_body_util::Full::new(bytes)));
+    *response.status_mut() = status;
     *response.headers_mut() = headers;
 
     Ok(response)
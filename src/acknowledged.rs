@@ -0,0 +1,194 @@
+//! Known differences that aren't fixed or accepted yet, but shouldn't drown out new regressions.
+//! An acknowledgement is for one new image: once its content changes, the snapshot shows up as
+//! changed again.
+
+use crate::flaky::source_key;
+use crate::loaders::LoadSnapshots;
+use crate::notifications::Notification;
+use crate::snapshot::{FileReference, Snapshot};
+use crate::state::ViewerAppStateRef;
+use eframe::egui::Context;
+use eframe::egui::mutex::Mutex;
+use std::collections::{HashMap, HashSet};
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+
+/// The acknowledged diffs of a source: by snapshot path, the hash of the new image.
+#[derive(Default, serde::Serialize, serde::Deserialize)]
+struct Stored {
+    key: String,
+    hashes: HashMap<PathBuf, u64>,
+}
+
+/// Checks the acknowledged snapshots once per load, whether their new image is still the one
+/// that was acknowledged.
+#[derive(Default)]
+pub struct Acknowledgements {
+    /// The snapshots of this load whose new image is the acknowledged one.
+    current: Arc<Mutex<HashSet<PathBuf>>>,
+    checked: bool,
+}
+
+impl Acknowledgements {
+    /// Check the current load once the loader is done.
+    pub fn update(&mut self, ctx: &Context, loader: &dyn LoadSnapshots) {
+        if self.checked || !loader.state().is_ready() {
+            return;
+        }
+        self.checked = true;
+
+        let stored = read(&source_key(loader));
+        let acknowledged: Vec<(PathBuf, FileReference, u64)> = loader
+            .snapshots()
+            .iter()
+            .filter_map(|snapshot| {
+                let hash = stored.hashes.get(&snapshot.path)?;
+                Some((snapshot.path.clone(), snapshot.new.clone()?, *hash))
+            })
+            .collect();
+        if acknowledged.is_empty() {
+            return;
+        }
+
+        let current = self.current.clone();
+        let ctx = ctx.clone();
+        hello_egui_utils::spawn(async move {
+            for (path, new, hash) in acknowledged {
+                match content_hash(&new).await {
+                    Ok(Some(new_hash)) if new_hash == hash => {
+                        current.lock().insert(path);
+                        ctx.request_repaint();
+                    }
+                    Ok(_) => {}
+                    Err(err) => {
+                        tracing::warn!("Failed to check {}: {err:#}", path.display());
+                    }
+                }
+            }
+        });
+    }
+
+    /// Check the next load again, e.g. after a refresh. The checks of this load still running
+    /// keep updating the old set.
+    pub fn reset(&mut self) {
+        *self = Self::default();
+    }
+
+    pub fn is_acknowledged(&self, path: &Path) -> bool {
+        self.current.lock().contains(path)
+    }
+
+    pub fn len(&self) -> usize {
+        self.current.lock().len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.current.lock().is_empty()
+    }
+}
+
+/// Acknowledge the diff of the snapshot as its new image is now, or remove the acknowledgement.
+pub fn set_acknowledged(state: &ViewerAppStateRef<'_>, snapshot: &Snapshot, acknowledged: bool) {
+    let key = source_key(&*state.loader);
+    let path = snapshot.path.clone();
+    let current = state.acknowledged.current.clone();
+    if !acknowledged {
+        let mut stored = read(&key);
+        stored.hashes.remove(&path);
+        write(&stored);
+        current.lock().remove(&path);
+        return;
+    }
+
+    let Some(new) = snapshot.new.clone() else {
+        return;
+    };
+    let ctx = state.app.egui_ctx.clone();
+    let tx = state.app.tx.clone();
+    hello_egui_utils::spawn(async move {
+        match content_hash(&new).await {
+            Ok(Some(hash)) => {
+                let mut stored = read(&key);
+                stored.hashes.insert(path.clone(), hash);
+                write(&stored);
+                current.lock().insert(path);
+                ctx.request_repaint();
+            }
+            Ok(None) => {
+                tx.send(
+                    Notification::error("Failed to acknowledge the diff", "No new image").into(),
+                )
+                .ok();
+            }
+            Err(err) => {
+                tx.send(Notification::error("Failed to acknowledge the diff", err).into())
+                    .ok();
+            }
+        }
+    });
+}
+
+/// XXH3 of the encoded file, re-encoding the same pixels counts as a change too.
+async fn content_hash(file: &FileReference) -> anyhow::Result<Option<u64>> {
+    Ok(file
+        .read()
+        .await?
+        .map(|bytes| xxhash_rust::xxh3::xxh3_64(&bytes)))
+}
+
+#[cfg(not(target_arch = "wasm32"))]
+static CACHE: crate::cache::CacheDir = crate::cache::CacheDir::new("acknowledged", 16 << 20);
+
+#[cfg(not(target_arch = "wasm32"))]
+fn stored_path(key: &str) -> Option<PathBuf> {
+    let hash = xxhash_rust::xxh3::xxh3_64(key.as_bytes());
+    Some(CACHE.path()?.join(format!("{hash:016x}.json")))
+}
+
+#[cfg(not(target_arch = "wasm32"))]
+fn read(key: &str) -> Stored {
+    stored_path(key)
+        .and_then(|path| {
+            let bytes = std::fs::read(&path).ok()?;
+            crate::cache::CacheDir::touch(&path);
+            Some(bytes)
+        })
+        .and_then(|bytes| serde_json::from_slice::<Stored>(&bytes).ok())
+        // Guards against hash collisions.
+        .filter(|stored| stored.key == key)
+        .unwrap_or_else(|| Stored {
+            key: key.to_owned(),
+            hashes: HashMap::new(),
+        })
+}
+
+#[cfg(not(target_arch = "wasm32"))]
+fn write(stored: &Stored) {
+    let Some(path) = stored_path(&stored.key) else {
+        return;
+    };
+    let result = path
+        .parent()
+        .map_or(Ok(()), std::fs::create_dir_all)
+        .and_then(|()| {
+            let json = serde_json::to_vec(stored)?;
+            std::fs::write(&path, &json)?;
+            CACHE.wrote(json.len() as u64);
+            Ok(())
+        });
+    if let Err(err) = result {
+        tracing::warn!("Failed to write {}: {err}", path.display());
+    }
+}
+
+/// The browser has no cache dir, acknowledgements only last as long as the page.
+#[cfg(target_arch = "wasm32")]
+fn read(key: &str) -> Stored {
+    Stored {
+        key: key.to_owned(),
+        hashes: HashMap::new(),
+    }
+}
+
+#[cfg(target_arch = "wasm32")]
+fn write(_stored: &Stored) {}
@@ -0,0 +1,90 @@
+//! Rectangles, arrows and notes drawn on top of a snapshot, to point out what's wrong with it.
+//!
+//! Positions are in image pixels, so annotations stay in place when the view is zoomed or
+//! resized.
+
+use eframe::egui::emath::RectTransform;
+use eframe::egui::{Align2, Color32, FontId, Painter, Pos2, Rect, Stroke, StrokeKind, Vec2};
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum AnnotationTool {
+    Rectangle,
+    Arrow,
+    Text,
+}
+
+impl AnnotationTool {
+    pub const ALL: [Self; 3] = [Self::Rectangle, Self::Arrow, Self::Text];
+
+    pub fn icon(self) -> &'static str {
+        match self {
+            Self::Rectangle => "▭",
+            Self::Arrow => "➡",
+            Self::Text => "T",
+        }
+    }
+}
+
+impl std::fmt::Display for AnnotationTool {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str(match self {
+            Self::Rectangle => "Rectangle",
+            Self::Arrow => "Arrow",
+            Self::Text => "Text",
+        })
+    }
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub enum Shape {
+    Rectangle(Rect),
+    Arrow { from: Pos2, to: Pos2 },
+    Text { pos: Pos2, text: String },
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct Annotation {
+    pub shape: Shape,
+    pub color: Color32,
+}
+
+const STROKE_WIDTH: f32 = 2.0;
+const FONT_SIZE: f32 = 16.0;
+
+/// Paint `annotations`, mapping image pixels to the screen with `to_screen`.
+///
+/// Strokes and text keep their size on screen, no matter how far the image is zoomed.
+pub fn paint(painter: &Painter, to_screen: RectTransform, annotations: &[Annotation]) {
+    for annotation in annotations {
+        let stroke = Stroke::new(STROKE_WIDTH, annotation.color);
+        match &annotation.shape {
+            Shape::Rectangle(rect) => {
+                painter.rect_stroke(
+                    to_screen.transform_rect(*rect),
+                    0.0,
+                    stroke,
+                    StrokeKind::Outside,
+                );
+            }
+            Shape::Arrow { from, to } => {
+                let from = to_screen * *from;
+                let to = to_screen * *to;
+                painter.arrow(from, to - from, stroke);
+            }
+            Shape::Text { pos, text } => {
+                let pos = to_screen * *pos;
+                let galley = painter.layout_no_wrap(
+                    text.clone(),
+                    FontId::proportional(FONT_SIZE),
+                    annotation.color,
+                );
+                // A backdrop, so the note stays readable on busy snapshots.
+                let rect = Align2::LEFT_TOP
+                    .anchor_size(pos, galley.size())
+                    .expand2(Vec2::splat(3.0));
+                painter.rect_filled(rect, 2.0, Color32::from_black_alpha(180));
+                painter.galley(pos, galley, annotation.color);
+            }
+        }
+    }
+}
@@ -1,18 +1,50 @@
+use crate::archive_index::ArchiveEntryLoader;
 use crate::diff_image_loader::DiffImageLoader;
+use crate::github::auth::AuthState;
+use crate::http::HttpLoader;
 use crate::settings::Settings;
+use crate::shared_bytes::SharedBytesLoader;
 use crate::state::{AppState, AppStateRef, PageRef, SystemCommand, ViewerSystemCommand};
+use crate::theme::Themes;
+use crate::thumbnail_loader::ThumbnailLoader;
+use crate::tile_loader::TileLoader;
 use crate::{DiffSource, bar, home, viewer};
 use crate::{config::Config, state::View};
-use eframe::egui::{Context, Modifiers, Ui};
+use eframe::egui::{Context, KeyboardShortcut, Modifiers, Ui};
 use eframe::{Frame, Storage, egui};
 use egui_extras::install_image_loaders;
 use egui_inbox::UiInbox;
 use std::sync::Arc;
 
+/// Auth is stored apart from [`Settings`], so settings can be shared without the login.
+const AUTH_KEY: &str = "auth";
+
+/// See [`crate::session`].
+const SESSION_KEY: &str = "session";
+
+const UNDO_SHORTCUT: KeyboardShortcut = KeyboardShortcut::new(Modifiers::COMMAND, egui::Key::Z);
+const REDO_SHORTCUT: KeyboardShortcut =
+    KeyboardShortcut::new(Modifiers::COMMAND.plus(Modifiers::SHIFT), egui::Key::Z);
+const REDO_SHORTCUT_ALT: KeyboardShortcut = KeyboardShortcut::new(Modifiers::COMMAND, egui::Key::Y);
+
+fn load_auth(storage: &dyn Storage) -> Option<AuthState> {
+    /// Before auth had its own key, it was a field of the settings.
+    #[derive(serde::Deserialize)]
+    struct LegacySettings {
+        auth: AuthState,
+    }
+
+    eframe::get_value(storage, AUTH_KEY).or_else(|| {
+        eframe::get_value::<LegacySettings>(storage, eframe::APP_KEY).map(|legacy| legacy.auth)
+    })
+}
+
 pub struct App {
     diff_loader: Arc<DiffImageLoader>,
+    http_loader: Arc<HttpLoader>,
     state: AppState,
     inbox: UiInbox<SystemCommand>,
+    themes: Themes,
 }
 
 impl App {
@@ -22,18 +54,45 @@ impl App {
         config: Config,
     ) -> Self {
         re_ui::apply_style_and_install_loaders(&cc.egui_ctx);
+        let themes = Themes::new(&cc.egui_ctx);
 
         let settings: Settings = cc
             .storage
             .and_then(|s| eframe::get_value(s, eframe::APP_KEY))
             .unwrap_or_default();
+        let auth = cc.storage.and_then(load_auth).unwrap_or_default();
 
         let inbox = UiInbox::new();
-        let state = AppState::new(settings, config, inbox.sender());
+        let mut state = AppState::new(settings, auth, config, inbox.sender());
+        // These tokens used to be plain settings, the keychain is the place for them.
+        let legacy_token = std::mem::take(&mut state.settings.jenkins.legacy_api_token);
+        if state.github_auth.jenkins_api_token().is_none() {
+            state.github_auth.set_jenkins_api_token(Some(legacy_token));
+        }
+        let legacy_token = std::mem::take(&mut state.settings.legacy_circleci_token);
+        if state.github_auth.circleci_token().is_none() {
+            state.github_auth.set_circleci_token(Some(legacy_token));
+        }
+        state.previous_session = cc.storage.and_then(|s| eframe::get_value(s, SESSION_KEY));
 
         install_image_loaders(&cc.egui_ctx);
-        let diff_loader = Arc::new(DiffImageLoader::default());
+        // Added after egui's http loader, so it's asked first.
+        let http_loader = Arc::new(HttpLoader::default());
+        cc.egui_ctx.add_bytes_loader(http_loader.clone());
+        cc.egui_ctx
+            .add_bytes_loader(Arc::new(ArchiveEntryLoader::default()));
+        cc.egui_ctx.add_bytes_loader(Arc::new(SharedBytesLoader));
+        #[cfg(not(target_arch = "wasm32"))]
+        cc.egui_ctx.add_image_loader(Arc::new(
+            crate::file_image_loader::FileImageLoader::default(),
+        ));
+        // Shares the decoded images with the viewer, through the loaders installed above.
+        let diff_loader = Arc::new(DiffImageLoader::new(&cc.egui_ctx));
         cc.egui_ctx.add_image_loader(diff_loader.clone());
+        cc.egui_ctx
+            .add_image_loader(Arc::new(ThumbnailLoader::new(diff_loader.clone())));
+        cc.egui_ctx
+            .add_image_loader(Arc::new(TileLoader::new(diff_loader.clone())));
 
         if let Some(source) = source {
             inbox.sender().send(SystemCommand::Open(source)).ok();
@@ -41,23 +100,53 @@ impl App {
 
         Self {
             diff_loader,
+            http_loader,
             state,
             inbox,
+            themes,
         }
     }
+
+    /// Use the given token for all GitHub requests instead of the logged-in user's token.
+    #[must_use]
+    pub fn with_github_token(mut self, token: Option<String>) -> Self {
+        self.state.github_auth.set_token_override(token);
+        self
+    }
+
+    /// Accept commands from other programs on this port of localhost.
+    #[cfg(not(target_arch = "wasm32"))]
+    #[must_use]
+    pub fn with_remote_control(
+        self,
+        remote_control: Option<(u16, crate::remote_control::Access)>,
+    ) -> Self {
+        if let Some((port, access)) = remote_control
+            && let Err(err) = crate::remote_control::serve(port, access, self.inbox.sender())
+        {
+            tracing::error!("Failed to start the remote control on port {port}: {err:#}");
+        }
+        self
+    }
 }
 
 impl eframe::App for App {
     fn save(&mut self, storage: &mut dyn Storage) {
         eframe::set_value(storage, eframe::APP_KEY, &self.state.persist());
+        eframe::set_value(storage, AUTH_KEY, &self.state.github_auth.persisted_state());
+        eframe::set_value(storage, SESSION_KEY, &self.state.session());
     }
 
     fn ui(&mut self, ui: &mut Ui, _frame: &mut Frame) {
         let ctx = ui.ctx().clone();
         self.state.update(&ctx);
+        self.http_loader
+            .set_token(self.state.github_auth.get_token());
         self.inbox.read(&ctx).for_each(|cmd| {
             self.state.handle(&ctx, cmd);
         });
+        self.state.update_background_work(&ctx, &self.diff_loader);
+        self.themes.update(&ctx, self.state.settings.high_contrast);
 
         {
             let state_ref = self
@@ -66,6 +155,12 @@ impl eframe::App for App {
 
             bar::bar(ui, &state_ref);
 
+            if state_ref.notifications.log_open {
+                egui::Panel::bottom("notification_log")
+                    .resizable(true)
+                    .show_inside(ui, |ui| state_ref.notifications.log_ui(ui, &state_ref));
+            }
+
             match &state_ref.page {
                 PageRef::Home => {
                     home::home_view(ui, &state_ref);
@@ -75,6 +170,9 @@ impl eframe::App for App {
                 }
             }
 
+            state_ref.notifications.toasts_ui(&ctx, &state_ref);
+            crate::confirm::confirm_modal(&ctx, &state_ref);
+
             Self::end_frame(&ctx, &state_ref);
         }
 
@@ -173,6 +271,20 @@ impl App {
                     state.send(ViewerSystemCommand::SelectSnapshot(new_index));
                 }
 
+                if ctx.wants_keyboard_input() {
+                    // Typing a number into a text field shouldn't switch the view.
+                    return;
+                }
+
+                // Redo first, Ctrl+Shift+Z also matches the undo shortcut.
+                if ctx.input_mut(|i| {
+                    i.consume_shortcut(&REDO_SHORTCUT) || i.consume_shortcut(&REDO_SHORTCUT_ALT)
+                }) {
+                    state.send(ViewerSystemCommand::Redo);
+                } else if ctx.input_mut(|i| i.consume_shortcut(&UNDO_SHORTCUT)) {
+                    state.send(ViewerSystemCommand::Undo);
+                }
+
                 let mut new_view = vs.state.view;
                 for view in View::ALL {
                     if ctx.input_mut(|i| i.consume_key(Default::default(), view.key())) {
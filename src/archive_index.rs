@@ -0,0 +1,237 @@
+//! Opening an archive again without extracting it.
+//!
+//! The first time a zip archive is opened, where its png files are is kept in the cache, by the
+//! hash of the archive. Opening the same archive again pairs up the snapshots from that index,
+//! and each file is only extracted once it's viewed, by [`ArchiveEntryLoader`].
+
+use crate::loaders::CancelFlag;
+use bytes::Bytes;
+use eframe::egui::Context;
+use eframe::egui::load::{BytesLoadResult, BytesLoader, BytesPoll, LoadError};
+use eframe::egui::mutex::Mutex;
+use eframe::epaint::ahash::HashMap;
+use kitdiff_core::archive::{ArchiveIndex, IndexEntry, content_hash};
+use std::path::PathBuf;
+use std::sync::{Arc, LazyLock, Weak};
+use std::task::Poll;
+
+const PREFIX: &str = "archive://";
+
+/// The archives opened from their index, by key. Weak, the snapshots referencing an archive keep
+/// it alive.
+static ARCHIVES: LazyLock<Mutex<HashMap<u64, Weak<Archive>>>> = LazyLock::new(Mutex::default);
+
+/// A zip archive opened from its index.
+pub struct Archive {
+    key: u64,
+    data: Bytes,
+    entries: HashMap<PathBuf, IndexEntry>,
+}
+
+/// A png file of an [`Archive`] that may not be extracted yet.
+#[derive(Clone)]
+pub struct ArchiveEntry {
+    archive: Arc<Archive>,
+    entry: IndexEntry,
+}
+
+impl std::fmt::Debug for ArchiveEntry {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("ArchiveEntry")
+            .field("path", &self.entry.path)
+            .finish_non_exhaustive()
+    }
+}
+
+impl ArchiveEntry {
+    /// The uri of an archive entry, looked up with [`Self::from_uri`].
+    pub fn uri(&self) -> String {
+        entry_uri(self.archive.key, &self.entry)
+    }
+
+    /// The entry behind the uri, if its archive is still open.
+    pub fn from_uri(uri: &str) -> Option<Self> {
+        let (key, path) = uri.strip_prefix(PREFIX)?.split_once('/')?;
+        let key = u64::from_str_radix(key, 16).ok()?;
+        let mut archives = ARCHIVES.lock();
+        let Some(archive) = archives.get(&key)?.upgrade() else {
+            archives.remove(&key);
+            return None;
+        };
+        let entry = archive.entries.get(&PathBuf::from(path))?.clone();
+        Some(Self { archive, entry })
+    }
+
+    /// Size of the extracted file.
+    pub fn size(&self) -> u64 {
+        self.entry.size
+    }
+
+    /// Extract the file.
+    pub fn read(&self) -> anyhow::Result<Vec<u8>> {
+        kitdiff_core::archive::read_entry(&self.archive.data, &self.entry)
+    }
+}
+
+fn entry_uri(key: u64, entry: &IndexEntry) -> String {
+    format!("{PREFIX}{key:016x}/{}", entry.path.display())
+}
+
+/// Like [`kitdiff_core::archive::discover`], but from the index of an archive that was opened
+/// before. Files of those snapshots are [`kitdiff_core::FileReference::Url`]s of
+/// [`ArchiveEntry`]s, which only resolve while the returned archive is alive.
+pub fn discover(
+    data: Bytes,
+    cancel: &CancelFlag,
+) -> anyhow::Result<(Vec<kitdiff_core::Snapshot>, Option<Arc<Archive>>)> {
+    let key = content_hash(&data);
+    if let Some(index) = read(key) {
+        tracing::debug!("Opening the archive from its index");
+        let snapshots = kitdiff_core::archive::snapshots_from_index(&index, |entry| {
+            kitdiff_core::FileReference::Url(entry_uri(key, entry))
+        });
+        let archive = Arc::new(Archive {
+            key,
+            data,
+            entries: index
+                .entries
+                .into_iter()
+                .map(|entry| (entry.path.clone(), entry))
+                .collect(),
+        });
+        ARCHIVES.lock().insert(key, Arc::downgrade(&archive));
+        return Ok((snapshots, Some(archive)));
+    }
+
+    let (snapshots, index) = kitdiff_core::archive::discover_indexed(&data, cancel)?;
+    if let Some(index) = index {
+        write(key, &index);
+    }
+    Ok((snapshots, None))
+}
+
+#[cfg(not(target_arch = "wasm32"))]
+static CACHE: crate::cache::CacheDir = crate::cache::CacheDir::new("archives", 64 << 20);
+
+#[cfg(not(target_arch = "wasm32"))]
+fn index_path(key: u64) -> Option<PathBuf> {
+    Some(CACHE.path()?.join(format!("{key:016x}.json")))
+}
+
+#[cfg(not(target_arch = "wasm32"))]
+fn read(key: u64) -> Option<ArchiveIndex> {
+    let path = index_path(key)?;
+    let bytes = std::fs::read(&path).ok()?;
+    crate::cache::CacheDir::touch(&path);
+    serde_json::from_slice(&bytes).ok()
+}
+
+#[cfg(not(target_arch = "wasm32"))]
+fn write(key: u64, index: &ArchiveIndex) {
+    let Some(path) = index_path(key) else {
+        return;
+    };
+    let result = path
+        .parent()
+        .map_or(Ok(()), std::fs::create_dir_all)
+        .and_then(|()| {
+            let json = serde_json::to_vec(index)?;
+            std::fs::write(&path, &json)?;
+            CACHE.wrote(json.len() as u64);
+            Ok(())
+        });
+    if let Err(err) = result {
+        tracing::warn!("Failed to write {}: {err}", path.display());
+    }
+}
+
+/// The browser has no cache dir, archives are extracted every time.
+#[cfg(target_arch = "wasm32")]
+fn read(_key: u64) -> Option<ArchiveIndex> {
+    None
+}
+
+#[cfg(target_arch = "wasm32")]
+fn write(_key: u64, _index: &ArchiveIndex) {}
+
+type Extracted = HashMap<String, Poll<Result<Arc<[u8]>, String>>>;
+
+/// Extracts the files of `archive://` uris when they're loaded. Forgetting them frees the
+/// extracted bytes, the archive itself stays in memory.
+#[derive(Default)]
+pub struct ArchiveEntryLoader {
+    extracted: Arc<Mutex<Extracted>>,
+}
+
+impl BytesLoader for ArchiveEntryLoader {
+    fn id(&self) -> &'static str {
+        "ArchiveEntryLoader"
+    }
+
+    fn load(&self, ctx: &Context, uri: &str) -> BytesLoadResult {
+        if !uri.starts_with(PREFIX) {
+            return Err(LoadError::NotSupported);
+        }
+
+        let mut extracted = self.extracted.lock();
+        match extracted.get(uri) {
+            Some(Poll::Ready(Ok(bytes))) => {
+                return Ok(BytesPoll::Ready {
+                    size: None,
+                    bytes: eframe::egui::load::Bytes::Shared(bytes.clone()),
+                    mime: Some("image/png".to_owned()),
+                });
+            }
+            Some(Poll::Ready(Err(err))) => return Err(LoadError::Loading(err.clone())),
+            Some(Poll::Pending) => return Ok(BytesPoll::Pending { size: None }),
+            None => {}
+        }
+        let Some(entry) = ArchiveEntry::from_uri(uri) else {
+            return Err(LoadError::Loading(format!("{uri} is not open anymore")));
+        };
+        extracted.insert(uri.to_owned(), Poll::Pending);
+        drop(extracted);
+
+        let extracted = self.extracted.clone();
+        let ctx = ctx.clone();
+        let uri = uri.to_owned();
+        let work = move || {
+            let result = entry
+                .read()
+                .map(Arc::from)
+                .map_err(|err| format!("{err:#}"));
+            // Unless it was forgotten while we were extracting it.
+            if let Some(file) = extracted.lock().get_mut(&uri) {
+                *file = Poll::Ready(result);
+            }
+            ctx.request_repaint();
+        };
+
+        // Reads from the archive file, so not on rayon's pool, which the diffs need.
+        #[cfg(not(target_arch = "wasm32"))]
+        tokio::task::spawn_blocking(work);
+        #[cfg(target_arch = "wasm32")]
+        work();
+
+        Ok(BytesPoll::Pending { size: None })
+    }
+
+    fn forget(&self, uri: &str) {
+        self.extracted.lock().remove(uri);
+    }
+
+    fn forget_all(&self) {
+        self.extracted.lock().clear();
+    }
+
+    fn byte_size(&self) -> usize {
+        self.extracted
+            .lock()
+            .values()
+            .map(|file| match file {
+                Poll::Ready(Ok(bytes)) => bytes.len(),
+                _ => 0,
+            })
+            .sum()
+    }
+}
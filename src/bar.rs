@@ -1,7 +1,9 @@
-use crate::github::auth::{GitHubAuth, GithubAuthCommand};
-use crate::state::AppStateRef;
+use crate::github::auth::{GitHubAuth, GithubAuthCommand, TokenSource};
+use crate::notifications::NotificationCommand;
+use crate::settings_file;
+use crate::state::{AppStateRef, PageRef, SystemCommand};
 use eframe::egui;
-use eframe::egui::{Popup, Ui};
+use eframe::egui::{Id, Popup, TextEdit, Ui};
 
 pub fn bar(ui: &mut Ui, state: &AppStateRef<'_>) {
     egui::Panel::top("top bar")
@@ -9,7 +11,18 @@ pub fn bar(ui: &mut Ui, state: &AppStateRef<'_>) {
         .show_inside(ui, |ui| {
             egui::Sides::new().show(
                 ui,
-                |_ui| {},
+                |ui| {
+                    if matches!(state.page, PageRef::DiffViewer(_))
+                        && ui
+                            .button("⏴ Home")
+                            .on_hover_text("Close the snapshots and free the memory they use")
+                            .clicked()
+                    {
+                        state.send(SystemCommand::Close);
+                    }
+                    notifications_button(ui, state);
+                    settings_menu(ui, state);
+                },
                 |ui| {
                     auth_ui(ui, state);
                 },
@@ -17,7 +30,125 @@ pub fn bar(ui: &mut Ui, state: &AppStateRef<'_>) {
         });
 }
 
+fn notifications_button(ui: &mut Ui, state: &AppStateRef<'_>) {
+    let notifications = &state.notifications;
+    let text = if notifications.is_empty() {
+        "Log".to_owned()
+    } else {
+        format!("⚠ {}", notifications.len())
+    };
+    let response = ui
+        .selectable_label(notifications.log_open, text)
+        .on_hover_text("Show errors and log messages");
+    if response.clicked() {
+        state.send(NotificationCommand::ToggleLog);
+    }
+}
+
+fn settings_menu(ui: &mut Ui, state: &AppStateRef<'_>) {
+    let response = ui.button("Settings");
+    Popup::menu(&response).show(|ui| {
+        if ui.button("Export settings…").clicked() {
+            settings_file::export(state);
+        }
+        if ui
+            .button("Import settings…")
+            .on_hover_text("Replaces all settings, except the GitHub login")
+            .clicked()
+        {
+            settings_file::import(state);
+        }
+        let mut settings = state.settings.clone();
+        ui.checkbox(&mut settings.read_only, "Read-only mode")
+            .on_hover_text(
+                "Never write, overwrite or delete files or push snapshots, e.g. on a demo \
+                machine. Those actions only list what they would change.",
+            );
+        if settings != state.settings {
+            state.send(SystemCommand::UpdateSettings(settings));
+        }
+        ui.separator();
+        ui.menu_button("Jenkins login", |ui| jenkins_ui(ui, state));
+        ui.menu_button("CircleCI token", |ui| circleci_ui(ui, state));
+    });
+}
+
+fn circleci_ui(ui: &mut Ui, state: &AppStateRef<'_>) {
+    let mut token = state
+        .github_auth
+        .circleci_token()
+        .unwrap_or_default()
+        .to_owned();
+    ui.label("Used to download the artifacts of private CircleCI projects.");
+    if ui
+        .add(
+            TextEdit::singleline(&mut token)
+                .password(true)
+                .hint_text("Personal API token"),
+        )
+        .on_hover_text("Add one under User Settings › Personal API Tokens in CircleCI")
+        .changed()
+    {
+        state.send(GithubAuthCommand::SetCircleciToken(Some(token)));
+    }
+}
+
+fn jenkins_ui(ui: &mut Ui, state: &AppStateRef<'_>) {
+    let mut settings = state.settings.clone();
+    ui.label("Used to download the artifacts of Jenkins builds.");
+    egui::Grid::new("jenkins_login")
+        .num_columns(2)
+        .show(ui, |ui| {
+            ui.label("Server");
+            ui.add(
+                TextEdit::singleline(&mut settings.jenkins.url)
+                    .hint_text("https://jenkins.example/"),
+            )
+            .on_hover_text("The login is only sent to links on this server");
+            ui.end_row();
+
+            ui.label("User");
+            ui.text_edit_singleline(&mut settings.jenkins.user);
+            ui.end_row();
+
+            ui.label("API token");
+            let mut token = state
+                .github_auth
+                .jenkins_api_token()
+                .unwrap_or_default()
+                .to_owned();
+            if ui
+                .add(TextEdit::singleline(&mut token).password(true))
+                .on_hover_text("Add one on your user's Security page in Jenkins")
+                .changed()
+            {
+                state.send(GithubAuthCommand::SetJenkinsApiToken(Some(token)));
+            }
+            ui.end_row();
+        });
+    if settings != state.settings {
+        state.send(SystemCommand::UpdateSettings(settings));
+    }
+}
+
 pub fn auth_ui(ui: &mut Ui, state: &AppStateRef<'_>) {
+    match state.github_auth.token_source() {
+        Some(TokenSource::Override) => {
+            ui.label("Using token from --token / GITHUB_TOKEN");
+            return;
+        }
+        Some(TokenSource::PersonalAccessToken) => {
+            let response = ui.button("Personal access token");
+            Popup::menu(&response).show(|ui| {
+                if ui.button("Remove token").clicked() {
+                    state.send(GithubAuthCommand::SetPersonalAccessToken(None));
+                }
+            });
+            return;
+        }
+        Some(TokenSource::OAuth) | None => {}
+    }
+
     match &state.github_auth.get_auth_state().logged_in {
         Some(logged_in) => {
             if let Some(image) = &logged_in.user_image {
@@ -39,6 +170,35 @@ pub fn auth_ui(ui: &mut Ui, state: &AppStateRef<'_>) {
             if ui.button("Log in with GitHub").clicked() {
                 state.send(GithubAuthCommand::Login);
             }
+
+            let response = ui.button("Use token…");
+            Popup::menu(&response)
+                .close_behavior(egui::PopupCloseBehavior::CloseOnClickOutside)
+                .show(|ui| personal_access_token_ui(ui, state));
         }
     }
 }
+
+fn personal_access_token_ui(ui: &mut Ui, state: &AppStateRef<'_>) {
+    let token_id = Id::new("personal_access_token");
+    let mut token = ui.memory_mut(|mem| mem.data.get_temp::<String>(token_id).unwrap_or_default());
+
+    ui.label("Paste a classic or fine-grained personal access token:");
+    let response = ui.add(
+        TextEdit::singleline(&mut token)
+            .password(true)
+            .hint_text("github_pat_…"),
+    );
+    let enter = response.lost_focus() && ui.input(|i| i.key_pressed(egui::Key::Enter));
+
+    let button = ui.add_enabled(!token.is_empty(), egui::Button::new("Save"));
+
+    if (button.clicked() || enter) && !token.is_empty() {
+        state.send(GithubAuthCommand::SetPersonalAccessToken(Some(
+            std::mem::take(&mut token),
+        )));
+        ui.close();
+    }
+
+    ui.memory_mut(|mem| mem.data.insert_temp(token_id, token));
+}
@@ -0,0 +1,226 @@
+//! Named sets of reference images saved to disk, e.g. the snapshots of a release, so later
+//! versions can be compared against them long after the source is gone.
+//!
+//! Every baseline is a folder in the cache dir with a `manifest.json` and the images below
+//! `images/`, laid out by snapshot path. Open one with [`crate::DiffSource::Baseline`].
+
+use crate::cache::cache_dir;
+use crate::snapshot::Snapshot;
+use anyhow::Context as _;
+use std::path::{Component, Path, PathBuf};
+use std::time::{SystemTime, UNIX_EPOCH};
+
+const MANIFEST: &str = "manifest.json";
+const IMAGES: &str = "images";
+
+#[derive(Debug, Clone, serde::Serialize, serde::Deserialize)]
+pub struct Manifest {
+    pub name: String,
+
+    /// What the images were saved from, e.g. the files header of the source.
+    pub source: String,
+
+    /// Unix seconds.
+    pub created: u64,
+
+    /// Snapshot paths, relative to the images folder.
+    pub images: Vec<PathBuf>,
+}
+
+#[derive(Debug, Clone)]
+pub struct SavedBaseline {
+    pub manifest: Manifest,
+    dir: PathBuf,
+}
+
+impl SavedBaseline {
+    pub fn images_dir(&self) -> PathBuf {
+        self.dir.join(IMAGES)
+    }
+
+    /// Like `2025-01-31 12:00`, in UTC.
+    pub fn created(&self) -> String {
+        chrono::DateTime::from_timestamp(self.manifest.created as i64, 0)
+            .map(|time| time.format("%Y-%m-%d %H:%M").to_string())
+            .unwrap_or_default()
+    }
+}
+
+fn baselines_dir() -> anyhow::Result<PathBuf> {
+    Ok(cache_dir()
+        .context("Failed to find the cache directory")?
+        .join("baselines"))
+}
+
+/// Names are free text, folders get a file name safe version of it. Names that only differ in
+/// punctuation share a folder, saving one replaces the other.
+///
+/// Dots are replaced too, so no name can become `.` or `..` and point outside of its folder.
+fn folder_name(name: &str) -> String {
+    name.trim()
+        .chars()
+        .map(|c| {
+            if c.is_alphanumeric() || c == '-' {
+                c
+            } else {
+                '_'
+            }
+        })
+        .collect()
+}
+
+/// The folder of baseline `name` in `baselines`, and the one it's written to before that.
+///
+/// Partial folders live in their own `.partial` folder, which [`list`] skips since it has no
+/// manifest, so they can't collide with a baseline.
+fn folders(baselines: &Path, name: &str) -> (PathBuf, PathBuf) {
+    let folder = folder_name(name);
+    let partial = baselines.join(".partial").join(format!("{folder}.partial"));
+    (baselines.join(folder), partial)
+}
+
+/// The saved baselines, newest first.
+pub fn list() -> Vec<SavedBaseline> {
+    let Ok(entries) = baselines_dir().and_then(|dir| Ok(std::fs::read_dir(dir)?)) else {
+        return Vec::new();
+    };
+    let mut baselines: Vec<SavedBaseline> = entries
+        .flatten()
+        .filter_map(|entry| {
+            let dir = entry.path();
+            let manifest = std::fs::read(dir.join(MANIFEST)).ok()?;
+            match serde_json::from_slice(&manifest) {
+                Ok(manifest) => Some(SavedBaseline { manifest, dir }),
+                Err(err) => {
+                    tracing::warn!("Ignoring baseline {}: {err}", dir.display());
+                    None
+                }
+            }
+        })
+        .collect();
+    baselines.sort_by_key(|baseline| std::cmp::Reverse(baseline.manifest.created));
+    baselines
+}
+
+pub fn find(name: &str) -> anyhow::Result<SavedBaseline> {
+    list()
+        .into_iter()
+        .find(|baseline| baseline.manifest.name == name)
+        .with_context(|| format!("There is no baseline named {name:?}"))
+}
+
+/// Saves the current image of every snapshot, the new one if there is one, as baseline `name`.
+/// Replaces a baseline of the same name. Returns the number of saved images.
+pub async fn save(name: &str, source: String, snapshots: Vec<Snapshot>) -> anyhow::Result<usize> {
+    anyhow::ensure!(!name.trim().is_empty(), "The baseline needs a name");
+
+    // Written to a partial folder and swapped in at the end, so a failure doesn't lose it.
+    let (dir, partial) = folders(&baselines_dir()?, name);
+    if tokio::fs::try_exists(&partial).await? {
+        tokio::fs::remove_dir_all(&partial).await?;
+    }
+
+    let mut images = Vec::new();
+    for snapshot in snapshots {
+        let Some(file) = snapshot.new.or(snapshot.old) else {
+            continue;
+        };
+        if !snapshot
+            .path
+            .components()
+            .all(|component| matches!(component, Component::Normal(_)))
+        {
+            tracing::warn!(
+                "Not saving {}, it's outside of the source",
+                snapshot.path.display()
+            );
+            continue;
+        }
+        let Some(bytes) = file
+            .read()
+            .await
+            .with_context(|| format!("Failed to read {}", snapshot.path.display()))?
+        else {
+            continue;
+        };
+        let target = partial.join(IMAGES).join(&snapshot.path);
+        if let Some(parent) = target.parent() {
+            tokio::fs::create_dir_all(parent).await?;
+        }
+        tokio::fs::write(&target, bytes).await?;
+        images.push(snapshot.path);
+    }
+
+    let manifest = Manifest {
+        name: name.trim().to_owned(),
+        source,
+        created: SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .map(|duration| duration.as_secs())
+            .unwrap_or_default(),
+        images,
+    };
+    tokio::fs::create_dir_all(&partial).await?;
+    tokio::fs::write(
+        partial.join(MANIFEST),
+        serde_json::to_vec_pretty(&manifest)?,
+    )
+    .await?;
+
+    if tokio::fs::try_exists(&dir).await? {
+        tokio::fs::remove_dir_all(&dir).await?;
+    }
+    tokio::fs::rename(&partial, &dir).await?;
+    tracing::info!(
+        "Saved {} images as baseline {:?} in {}",
+        manifest.images.len(),
+        manifest.name,
+        dir.display()
+    );
+    Ok(manifest.images.len())
+}
+
+pub fn delete(baseline: &SavedBaseline) -> anyhow::Result<()> {
+    std::fs::remove_dir_all(&baseline.dir)
+        .with_context(|| format!("Failed to delete {}", baseline.dir.display()))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn folders_stay_below_baselines() {
+        let baselines = Path::new("/cache/baselines");
+        for name in ["..", ".", " .. ", "../other", "v1.0", "v1.partial"] {
+            let (dir, partial) = folders(baselines, name);
+            assert_eq!(dir.parent(), Some(baselines), "{name:?}");
+            assert_eq!(
+                partial.parent(),
+                Some(baselines.join(".partial").as_path()),
+                "{name:?}"
+            );
+            assert!(
+                dir.components().all(|component| matches!(
+                    component,
+                    Component::RootDir | Component::Normal(_)
+                )),
+                "{name:?}"
+            );
+            assert_ne!(dir, partial, "{name:?}");
+        }
+
+        assert_eq!(folders(baselines, "..").0, baselines.join("__"));
+        assert_eq!(folders(baselines, ".").0, baselines.join("_"));
+
+        // Dotted names don't share a partial folder, and none is another's partial folder.
+        let (v10, v10_partial) = folders(baselines, "v1.0");
+        let (v12, v12_partial) = folders(baselines, "v1.2");
+        assert_ne!(v10, v12);
+        assert_ne!(v10_partial, v12_partial);
+        assert_ne!(
+            folders(baselines, "v1.partial").0,
+            folders(baselines, "v1").1
+        );
+    }
+}
@@ -0,0 +1,94 @@
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::time::SystemTime;
+
+/// The directory kitdiff keeps its caches in, e.g. `~/.cache/kitdiff` on Linux.
+pub fn cache_dir() -> Option<PathBuf> {
+    directories::ProjectDirs::from("io", "rerun", "kitdiff").map(|dirs| dirs.cache_dir().to_owned())
+}
+
+/// A directory in the [`cache_dir`] that is kept below a size by deleting the least recently
+/// used files, see [`Self::touch`].
+pub struct CacheDir {
+    name: &'static str,
+    max_bytes: u64,
+
+    /// Bytes written since the last pruning, starts full so the first write of a run prunes.
+    written: AtomicU64,
+}
+
+impl CacheDir {
+    pub const fn new(name: &'static str, max_bytes: u64) -> Self {
+        Self {
+            name,
+            max_bytes,
+            written: AtomicU64::new(max_bytes),
+        }
+    }
+
+    pub fn path(&self) -> Option<PathBuf> {
+        Some(cache_dir()?.join(self.name))
+    }
+
+    /// Marks a file as used, so it's among the last to be pruned.
+    pub fn touch(path: &Path) {
+        let result = std::fs::File::options()
+            .write(true)
+            .open(path)
+            .and_then(|file| file.set_modified(SystemTime::now()));
+        if let Err(err) = result {
+            tracing::debug!("Failed to touch {}: {err}", path.display());
+        }
+    }
+
+    /// Call after writing `bytes` to the directory. Once an eighth of its size was written since
+    /// the last time, the oldest files are deleted in the background until it fits again.
+    pub fn wrote(&self, bytes: u64) {
+        let written = self.written.fetch_add(bytes, Ordering::Relaxed) + bytes;
+        if written < self.max_bytes / 8 {
+            return;
+        }
+        self.written.store(0, Ordering::Relaxed);
+
+        let Some(dir) = self.path() else {
+            return;
+        };
+        let max_bytes = self.max_bytes;
+        rayon::spawn(move || {
+            if let Err(err) = prune(&dir, max_bytes) {
+                tracing::warn!("Failed to prune {}: {err}", dir.display());
+            }
+        });
+    }
+}
+
+fn prune(dir: &Path, max_bytes: u64) -> std::io::Result<()> {
+    let mut files = Vec::new();
+    for entry in std::fs::read_dir(dir)? {
+        let entry = entry?;
+        let metadata = entry.metadata()?;
+        if metadata.is_file() {
+            files.push((metadata.modified()?, metadata.len(), entry.path()));
+        }
+    }
+
+    let mut total: u64 = files.iter().map(|(_, len, _)| len).sum();
+    files.sort_unstable_by_key(|(modified, ..)| *modified);
+    let mut removed = 0;
+    for (_, len, path) in files {
+        if total <= max_bytes {
+            break;
+        }
+        match std::fs::remove_file(&path) {
+            Ok(()) => removed += 1,
+            // Pruned at the same time by another window.
+            Err(err) if err.kind() == std::io::ErrorKind::NotFound => {}
+            Err(err) => return Err(err),
+        }
+        total -= len;
+    }
+    if removed > 0 {
+        tracing::info!("Pruned {removed} files from {}", dir.display());
+    }
+    Ok(())
+}
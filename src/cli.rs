@@ -1,13 +1,139 @@
-use clap::{Parser, Subcommand};
+use anyhow::Context as _;
+use clap::{Parser, Subcommand, ValueEnum};
 use kitdiff::DiffSource;
-use kitdiff::github::auth::parse_github_artifact_url;
+use kitdiff::baselines;
+use kitdiff::config::Config;
+use kitdiff::github::auth::{self, GitHubAuth, TokenSource, parse_github_artifact_url};
+use kitdiff_core::diff::{DiffOptions, MinChange};
+use kitdiff_core::path_filter::{PathFilter, PathFilterSettings};
+use kitdiff_core::{CancelFlag, Snapshot};
+use std::ffi::OsString;
+use std::path::{Path, PathBuf};
+
+/// `cargo kitdiff` runs the `cargo-kitdiff` binary with `kitdiff` as its first argument.
+const CARGO_BINARY: &str = "cargo-kitdiff";
 
 #[derive(Parser)]
 #[command(name = "kitdiff")]
 #[command(about = "A viewer for egui kittest snapshot test files")]
+#[command(args_conflicts_with_subcommands = true)]
 pub struct Cli {
     #[command(subcommand)]
     pub command: Option<Commands>,
+
+    /// A folder, a .zip/.tar.gz archive, a url or a `kitdiff://open?url=...` link to open
+    pub path: Option<String>,
+
+    /// GitHub personal access token (classic or fine-grained), used instead of logging in
+    #[arg(long, env = "GITHUB_TOKEN", global = true, hide_env_values = true)]
+    pub token: Option<String>,
+
+    /// A pem file with root certificates to trust in addition to the system ones, for networks
+    /// that intercept TLS
+    #[arg(long, env = "KITDIFF_CA_BUNDLE", global = true)]
+    pub ca_bundle: Option<PathBuf>,
+
+    /// Log debug messages, to stderr and the log panel. `RUST_LOG` takes precedence
+    #[arg(long, short, global = true)]
+    pub verbose: bool,
+
+    /// Only show the snapshots of this crate of the cargo workspace
+    #[arg(long, short, conflicts_with = "path")]
+    pub package: Option<String>,
+
+    /// Print events like review decisions to stdout, one per line, for editor integrations
+    #[arg(long, value_enum, global = true)]
+    pub event_stream: Option<EventStream>,
+
+    /// Accept commands from other programs on this port of localhost, like opening a source or
+    /// selecting a snapshot, see the readme. Also where a running kitdiff is looked for, instead
+    /// of the default port, which only accepts sources handed over by another kitdiff
+    #[arg(long, env = "KITDIFF_REMOTE_CONTROL")]
+    pub remote_control: Option<u16>,
+
+    /// Open a new window instead of handing the source to a kitdiff that is already running
+    #[arg(long)]
+    pub new_window: bool,
+
+    /// Run as `cargo kitdiff`, which opens the workspace root instead of the current folder.
+    #[arg(skip)]
+    pub cargo: bool,
+}
+
+impl Cli {
+    /// Parses the arguments, also when run as `cargo kitdiff`.
+    pub fn parse_args() -> Self {
+        let mut args: Vec<OsString> = std::env::args_os().collect();
+        let cargo = args
+            .first()
+            .and_then(|binary| Path::new(binary).file_stem())
+            .is_some_and(|stem| stem == CARGO_BINARY);
+        if cargo && args.get(1).is_some_and(|arg| arg == "kitdiff") {
+            args.remove(1);
+        }
+        let mut cli = Self::parse_from(args);
+        cli.cargo = cargo;
+        cli
+    }
+
+    /// The folder to look for snapshots in when no source is given.
+    pub fn default_directory(&self) -> anyhow::Result<PathBuf> {
+        if !self.cargo && self.package.is_none() {
+            return Ok(".".into());
+        }
+        let metadata = cargo_metadata()?;
+        let Some(name) = &self.package else {
+            return Ok(metadata.workspace_root);
+        };
+        metadata
+            .packages
+            .iter()
+            .find(|package| package.name == *name)
+            .and_then(|package| package.manifest_path.parent())
+            .map(Path::to_path_buf)
+            .with_context(|| {
+                format!(
+                    "There is no package {name} in the workspace at {}",
+                    metadata.workspace_root.display()
+                )
+            })
+    }
+}
+
+/// The parts of `cargo metadata` we need.
+#[derive(serde::Deserialize)]
+struct CargoMetadata {
+    workspace_root: PathBuf,
+    packages: Vec<CargoPackage>,
+}
+
+#[derive(serde::Deserialize)]
+struct CargoPackage {
+    name: String,
+    manifest_path: PathBuf,
+}
+
+/// Asks cargo for the workspace of the current folder, so members and excludes are resolved
+/// like cargo does.
+fn cargo_metadata() -> anyhow::Result<CargoMetadata> {
+    // Set by cargo for its subcommands, to run the same toolchain.
+    let cargo = std::env::var_os("CARGO").unwrap_or_else(|| "cargo".into());
+    let output = std::process::Command::new(cargo)
+        .args(["metadata", "--no-deps", "--format-version", "1"])
+        .output()
+        .context("Failed to run cargo metadata")?;
+    anyhow::ensure!(
+        output.status.success(),
+        "cargo metadata failed: {}",
+        String::from_utf8_lossy(&output.stderr).trim()
+    );
+    serde_json::from_slice(&output.stdout).context("Failed to parse the output of cargo metadata")
+}
+
+#[derive(Clone, Copy, ValueEnum)]
+pub enum EventStream {
+    /// Newline-delimited JSON objects with an `event` field
+    Json,
 }
 
 #[derive(Subcommand)]
@@ -24,12 +150,77 @@ pub enum Commands {
     Archive { source: String },
     /// Load and compare snapshot files from a GitHub artifact
     GhArtifact { url: String },
+    /// Compare the current images of two sources, e.g. a CI artifact against local snapshots,
+    /// pairing them by relative path
+    Compare {
+        /// Folder, archive or url to compare against, e.g. a GitHub artifact url, or
+        /// `baseline:NAME` for a baseline saved in the app
+        #[arg(long)]
+        old: String,
+
+        /// Folder, archive or url to check, e.g. `./tests/snapshots`
+        #[arg(long)]
+        new: String,
+    },
+    /// List the baselines saved in the app, to compare against with `--old baseline:NAME`
+    Baselines,
+    /// Add kitdiff to the "Open with" menu of archives and folders, and handle kitdiff:// links
+    RegisterFileTypes,
+    /// Write the old/new/diff images of every changed snapshot in a folder or archive to `output`
+    Export {
+        /// A folder or a local .zip/.tar.gz archive
+        source: String,
+
+        /// A folder, or a file ending in .zip to write an archive
+        output: PathBuf,
+
+        /// Settings profile from kitdiff.toml to diff with, defaults to the configured one
+        #[arg(long)]
+        profile: Option<String>,
+
+        /// Skip snapshots where fewer pixels changed, e.g. only a few anti-aliased ones
+        #[arg(long, default_value_t = 0)]
+        min_changed_pixels: u32,
+
+        /// Skip snapshots where less than this percentage of the image changed
+        #[arg(long, default_value_t = 0.0)]
+        min_changed_percent: f32,
+    },
+    /// Compare images for a test framework like kittest, so tests and the app use the same
+    /// thresholds. Reads one JSON request per line from stdin and answers each on stdout
+    DiffServer {
+        /// Settings profile from kitdiff.toml to diff with, defaults to the configured one
+        #[arg(long)]
+        profile: Option<String>,
+    },
+    /// Sign in to GitHub without opening the app, e.g. to set up a new machine
+    Auth {
+        #[command(subcommand)]
+        action: AuthAction,
+    },
+}
+
+#[derive(Subcommand)]
+pub enum AuthAction {
+    /// Sign in in the browser and store the token in the keychain, where the app finds it
+    Login,
+    /// Print which GitHub account kitdiff uses. Fails if there is none or the token doesn't work
+    Status,
+    /// Remove the stored login. A personal access token from the settings is kept
+    Logout,
 }
 
 impl Commands {
     pub fn to_source(&self) -> Option<DiffSource> {
         Some(match self {
-            Self::Ui => return None,
+            Self::Ui
+            | Self::RegisterFileTypes
+            | Self::Export { .. }
+            | Self::DiffServer { .. }
+            | Self::Auth { .. }
+            | Self::Baselines => {
+                return None;
+            }
             Self::Files { directory } => {
                 DiffSource::Files(directory.clone().unwrap_or_else(|| ".".into()).into())
             }
@@ -53,6 +244,10 @@ impl Commands {
                     DiffSource::Archive(kitdiff::DataReference::Path(source.clone().into()))
                 }
             }
+            Self::Compare { old, new } => DiffSource::Compare {
+                old: Box::new(compare_side(old)),
+                new: Box::new(compare_side(new)),
+            },
             Self::GhArtifact { url } => {
                 if let Some(link) = parse_github_artifact_url(url) {
                     DiffSource::GHArtifact(link)
@@ -63,3 +258,143 @@ impl Commands {
         })
     }
 }
+
+/// One side of [`Commands::Compare`]. Folders show all their images, not only the changed ones.
+fn compare_side(side: &str) -> DiffSource {
+    if let Some(name) = side.strip_prefix("baseline:") {
+        match baselines::find(name) {
+            Ok(baseline) => DiffSource::Baseline(baseline),
+            Err(err) => panic!("{err}, see `kitdiff baselines`"),
+        }
+    } else if !side.contains("://") && Path::new(side).is_dir() {
+        DiffSource::Images(side.into())
+    } else {
+        DiffSource::from_path(side)
+    }
+}
+
+/// Runs [`Commands::Baselines`].
+#[expect(clippy::print_stdout, reason = "the list is the output")]
+pub fn list_baselines() {
+    let saved = baselines::list();
+    if saved.is_empty() {
+        println!("No baselines saved yet, save one from the 📌 menu in the app");
+    }
+    for baseline in saved {
+        println!(
+            "{}\t{} images\t{}\t{}",
+            baseline.manifest.name,
+            baseline.manifest.images.len(),
+            baseline.created(),
+            baseline.manifest.source
+        );
+    }
+}
+
+/// The options of `profile`, or of the profile configured in the [`Config`] of `dir`.
+pub fn diff_options(dir: &Path, profile: Option<&str>) -> anyhow::Result<DiffOptions> {
+    let config = Config::discover(dir).unwrap_or_default();
+    Ok(match profile.or(config.profile.as_deref()) {
+        Some(name) => {
+            config
+                .profiles
+                .get(name)
+                .with_context(|| format!("Unknown settings profile {name:?}"))?
+                .options
+        }
+        None => DiffOptions::default(),
+    })
+}
+
+/// Runs [`Commands::Export`] without opening a window.
+pub fn export(
+    source: &str,
+    output: &Path,
+    profile: Option<&str>,
+    min_change: MinChange,
+) -> anyhow::Result<()> {
+    let source_path = Path::new(source);
+    let config_dir = if source_path.is_dir() {
+        source_path
+    } else {
+        source_path.parent().unwrap_or(Path::new("."))
+    };
+    let options = diff_options(config_dir, profile)?;
+
+    let cancel = CancelFlag::default();
+    let mut snapshots: Vec<Snapshot> = if source_path.is_dir() {
+        let (sender, receiver) = std::sync::mpsc::channel();
+        let filter = PathFilter::new(&PathFilterSettings::default());
+        kitdiff_core::files::discover(source_path, &filter, &cancel, &sender);
+        drop(sender);
+        receiver.into_iter().flatten().collect()
+    } else {
+        let data = std::fs::read(source_path)
+            .with_context(|| format!("Failed to read {}", source_path.display()))?;
+        kitdiff_core::archive::discover(&data, &cancel)?
+    };
+    snapshots.sort_by(|a, b| a.path.cmp(&b.path));
+
+    let summary = if output
+        .extension()
+        .is_some_and(|extension| extension == "zip")
+    {
+        let file = std::fs::File::create(output)
+            .with_context(|| format!("Failed to create {}", output.display()))?;
+        kitdiff_core::export::export_zip(&snapshots, file, &options, min_change, &cancel)?
+    } else {
+        kitdiff_core::export::export_dir(&snapshots, output, &options, min_change, &cancel)?
+    };
+    anyhow::ensure!(
+        summary.failed == 0,
+        "{summary}, run with --verbose for details"
+    );
+    Ok(())
+}
+
+/// Runs [`Commands::Auth`] without opening a window. `token` is the `--token` argument.
+#[expect(
+    clippy::print_stdout,
+    reason = "the output is the point of these commands"
+)]
+pub async fn auth(action: &AuthAction, token: Option<&str>) -> anyhow::Result<()> {
+    match action {
+        AuthAction::Login => {
+            let logged_in = auth::login_in_browser(|url| {
+                println!("Sign in to GitHub at {url}");
+                if let Err(err) = opener::open_browser(&url) {
+                    tracing::warn!("Failed to open the browser: {err}");
+                }
+            })
+            .await?;
+            println!("Logged in as {}", logged_in.username);
+        }
+        AuthAction::Status => {
+            let (source, token) = match token {
+                Some(token) => (TokenSource::Override, token.to_owned()),
+                None => auth::stored_token()?
+                    .context("Not logged in, run `kitdiff auth login` or pass --token")?,
+            };
+            let user = GitHubAuth::fetch_user_info(&token)
+                .await
+                .context("The token doesn't work, it may have expired or been revoked")?;
+            let source = match source {
+                TokenSource::Override => "the --token argument or GITHUB_TOKEN",
+                TokenSource::PersonalAccessToken => "the personal access token from the settings",
+                TokenSource::OAuth => "the stored login",
+            };
+            println!("Logged in as {} with {source}", user.login);
+        }
+        AuthAction::Logout => {
+            auth::forget_login()?;
+            println!("Logged out");
+            if let Some((TokenSource::PersonalAccessToken, _)) = auth::stored_token()? {
+                println!(
+                    "The personal access token from the settings is still used, \
+                    remove it in the app"
+                );
+            }
+        }
+    }
+    Ok(())
+}
@@ -1,12 +1,104 @@
-use octocrab::models::WorkflowId;
+use crate::diff_image_loader::DiffOptions;
+use kitdiff_core::diff::DiffOverride;
+use std::collections::BTreeMap;
 
-#[derive(Debug, Clone, PartialEq, Eq, Default, serde::Serialize, serde::Deserialize)]
+/// Name of the per-repository config file.
+pub const FILE_NAME: &str = "kitdiff.toml";
+
+#[derive(Debug, Clone, PartialEq, Default, serde::Serialize, serde::Deserialize)]
 pub struct Config {
     #[serde(default)]
     pub github: Github,
+
+    /// Settings profile to use for this repository.
+    #[serde(default)]
+    pub profile: Option<String>,
+
+    /// Profiles shipped with the repository. Profiles the user saved with the same name win.
+    #[serde(default)]
+    pub profiles: BTreeMap<String, Profile>,
+
+    /// Command that runs the snapshot tests from the viewer, e.g. `cargo test -p my-crate
+    /// --features snapshots`. Runs in the opened folder, arguments are split on whitespace.
+    #[serde(default)]
+    pub test_command: Option<String>,
+
+    /// Diff options for some of the snapshots, e.g.
+    /// `[[overrides]] path = "**/text_rendering/**"` with `threshold = 150`.
+    #[serde(default)]
+    pub overrides: Vec<DiffOverride>,
 }
 
 #[derive(Debug, Clone, PartialEq, Eq, Default, serde::Serialize, serde::Deserialize)]
 pub struct Github {
-    pub update_snapshot_workflow_name: Option<WorkflowId>,
+    /// File name or id of the workflow that commits updated snapshots, see
+    /// [`crate::loaders::update_workflow::DEFAULT_WORKFLOW`].
+    pub update_snapshot_workflow_name: Option<String>,
+
+    /// Where the snapshots of artifacts are in the repository, e.g. `crates/viewer/tests`.
+    /// Pushing accepted snapshots puts their artifact paths below it.
+    pub snapshot_root: Option<String>,
+}
+
+/// Settings that usually differ between projects.
+#[derive(Debug, Clone, PartialEq, serde::Serialize, serde::Deserialize)]
+#[serde(default)]
+pub struct Profile {
+    pub options: DiffOptions,
+    pub use_original_diff: bool,
+}
+
+impl Default for Profile {
+    fn default() -> Self {
+        Self {
+            options: DiffOptions::default(),
+            use_original_diff: true,
+        }
+    }
+}
+
+#[cfg(not(target_arch = "wasm32"))]
+impl Config {
+    /// Look for a [`FILE_NAME`], or a `Cargo.toml` with a `[package.metadata.kitdiff]` or
+    /// `[workspace.metadata.kitdiff]` table, in `dir` and its parents. A [`FILE_NAME`] wins over
+    /// the `Cargo.toml` next to it.
+    pub fn discover(dir: &std::path::Path) -> Option<Self> {
+        let dir = dir.canonicalize().unwrap_or_else(|_| dir.to_path_buf());
+        let parsers: [(&str, fn(&str) -> Result<Option<Self>, toml::de::Error>); 2] = [
+            (FILE_NAME, |text| toml::from_str(text).map(Some)),
+            ("Cargo.toml", Self::from_cargo_manifest),
+        ];
+        for dir in dir.ancestors() {
+            for (name, parse) in parsers {
+                let path = dir.join(name);
+                match std::fs::read_to_string(&path) {
+                    Ok(text) => match parse(&text) {
+                        Ok(Some(config)) => return Some(config),
+                        Ok(None) => {}
+                        Err(err) => {
+                            tracing::warn!("Failed to parse {}: {err}", path.display());
+                            return None;
+                        }
+                    },
+                    Err(err) if err.kind() == std::io::ErrorKind::NotFound => {}
+                    Err(err) => {
+                        tracing::warn!("Failed to read {}: {err}", path.display());
+                        return None;
+                    }
+                }
+            }
+        }
+        None
+    }
+
+    /// `None` if the manifest has no kitdiff table, e.g. a crate of a workspace that keeps the
+    /// config in the workspace root.
+    fn from_cargo_manifest(text: &str) -> Result<Option<Self>, toml::de::Error> {
+        let manifest: toml::Table = toml::from_str(text)?;
+        ["package", "workspace"]
+            .into_iter()
+            .find_map(|table| manifest.get(table)?.get("metadata")?.get("kitdiff"))
+            .map(|config| config.clone().try_into())
+            .transpose()
+    }
 }
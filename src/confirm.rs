@@ -0,0 +1,160 @@
+//! Actions that write, overwrite or delete snapshot files first list exactly which paths they'll
+//! change and wait for a confirmation. In read-only mode, that list is all they do.
+
+use crate::state::AppStateRef;
+use eframe::egui::mutex::Mutex;
+use eframe::egui::{Button, Context, Id, Modal, RichText, ScrollArea, Ui};
+use std::sync::Arc;
+
+#[derive(Clone, Copy, PartialEq, Eq)]
+pub enum ChangeKind {
+    Add,
+    Overwrite,
+    Delete,
+}
+
+impl ChangeKind {
+    fn icon(self) -> &'static str {
+        match self {
+            Self::Add => "+",
+            Self::Overwrite => "~",
+            Self::Delete => "−",
+        }
+    }
+}
+
+pub struct Change {
+    pub kind: ChangeKind,
+    pub path: String,
+}
+
+impl Change {
+    pub fn new(kind: ChangeKind, path: impl Into<String>) -> Self {
+        Self {
+            kind,
+            path: path.into(),
+        }
+    }
+}
+
+type Action = Box<dyn FnOnce() + Send>;
+
+struct Pending {
+    title: String,
+
+    /// Where the paths are, e.g. "in the folder you pick next".
+    location: String,
+    changes: Vec<Change>,
+    action: Mutex<Option<Action>>,
+}
+
+fn id() -> Id {
+    Id::new("confirm_changes")
+}
+
+/// Shows `changes` and runs `action` once they are confirmed.
+pub fn confirm(
+    ctx: &Context,
+    title: impl Into<String>,
+    location: impl Into<String>,
+    changes: Vec<Change>,
+    action: impl FnOnce() + Send + 'static,
+) {
+    let pending = Pending {
+        title: title.into(),
+        location: location.into(),
+        changes,
+        action: Mutex::new(Some(Box::new(action))),
+    };
+    ctx.data_mut(|d| d.insert_temp(id(), Arc::new(pending)));
+}
+
+/// Hover text for the buttons of file-changing actions.
+pub fn hover_text(state: &AppStateRef<'_>, text: &str) -> String {
+    if state.settings.read_only {
+        format!("{text}\n\nRead-only mode is on, this only lists what would change.")
+    } else {
+        text.to_owned()
+    }
+}
+
+/// The list of changes, while one is waiting for a confirmation.
+pub fn confirm_modal(ctx: &Context, state: &AppStateRef<'_>) {
+    let Some(pending) = ctx.data(|d| d.get_temp::<Arc<Pending>>(id())) else {
+        return;
+    };
+    let read_only = state.settings.read_only;
+
+    let mut close = false;
+    let modal = Modal::new(id().with("modal")).show(ctx, |ui| {
+        ui.set_width(600.0);
+        ui.heading(&pending.title);
+        ui.label(summary(&pending));
+
+        ScrollArea::vertical().max_height(400.0).show(ui, |ui| {
+            for change in &pending.changes {
+                change_ui(ui, change);
+            }
+        });
+
+        if read_only {
+            ui.colored_label(
+                ui.visuals().warn_fg_color,
+                "Read-only mode is on, nothing will be changed. Turn it off in the settings.",
+            );
+        }
+        ui.horizontal(|ui| {
+            if ui.add_enabled(!read_only, Button::new("Confirm")).clicked() {
+                if let Some(action) = pending.action.lock().take() {
+                    action();
+                }
+                close = true;
+            }
+            if ui
+                .button(if read_only { "Close" } else { "Cancel" })
+                .clicked()
+            {
+                close = true;
+            }
+        });
+    });
+
+    if close || modal.should_close() {
+        ctx.data_mut(|d| d.remove::<Arc<Pending>>(id()));
+    }
+}
+
+fn summary(pending: &Pending) -> String {
+    let count = |kind| {
+        pending
+            .changes
+            .iter()
+            .filter(|change| change.kind == kind)
+            .count()
+    };
+    let parts: Vec<String> = [
+        (ChangeKind::Add, "written"),
+        (ChangeKind::Overwrite, "overwritten"),
+        (ChangeKind::Delete, "deleted"),
+    ]
+    .into_iter()
+    .filter_map(|(kind, verb)| {
+        let count = count(kind);
+        (count > 0).then(|| format!("{count} {verb}"))
+    })
+    .collect();
+    if parts.is_empty() {
+        format!("Nothing changes {}.", pending.location)
+    } else {
+        format!("Files {}: {}.", pending.location, parts.join(", "))
+    }
+}
+
+fn change_ui(ui: &mut Ui, change: &Change) {
+    let color = match change.kind {
+        ChangeKind::Add => ui.visuals().text_color(),
+        ChangeKind::Overwrite => ui.visuals().warn_fg_color,
+        ChangeKind::Delete => ui.visuals().error_fg_color,
+    };
+    ui.label(RichText::new(format!("{} {}", change.kind.icon(), change.path)).color(color));
+}
@@ -0,0 +1,106 @@
+//! On-disk cache for computed diffs, so re-opening a source doesn't recompute every diff.
+
+use crate::cache::CacheDir;
+use crate::diff_image_loader::{DiffInfo, DiffOptions};
+use eframe::egui::load::LoadError;
+use eframe::egui::{Color32, ColorImage};
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+use xxhash_rust::xxh3::Xxh3;
+
+static CACHE: CacheDir = CacheDir::new("diffs", 1 << 30);
+
+#[derive(serde::Serialize, serde::Deserialize)]
+struct CachedDiff {
+    diff: i32,
+}
+
+/// Return the cached diff for these images, or compute and cache it.
+pub fn load_or_compute(
+    old: &ColorImage,
+    new: &ColorImage,
+    options: &DiffOptions,
+    compute: impl FnOnce() -> Result<DiffInfo, LoadError>,
+) -> Result<DiffInfo, LoadError> {
+    let Some(dir) = CACHE.path() else {
+        return compute();
+    };
+    let key = cache_key(old, new, options);
+
+    if let Some(info) = read(&dir, key) {
+        return Ok(info);
+    }
+
+    let result = compute();
+    if let Ok(info) = &result
+        && let Err(err) = write(&dir, key, info)
+    {
+        tracing::debug!("Failed to write diff cache entry: {err}");
+    }
+    result
+}
+
+/// XXH3 of the images and options, the same in every build so the cache outlives updates.
+fn cache_key(old: &ColorImage, new: &ColorImage, options: &DiffOptions) -> u64 {
+    let mut hasher = Xxh3::new();
+    for image in [old, new] {
+        for side in image.size {
+            hasher.update(&(side as u64).to_le_bytes());
+        }
+        hasher.update(image.as_raw());
+    }
+    hasher.update(
+        serde_json::to_string(options)
+            .unwrap_or_default()
+            .as_bytes(),
+    );
+    hasher.digest()
+}
+
+fn paths(dir: &Path, key: u64) -> (PathBuf, PathBuf) {
+    (
+        dir.join(format!("{key:016x}.png")),
+        dir.join(format!("{key:016x}.json")),
+    )
+}
+
+fn read(dir: &Path, key: u64) -> Option<DiffInfo> {
+    let (image_path, info_path) = paths(dir, key);
+    let info: CachedDiff = serde_json::from_slice(&std::fs::read(&info_path).ok()?).ok()?;
+    let image = image::open(&image_path).ok()?.to_rgba8();
+    CacheDir::touch(&image_path);
+    CacheDir::touch(&info_path);
+    let image = ColorImage::from_rgba_unmultiplied(
+        [image.width() as usize, image.height() as usize],
+        image.as_raw(),
+    );
+    Some(DiffInfo {
+        image: Arc::new(image),
+        diff: info.diff,
+    })
+}
+
+fn write(dir: &Path, key: u64, info: &DiffInfo) -> anyhow::Result<()> {
+    std::fs::create_dir_all(dir)?;
+    let (image_path, info_path) = paths(dir, key);
+
+    let rgba: Vec<u8> = info
+        .image
+        .pixels
+        .iter()
+        .flat_map(|c: &Color32| c.to_srgba_unmultiplied())
+        .collect();
+    image::save_buffer(
+        &image_path,
+        &rgba,
+        info.image.width() as u32,
+        info.image.height() as u32,
+        image::ExtendedColorType::Rgba8,
+    )?;
+
+    // Written last, so a half-written image is never picked up.
+    let json = serde_json::to_vec(&CachedDiff { diff: info.diff })?;
+    std::fs::write(info_path, &json)?;
+    CACHE.wrote(std::fs::metadata(&image_path)?.len() + json.len() as u64);
+    Ok(())
+}
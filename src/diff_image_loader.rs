@@ -3,15 +3,93 @@ use eframe::egui::mutex::Mutex;
 use eframe::egui::{Color32, ColorImage, Context, SizeHint};
 use eframe::epaint::ahash::HashMap;
 use egui_extras::loaders::image_loader::ImageCrateLoader;
-use std::sync::Arc;
+use kitdiff_core::diff::{DiffOverride, DiffOverrides};
+use std::path::Path;
+use std::sync::{Arc, LazyLock, Weak};
 use std::task::Poll;
 
+pub use kitdiff_core::diff::DiffOptions;
+
+/// The [`crate::config::Config::overrides`] of the open source, and what they were compiled from.
+static OVERRIDES: LazyLock<Mutex<(Vec<DiffOverride>, Arc<DiffOverrides>)>> =
+    LazyLock::new(Mutex::default);
+
+/// Use these overrides for the diffs from now on. Cheap if they didn't change.
+pub fn set_overrides(overrides: &[DiffOverride]) {
+    let mut current = OVERRIDES.lock();
+    if current.0 != overrides {
+        *current = (overrides.to_vec(), Arc::new(DiffOverrides::new(overrides)));
+    }
+}
+
+/// The options to diff the snapshot at `path` with. They are part of the diff uri, so snapshots
+/// with different options are cached separately.
+pub fn options_for(path: &Path, options: DiffOptions) -> DiffOptions {
+    let overrides = OVERRIDES.lock().1.clone();
+    overrides.options(path, options)
+}
+
 type DiffMap = HashMap<String, Result<Poll<DiffInfo>, LoadError>>;
 
-#[derive(Default)]
+/// Changed pixels per diff uri, or `None` if the diff failed.
+///
+/// Unlike the diff images these are tiny, so they are kept around even when the image is
+/// forgotten.
+type StatsMap = HashMap<String, Option<ChangedPixels>>;
+
+/// The images [`DiffImageLoader`] got from egui's image loader, by uri. Weak, so an image
+/// egui forgets is freed as usual.
+type DecodedMap = HashMap<String, Weak<ColorImage>>;
+
+/// Sizes of the images and diffs loaded so far, by uri. Like the stats they outlive the images,
+/// so a preview can be shown at the size the image will have once it's decoded again.
+type SizeMap = HashMap<String, [usize; 2]>;
+
+#[derive(Debug, Clone, Copy)]
+pub struct ChangedPixels {
+    /// Number of pixels above the threshold.
+    pub count: i32,
+
+    /// Share of the image, from 0 to 1.
+    pub fraction: f32,
+}
+
 pub struct DiffImageLoader {
     image_loader: Arc<ImageCrateLoader>,
+    #[cfg(not(target_arch = "wasm32"))]
+    file_loader: Arc<crate::file_image_loader::FileImageLoader>,
     diffs: Arc<Mutex<DiffMap>>,
+    stats: Arc<Mutex<StatsMap>>,
+    decoded: Mutex<DecodedMap>,
+    sizes: Mutex<SizeMap>,
+    #[cfg(not(target_arch = "wasm32"))]
+    workers: DiffWorkers,
+}
+
+/// Limits how many diffs are computed at once, so loading hundreds of snapshots doesn't spawn
+/// hundreds of threads.
+#[cfg(not(target_arch = "wasm32"))]
+struct DiffWorkers(Arc<tokio::sync::Semaphore>);
+
+#[cfg(not(target_arch = "wasm32"))]
+impl Default for DiffWorkers {
+    fn default() -> Self {
+        let workers = std::thread::available_parallelism().map_or(4, |n| n.get());
+        Self(Arc::new(tokio::sync::Semaphore::new(workers)))
+    }
+}
+
+#[cfg(not(target_arch = "wasm32"))]
+impl DiffWorkers {
+    fn spawn(&self, job: impl FnOnce() + Send + 'static) {
+        let semaphore = self.0.clone();
+        tokio::spawn(async move {
+            let Ok(_permit) = semaphore.acquire_owned().await else {
+                return;
+            };
+            tokio::task::spawn_blocking(job).await.ok();
+        });
+    }
 }
 
 #[derive(Debug, Clone)]
@@ -20,17 +98,17 @@ pub struct DiffInfo {
     pub diff: i32,
 }
 
-#[derive(Debug, Clone, Copy, PartialEq, serde::Serialize, serde::Deserialize)]
-pub struct DiffOptions {
-    pub threshold: f32,
-    pub detect_aa_pixels: bool,
-}
-
-impl Default for DiffOptions {
-    fn default() -> Self {
-        Self {
-            threshold: 1.0,
-            detect_aa_pixels: true,
+impl DiffInfo {
+    fn changed_pixels(&self) -> ChangedPixels {
+        // Identical images get a 1×1 diff image, but then no pixel changed anyway.
+        let total = self.image.width() * self.image.height();
+        ChangedPixels {
+            count: self.diff,
+            fraction: if total == 0 {
+                0.0
+            } else {
+                self.diff as f32 / total as f32
+            },
         }
     }
 }
@@ -65,11 +143,91 @@ impl DiffImageLoader {
             .iter()
             .find_map(|l| Arc::downcast(l.clone()).ok())
             .expect("egui_extra ImageLoader should be installed");
+        #[cfg(not(target_arch = "wasm32"))]
+        let file_loader = ctx
+            .loaders()
+            .image
+            .lock()
+            .iter()
+            .find_map(|l| Arc::downcast(l.clone()).ok())
+            .expect("FileImageLoader should be installed");
 
         Self {
             image_loader,
+            #[cfg(not(target_arch = "wasm32"))]
+            file_loader,
             diffs: Arc::new(Mutex::new(HashMap::default())),
+            stats: Arc::new(Mutex::new(HashMap::default())),
+            decoded: Mutex::default(),
+            sizes: Mutex::default(),
+            #[cfg(not(target_arch = "wasm32"))]
+            workers: DiffWorkers::default(),
+        }
+    }
+
+    /// Load a decoded image, computing it if it is a `diff://` uri.
+    ///
+    /// Other loaders can use this to get at decoded images without going through
+    /// [`Context::try_load_image`], which would deadlock from inside a loader.
+    pub fn load_source(&self, ctx: &Context, uri: &str, size_hint: SizeHint) -> ImageLoadResult {
+        if uri.starts_with("diff://") {
+            self.load(ctx, uri, size_hint)
+        } else {
+            self.load_decoded(ctx, uri, size_hint)
+        }
+    }
+
+    /// The pixels of an image that is decoded, or a diff that is computed already, e.g. to make a
+    /// thumbnail without decoding the file again. `None` if it isn't, this never starts decoding.
+    pub fn decoded(&self, uri: &str) -> Option<Arc<ColorImage>> {
+        if let Some(Ok(Poll::Ready(diff))) = self.diffs.lock().get(uri) {
+            return Some(diff.image.clone());
+        }
+        let mut decoded = self.decoded.lock();
+        let image = decoded.get(uri)?.upgrade();
+        if image.is_none() {
+            decoded.remove(uri);
         }
+        image
+    }
+
+    /// Decodes with egui's image loader, which the viewer shows the images from, so the viewer
+    /// and the differ share one decoded copy of each image.
+    fn load_decoded(&self, ctx: &Context, uri: &str, size_hint: SizeHint) -> ImageLoadResult {
+        #[cfg(not(target_arch = "wasm32"))]
+        let result = match self.file_loader.load(ctx, uri, size_hint) {
+            Err(LoadError::NotSupported) => self.image_loader.load(ctx, uri, size_hint),
+            result => result,
+        };
+        #[cfg(target_arch = "wasm32")]
+        let result = self.image_loader.load(ctx, uri, size_hint);
+        if let Ok(ImagePoll::Ready { image }) = &result {
+            self.decoded
+                .lock()
+                .insert(uri.to_owned(), Arc::downgrade(image));
+            self.sizes.lock().insert(uri.to_owned(), image.size);
+        }
+        result
+    }
+
+    /// Size of the image or diff, if it was loaded before, even if it has been forgotten since.
+    pub fn image_size(&self, uri: &str) -> Option<[usize; 2]> {
+        self.sizes.lock().get(uri).copied()
+    }
+
+    /// Number of pixels above the threshold, if the diff has been computed.
+    pub fn diff_pixels(&self, uri: &str) -> Option<i32> {
+        self.changed_pixels(uri).map(|changed| changed.count)
+    }
+
+    /// Like [`Self::diff_pixels`], with the share of the image they make up.
+    pub fn changed_pixels(&self, uri: &str) -> Option<ChangedPixels> {
+        self.stats.lock().get(uri).copied().flatten()
+    }
+
+    /// True once the diff either completed or failed.
+    pub fn is_finished(&self, uri: &str) -> bool {
+        self.stats.lock().contains_key(uri)
     }
 
     pub fn diff_info(&self, uri: &str) -> Option<DiffInfo> {
@@ -95,15 +253,18 @@ impl ImageLoader for DiffImageLoader {
         }
         if let Some(image) = self.diffs.lock().get(uri) {
             match image {
-                Ok(Poll::Ready(result)) => ImageLoadResult::Ok(ImagePoll::Ready {
-                    image: result.image.clone(),
-                }),
+                Ok(Poll::Ready(result)) => {
+                    self.sizes.lock().insert(uri.to_owned(), result.image.size);
+                    ImageLoadResult::Ok(ImagePoll::Ready {
+                        image: result.image.clone(),
+                    })
+                }
                 Ok(Poll::Pending) => ImageLoadResult::Ok(ImagePoll::Pending { size: None }),
                 Err(err) => ImageLoadResult::Err(err.clone()),
             }
         } else if let Some(diff_uri) = DiffUri::from_uri(uri) {
-            let old_image = self.image_loader.load(ctx, &diff_uri.old, size_hint);
-            let new_image = self.image_loader.load(ctx, &diff_uri.new, size_hint);
+            let old_image = self.load_decoded(ctx, &diff_uri.old, size_hint);
+            let new_image = self.load_decoded(ctx, &diff_uri.new, size_hint);
 
             let (old_image, new_image) = (old_image?, new_image?);
 
@@ -117,22 +278,32 @@ impl ImageLoader for DiffImageLoader {
                     .lock()
                     .insert(diff_uri.to_uri(), Ok(Poll::Pending));
 
+                let stats = self.stats.clone();
                 let uri = uri.to_owned();
+                let finish = move |ctx: &Context, result: Result<DiffInfo, LoadError>| {
+                    stats.lock().insert(
+                        uri.clone(),
+                        result.as_ref().ok().map(DiffInfo::changed_pixels),
+                    );
+                    cache.lock().insert(uri, result.map(Poll::Ready));
+                    ctx.request_repaint();
+                };
+
                 #[cfg(not(target_arch = "wasm32"))]
-                std::thread::Builder::new()
-                    .name(format!("diff for {uri}"))
-                    .spawn(move || {
-                        ctx.request_repaint();
-                        let result = load_diffs(&ctx, &old_image, &new_image, size_hint, &diff_uri);
-                        cache.lock().insert(uri, result.map(Poll::Ready));
-                    })
-                    .expect("Failed to spawn diff thread");
+                self.workers.spawn(move || {
+                    let result = crate::diff_cache::load_or_compute(
+                        &old_image,
+                        &new_image,
+                        &diff_uri.options,
+                        || load_diffs(&ctx, &old_image, &new_image, size_hint, &diff_uri),
+                    );
+                    finish(&ctx, result);
+                });
                 #[cfg(target_arch = "wasm32")]
                 {
                     wasm_bindgen_futures::spawn_local(async move {
-                        ctx.request_repaint();
                         let result = load_diffs(&ctx, &old_image, &new_image, size_hint, &diff_uri);
-                        cache.lock().insert(uri, result.map(Poll::Ready));
+                        finish(&ctx, result);
                     });
                 }
             }
@@ -144,10 +315,13 @@ impl ImageLoader for DiffImageLoader {
 
     fn forget(&self, uri: &str) {
         self.diffs.lock().remove(uri);
+        self.decoded.lock().remove(uri);
     }
 
     fn forget_all(&self) {
         self.diffs.lock().clear();
+        self.decoded.lock().clear();
+        self.sizes.lock().clear();
     }
 
     fn byte_size(&self) -> usize {
@@ -187,37 +361,18 @@ pub fn load_diffs(
         "Failed to convert to RgbaImage".to_owned(),
     ))?;
 
-    if old.dimensions() != new.dimensions() {
-        return Err(LoadError::Loading(
-            "Images must have the same dimensions".to_owned(),
-        ));
-    }
-
-    let result = dify::diff::get_results(
-        old,
-        new,
-        diff_uri.options.threshold,
-        diff_uri.options.detect_aa_pixels,
-        None,
-        &None,
-        &None,
-    );
+    let result = kitdiff_core::diff::diff_rgba(old, new, &diff_uri.options)
+        .map_err(|err| LoadError::Loading(err.to_string()))?;
 
-    if let Some((pixels, image)) = result {
-        let image = ColorImage::from_rgba_unmultiplied(
+    let image = match result.image {
+        Some(image) => ColorImage::from_rgba_unmultiplied(
             [image.width() as usize, image.height() as usize],
             image.as_raw(),
-        );
-
-        let arc_image = Arc::new(image);
-        Ok(DiffInfo {
-            image: arc_image,
-            diff: pixels,
-        })
-    } else {
-        Ok(DiffInfo {
-            image: Arc::new(ColorImage::filled([1, 1], Color32::TRANSPARENT)),
-            diff: 0,
-        })
-    }
+        ),
+        None => ColorImage::filled([1, 1], Color32::TRANSPARENT),
+    };
+    Ok(DiffInfo {
+        image: Arc::new(image),
+        diff: result.pixels,
+    })
 }
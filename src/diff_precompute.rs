@@ -0,0 +1,216 @@
+use crate::diff_image_loader::{DiffImageLoader, DiffOptions, DiffUri};
+use crate::loaders::{Phase, Progress};
+use crate::snapshot::Snapshot;
+use crate::thumbnail_loader::thumbnail_uri;
+use eframe::egui::load::ImagePoll;
+use eframe::egui::{Context, SizeHint};
+use std::collections::{HashMap, HashSet};
+use std::path::PathBuf;
+
+/// Snapshots this close to the active one keep their decoded images around for fast navigation.
+pub const NEIGHBORHOOD: usize = 10;
+
+/// Snapshots this close to the active one get a low resolution preview, shown while their images
+/// decode, so flipping through them quickly doesn't wait on every decode.
+const PREVIEWED: usize = 50;
+
+/// Walks the snapshot list in the background and computes diff stats for every snapshot, so
+/// features like sorting by diff size have data without the user visiting every snapshot.
+///
+/// Work is done in priority order: the active snapshot, its neighbors, then everything else.
+#[derive(Default)]
+pub struct DiffPrecompute {
+    /// Diff uri -> snapshot path and the old/new uris of the images it needs.
+    in_flight: HashMap<String, (PathBuf, String, String)>,
+    done: HashSet<PathBuf>,
+    /// Diff uri -> the previews we made for it, forgotten again once it's far from the active one.
+    previews: HashMap<String, [String; 3]>,
+    options: Option<DiffOptions>,
+}
+
+impl DiffPrecompute {
+    /// Computed diff uri for a snapshot, ignoring any diff file shipped with it.
+    pub fn diff_uri(snapshot: &Snapshot, options: DiffOptions) -> Option<String> {
+        snapshot
+            .old_uri()
+            .zip(snapshot.new_uri())
+            .map(|(old, new)| {
+                let options = options_for(&snapshot.path, options);
+                DiffUri { old, new, options }.to_uri()
+            })
+    }
+
+    /// How many of the snapshots have their diff stats, while some are still missing.
+    pub fn progress(&self, snapshots: &[Snapshot]) -> Option<Progress> {
+        let done = self.done.len().min(snapshots.len());
+        (done < snapshots.len()).then(|| {
+            Progress::new(Phase::Diffing)
+                .with_fraction(Some(done as f32 / snapshots.len() as f32))
+                .with_detail(format!("{done} / {}", snapshots.len()))
+        })
+    }
+
+    /// `priority` are indices into `snapshots`, most important first.
+    pub fn update(
+        &mut self,
+        ctx: &Context,
+        loader: &DiffImageLoader,
+        snapshots: &[Snapshot],
+        priority: &[usize],
+        options: DiffOptions,
+    ) {
+        if self.options != Some(options) {
+            self.options = Some(options);
+            self.done.clear();
+        }
+
+        // Identical images share their uri, so the images of the neighbors are kept too.
+        let kept_uris: HashSet<String> = priority
+            .iter()
+            .take(NEIGHBORHOOD * 2 + 1)
+            .filter_map(|i| snapshots.get(*i))
+            .flat_map(|snapshot| {
+                [
+                    Self::diff_uri(snapshot, options),
+                    snapshot.old_uri(),
+                    snapshot.new_uri(),
+                ]
+            })
+            .flatten()
+            .collect();
+
+        let previewed: HashSet<String> = priority
+            .iter()
+            .take(PREVIEWED * 2 + 1)
+            .filter_map(|i| Self::diff_uri(snapshots.get(*i)?, options))
+            .collect();
+
+        self.previews.retain(|diff_uri, uris| {
+            let keep = previewed.contains(diff_uri);
+            if !keep {
+                for uri in uris.iter() {
+                    forget_preview(ctx, uri);
+                }
+            }
+            keep
+        });
+
+        let done = &mut self.done;
+        let previews = &mut self.previews;
+        self.in_flight.retain(|diff_uri, (path, old_uri, new_uri)| {
+            let poll = ctx.try_load_image(diff_uri, SizeHint::default());
+            let finished = match poll {
+                Ok(ImagePoll::Pending { .. }) => loader.is_finished(diff_uri),
+                Ok(ImagePoll::Ready { .. }) | Err(_) => true,
+            };
+            if finished {
+                done.insert(path.clone());
+                if matches!(poll, Ok(ImagePoll::Ready { .. })) && previewed.contains(diff_uri) {
+                    // Scaled down from the decoded images, before we drop them below.
+                    let uris = [&*diff_uri, &*old_uri, &*new_uri].map(|uri| thumbnail_uri(uri));
+                    for uri in &uris {
+                        ctx.try_load_image(uri, SizeHint::default()).ok();
+                    }
+                    previews.insert(diff_uri.clone(), uris);
+                }
+                // We only wanted the stats, drop the decoded images to keep memory bounded.
+                for uri in [&*diff_uri, &*old_uri, &*new_uri] {
+                    if !kept_uris.contains(uri) {
+                        forget_decoded(ctx, uri);
+                    }
+                }
+            }
+            !finished
+        });
+
+        let max_in_flight = std::thread::available_parallelism().map_or(4, |n| n.get());
+
+        for index in priority.iter().copied() {
+            if self.in_flight.len() >= max_in_flight {
+                break;
+            }
+            let Some(snapshot) = snapshots.get(index) else {
+                continue;
+            };
+            if self.done.contains(&snapshot.path) {
+                continue;
+            }
+            let (Some(old_uri), Some(new_uri)) = (snapshot.old_uri(), snapshot.new_uri()) else {
+                self.done.insert(snapshot.path.clone());
+                continue;
+            };
+            let diff_uri = DiffUri {
+                old: old_uri.clone(),
+                new: new_uri.clone(),
+                options: options_for(&snapshot.path, options),
+            }
+            .to_uri();
+
+            if loader.is_finished(&diff_uri) {
+                self.done.insert(snapshot.path.clone());
+                continue;
+            }
+            if self.in_flight.contains_key(&diff_uri) {
+                continue;
+            }
+
+            ctx.try_load_image(&diff_uri, SizeHint::default()).ok();
+            self.in_flight
+                .insert(diff_uri, (snapshot.path.clone(), old_uri, new_uri));
+        }
+    }
+}
+
+/// Forget a decoded image without dropping the bytes backing it, so decoding it again doesn't
+/// mean downloading it again.
+fn forget_decoded(ctx: &Context, uri: &str) {
+    for loader in ctx.loaders().image.lock().iter() {
+        loader.forget(uri);
+    }
+}
+
+/// Forget a preview along with its texture, which is uploaded once the preview was shown.
+fn forget_preview(ctx: &Context, uri: &str) {
+    let loaders = ctx.loaders();
+    for loader in loaders.texture.lock().iter() {
+        loader.forget(uri);
+    }
+    for loader in loaders.image.lock().iter() {
+        loader.forget(uri);
+    }
+}
+
+/// Snapshot indices in the order they should be diffed: the active one, its neighbors in the
+/// filtered list, the rest of the filtered list, then everything else.
+pub fn priority_order(
+    filtered: &[usize],
+    active_filtered_index: usize,
+    total: usize,
+) -> Vec<usize> {
+    let mut order = Vec::with_capacity(total);
+    let mut seen = HashSet::with_capacity(total);
+    let mut push = |index: usize, order: &mut Vec<usize>| {
+        if seen.insert(index) {
+            order.push(index);
+        }
+    };
+
+    if let Some(active) = filtered.get(active_filtered_index) {
+        push(*active, &mut order);
+    }
+    for distance in 1..=NEIGHBORHOOD {
+        if let Some(index) = filtered.get(active_filtered_index + distance) {
+            push(*index, &mut order);
+        }
+        if let Some(index) = active_filtered_index
+            .checked_sub(distance)
+            .and_then(|i| filtered.get(i))
+        {
+            push(*index, &mut order);
+        }
+    }
+    for index in filtered.iter().copied().chain(0..total) {
+        push(index, &mut order);
+    }
+    order
+}
@@ -0,0 +1,114 @@
+//! `kitdiff diff-server`: compares images for a test framework like kittest with the diff engine
+//! of the app, so a test and the review never disagree about what changed.
+//!
+//! Every line on stdin is a request like
+//! `{"id": 1, "baseline": "tests/snapshots/button.png", "candidate": "/tmp/button.png"}`, every
+//! line on stdout the response, e.g. `{"id": 1, "pass": false, "changed_pixels": 12, ...}`.
+
+use anyhow::Context as _;
+use base64::Engine as _;
+use kitdiff::config::Config;
+use kitdiff_core::diff::{self, DiffOptions, DiffOverrides};
+use std::io::{BufRead as _, Write as _};
+use std::path::{Path, PathBuf};
+
+#[derive(serde::Deserialize)]
+struct Request {
+    /// Returned with the response, to match them up.
+    #[serde(default)]
+    id: serde_json::Value,
+    baseline: PathBuf,
+
+    /// The image the test rendered, as a path or as a base64 encoded png.
+    candidate: Option<PathBuf>,
+    candidate_png: Option<String>,
+
+    /// Where to write the diff image of a failed comparison. Without it, the response contains
+    /// the image.
+    diff: Option<PathBuf>,
+}
+
+#[derive(Default, serde::Serialize)]
+struct Response {
+    id: serde_json::Value,
+    pass: bool,
+    changed_pixels: u32,
+
+    /// Overall similarity from 0 to 1, for algorithms that measure one, like SSIM and DSSIM.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    similarity: Option<f32>,
+
+    /// Base64 encoded png, if the images differ and the request has no `diff` path.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    diff_png: Option<String>,
+
+    /// Why the images couldn't be compared, e.g. a missing baseline. Never a pass.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    error: Option<String>,
+}
+
+/// Answers requests until stdin is closed. The diff options and their overrides for some paths
+/// come from the kitdiff.toml of the current folder, like in the app.
+pub fn run(profile: Option<&str>) -> anyhow::Result<()> {
+    let options = crate::cli::diff_options(Path::new("."), profile)?;
+    let config = Config::discover(Path::new(".")).unwrap_or_default();
+    let overrides = DiffOverrides::new(&config.overrides);
+    let mut stdout = std::io::stdout().lock();
+    for line in std::io::stdin().lock().lines() {
+        let line = line.context("Failed to read stdin")?;
+        if line.trim().is_empty() {
+            continue;
+        }
+        let response = match serde_json::from_str::<Request>(&line) {
+            Ok(request) => {
+                let id = request.id.clone();
+                let options = overrides.options(&request.baseline, options);
+                compare(request, &options).unwrap_or_else(|err| Response {
+                    id,
+                    error: Some(format!("{err:#}")),
+                    ..Default::default()
+                })
+            }
+            Err(err) => Response {
+                error: Some(format!("Invalid request: {err}")),
+                ..Default::default()
+            },
+        };
+        serde_json::to_writer(&mut stdout, &response)?;
+        writeln!(stdout)?;
+        stdout.flush()?;
+    }
+    Ok(())
+}
+
+fn compare(request: Request, options: &DiffOptions) -> anyhow::Result<Response> {
+    let baseline = std::fs::read(&request.baseline)
+        .with_context(|| format!("Failed to read {}", request.baseline.display()))?;
+    let candidate = match (&request.candidate, &request.candidate_png) {
+        (Some(path), None) => {
+            std::fs::read(path).with_context(|| format!("Failed to read {}", path.display()))?
+        }
+        (None, Some(png)) => base64::engine::general_purpose::STANDARD
+            .decode(png)
+            .context("candidate_png isn't valid base64")?,
+        _ => anyhow::bail!("Pass either candidate or candidate_png"),
+    };
+
+    let result = diff::diff_images(&baseline, &candidate, options)?;
+    let mut response = Response {
+        id: request.id,
+        pass: !result.is_different(),
+        changed_pixels: result.pixels.unsigned_abs(),
+        similarity: result.similarity,
+        ..Default::default()
+    };
+    if let Some(image) = result.image.filter(|_| result.pixels > 0) {
+        let png = kitdiff_core::export::encode_png(&image)?;
+        match &request.diff {
+            Some(path) => std::fs::write(path, png)
+                .with_context(|| format!("Failed to write {}", path.display()))?,
+            None => response.diff_png = Some(base64::engine::general_purpose::STANDARD.encode(png)),
+        }
+    }
+    Ok(response)
+}
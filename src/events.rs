@@ -0,0 +1,131 @@
+//! Newline-delimited JSON on stdout about what happens in the viewer, so editor plugins and
+//! wrapper scripts can react to review decisions, see `--event-stream json`.
+
+use crate::diff_image_loader::{DiffImageLoader, DiffOptions};
+use crate::diff_precompute::DiffPrecompute;
+use crate::loaders::LoadSnapshots;
+use crate::state::ReviewStatus;
+use std::path::Path;
+use std::sync::atomic::{AtomicBool, Ordering};
+
+static ENABLED: AtomicBool = AtomicBool::new(false);
+
+#[derive(serde::Serialize)]
+#[serde(tag = "event", rename_all = "snake_case")]
+pub enum Event<'a> {
+    /// The source is done loading.
+    Loaded {
+        source: &'a str,
+        snapshots: usize,
+    },
+    Selected {
+        path: &'a Path,
+    },
+
+    /// `status` is `null` when the decision was cleared or undone.
+    Reviewed {
+        path: &'a Path,
+        status: Option<ReviewStatus>,
+    },
+
+    /// Every diff of the loaded source is computed.
+    DiffStats {
+        changed: usize,
+        below_threshold: usize,
+        unchanged: usize,
+        added: usize,
+        deleted: usize,
+        changed_pixels: u64,
+    },
+}
+
+/// Print events from now on.
+pub fn enable() {
+    ENABLED.store(true, Ordering::Relaxed);
+}
+
+pub fn emit(event: &Event<'_>) {
+    if !ENABLED.load(Ordering::Relaxed) {
+        return;
+    }
+    match serde_json::to_string(event) {
+        Ok(line) => {
+            #[expect(clippy::print_stdout, reason = "the events are the output")]
+            {
+                println!("{line}");
+            }
+        }
+        Err(err) => tracing::warn!("Failed to serialize an event: {err}"),
+    }
+}
+
+/// Emits [`Event::Loaded`] and [`Event::DiffStats`] once per load.
+#[derive(Default)]
+pub struct LoadEvents {
+    loaded: bool,
+    stats: bool,
+}
+
+impl LoadEvents {
+    pub fn update(
+        &mut self,
+        loader: &dyn LoadSnapshots,
+        precompute: &DiffPrecompute,
+        diff_image_loader: &DiffImageLoader,
+        options: DiffOptions,
+    ) {
+        if self.stats || !ENABLED.load(Ordering::Relaxed) || !loader.state().is_ready() {
+            return;
+        }
+        let snapshots = loader.snapshots();
+        if !self.loaded {
+            self.loaded = true;
+            emit(&Event::Loaded {
+                source: &loader.files_header(),
+                snapshots: snapshots.len(),
+            });
+        }
+        if precompute.progress(snapshots).is_some() {
+            return;
+        }
+        self.stats = true;
+
+        let (mut changed, mut below_threshold, mut unchanged, mut added, mut deleted) =
+            (0, 0, 0, 0, 0);
+        let mut changed_pixels = 0;
+        for snapshot in snapshots {
+            if snapshot.added() {
+                added += 1;
+            } else if snapshot.deleted() {
+                deleted += 1;
+            } else if snapshot.unchanged {
+                unchanged += 1;
+            } else {
+                match DiffPrecompute::diff_uri(snapshot, options)
+                    .and_then(|uri| diff_image_loader.diff_pixels(&uri))
+                {
+                    Some(0) => below_threshold += 1,
+                    Some(pixels) => {
+                        changed += 1;
+                        changed_pixels += u64::from(pixels.unsigned_abs());
+                    }
+                    // The diff failed, it's reported in the viewer.
+                    None => {}
+                }
+            }
+        }
+        emit(&Event::DiffStats {
+            changed,
+            below_threshold,
+            unchanged,
+            added,
+            deleted,
+            changed_pixels,
+        });
+    }
+
+    /// Emit the events of the next load again, e.g. after a refresh.
+    pub fn reset(&mut self) {
+        *self = Self::default();
+    }
+}
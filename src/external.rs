@@ -0,0 +1,36 @@
+//! Handing snapshot files to other applications.
+
+use anyhow::Context as _;
+use std::path::Path;
+
+/// Show the file in Finder / Explorer / the system file manager.
+pub fn reveal(path: &Path) -> anyhow::Result<()> {
+    opener::reveal(path).with_context(|| format!("Failed to reveal {}", path.display()))
+}
+
+/// Open the file in the system's default image viewer.
+pub fn open(path: &Path) -> anyhow::Result<()> {
+    opener::open(path).with_context(|| format!("Failed to open {}", path.display()))
+}
+
+/// Run the user's diff tool, e.g. `code --diff {old} {new}`.
+///
+/// `{old}` and `{new}` are replaced by the file paths. Arguments are split on whitespace.
+pub fn open_diff_tool(command: &str, old: &Path, new: &Path) -> anyhow::Result<()> {
+    let mut args = command.split_whitespace().map(|arg| {
+        arg.replace("{old}", &old.to_string_lossy())
+            .replace("{new}", &new.to_string_lossy())
+    });
+    let program = args.next().context("No diff tool configured")?;
+
+    let mut child = std::process::Command::new(&program)
+        .args(args)
+        .spawn()
+        .with_context(|| format!("Failed to run {program}"))?;
+
+    // Reap the process once the tool is closed.
+    std::thread::Builder::new()
+        .name("diff tool".to_owned())
+        .spawn(move || child.wait())?;
+    Ok(())
+}
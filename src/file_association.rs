@@ -0,0 +1,135 @@
+//! Adding kitdiff to the system's "Open with" menu for archives and folders, and handling
+//! `kitdiff://` links.
+//!
+//! kitdiff is only offered as an option, it never becomes the default app for zip files.
+//! Launching it with a path (`kitdiff artifact.zip`) or link is handled by the cli.
+
+use anyhow::Context as _;
+
+/// Register kitdiff for `.zip`, `.tar.gz`, folders and `kitdiff://` links, for the current user.
+pub fn register() -> anyhow::Result<()> {
+    let exe = std::env::current_exe().context("Failed to find the kitdiff executable")?;
+    platform::register(&exe)
+}
+
+#[cfg(target_os = "linux")]
+mod platform {
+    use anyhow::Context as _;
+    use std::path::Path;
+
+    pub fn register(exe: &Path) -> anyhow::Result<()> {
+        let dirs = directories::BaseDirs::new().context("Failed to find the home directory")?;
+        let applications = dirs.data_dir().join("applications");
+        std::fs::create_dir_all(&applications)?;
+
+        // Files and links need different `Exec` arguments, so they get an entry each.
+        let entries = [
+            (
+                "kitdiff.desktop",
+                "%f",
+                "application/zip;application/gzip;application/x-compressed-tar;inode/directory;",
+            ),
+            ("kitdiff-url.desktop", "%u", "x-scheme-handler/kitdiff;"),
+        ];
+        for (name, argument, mime_types) in entries {
+            let entry = format!(
+                "[Desktop Entry]\n\
+                 Type=Application\n\
+                 Name=kitdiff\n\
+                 Comment=Review snapshot test changes\n\
+                 Exec=\"{}\" {argument}\n\
+                 Terminal=false\n\
+                 NoDisplay=true\n\
+                 MimeType={mime_types}\n\
+                 Categories=Development;Graphics;\n",
+                exe.display()
+            );
+            let path = applications.join(name);
+            std::fs::write(&path, entry)
+                .with_context(|| format!("Failed to write {}", path.display()))?;
+            tracing::info!("Registered {}", path.display());
+        }
+
+        // Nothing else handles our scheme, so it's fine to make us the default for it.
+        std::process::Command::new("xdg-mime")
+            .args(["default", "kitdiff-url.desktop", "x-scheme-handler/kitdiff"])
+            .status()
+            .ok();
+
+        // Not all desktops watch the directory, refresh the cache if we can.
+        std::process::Command::new("update-desktop-database")
+            .arg(&applications)
+            .status()
+            .ok();
+
+        Ok(())
+    }
+}
+
+#[cfg(target_os = "windows")]
+mod platform {
+    use anyhow::Context as _;
+    use std::path::Path;
+
+    const PROG_ID: &str = r"HKCU\Software\Classes\kitdiff.archive";
+
+    pub fn register(exe: &Path) -> anyhow::Result<()> {
+        let command = format!("\"{}\" \"%1\"", exe.display());
+
+        reg(&[PROG_ID, "/ve", "/d", "Snapshot archive"])?;
+        reg(&[
+            &format!(r"{PROG_ID}\shell\open\command"),
+            "/ve",
+            "/d",
+            &command,
+        ])?;
+        for extension in [".zip", ".tgz", ".gz"] {
+            reg(&[
+                &format!(r"HKCU\Software\Classes\{extension}\OpenWithProgids"),
+                "/v",
+                "kitdiff.archive",
+                "/d",
+                "",
+            ])?;
+        }
+
+        let folder = r"HKCU\Software\Classes\Directory\shell\kitdiff";
+        reg(&[folder, "/ve", "/d", "Open with kitdiff"])?;
+        reg(&[&format!(r"{folder}\command"), "/ve", "/d", &command])?;
+
+        let scheme = r"HKCU\Software\Classes\kitdiff";
+        reg(&[scheme, "/ve", "/d", "URL:kitdiff"])?;
+        reg(&[scheme, "/v", "URL Protocol", "/d", ""])?;
+        reg(&[
+            &format!(r"{scheme}\shell\open\command"),
+            "/ve",
+            "/d",
+            &command,
+        ])?;
+
+        tracing::info!("Registered kitdiff for archives, folders and kitdiff:// links");
+        Ok(())
+    }
+
+    fn reg(args: &[&str]) -> anyhow::Result<()> {
+        let status = std::process::Command::new("reg")
+            .arg("add")
+            .args(args)
+            .arg("/f")
+            .status()
+            .context("Failed to run reg")?;
+        anyhow::ensure!(status.success(), "reg add {} failed", args[0]);
+        Ok(())
+    }
+}
+
+#[cfg(not(any(target_os = "linux", target_os = "windows")))]
+mod platform {
+    use std::path::Path;
+
+    pub fn register(_exe: &Path) -> anyhow::Result<()> {
+        // On macOS, files are passed as Apple Events rather than arguments, which winit doesn't
+        // hand to us yet.
+        anyhow::bail!("Registering file types isn't supported on this platform yet")
+    }
+}
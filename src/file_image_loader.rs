@@ -0,0 +1,117 @@
+//! Decoding local snapshots straight from their file.
+//!
+//! egui's file loader keeps the encoded bytes next to the decoded image for as long as it's
+//! loaded. Here they are dropped right after decoding, which keeps the memory down on
+//! directories with gigabytes of snapshots.
+//!
+//! Files are read on tokio's blocking threads and decoded on rayon's pool, so opening many
+//! snapshots at once neither starts a thread for each nor has slow disks hold up the diffs.
+
+use anyhow::Context as _;
+use eframe::egui::load::{ImageLoadResult, ImageLoader, ImagePoll, LoadError};
+use eframe::egui::mutex::Mutex;
+use eframe::egui::{ColorImage, Context, SizeHint};
+use eframe::epaint::ahash::HashMap;
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+use std::task::Poll;
+
+const PREFIX: &str = "file://";
+
+type ImageMap = HashMap<String, Result<Poll<Arc<ColorImage>>, LoadError>>;
+
+/// Loads `file://` uris of the formats the image crate decodes. Others, e.g. svg, are left to
+/// egui's loaders.
+#[derive(Default)]
+pub struct FileImageLoader {
+    images: Arc<Mutex<ImageMap>>,
+}
+
+/// The path of a `file://` uri.
+pub fn file_path(uri: &str) -> Option<PathBuf> {
+    uri.strip_prefix(PREFIX).map(PathBuf::from)
+}
+
+impl ImageLoader for FileImageLoader {
+    fn id(&self) -> &'static str {
+        "FileImageLoader"
+    }
+
+    fn load(&self, ctx: &Context, uri: &str, _size_hint: SizeHint) -> ImageLoadResult {
+        let Some(path) = file_path(uri) else {
+            return Err(LoadError::NotSupported);
+        };
+        if image::ImageFormat::from_path(&path).is_err() {
+            return Err(LoadError::NotSupported);
+        }
+
+        let mut images = self.images.lock();
+        if let Some(entry) = images.get(uri) {
+            return match entry {
+                Ok(Poll::Ready(image)) => Ok(ImagePoll::Ready {
+                    image: image.clone(),
+                }),
+                Ok(Poll::Pending) => Ok(ImagePoll::Pending { size: None }),
+                Err(err) => Err(err.clone()),
+            };
+        }
+        images.insert(uri.to_owned(), Ok(Poll::Pending));
+        drop(images);
+
+        let images = self.images.clone();
+        let ctx = ctx.clone();
+        let uri = uri.to_owned();
+        tokio::task::spawn_blocking(move || {
+            let bytes = read_file(&path);
+            rayon::spawn(move || {
+                let result = bytes
+                    .and_then(|bytes| decode(&path, &bytes))
+                    .map(|image| {
+                        Poll::Ready(Arc::new(ColorImage::from_rgba_unmultiplied(
+                            [image.width() as usize, image.height() as usize],
+                            image.as_raw(),
+                        )))
+                    })
+                    .map_err(|err| LoadError::Loading(format!("{err:#}")));
+                // Unless it was forgotten while we were decoding.
+                if let Some(entry) = images.lock().get_mut(&uri) {
+                    *entry = result;
+                }
+                ctx.request_repaint();
+            });
+        });
+
+        Ok(ImagePoll::Pending { size: None })
+    }
+
+    fn forget(&self, uri: &str) {
+        self.images.lock().remove(uri);
+    }
+
+    fn forget_all(&self) {
+        self.images.lock().clear();
+    }
+
+    fn byte_size(&self) -> usize {
+        self.images
+            .lock()
+            .values()
+            .map(|entry| match entry {
+                Ok(Poll::Ready(image)) => image.as_raw().len(),
+                _ => 0,
+            })
+            .sum()
+    }
+}
+
+/// Read the image file at `path`. Blocks, so call it off rayon's pool.
+pub fn read_file(path: &Path) -> anyhow::Result<Vec<u8>> {
+    std::fs::read(path).with_context(|| format!("Failed to read {}", path.display()))
+}
+
+/// Decode the `bytes` of the image file at `path`.
+pub fn decode(path: &Path, bytes: &[u8]) -> anyhow::Result<image::RgbaImage> {
+    Ok(image::load_from_memory(bytes)
+        .with_context(|| format!("Failed to decode {}", path.display()))?
+        .to_rgba8())
+}
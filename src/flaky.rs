@@ -0,0 +1,223 @@
+//! Remembers which snapshots were changed in earlier runs of a source, to tell flaky snapshot
+//! tests from real regressions: a flaky test flips between changed and unchanged from run to run
+//! without anyone touching it.
+//!
+//! A run is a set of images, loading the same ones again doesn't count as another run. Outcomes
+//! depend on the diff options, so each set of options has its own history.
+
+use crate::diff_image_loader::{DiffImageLoader, DiffOptions};
+use crate::diff_precompute::DiffPrecompute;
+use crate::loaders::LoadSnapshots;
+use crate::snapshot::Snapshot;
+use std::collections::{BTreeMap, HashMap, HashSet};
+use std::path::{Path, PathBuf};
+use xxhash_rust::xxh3::Xxh3;
+
+/// Runs kept per source, older ones are dropped.
+const MAX_RUNS: usize = 10;
+
+/// A snapshot that went from changed to unchanged and back (or the other way round) is flaky.
+/// A single flip is a snapshot that was changed or fixed.
+const MIN_FLIPS: usize = 2;
+
+/// What the earlier runs of a source found with one set of diff options, oldest first.
+#[derive(Default, serde::Serialize, serde::Deserialize)]
+struct History {
+    key: String,
+    runs: Vec<Run>,
+}
+
+#[derive(serde::Serialize, serde::Deserialize)]
+struct Run {
+    /// The images of the run, see [`run_id`].
+    id: u64,
+
+    /// Whether each snapshot was changed.
+    changed: BTreeMap<PathBuf, bool>,
+}
+
+/// Records the outcome of every snapshot once per run, after all diffs are computed.
+#[derive(Default)]
+pub struct FlakyTracker {
+    /// Outcomes by snapshot, oldest first. `true` means changed.
+    runs: HashMap<PathBuf, Vec<bool>>,
+    flaky: HashSet<PathBuf>,
+    recorded: bool,
+}
+
+impl FlakyTracker {
+    /// Record the current load once the loader is done and every diff is known.
+    pub fn update(
+        &mut self,
+        loader: &dyn LoadSnapshots,
+        precompute: &DiffPrecompute,
+        diff_image_loader: &DiffImageLoader,
+        options: DiffOptions,
+    ) {
+        let snapshots = loader.snapshots();
+        if self.recorded
+            || snapshots.is_empty()
+            || !loader.state().is_ready()
+            || precompute.progress(snapshots).is_some()
+        {
+            return;
+        }
+        self.recorded = true;
+
+        let source = source_key(loader);
+        let key = format!(
+            "{source}\n{}",
+            serde_json::to_string(&options).unwrap_or_default()
+        );
+        let mut history = read(&key);
+        let id = run_id(snapshots);
+        if history.runs.iter().any(|run| run.id == id) {
+            tracing::debug!("This run of {source} is recorded already");
+        } else {
+            let changed = snapshots
+                .iter()
+                .filter_map(|snapshot| {
+                    // Added and deleted snapshots didn't run on one side, there's nothing to
+                    // compare.
+                    let changed = if snapshot.unchanged {
+                        false
+                    } else {
+                        DiffPrecompute::diff_uri(snapshot, options)
+                            .and_then(|uri| diff_image_loader.diff_pixels(&uri))?
+                            > 0
+                    };
+                    Some((snapshot.path.clone(), changed))
+                })
+                .collect();
+            history.runs.push(Run { id, changed });
+            if history.runs.len() > MAX_RUNS {
+                history.runs.drain(..history.runs.len() - MAX_RUNS);
+            }
+            write(&history);
+        }
+
+        self.runs = HashMap::new();
+        for run in &history.runs {
+            for (path, changed) in &run.changed {
+                self.runs.entry(path.clone()).or_default().push(*changed);
+            }
+        }
+        self.flaky = self
+            .runs
+            .iter()
+            .filter(|(_, runs)| flips(runs) >= MIN_FLIPS)
+            .map(|(path, _)| path.clone())
+            .collect();
+        if !self.flaky.is_empty() {
+            tracing::info!("{} flaky snapshots in {source}", self.flaky.len());
+        }
+    }
+
+    /// Check the next load again, e.g. after a refresh. It only counts if its images are new.
+    pub fn reset(&mut self) {
+        self.recorded = false;
+    }
+
+    pub fn is_flaky(&self, path: &Path) -> bool {
+        self.flaky.contains(path)
+    }
+
+    pub fn len(&self) -> usize {
+        self.flaky.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.flaky.is_empty()
+    }
+
+    /// Like `●○●●○`, oldest first, where `●` is a load that found the snapshot changed.
+    pub fn history(&self, path: &Path) -> Option<String> {
+        let runs = self.runs.get(path)?;
+        Some(
+            runs.iter()
+                .map(|changed| if *changed { '●' } else { '○' })
+                .collect(),
+        )
+    }
+}
+
+/// Tells runs apart by their images: the same artifact or untouched files loaded again are the
+/// same run.
+fn run_id(snapshots: &[Snapshot]) -> u64 {
+    let mut hasher = Xxh3::new();
+    for snapshot in snapshots {
+        let version = serde_json::to_vec(&(&snapshot.path, snapshot.version()));
+        hasher.update(&version.unwrap_or_default());
+    }
+    hasher.digest()
+}
+
+fn flips(runs: &[bool]) -> usize {
+    runs.windows(2)
+        .filter(|pair| matches!(pair, [a, b] if a != b))
+        .count()
+}
+
+/// Loads of a pull request share their history, whatever artifact or commit they came from.
+pub fn source_key(loader: &dyn LoadSnapshots) -> String {
+    loader
+        .pull_request()
+        .map_or_else(|| loader.files_header(), |link| link.short_name())
+}
+
+#[cfg(not(target_arch = "wasm32"))]
+static CACHE: crate::cache::CacheDir = crate::cache::CacheDir::new("flaky", 16 << 20);
+
+#[cfg(not(target_arch = "wasm32"))]
+fn history_path(key: &str) -> Option<PathBuf> {
+    let hash = xxhash_rust::xxh3::xxh3_64(key.as_bytes());
+    Some(CACHE.path()?.join(format!("{hash:016x}.json")))
+}
+
+#[cfg(not(target_arch = "wasm32"))]
+fn read(key: &str) -> History {
+    history_path(key)
+        .and_then(|path| {
+            let bytes = std::fs::read(&path).ok()?;
+            crate::cache::CacheDir::touch(&path);
+            Some(bytes)
+        })
+        .and_then(|bytes| serde_json::from_slice::<History>(&bytes).ok())
+        // Guards against hash collisions.
+        .filter(|history| history.key == key)
+        .unwrap_or_else(|| History {
+            key: key.to_owned(),
+            runs: Vec::new(),
+        })
+}
+
+#[cfg(not(target_arch = "wasm32"))]
+fn write(history: &History) {
+    let Some(path) = history_path(&history.key) else {
+        return;
+    };
+    let result = path
+        .parent()
+        .map_or(Ok(()), std::fs::create_dir_all)
+        .and_then(|()| {
+            let json = serde_json::to_vec(history)?;
+            std::fs::write(&path, &json)?;
+            CACHE.wrote(json.len() as u64);
+            Ok(())
+        });
+    if let Err(err) = result {
+        tracing::debug!("Failed to write {}: {err}", path.display());
+    }
+}
+
+/// The browser has no cache dir, the history only lasts as long as the page.
+#[cfg(target_arch = "wasm32")]
+fn read(key: &str) -> History {
+    History {
+        key: key.to_owned(),
+        runs: Vec::new(),
+    }
+}
+
+#[cfg(target_arch = "wasm32")]
+fn write(_history: &History) {}
@@ -1,4 +1,5 @@
 use crate::github::model::{GithubArtifactLink, GithubRepoLink};
+use crate::notifications::Notification;
 use crate::state::SystemCommand;
 use eframe::egui;
 use eframe::egui::{Context, ViewportCommand};
@@ -12,9 +13,15 @@ mod auth_impl;
 #[path = "auth/native.rs"]
 mod auth_impl;
 
+#[cfg(not(target_arch = "wasm32"))]
+pub use auth_impl::{forget_login, login_in_browser, stored_token};
+
 pub enum GithubAuthCommand {
     Login,
     Logout,
+    SetPersonalAccessToken(Option<String>),
+    SetJenkinsApiToken(Option<String>),
+    SetCircleciToken(Option<String>),
 }
 
 impl From<GithubAuthCommand> for SystemCommand {
@@ -26,6 +33,96 @@ impl From<GithubAuthCommand> for SystemCommand {
 #[derive(Debug, Clone, Default, PartialEq, Eq, serde::Serialize, serde::Deserialize)]
 pub struct AuthState {
     pub logged_in: Option<LoggedInState>,
+
+    /// A classic or fine-grained personal access token, used instead of the OAuth login.
+    #[serde(default)]
+    pub personal_access_token: Option<String>,
+
+    /// For the server in [`crate::settings::JenkinsSettings`].
+    #[serde(default)]
+    pub jenkins_api_token: Option<String>,
+
+    /// A personal API token for the artifacts of private CircleCI projects.
+    #[serde(default)]
+    pub circleci_token: Option<String>,
+}
+
+impl AuthState {
+    pub(crate) fn secrets(&self) -> AuthSecrets {
+        AuthSecrets {
+            github_token: self
+                .logged_in
+                .as_ref()
+                .map(|l| l.github_token.clone())
+                .filter(|t| !t.is_empty()),
+            personal_access_token: self.personal_access_token.clone(),
+            jenkins_api_token: self.jenkins_api_token.clone(),
+            circleci_token: self.circleci_token.clone(),
+            username: self.logged_in.as_ref().map(|l| l.username.clone()),
+        }
+    }
+
+    /// Copy of the state with all tokens removed, safe to put in plain storage.
+    pub(crate) fn without_secrets(&self) -> Self {
+        let mut state = self.clone();
+        if let Some(logged_in) = &mut state.logged_in {
+            logged_in.github_token.clear();
+        }
+        state.personal_access_token = None;
+        state.jenkins_api_token = None;
+        state.circleci_token = None;
+        state
+    }
+
+    /// Fill in tokens loaded from secure storage. A login without a token is dropped.
+    pub(crate) fn with_secrets(mut self, secrets: AuthSecrets) -> Self {
+        self.personal_access_token = secrets.personal_access_token;
+        self.jenkins_api_token = secrets.jenkins_api_token;
+        self.circleci_token = secrets.circleci_token;
+        self.logged_in = secrets.github_token.and_then(|token| {
+            // `kitdiff auth login` only writes the secrets, so the username may be new to us.
+            let previous = self.logged_in.take();
+            let username = secrets
+                .username
+                .or_else(|| previous.as_ref().map(|l| l.username.clone()))?;
+            let user_image = previous
+                .filter(|l| l.username == username)
+                .and_then(|l| l.user_image);
+            Some(LoggedInState {
+                github_token: token,
+                username,
+                user_image,
+            })
+        });
+        self
+    }
+}
+
+/// The parts of [`AuthState`] that should not end up in plain eframe storage.
+#[derive(Debug, Clone, Default, PartialEq, Eq, serde::Serialize, serde::Deserialize)]
+pub(crate) struct AuthSecrets {
+    pub github_token: Option<String>,
+    pub personal_access_token: Option<String>,
+
+    #[serde(default)]
+    pub jenkins_api_token: Option<String>,
+
+    #[serde(default)]
+    pub circleci_token: Option<String>,
+
+    /// Who `github_token` belongs to. Not secret, but the plain state doesn't know it if the
+    /// login happened on the command line.
+    #[serde(default)]
+    pub username: Option<String>,
+}
+
+impl AuthSecrets {
+    pub fn is_empty(&self) -> bool {
+        self.github_token.is_none()
+            && self.personal_access_token.is_none()
+            && self.jenkins_api_token.is_none()
+            && self.circleci_token.is_none()
+    }
 }
 
 #[derive(Debug, Clone, PartialEq, Eq, serde::Serialize, serde::Deserialize)]
@@ -35,18 +132,60 @@ pub struct LoggedInState {
     pub user_image: Option<String>,
 }
 
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum TokenSource {
+    /// Passed via `--token` or the `GITHUB_TOKEN` environment variable.
+    Override,
+    PersonalAccessToken,
+    OAuth,
+}
+
 #[derive(Debug)]
 pub struct GitHubAuth {
     state: AuthState,
+
+    /// Token passed via `--token` / `GITHUB_TOKEN`. Takes precedence over everything else and is
+    /// never persisted.
+    token_override: Option<String>,
+    storage: auth_impl::SecureStorage,
     inbox: UiInbox<AuthEvent>,
     sender: UiInboxSender<SystemCommand>,
 }
 
 impl GitHubAuth {
-    fn make_client(token: Option<&str>) -> octocrab::Octocrab {
-        let builder = octocrab_wasm::builder();
+    /// Goes through [`crate::http`] when a proxy or extra certificates are configured, which
+    /// octocrab's own client doesn't know about.
+    #[cfg(not(target_arch = "wasm32"))]
+    fn make_proxied_client() -> Option<octocrab::Octocrab> {
+        if !crate::http::is_customized() {
+            return None;
+        }
+        // Artifact and log downloads read the redirect to blob storage themselves.
+        let client = reqwest::Client::builder().redirect(reqwest::redirect::Policy::none());
+        match crate::http::build_client(client) {
+            Ok(client) => Some(
+                octocrab_wasm::reqwest_builder(client)
+                    .build()
+                    .expect("Failed to build Octocrab client"),
+            ),
+            Err(err) => {
+                tracing::error!("Failed to set up the http client for GitHub: {err:#}");
+                None
+            }
+        }
+    }
+
+    #[cfg(target_arch = "wasm32")]
+    fn make_proxied_client() -> Option<octocrab::Octocrab> {
+        None
+    }
 
-        let mut client = builder.build().expect("Failed to build Octocrab client");
+    fn make_client(token: Option<&str>) -> octocrab::Octocrab {
+        let mut client = Self::make_proxied_client().unwrap_or_else(|| {
+            octocrab_wasm::builder()
+                .build()
+                .expect("Failed to build Octocrab client")
+        });
 
         if let Some(token) = token {
             client = client
@@ -64,7 +203,7 @@ impl GitHubAuth {
 
 #[derive(Debug, Clone)]
 pub enum AuthEvent {
-    LoginSuccessful(AuthState),
+    LoginSuccessful(LoggedInState),
     Error(String),
 }
 
@@ -120,8 +259,11 @@ impl GitHubAuth {
         "https://github.com/apps/kitdiff/installations/new";
 
     pub fn new(state: AuthState, sender: UiInboxSender<SystemCommand>) -> Self {
+        let storage = auth_impl::SecureStorage::default();
         let this = Self {
-            state,
+            state: storage.restore(state),
+            token_override: None,
+            storage,
             inbox: UiInbox::new(),
             sender,
         };
@@ -138,6 +280,16 @@ impl GitHubAuth {
             GithubAuthCommand::Logout => {
                 self.logout();
             }
+            GithubAuthCommand::SetPersonalAccessToken(token) => {
+                self.state.personal_access_token = token.filter(|t| !t.trim().is_empty());
+                self.sender.send(SystemCommand::Refresh).ok();
+            }
+            GithubAuthCommand::SetJenkinsApiToken(token) => {
+                self.set_jenkins_api_token(token);
+            }
+            GithubAuthCommand::SetCircleciToken(token) => {
+                self.set_circleci_token(token);
+            }
         }
     }
 
@@ -164,12 +316,10 @@ impl GitHubAuth {
 
         match username {
             Ok(username) => {
-                tx.send(AuthEvent::LoginSuccessful(AuthState {
-                    logged_in: Some(LoggedInState {
-                        github_token: data.token,
-                        username: username.login,
-                        user_image: Some(username.avatar_url.to_string()),
-                    }),
+                tx.send(AuthEvent::LoginSuccessful(LoggedInState {
+                    github_token: data.token,
+                    username: username.login,
+                    user_image: Some(username.avatar_url.to_string()),
                 }))
                 .ok();
             }
@@ -182,7 +332,7 @@ impl GitHubAuth {
         }
     }
 
-    async fn fetch_user_info(token: &str) -> anyhow::Result<Author> {
+    pub async fn fetch_user_info(token: &str) -> anyhow::Result<Author> {
         let client = Self::make_client(Some(token));
         let user = client.current().user().await?;
 
@@ -193,11 +343,50 @@ impl GitHubAuth {
         self.state.logged_in.as_ref().map(|s| s.username.as_str())
     }
 
+    /// The token used for API requests, in order of precedence: the `--token` / `GITHUB_TOKEN`
+    /// override, a personal access token from the settings, and finally the OAuth login.
     pub fn get_token(&self) -> Option<&str> {
-        self.state
-            .logged_in
-            .as_ref()
-            .map(|s| s.github_token.as_str())
+        self.token_override
+            .as_deref()
+            .or(self.state.personal_access_token.as_deref())
+            .or_else(|| {
+                self.state
+                    .logged_in
+                    .as_ref()
+                    .map(|s| s.github_token.as_str())
+            })
+    }
+
+    pub fn token_source(&self) -> Option<TokenSource> {
+        if self.token_override.is_some() {
+            Some(TokenSource::Override)
+        } else if self.state.personal_access_token.is_some() {
+            Some(TokenSource::PersonalAccessToken)
+        } else if self.state.logged_in.is_some() {
+            Some(TokenSource::OAuth)
+        } else {
+            None
+        }
+    }
+
+    pub fn jenkins_api_token(&self) -> Option<&str> {
+        self.state.jenkins_api_token.as_deref()
+    }
+
+    pub fn set_jenkins_api_token(&mut self, token: Option<String>) {
+        self.state.jenkins_api_token = token.filter(|t| !t.trim().is_empty());
+    }
+
+    pub fn circleci_token(&self) -> Option<&str> {
+        self.state.circleci_token.as_deref()
+    }
+
+    pub fn set_circleci_token(&mut self, token: Option<String>) {
+        self.state.circleci_token = token.filter(|t| !t.trim().is_empty());
+    }
+
+    pub fn set_token_override(&mut self, token: Option<String>) {
+        self.token_override = token.filter(|t| !t.trim().is_empty());
     }
 
     pub fn logout(&mut self) {
@@ -208,17 +397,25 @@ impl GitHubAuth {
         &self.state
     }
 
+    /// The state to write to eframe storage. On native the tokens are kept in the OS keychain
+    /// instead.
+    pub fn persisted_state(&self) -> AuthState {
+        self.storage.persist(&self.state)
+    }
+
     pub fn update(&mut self, _ctx: &egui::Context) {
         // Check for messages from auth flow
         for event in self.inbox.read(_ctx) {
             match event {
-                AuthEvent::LoginSuccessful(state) => {
-                    self.state = state;
+                AuthEvent::LoginSuccessful(logged_in) => {
+                    self.state.logged_in = Some(logged_in);
                     _ctx.send_viewport_cmd(ViewportCommand::Focus);
                     self.sender.send(SystemCommand::Refresh).ok();
                 }
                 AuthEvent::Error(error) => {
-                    log::error!("Auth error: {error}");
+                    let notification = Notification::error("GitHub login failed", error)
+                        .with_retry(GithubAuthCommand::Login);
+                    self.sender.send(notification.into()).ok();
                 }
             }
         }
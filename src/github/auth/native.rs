@@ -1,17 +1,23 @@
-use crate::github::auth::{AuthSender, GitHubAuth, parse_auth_fragment};
+use crate::github::auth::{
+    AuthFragment, AuthSecrets, AuthSender, AuthState, GitHubAuth, LoggedInState, TokenSource,
+    parse_auth_fragment,
+};
 use axum::Json;
 use axum::extract::State;
 use axum::http::StatusCode;
 use axum::response::{Html, Response};
+use eframe::egui::mutex::Mutex;
 use eframe::egui::{Context, OpenUrl};
 use std::net::{Ipv4Addr, SocketAddrV4};
+use std::sync::Arc;
 use tokio::spawn;
+use tokio::sync::oneshot;
 
 pub fn login_github(ctx: &Context, tx: AuthSender) {
     let ctx = ctx.clone();
     spawn(async move {
         if let Err(err) = login(ctx, tx).await {
-            log::error!("Error during GitHub login: {err:?}");
+            tracing::error!("Error during GitHub login: {err:?}");
         }
     });
 }
@@ -21,22 +27,76 @@ pub fn check_for_auth_callback(_sender: AuthSender) {
 }
 
 pub async fn login(ctx: Context, tx: AuthSender) -> anyhow::Result<()> {
+    let data = receive_token(|url| ctx.open_url(OpenUrl::new_tab(url))).await?;
+    GitHubAuth::handle_callback_fragment(tx, data).await;
+    Ok(())
+}
+
+/// Signs in without the app, for `kitdiff auth login`, and stores the token in the keychain
+/// where the app finds it.
+pub async fn login_in_browser(open_url: impl FnOnce(String)) -> anyhow::Result<LoggedInState> {
+    let data = receive_token(open_url).await?;
+    let user = GitHubAuth::fetch_user_info(&data.token).await?;
+    let logged_in = LoggedInState {
+        github_token: data.token,
+        username: user.login,
+        user_image: Some(user.avatar_url.to_string()),
+    };
+
+    let mut secrets = load_secrets()?.unwrap_or_default();
+    secrets.github_token = Some(logged_in.github_token.clone());
+    secrets.username = Some(logged_in.username.clone());
+    store_secrets(&secrets)?;
+    Ok(logged_in)
+}
+
+/// The token the app would use from the keychain, and where it came from.
+pub fn stored_token() -> anyhow::Result<Option<(TokenSource, String)>> {
+    let secrets = load_secrets()?.unwrap_or_default();
+    Ok(secrets
+        .personal_access_token
+        .map(|token| (TokenSource::PersonalAccessToken, token))
+        .or_else(|| {
+            secrets
+                .github_token
+                .map(|token| (TokenSource::OAuth, token))
+        }))
+}
+
+/// Removes the OAuth login from the keychain, like signing out in the app. A personal access
+/// token is kept.
+pub fn forget_login() -> anyhow::Result<()> {
+    let mut secrets = load_secrets()?.unwrap_or_default();
+    secrets.github_token = None;
+    secrets.username = None;
+    store_secrets(&secrets)
+}
+
+/// Serves the page GitHub redirects to after signing in at the url passed to `open_url`, until
+/// it posts the token back to us.
+async fn receive_token(open_url: impl FnOnce(String)) -> anyhow::Result<AuthFragment> {
     let listener = tokio::net::TcpListener::bind(SocketAddrV4::new(Ipv4Addr::LOCALHOST, 0)).await?;
 
     let addr = listener.local_addr()?;
 
-    ctx.open_url(OpenUrl::new_tab(GitHubAuth::auth_url(&format!(
-        "http://{addr}"
-    ))));
+    open_url(GitHubAuth::auth_url(&format!("http://{addr}")));
 
+    let (tx, rx) = oneshot::channel();
     let router = axum::Router::new()
         .route("/", axum::routing::get(home_route))
         .route("/api/auth", axum::routing::post(auth_route))
-        .with_state(tx);
+        .with_state(Arc::new(Mutex::new(Some(tx))));
 
-    axum::serve(listener, router).await?;
+    let (token_tx, token_rx) = oneshot::channel();
+    axum::serve(listener, router)
+        .with_graceful_shutdown(async move {
+            if let Ok(data) = rx.await {
+                token_tx.send(data).ok();
+            }
+        })
+        .await?;
 
-    Ok(())
+    Ok(token_rx.await?)
 }
 
 pub async fn home_route() -> Html<&'static str> {
@@ -48,8 +108,11 @@ struct AuthBody {
     fragment: String,
 }
 
+/// Where [`auth_route`] sends the token, taken by the first request.
+type TokenSender = Arc<Mutex<Option<oneshot::Sender<AuthFragment>>>>;
+
 async fn auth_route(
-    State(tx): State<AuthSender>,
+    State(tx): State<TokenSender>,
     Json(body): Json<AuthBody>,
 ) -> Result<String, Response<String>> {
     let fragment = body.fragment;
@@ -61,7 +124,85 @@ async fn auth_route(
             .expect("Failed to build error response")
     })?;
 
-    GitHubAuth::handle_callback_fragment(tx, data).await;
+    if let Some(tx) = tx.lock().take() {
+        tx.send(data).ok();
+    }
 
     Ok("Success".to_owned())
 }
+
+const KEYRING_SERVICE: &str = "kitdiff";
+const KEYRING_USER: &str = "github";
+
+/// Keeps tokens in the OS keychain (macOS Keychain, Windows Credential Manager, Secret Service).
+#[derive(Debug, Default)]
+pub struct SecureStorage {
+    /// What we last wrote to the keychain, so we only touch it when something changed.
+    last_stored: Mutex<Option<AuthSecrets>>,
+}
+
+impl SecureStorage {
+    pub fn restore(&self, state: AuthState) -> AuthState {
+        let legacy = state.secrets();
+        if !legacy.is_empty() {
+            // Tokens from before we used the keychain, move them over.
+            match store_secrets(&legacy) {
+                Ok(()) => *self.last_stored.lock() = Some(legacy),
+                Err(err) => tracing::warn!("Failed to migrate tokens to the keychain: {err}"),
+            }
+            return state;
+        }
+
+        match load_secrets() {
+            Ok(secrets) => {
+                let secrets = secrets.unwrap_or_default();
+                *self.last_stored.lock() = Some(secrets.clone());
+                state.with_secrets(secrets)
+            }
+            Err(err) => {
+                tracing::warn!("Failed to read tokens from the keychain: {err}");
+                state.with_secrets(AuthSecrets::default())
+            }
+        }
+    }
+
+    pub fn persist(&self, state: &AuthState) -> AuthState {
+        let secrets = state.secrets();
+        let mut last_stored = self.last_stored.lock();
+        if last_stored.as_ref() != Some(&secrets) {
+            if let Err(err) = store_secrets(&secrets) {
+                tracing::warn!(
+                    "Failed to write tokens to the keychain, using plain storage: {err}"
+                );
+                return state.clone();
+            }
+            *last_stored = Some(secrets);
+        }
+        state.without_secrets()
+    }
+}
+
+fn keyring_entry() -> keyring::Result<keyring::Entry> {
+    keyring::Entry::new(KEYRING_SERVICE, KEYRING_USER)
+}
+
+fn load_secrets() -> anyhow::Result<Option<AuthSecrets>> {
+    match keyring_entry()?.get_password() {
+        Ok(json) => Ok(Some(serde_json::from_str(&json)?)),
+        Err(keyring::Error::NoEntry) => Ok(None),
+        Err(err) => Err(err.into()),
+    }
+}
+
+fn store_secrets(secrets: &AuthSecrets) -> anyhow::Result<()> {
+    let entry = keyring_entry()?;
+    if secrets.is_empty() {
+        match entry.delete_credential() {
+            Ok(()) | Err(keyring::Error::NoEntry) => Ok(()),
+            Err(err) => Err(err.into()),
+        }
+    } else {
+        entry.set_password(&serde_json::to_string(secrets)?)?;
+        Ok(())
+    }
+}
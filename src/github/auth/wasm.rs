@@ -1,4 +1,4 @@
-use crate::github::auth::{AuthSender, GitHubAuth, parse_auth_fragment};
+use crate::github::auth::{AuthSender, AuthState, GitHubAuth, parse_auth_fragment};
 use eframe::egui;
 use eframe::egui::OpenUrl;
 use hello_egui_utils::spawn;
@@ -33,3 +33,18 @@ pub fn check_for_auth_callback(sender: AuthSender) {
         }
     }
 }
+
+/// There is no keychain on the web, so the tokens stay in eframe storage.
+#[derive(Debug, Default)]
+pub struct SecureStorage;
+
+#[expect(clippy::unused_self)]
+impl SecureStorage {
+    pub fn restore(&self, state: AuthState) -> AuthState {
+        state
+    }
+
+    pub fn persist(&self, state: &AuthState) -> AuthState {
+        state.clone()
+    }
+}
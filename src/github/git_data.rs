@@ -0,0 +1,173 @@
+//! Creating commits through the git data API, without a local checkout.
+
+use crate::github::octokit::RepoClient;
+use base64::Engine as _;
+use serde_json::json;
+use std::collections::HashSet;
+
+/// New content for a file, `None` deletes it.
+pub struct FileChange {
+    pub path: String,
+    pub content: Option<Vec<u8>>,
+}
+
+#[derive(serde::Deserialize)]
+struct ShaResponse {
+    sha: String,
+}
+
+#[derive(serde::Deserialize)]
+struct RefResponse {
+    object: ShaResponse,
+}
+
+#[derive(serde::Deserialize)]
+struct CommitResponse {
+    tree: ShaResponse,
+}
+
+#[derive(serde::Deserialize)]
+struct TreeResponse {
+    tree: Vec<TreeEntry>,
+    truncated: bool,
+}
+
+#[derive(serde::Deserialize)]
+struct TreeEntry {
+    path: String,
+    #[serde(rename = "type")]
+    kind: String,
+}
+
+/// What's in the tree of a commit.
+#[derive(Default)]
+pub struct RepoTree {
+    pub files: HashSet<String>,
+    pub dirs: HashSet<String>,
+}
+
+fn route(repo: &RepoClient, path: &str) -> String {
+    format!(
+        "/repos/{}/{}/git/{path}",
+        repo.repo().owner,
+        repo.repo().repo
+    )
+}
+
+/// Sha of the commit the branch points to.
+pub async fn branch_head(repo: &RepoClient, branch: &str) -> anyhow::Result<String> {
+    let response: RefResponse = repo
+        .get(route(repo, &format!("ref/heads/{branch}")), None::<&()>)
+        .await?;
+    Ok(response.object.sha)
+}
+
+/// All paths in the tree of `commit`.
+pub async fn tree(repo: &RepoClient, commit: &str) -> anyhow::Result<RepoTree> {
+    let commit: CommitResponse = repo
+        .get(route(repo, &format!("commits/{commit}")), None::<&()>)
+        .await?;
+    let response: TreeResponse = repo
+        .get(
+            route(repo, &format!("trees/{}", commit.tree.sha)),
+            Some(&[("recursive", "1")]),
+        )
+        .await?;
+    anyhow::ensure!(
+        !response.truncated,
+        "The repository has too many files to list them all"
+    );
+
+    let mut tree = RepoTree::default();
+    for entry in response.tree {
+        match entry.kind.as_str() {
+            "blob" => tree.files.insert(entry.path),
+            "tree" => tree.dirs.insert(entry.path),
+            _ => false,
+        };
+    }
+    Ok(tree)
+}
+
+/// Creates a commit on top of `parent` with the changed files, returning its sha.
+///
+/// This doesn't move any branch, see [`update_branch`] and [`create_branch`].
+pub async fn create_commit(
+    repo: &RepoClient,
+    parent: &str,
+    message: &str,
+    changes: &[FileChange],
+) -> anyhow::Result<String> {
+    let parent_commit: CommitResponse = repo
+        .get(route(repo, &format!("commits/{parent}")), None::<&()>)
+        .await?;
+
+    let mut tree = Vec::with_capacity(changes.len());
+    for change in changes {
+        let sha = match &change.content {
+            Some(content) => {
+                let blob: ShaResponse = repo
+                    .post(
+                        route(repo, "blobs"),
+                        Some(&json!({
+                            "content": base64::engine::general_purpose::STANDARD.encode(content),
+                            "encoding": "base64",
+                        })),
+                    )
+                    .await?;
+                Some(blob.sha)
+            }
+            None => None,
+        };
+        tree.push(json!({
+            "path": change.path,
+            "mode": "100644",
+            "type": "blob",
+            "sha": sha,
+        }));
+    }
+
+    let tree: ShaResponse = repo
+        .post(
+            route(repo, "trees"),
+            Some(&json!({
+                "base_tree": parent_commit.tree.sha,
+                "tree": tree,
+            })),
+        )
+        .await?;
+
+    let commit: ShaResponse = repo
+        .post(
+            route(repo, "commits"),
+            Some(&json!({
+                "message": message,
+                "tree": tree.sha,
+                "parents": [parent],
+            })),
+        )
+        .await?;
+    Ok(commit.sha)
+}
+
+/// Fast-forwards the branch to `sha`. Fails if someone pushed in the meantime.
+pub async fn update_branch(repo: &RepoClient, branch: &str, sha: &str) -> anyhow::Result<()> {
+    let _: serde_json::Value = repo
+        .patch(
+            route(repo, &format!("refs/heads/{branch}")),
+            Some(&json!({ "sha": sha, "force": false })),
+        )
+        .await?;
+    Ok(())
+}
+
+/// Creates a new branch pointing at `sha`.
+pub async fn create_branch(repo: &RepoClient, branch: &str, sha: &str) -> anyhow::Result<()> {
+    let _: serde_json::Value = repo
+        .post(
+            route(repo, "refs"),
+            Some(&json!({ "ref": format!("refs/heads/{branch}"), "sha": sha })),
+        )
+        .await?;
+    Ok(())
+}
@@ -1,4 +1,5 @@
 pub mod auth;
+pub mod git_data;
 pub mod model;
 pub mod octokit;
 pub mod pr;
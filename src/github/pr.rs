@@ -8,13 +8,14 @@ use futures::TryStreamExt as _;
 use futures::stream::FuturesUnordered;
 use graphql_client::GraphQLQuery;
 use octocrab::Octocrab;
-use octocrab::models::{RunId, workflows::WorkflowListArtifact};
+use octocrab::models::{ArtifactId, RunId, workflows::WorkflowListArtifact};
 use re_ui::egui_ext::boxed_widget::BoxedWidgetLocalExt as _;
 use re_ui::list_item::{LabelContent, ListItemContentButtonsExt as _, list_item_scope};
 use re_ui::{SectionCollapsingHeader, UiExt as _, icons};
+use std::collections::HashMap;
 use std::collections::hash_map::Entry;
-use std::collections::{HashMap, HashSet};
 use std::task::Poll;
+use std::time::Duration;
 pub type GitObjectID = String;
 pub type DateTime = String;
 #[expect(clippy::upper_case_acronyms)]
@@ -27,6 +28,110 @@ pub type URI = String;
     response_derives = "Debug, Clone"
 )]
 pub struct PrDetailsQuery;
+
+#[derive(GraphQLQuery, Debug)]
+#[graphql(
+    schema_path = "github.graphql",
+    query_path = "src/github/pr.graphql",
+    response_derives = "Debug, Clone"
+)]
+pub struct CommitCheckSuitesQuery;
+
+/// What we need to know about a check suite of a workflow run.
+#[derive(Debug, Clone)]
+struct CheckSuite {
+    workflow_id: String,
+    workflow_name: String,
+    run_id: Option<i64>,
+    run_url: String,
+    pending: bool,
+    failed: bool,
+
+    /// The conclusion, or the status while it's still running, e.g. "timed out" or "queued".
+    label: String,
+
+    /// Only known once it completed.
+    duration: Option<Duration>,
+}
+
+/// Both queries generate their own copy of the `CheckSuiteFields` fragment and its enums.
+macro_rules! impl_into_check_suite {
+    ($query:ident) => {
+        impl $query::CheckSuiteFields {
+            /// `None` if the suite doesn't belong to a workflow run.
+            fn into_check_suite(self) -> Option<CheckSuite> {
+                let workflow_run = self.workflow_run?;
+                let pending = match self.status {
+                    $query::CheckStatusState::IN_PROGRESS
+                    | $query::CheckStatusState::PENDING
+                    | $query::CheckStatusState::QUEUED
+                    | $query::CheckStatusState::REQUESTED
+                    | $query::CheckStatusState::WAITING => true,
+                    $query::CheckStatusState::COMPLETED | $query::CheckStatusState::Other(_) => {
+                        false
+                    }
+                };
+                let failed = match self.conclusion {
+                    Some(
+                        $query::CheckConclusionState::ACTION_REQUIRED
+                        | $query::CheckConclusionState::CANCELLED
+                        | $query::CheckConclusionState::FAILURE
+                        | $query::CheckConclusionState::STARTUP_FAILURE
+                        | $query::CheckConclusionState::TIMED_OUT
+                        | $query::CheckConclusionState::Other(_),
+                    ) => true,
+                    Some(
+                        $query::CheckConclusionState::NEUTRAL
+                        | $query::CheckConclusionState::SKIPPED
+                        | $query::CheckConclusionState::STALE
+                        | $query::CheckConclusionState::SUCCESS,
+                    )
+                    | None => false,
+                };
+                let label = match &self.conclusion {
+                    Some(conclusion) if !pending => format!("{conclusion:?}"),
+                    _ => format!("{:?}", self.status),
+                };
+                let duration = if pending {
+                    None
+                } else {
+                    duration(&self.created_at, &self.updated_at)
+                };
+                Some(CheckSuite {
+                    workflow_id: workflow_run.workflow.id,
+                    workflow_name: workflow_run.workflow.name,
+                    run_id: workflow_run.database_id,
+                    run_url: workflow_run.url,
+                    pending,
+                    failed,
+                    label: label.to_lowercase().replace('_', " "),
+                    duration,
+                })
+            }
+        }
+    };
+}
+
+impl_into_check_suite!(pr_details_query);
+impl_into_check_suite!(commit_check_suites_query);
+
+fn duration(created_at: &str, updated_at: &str) -> Option<Duration> {
+    let created_at = chrono::DateTime::parse_from_rfc3339(created_at).ok()?;
+    let updated_at = chrono::DateTime::parse_from_rfc3339(updated_at).ok()?;
+    (updated_at - created_at).to_std().ok()
+}
+
+impl CheckSuite {
+    fn state(&self) -> CommitState {
+        if self.failed {
+            CommitState::Failure
+        } else if self.pending {
+            CommitState::Pending
+        } else {
+            CommitState::Success
+        }
+    }
+}
 use crate::github::model::{GithubArtifactLink, GithubPrLink, PrNumber};
 use anyhow::{Error, Result, anyhow};
 use eframe::emath::RectAlign;
@@ -121,6 +226,9 @@ struct CommitData {
     sha: String,
     status: CommitState,
     workflow_run_ids: Vec<u64>,
+
+    /// The latest check suite of every workflow, by name.
+    workflows: Vec<CheckSuite>,
 }
 
 impl GithubPr {
@@ -181,110 +289,181 @@ impl GithubPr {
             }
         }
     }
+
+    /// The PR's commits as `(sha, message headline)`, oldest first. `None` while loading.
+    pub fn commits(&self) -> Option<Vec<(String, String)>> {
+        match &self.data {
+            Poll::Ready(Ok(data)) => Some(
+                data.commits
+                    .iter()
+                    .map(|c| (c.sha.clone(), c.message.clone()))
+                    .collect(),
+            ),
+            _ => None,
+        }
+    }
 }
 
 async fn get_pr_commits(repo: &RepoClient, pr: PrNumber) -> Result<PrWithCommits> {
-    let response: graphql_client::Response<pr_details_query::ResponseData> = repo
-        .graphql(&PrDetailsQuery::build_query(pr_details_query::Variables {
-            owner: repo.repo().owner.clone(),
-            repo: repo.repo().repo.clone(),
-            oid: pr as _,
-        }))
-        .await?;
-
-    let response = response
-        .data
-        .ok_or_else(|| anyhow!("No data in response"))?
-        .repository
-        .ok_or_else(|| anyhow!("Repository not found"))?
-        .pull_request
-        .ok_or_else(|| anyhow!("Pull request not found"))?;
-
-    let mut data = PrWithCommits {
-        title: response.title,
-        head_branch: response.head_ref_name,
-        base_branch: response.base_ref_name,
-        commits: Vec::new(),
-        artifacts: HashMap::new(),
-    };
-
-    for commit in response
-        .commits
-        .nodes
-        .ok_or_else(|| anyhow!("No commits found"))?
-        .into_iter()
-        .flatten()
-    {
-        let commit = commit.commit;
-        let sha = commit.oid;
-        let message = commit.message_headline;
-
-        let mut status = CommitState::Success;
-        let mut workflow_run_ids = HashSet::new();
-
-        // Unfortunately github has no easy way to get the status for a commit, best thing seems to be
-        // to query all check suites and group them by workflow.
-        let mut last_suite_per_workflow = HashMap::new();
-
-        if let Some(suites) = commit.check_suites
-            && let Some(nodes) = suites.nodes
+    let mut data: Option<PrWithCommits> = None;
+    let mut before = None;
+
+    // The commits are paged backwards from the latest, with up to 100 per page.
+    loop {
+        let response: graphql_client::Response<pr_details_query::ResponseData> = repo
+            .graphql(&PrDetailsQuery::build_query(pr_details_query::Variables {
+                owner: repo.repo().owner.clone(),
+                repo: repo.repo().repo.clone(),
+                oid: pr as _,
+                before: before.take(),
+            }))
+            .await?;
+
+        let response = response
+            .data
+            .ok_or_else(|| anyhow!("No data in response"))?
+            .repository
+            .ok_or_else(|| anyhow!("Repository not found"))?
+            .pull_request
+            .ok_or_else(|| anyhow!("Pull request not found"))?;
+
+        let mut commits = Vec::new();
+        for commit in response
+            .commits
+            .nodes
+            .ok_or_else(|| anyhow!("No commits found"))?
+            .into_iter()
+            .flatten()
         {
-            for node in nodes.into_iter().flatten() {
-                if let Some(workflow_run) = node.workflow_run.clone() {
-                    last_suite_per_workflow.insert(workflow_run.workflow.id, node);
+            let commit = commit.commit;
+
+            let mut suites = Vec::new();
+            if let Some(check_suites) = commit.check_suites {
+                if check_suites.page_info.has_previous_page
+                    && let Some(cursor) = check_suites.page_info.start_cursor
+                {
+                    suites = older_check_suites(repo, &commit.oid, cursor).await?;
                 }
+                suites.extend(
+                    check_suites
+                        .nodes
+                        .into_iter()
+                        .flatten()
+                        .flatten()
+                        .filter_map(pr_details_query::CheckSuiteFields::into_check_suite),
+                );
             }
+
+            let workflows = latest_per_workflow(suites);
+            commits.push(CommitData {
+                message: commit.message_headline,
+                sha: commit.oid,
+                status: commit_state(&workflows),
+                workflow_run_ids: workflows
+                    .iter()
+                    .filter_map(|suite| suite.run_id)
+                    .map(|id| id as u64)
+                    .collect(),
+                workflows,
+            });
         }
 
-        #[expect(clippy::iter_over_hash_type)]
-        for suite in last_suite_per_workflow.values() {
-            let pending = match suite.status {
-                pr_details_query::CheckStatusState::IN_PROGRESS
-                | pr_details_query::CheckStatusState::PENDING
-                | pr_details_query::CheckStatusState::QUEUED
-                | pr_details_query::CheckStatusState::REQUESTED
-                | pr_details_query::CheckStatusState::WAITING => true,
-                pr_details_query::CheckStatusState::COMPLETED
-                | pr_details_query::CheckStatusState::Other(_) => false,
-            };
-            let error = if let Some(conclusion) = &suite.conclusion {
-                match conclusion {
-                    pr_details_query::CheckConclusionState::ACTION_REQUIRED
-                    | pr_details_query::CheckConclusionState::CANCELLED
-                    | pr_details_query::CheckConclusionState::FAILURE
-                    | pr_details_query::CheckConclusionState::STARTUP_FAILURE
-                    | pr_details_query::CheckConclusionState::TIMED_OUT
-                    | pr_details_query::CheckConclusionState::Other(_) => true,
-                    pr_details_query::CheckConclusionState::NEUTRAL
-                    | pr_details_query::CheckConclusionState::SKIPPED
-                    | pr_details_query::CheckConclusionState::STALE
-                    | pr_details_query::CheckConclusionState::SUCCESS => false,
-                }
-            } else {
-                false
-            };
-            if error {
-                status = CommitState::Failure;
-            } else if pending && status != CommitState::Failure {
-                status = CommitState::Pending;
-            }
+        let data = data.get_or_insert_with(|| PrWithCommits {
+            title: response.title,
+            head_branch: response.head_ref_name,
+            base_branch: response.base_ref_name,
+            commits: Vec::new(),
+            artifacts: HashMap::new(),
+        });
+        // Older pages go in front, so the commits stay oldest first.
+        commits.append(&mut data.commits);
+        data.commits = commits;
+
+        let page_info = response.commits.page_info;
+        match page_info.start_cursor {
+            Some(cursor) if page_info.has_previous_page => before = Some(cursor),
+            _ => break,
+        }
+    }
 
-            if let Some(run) = &suite.workflow_run
-                && let Some(db_id) = run.database_id
-            {
-                workflow_run_ids.insert(db_id as u64);
-            }
+    data.ok_or_else(|| anyhow!("No commits found"))
+}
+
+/// The check suites of a commit that come before `cursor`, oldest first.
+async fn older_check_suites(
+    repo: &RepoClient,
+    oid: &str,
+    cursor: String,
+) -> Result<Vec<CheckSuite>> {
+    let mut suites = Vec::new();
+    let mut before = Some(cursor);
+
+    while let Some(cursor) = before.take() {
+        let response: graphql_client::Response<commit_check_suites_query::ResponseData> = repo
+            .graphql(&CommitCheckSuitesQuery::build_query(
+                commit_check_suites_query::Variables {
+                    owner: repo.repo().owner.clone(),
+                    repo: repo.repo().repo.clone(),
+                    oid: oid.to_owned(),
+                    before: Some(cursor),
+                },
+            ))
+            .await?;
+
+        let object = response
+            .data
+            .ok_or_else(|| anyhow!("No data in response"))?
+            .repository
+            .ok_or_else(|| anyhow!("Repository not found"))?
+            .object;
+        let Some(commit_check_suites_query::CommitCheckSuitesQueryRepositoryObject::Commit(commit)) =
+            object
+        else {
+            return Err(anyhow!("Commit {oid} not found"));
+        };
+        let Some(check_suites) = commit.check_suites else {
+            break;
+        };
+
+        let mut page: Vec<_> = check_suites
+            .nodes
+            .into_iter()
+            .flatten()
+            .flatten()
+            .filter_map(commit_check_suites_query::CheckSuiteFields::into_check_suite)
+            .collect();
+        page.append(&mut suites);
+        suites = page;
+
+        if check_suites.page_info.has_previous_page {
+            before = check_suites.page_info.start_cursor;
         }
+    }
 
-        data.commits.push(CommitData {
-            message,
-            sha,
-            status,
-            workflow_run_ids: workflow_run_ids.into_iter().collect(),
-        });
+    Ok(suites)
+}
+
+/// Unfortunately github has no easy way to get the status for a commit, best thing seems to be
+/// to query all check suites and group them by workflow.
+fn latest_per_workflow(suites: Vec<CheckSuite>) -> Vec<CheckSuite> {
+    let mut last_suite_per_workflow = HashMap::new();
+    for suite in suites {
+        last_suite_per_workflow.insert(suite.workflow_id.clone(), suite);
     }
+    let mut workflows: Vec<_> = last_suite_per_workflow.into_values().collect();
+    workflows.sort_by(|a, b| a.workflow_name.cmp(&b.workflow_name));
+    workflows
+}
 
-    Ok(data)
+/// Failed if any workflow failed, otherwise pending if any is still running.
+fn commit_state(workflows: &[CheckSuite]) -> CommitState {
+    if workflows.iter().any(|suite| suite.failed) {
+        CommitState::Failure
+    } else if workflows.iter().any(|suite| suite.pending) {
+        CommitState::Pending
+    } else {
+        CommitState::Success
+    }
 }
 
 async fn fetch_commit_artifacts(repo: &RepoClient, run_ids: Vec<u64>) -> Result<Vec<ArtifactData>> {
@@ -343,7 +522,9 @@ pub fn pr_ui(ui: &mut egui::Ui, state: &AppStateRef<'_>, pr: &GithubPr) {
                             .with_button(button)
                             .with_always_show_buttons(true);
 
-                        let response = item.show_hierarchical(ui, content);
+                        let response = item
+                            .show_hierarchical(ui, content)
+                            .on_hover_ui(|ui| workflows_ui(ui, &commit.workflows));
                         if response.clicked() {
                             pr.inbox
                                 .sender()
@@ -375,20 +556,18 @@ pub fn pr_ui(ui: &mut egui::Ui, state: &AppStateRef<'_>, pr: &GithubPr) {
                                         if artifacts.is_empty() {
                                             ui.label("No artifacts found");
                                         } else {
-                                            for artifact in artifacts {
-                                                if ui.button(&artifact.data.name).clicked() {
-                                                    selected_source = Some(DiffSource::GHArtifact(
-                                                        GithubArtifactLink {
-                                                            repo: pr.link.repo.clone(),
-                                                            artifact_id: artifact.data.id,
-                                                            name: Some(artifact.data.name.clone()),
-                                                            branch_name: Some(
-                                                                data.head_branch.clone(),
-                                                            ),
-                                                            run_id: Some(artifact.run_id),
-                                                        },
-                                                    ));
-                                                }
+                                            let link =
+                                                |artifact: &ArtifactData| GithubArtifactLink {
+                                                    repo: pr.link.repo.clone(),
+                                                    artifact_id: artifact.data.id,
+                                                    name: Some(artifact.data.name.clone()),
+                                                    branch_name: Some(data.head_branch.clone()),
+                                                    run_id: Some(artifact.run_id),
+                                                };
+                                            if let Some(source) =
+                                                artifact_list_ui(ui, &commit.sha, artifacts, link)
+                                            {
+                                                selected_source = Some(source);
                                             }
                                         }
                                     }
@@ -413,3 +592,104 @@ pub fn pr_ui(ui: &mut egui::Ui, state: &AppStateRef<'_>, pr: &GithubPr) {
         state.send(SystemCommand::Open(source));
     }
 }
+
+/// One row per workflow with its conclusion, how long it took and a link to the run, so it's
+/// clear which job produced a failing snapshot.
+fn workflows_ui(ui: &mut egui::Ui, workflows: &[CheckSuite]) {
+    if workflows.is_empty() {
+        ui.weak("No workflow runs");
+        return;
+    }
+    egui::Grid::new("commit_workflows")
+        .num_columns(3)
+        .show(ui, |ui| {
+            for workflow in workflows {
+                match workflow.state() {
+                    CommitState::Failure => {
+                        let tint = ui.tokens().alert_error.icon;
+                        ui.add(icons::ERROR.as_image().tint(tint));
+                    }
+                    CommitState::Pending => {
+                        ui.spinner();
+                    }
+                    CommitState::Success => {
+                        let tint = ui.tokens().alert_success.icon;
+                        ui.add(icons::SUCCESS.as_image().tint(tint));
+                    }
+                }
+                ui.hyperlink_to(&workflow.workflow_name, &workflow.run_url);
+                match workflow.duration {
+                    Some(duration) => {
+                        ui.weak(format!("{}, {}", workflow.label, format_duration(duration)))
+                    }
+                    None => ui.weak(&workflow.label),
+                };
+                ui.end_row();
+            }
+        });
+}
+
+fn format_duration(duration: Duration) -> String {
+    let seconds = duration.as_secs();
+    if seconds < 60 {
+        format!("{seconds}s")
+    } else if seconds < 60 * 60 {
+        format!("{}m {}s", seconds / 60, seconds % 60)
+    } else {
+        format!("{}h {}m", seconds / 3600, seconds % 3600 / 60)
+    }
+}
+
+/// One button per artifact, plus checkboxes to load several artifacts merged into one list.
+fn artifact_list_ui(
+    ui: &mut egui::Ui,
+    sha: &str,
+    artifacts: &[ArtifactData],
+    link: impl Fn(&ArtifactData) -> GithubArtifactLink,
+) -> Option<DiffSource> {
+    let id = ui.id().with(("merge_artifacts", sha));
+    let mut merge: Vec<ArtifactId> = ui.data(|d| d.get_temp(id)).unwrap_or_default();
+    let mut source = None;
+
+    for artifact in artifacts {
+        ui.horizontal(|ui| {
+            let mut checked = merge.contains(&artifact.data.id);
+            if ui
+                .checkbox(&mut checked, "")
+                .on_hover_text("Select to load together with other artifacts")
+                .changed()
+            {
+                if checked {
+                    merge.push(artifact.data.id);
+                } else {
+                    merge.retain(|id| *id != artifact.data.id);
+                }
+            }
+            if ui.button(&artifact.data.name).clicked() {
+                source = Some(DiffSource::GHArtifact(link(artifact)));
+            }
+        });
+    }
+
+    if merge.len() > 1
+        && ui
+            .button(format!("Load {} artifacts merged", merge.len()))
+            .clicked()
+    {
+        source = Some(DiffSource::Merged(
+            artifacts
+                .iter()
+                .filter(|artifact| merge.contains(&artifact.data.id))
+                .map(|artifact| {
+                    (
+                        artifact.data.name.clone(),
+                        DiffSource::GHArtifact(link(artifact)),
+                    )
+                })
+                .collect(),
+        ));
+    }
+
+    ui.data_mut(|d| d.insert_temp(id, merge));
+    source
+}
@@ -1,27 +1,49 @@
 use crate::DiffSource;
+use crate::github::model::{GithubArtifactLink, GithubPrLink};
+use crate::notifications::Notification;
 use crate::state::{AppStateRef, SystemCommand};
 use eframe::egui;
+use eframe::egui::mutex::Mutex;
 use eframe::egui::{CentralPanel, Id, TextEdit, Ui};
+use octocrab::Octocrab;
+use std::collections::HashMap;
+use std::sync::Arc;
+use std::task::Poll;
 
 pub fn home_view(ui: &mut Ui, app: &AppStateRef<'_>) {
     CentralPanel::default().show_inside(ui, |ui| {
         ui.heading("Kitdiff");
 
+        let url_text_id = Id::new("url_text");
+        let mut url_text =
+            ui.memory_mut(|mem| mem.data.get_temp::<String>(url_text_id).unwrap_or_default());
         ui.horizontal(|ui| {
-            let url_text_id = Id::new("url_text");
-            let mut url_text =
-                ui.memory_mut(|mem| mem.data.get_temp::<String>(url_text_id).unwrap_or_default());
             let text_resp = ui.add(TextEdit::singleline(&mut url_text).hint_text("Enter url..."));
+            if text_resp.changed() {
+                let now = ui.input(|i| i.time);
+                ui.data_mut(|d| d.insert_temp(url_text_id.with("edited"), now));
+            }
 
             let button = ui.add_enabled(!url_text.is_empty(), egui::Button::new("Load"));
 
             let enter = text_resp.lost_focus() && ui.input(|i| i.key_pressed(egui::Key::Enter));
 
             if (button.clicked() || enter) && !url_text.is_empty() {
-                app.send(SystemCommand::Open(DiffSource::from_url(&url_text)));
+                app.send(SystemCommand::Open(DiffSource::from_url(url_text.trim())));
             }
-            ui.memory_mut(|mem| mem.data.insert_temp(url_text_id, url_text.clone()));
         });
+        repo_suggestions_ui(ui, app, &mut url_text);
+        url_check_ui(ui, app, &url_text, url_text_id);
+        ui.memory_mut(|mem| mem.data.insert_temp(url_text_id, url_text));
+        previous_session_ui(ui, app);
+        workspaces_ui(ui, app);
+        #[cfg(not(target_arch = "wasm32"))]
+        open_dialogs_ui(ui, app);
+        #[cfg(target_arch = "wasm32")]
+        open_directory_ui(ui, app);
+        #[cfg(target_arch = "wasm32")]
+        offline_ui(ui, app);
+
         ui.label("Valid urls are link to github PRs, links to github artifacts, or direct links to zip/tar.gz files.");
 
         ui.label("You need to sign in to load artifacts. You can see PR diffs without signing in but will quickly run into github rate limits.");
@@ -29,3 +51,436 @@ pub fn home_view(ui: &mut Ui, app: &AppStateRef<'_>) {
         ui.hyperlink_to("View kitdiff on github", "https://github.com/rerun-io/kitdiff");
     });
 }
+
+/// Offers to pick up where the last window was closed.
+fn previous_session_ui(ui: &mut Ui, app: &AppStateRef<'_>) {
+    let Some(session) = &app.previous_session else {
+        return;
+    };
+    ui.horizontal(|ui| {
+        if ui.button("⟲ Restore previous session").clicked() {
+            app.send(SystemCommand::RestoreSession(session.clone()));
+        }
+        ui.weak(&session.title);
+        if !session.reviews.is_empty() {
+            ui.weak(format!("({} reviewed)", session.reviews.len()));
+        }
+    });
+}
+
+/// Saved sets of sources, see [`crate::workspace`].
+fn workspaces_ui(ui: &mut Ui, app: &AppStateRef<'_>) {
+    if app.settings.workspaces.is_empty() {
+        return;
+    }
+    let mut settings = app.settings.clone();
+    ui.collapsing("Workspaces", |ui| {
+        settings.workspaces.retain(|name, workspace| {
+            let mut keep = true;
+            ui.horizontal(|ui| {
+                let names: Vec<&str> = workspace
+                    .sources
+                    .iter()
+                    .map(|(name, _)| name.as_str())
+                    .collect();
+                if ui.button(name).on_hover_text(names.join("\n")).clicked() {
+                    match workspace.source() {
+                        Some(source) => app.send(SystemCommand::Open(source)),
+                        None => app.send(Notification::error(
+                            "Failed to open the workspace",
+                            "none of its sources can be opened anymore",
+                        )),
+                    }
+                }
+                ui.weak(format!("{} sources", workspace.sources.len()));
+                if ui
+                    .small_button("🗑")
+                    .on_hover_text("Delete workspace")
+                    .clicked()
+                {
+                    keep = false;
+                }
+            });
+            keep
+        });
+    });
+    if settings != app.settings {
+        app.send(SystemCommand::UpdateSettings(settings));
+    }
+}
+
+/// Lookups started from the url box, by url. Shared with the tasks doing them.
+type Previews = Arc<Mutex<HashMap<String, Poll<Result<String, String>>>>>;
+
+/// Seconds without typing before the url is looked up, so we don't hit the api for every key.
+const LOOKUP_DELAY: f64 = 0.5;
+
+/// Says what the typed url will open, and looks it up on GitHub, so typos show up before
+/// loading instead of as a loader error.
+fn url_check_ui(ui: &mut Ui, app: &AppStateRef<'_>, url: &str, url_text_id: Id) {
+    let url = url.trim();
+    if url.is_empty() {
+        return;
+    }
+
+    let (kind, lookup) = match DiffSource::from_url(url) {
+        DiffSource::Pr(link) => {
+            if !is_pull_request_url(url) {
+                problem(
+                    ui,
+                    "Not a pull request url, expected https://github.com/owner/repo/pull/123",
+                );
+                return;
+            }
+            ("Pull request", Some(Lookup::Pr(link)))
+        }
+        DiffSource::GHArtifact(link) => ("GitHub artifact", Some(Lookup::Artifact(link))),
+        DiffSource::Jenkins(link) => {
+            if link.path.is_empty() {
+                ui.weak("All artifacts of a Jenkins build");
+            } else {
+                ui.weak("Jenkins build artifact");
+            }
+            if !app.jenkins_credentials().applies_to(&link.build_url) {
+                ui.weak("If the server needs a login, set it in Settings › Jenkins login");
+            }
+            return;
+        }
+        DiffSource::CircleCi(link) => {
+            ui.weak(format!("CircleCI job {}", link.job_number));
+            if app.github_auth.circleci_token().is_none() {
+                ui.weak("For private projects, set a token in Settings › CircleCI token");
+            }
+            return;
+        }
+        #[cfg(not(target_arch = "wasm32"))]
+        DiffSource::Bucket(link) => {
+            ui.weak(format!(
+                "Bucket {}, credentials are taken from the environment",
+                link.bucket
+            ));
+            return;
+        }
+        DiffSource::Registered(source) => {
+            ui.weak(format!("Opened by {}", source.provider_name()));
+            return;
+        }
+        DiffSource::Archive(_) => {
+            if !url.starts_with("https://") && !url.starts_with("http://") {
+                problem(ui, "Not a url, expected a link starting with https://");
+                return;
+            }
+            if url.starts_with("https://github.com/") {
+                problem(ui, "Not a GitHub pull request or artifact url");
+                return;
+            }
+            let path = url.split(['?', '#']).next().unwrap_or(url);
+            if [".zip", ".tar.gz", ".tgz"]
+                .iter()
+                .any(|extension| path.ends_with(extension))
+            {
+                ui.weak("Archive");
+            } else {
+                ui.colored_label(
+                    ui.visuals().warn_fg_color,
+                    "Unknown url, kitdiff will try to download it as a zip or tar.gz archive",
+                );
+            }
+            return;
+        }
+        _ => return,
+    };
+
+    let previews_id = Id::new("url_previews");
+    let previews = ui.data_mut(|d| d.get_temp_mut_or_default::<Previews>(previews_id).clone());
+    let preview = previews.lock().get(url).cloned();
+
+    ui.horizontal(|ui| {
+        ui.weak(kind);
+        match preview {
+            Some(Poll::Ready(Ok(text))) => {
+                ui.label(text);
+            }
+            Some(Poll::Ready(Err(err))) => {
+                ui.colored_label(ui.visuals().error_fg_color, err);
+            }
+            Some(Poll::Pending) => {
+                ui.spinner();
+            }
+            None => {
+                let now = ui.input(|i| i.time);
+                let edited = ui
+                    .data(|d| d.get_temp::<f64>(url_text_id.with("edited")))
+                    .unwrap_or_default();
+                if now - edited < LOOKUP_DELAY {
+                    ui.ctx()
+                        .request_repaint_after_secs((LOOKUP_DELAY - (now - edited)) as f32);
+                    return;
+                }
+                if let Some(lookup) = lookup {
+                    previews.lock().insert(url.to_owned(), Poll::Pending);
+                    let client = app.github_auth.client();
+                    let url = url.to_owned();
+                    let ctx = ui.ctx().clone();
+                    hello_egui_utils::spawn(async move {
+                        let result = lookup.run(client).await;
+                        previews.lock().insert(url, Poll::Ready(result));
+                        ctx.request_repaint();
+                    });
+                }
+            }
+        }
+    });
+}
+
+fn problem(ui: &mut Ui, text: &str) {
+    ui.colored_label(ui.visuals().error_fg_color, text);
+}
+
+/// [`crate::github::model::GithubPrLink`] also parses other urls of a repository, like issues.
+fn is_pull_request_url(url: &str) -> bool {
+    let path = url.strip_prefix("https://github.com/").unwrap_or(url);
+    path.split('/').nth(2) == Some("pull")
+}
+
+enum Lookup {
+    Pr(GithubPrLink),
+    Artifact(GithubArtifactLink),
+}
+
+impl Lookup {
+    /// A one line description, or why it can't be loaded.
+    async fn run(self, client: Octocrab) -> Result<String, String> {
+        match self {
+            Self::Pr(link) => {
+                let pr = client
+                    .pulls(&link.repo.owner, &link.repo.repo)
+                    .get(link.pr_number)
+                    .await
+                    .map_err(|err| not_found_or(&err))?;
+                let title = pr.title.unwrap_or_default();
+                Ok(if pr.merged_at.is_some() {
+                    format!("{title} (merged)")
+                } else if pr.closed_at.is_some() {
+                    format!("{title} (closed)")
+                } else {
+                    title
+                })
+            }
+            Self::Artifact(link) => {
+                let route = format!(
+                    "/repos/{}/{}/actions/artifacts/{}",
+                    link.repo.owner, link.repo.repo, link.artifact_id
+                );
+                let artifact: serde_json::Value = client
+                    .get(route, None::<&()>)
+                    .await
+                    .map_err(|err| not_found_or(&err))?;
+                if artifact["expired"].as_bool() == Some(true) {
+                    return Err("This artifact has expired".to_owned());
+                }
+                let name = artifact["name"].as_str().unwrap_or("Unnamed artifact");
+                Ok(match artifact["size_in_bytes"].as_u64() {
+                    Some(size) => {
+                        format!("{name} ({})", crate::loaders::download::format_bytes(size))
+                    }
+                    None => name.to_owned(),
+                })
+            }
+        }
+    }
+}
+
+/// GitHub answers with not found for private repositories kitdiff can't access too.
+fn not_found_or(err: &octocrab::Error) -> String {
+    match err {
+        octocrab::Error::GitHub { source, .. }
+            if source.status_code == reqwest::StatusCode::NOT_FOUND =>
+        {
+            "Not found, or kitdiff has no access to it".to_owned()
+        }
+        _ => err.to_string(),
+    }
+}
+
+/// The repositories of the signed in user, once they're fetched.
+type UserRepos = Arc<Mutex<Option<Vec<String>>>>;
+
+/// Suggests repositories of the signed in user while the owner and name are typed.
+fn repo_suggestions_ui(ui: &mut Ui, app: &AppStateRef<'_>, url_text: &mut String) {
+    const MAX_SUGGESTIONS: usize = 8;
+
+    if app.github_auth.get_username().is_none() {
+        return;
+    }
+    let typed = url_text.trim();
+    let typed = typed.strip_prefix("https://github.com/").unwrap_or(typed);
+    if typed.is_empty() || typed.contains("://") || typed.matches('/').count() > 1 {
+        return;
+    }
+
+    let repos_id = Id::new("user_repos");
+    let repos = ui.data(|d| d.get_temp::<UserRepos>(repos_id));
+    let Some(repos) = repos else {
+        let repos = UserRepos::default();
+        ui.data_mut(|d| d.insert_temp(repos_id, repos.clone()));
+        let client = app.github_auth.client();
+        let ctx = ui.ctx().clone();
+        hello_egui_utils::spawn(async move {
+            let result = client
+                .current()
+                .list_repos_for_authenticated_user()
+                .per_page(100)
+                .send()
+                .await;
+            let names = match result {
+                Ok(page) => page
+                    .items
+                    .into_iter()
+                    .filter_map(|repo| repo.full_name)
+                    .collect(),
+                Err(err) => {
+                    tracing::warn!("Failed to list your repositories: {err}");
+                    Vec::new()
+                }
+            };
+            *repos.lock() = Some(names);
+            ctx.request_repaint();
+        });
+        return;
+    };
+
+    let typed = typed.to_lowercase();
+    let suggestions: Vec<String> = repos
+        .lock()
+        .iter()
+        .flatten()
+        .filter(|name| {
+            let name = name.to_lowercase();
+            name.starts_with(&typed) && name != typed
+        })
+        .take(MAX_SUGGESTIONS)
+        .cloned()
+        .collect();
+
+    for name in suggestions {
+        if ui.link(&name).clicked() {
+            *url_text = format!("https://github.com/{name}/pull/");
+        }
+    }
+}
+
+/// Lets the hosted version review local snapshot files, in browsers that support it.
+#[cfg(target_arch = "wasm32")]
+fn open_directory_ui(ui: &mut Ui, app: &AppStateRef<'_>) {
+    use crate::web_loaders::directory_loader;
+
+    if !directory_loader::is_supported() {
+        return;
+    }
+    if ui.button("Open local folder…").clicked() {
+        let tx = app.tx.clone();
+        wasm_bindgen_futures::spawn_local(async move {
+            match directory_loader::pick_directory().await {
+                Ok(Some(handle)) => {
+                    tx.send(SystemCommand::Open(DiffSource::Directory(handle)))
+                        .ok();
+                }
+                Ok(None) => {}
+                Err(err) => tracing::error!("Failed to open folder: {err:#}"),
+            }
+        });
+    }
+}
+
+/// Installing the app, and reopening downloads that are cached in the browser.
+#[cfg(target_arch = "wasm32")]
+fn offline_ui(ui: &mut Ui, app: &AppStateRef<'_>) {
+    use crate::web_loaders::opfs_cache::{self, CachedDownload};
+    use crate::{DataReference, pwa};
+
+    type Cached = Arc<Mutex<Option<Vec<CachedDownload>>>>;
+
+    if pwa::can_install() && ui.button("⬇ Install kitdiff").clicked() {
+        pwa::install();
+    }
+    if pwa::is_offline() {
+        ui.colored_label(
+            ui.visuals().warn_fg_color,
+            "You're offline. Only downloads cached in this browser can be opened.",
+        );
+    }
+
+    let cached_id = Id::new("cached_downloads");
+    let cached = ui.data_mut(|data| data.get_temp::<Cached>(cached_id));
+    let Some(cached) = cached else {
+        let cached = Cached::default();
+        ui.data_mut(|data| data.insert_temp(cached_id, cached.clone()));
+        let ctx = ui.ctx().clone();
+        wasm_bindgen_futures::spawn_local(async move {
+            *cached.lock() = Some(opfs_cache::list().await);
+            ctx.request_repaint();
+        });
+        return;
+    };
+    let cached = cached.lock();
+    let Some(downloads) = cached.as_ref().filter(|downloads| !downloads.is_empty()) else {
+        return;
+    };
+
+    ui.collapsing("Available offline", |ui| {
+        for download in downloads {
+            let label = format!(
+                "{} ({})",
+                download.name,
+                crate::loaders::download::format_bytes(download.size as u64)
+            );
+            if ui.link(label).clicked() {
+                let tx = app.tx.clone();
+                let download = download.clone();
+                wasm_bindgen_futures::spawn_local(async move {
+                    if let Some(data) = opfs_cache::get(&download.key).await {
+                        let reference = DataReference::Data(data, download.name);
+                        tx.send(SystemCommand::Open(DiffSource::Archive(reference)))
+                            .ok();
+                    }
+                });
+            }
+        }
+    });
+}
+
+/// For people who didn't start kitdiff from a terminal in the right directory.
+#[cfg(not(target_arch = "wasm32"))]
+fn open_dialogs_ui(ui: &mut Ui, app: &AppStateRef<'_>) {
+    ui.horizontal(|ui| {
+        if ui.button("📁 Open folder…").clicked() {
+            let tx = app.tx.clone();
+            tokio::spawn(async move {
+                if let Some(folder) = rfd::AsyncFileDialog::new()
+                    .set_title("Open snapshot folder")
+                    .pick_folder()
+                    .await
+                {
+                    let path = folder.path().to_owned();
+                    tx.send(SystemCommand::Open(DiffSource::Files(path))).ok();
+                }
+            });
+        }
+        if ui.button("📦 Open archive…").clicked() {
+            let tx = app.tx.clone();
+            tokio::spawn(async move {
+                if let Some(file) = rfd::AsyncFileDialog::new()
+                    .set_title("Open snapshot archive")
+                    .add_filter("Archives", &["zip", "gz", "tgz"])
+                    .pick_file()
+                    .await
+                {
+                    let reference = crate::DataReference::Path(file.path().to_owned());
+                    tx.send(SystemCommand::Open(DiffSource::Archive(reference)))
+                        .ok();
+                }
+            });
+        }
+    });
+}
@@ -0,0 +1,276 @@
+//! The http client for everything kitdiff downloads outside of the GitHub api: archives,
+//! artifacts and images.
+//!
+//! `HTTPS_PROXY`, `HTTP_PROXY`, `ALL_PROXY` and `NO_PROXY` are respected. Networks that intercept
+//! TLS can add their root certificate with `--ca-bundle` or `KITDIFF_CA_BUNDLE`.
+
+use eframe::egui::Context;
+use eframe::egui::load::{Bytes, BytesLoadResult, BytesLoader, BytesPoll, LoadError};
+use eframe::egui::mutex::{Mutex, RwLock};
+use eframe::epaint::ahash::HashMap;
+use std::collections::VecDeque;
+use std::sync::{Arc, LazyLock};
+use std::task::Poll;
+
+#[cfg(not(target_arch = "wasm32"))]
+static CA_BUNDLE: std::sync::OnceLock<std::path::PathBuf> = std::sync::OnceLock::new();
+
+static CLIENT: LazyLock<reqwest::Client> = LazyLock::new(|| {
+    build_client(reqwest::Client::builder()).unwrap_or_else(|err| {
+        tracing::error!("Failed to set up the http client, using the defaults: {err:#}");
+        reqwest::Client::new()
+    })
+});
+
+/// Trust the certificates in a pem file in addition to the system ones. Must be called before
+/// the first request.
+#[cfg(not(target_arch = "wasm32"))]
+pub fn set_ca_bundle(path: std::path::PathBuf) {
+    if CA_BUNDLE.set(path).is_err() {
+        tracing::warn!("The CA bundle was already set");
+    }
+}
+
+/// The file passed to [`set_ca_bundle`], for http clients that aren't built here.
+#[cfg(not(target_arch = "wasm32"))]
+pub fn ca_bundle() -> Option<&'static std::path::Path> {
+    CA_BUNDLE.get().map(std::path::PathBuf::as_path)
+}
+
+/// Shared, so connections are reused.
+pub fn client() -> reqwest::Client {
+    CLIENT.clone()
+}
+
+/// Applies the proxy and certificate settings to `builder`.
+pub fn build_client(builder: reqwest::ClientBuilder) -> anyhow::Result<reqwest::Client> {
+    #[cfg(not(target_arch = "wasm32"))]
+    let builder = {
+        use anyhow::Context as _;
+
+        let mut builder = builder;
+        if let Some(path) = CA_BUNDLE.get() {
+            let pem = std::fs::read(path)
+                .with_context(|| format!("Failed to read the CA bundle {}", path.display()))?;
+            for certificate in reqwest::Certificate::from_pem_bundle(&pem)? {
+                builder = builder.add_root_certificate(certificate);
+            }
+        }
+        builder
+    };
+    Ok(builder.build()?)
+}
+
+/// Whether requests have to go through [`build_client`] to get out, instead of any http client.
+pub fn is_customized() -> bool {
+    #[cfg(not(target_arch = "wasm32"))]
+    {
+        const PROXY_VARIABLES: &[&str] = &[
+            "HTTPS_PROXY",
+            "https_proxy",
+            "HTTP_PROXY",
+            "http_proxy",
+            "ALL_PROXY",
+            "all_proxy",
+        ];
+        CA_BUNDLE.get().is_some()
+            || PROXY_VARIABLES
+                .iter()
+                .any(|name| std::env::var_os(name).is_some())
+    }
+    // The browser takes care of proxies and certificates.
+    #[cfg(target_arch = "wasm32")]
+    false
+}
+
+/// Where GitHub serves the files of a repository, including LFS files.
+const GITHUB_HOSTS: &[&str] = &[
+    "https://media.githubusercontent.com/",
+    "https://raw.githubusercontent.com/",
+];
+
+/// Images downloaded at once. GitHub throttles clients that open many more connections, and they
+/// would only compete for bandwidth with the images on screen.
+const MAX_CONNECTIONS: usize = 6;
+
+static SCHEDULER: LazyLock<Mutex<Scheduler>> = LazyLock::new(Mutex::default);
+
+/// Image downloads waiting for a connection, first come first served unless [`prioritize`]d.
+#[derive(Default)]
+struct Scheduler {
+    running: usize,
+    queue: VecDeque<Queued>,
+}
+
+struct Queued {
+    uri: String,
+
+    /// Spawns the download, or returns false if it isn't wanted anymore.
+    start: Box<dyn FnOnce() -> bool + Send>,
+}
+
+/// Download `uri` before the other queued images, e.g. because it's on screen. Does nothing if it
+/// isn't queued.
+pub fn prioritize(uri: &str) {
+    let mut scheduler = SCHEDULER.lock();
+    if let Some(index) = scheduler.queue.iter().position(|queued| queued.uri == uri)
+        && let Some(queued) = scheduler.queue.remove(index)
+    {
+        scheduler.queue.push_front(queued);
+    }
+}
+
+/// Start queued downloads while there are connections to spare.
+fn start_queued() {
+    loop {
+        let queued = {
+            let mut scheduler = SCHEDULER.lock();
+            if scheduler.running >= MAX_CONNECTIONS {
+                return;
+            }
+            let Some(queued) = scheduler.queue.pop_front() else {
+                return;
+            };
+            scheduler.running += 1;
+            queued
+        };
+        if !(queued.start)() {
+            SCHEDULER.lock().running -= 1;
+        }
+    }
+}
+
+fn finished() {
+    SCHEDULER.lock().running -= 1;
+    start_queued();
+}
+
+type Downloads = HashMap<String, Poll<Result<Arc<[u8]>, String>>>;
+
+/// Loads images from http urls with [`client`], so they go through the configured proxy.
+///
+/// Files of GitHub repositories are requested with the token of the signed in user, so LFS
+/// snapshots of private repositories load. egui's http loader sends neither.
+///
+/// Every uri is downloaded once, however often it's requested, and at most [`MAX_CONNECTIONS`]
+/// at a time, so preloading a PR doesn't get us throttled.
+#[derive(Default)]
+pub struct HttpLoader {
+    token: RwLock<Option<String>>,
+    downloads: Arc<Mutex<Downloads>>,
+}
+
+impl HttpLoader {
+    /// Call whenever the token may have changed. Failed downloads are tried again with a new
+    /// token.
+    pub fn set_token(&self, token: Option<&str>) {
+        if self.token.read().as_deref() == token {
+            return;
+        }
+        *self.token.write() = token.map(ToOwned::to_owned);
+        self.downloads
+            .lock()
+            .retain(|_, download| !matches!(download, Poll::Ready(Err(_))));
+    }
+}
+
+impl BytesLoader for HttpLoader {
+    fn id(&self) -> &'static str {
+        "HttpLoader"
+    }
+
+    fn load(&self, ctx: &Context, uri: &str) -> BytesLoadResult {
+        if !uri.starts_with("https://") && !uri.starts_with("http://") {
+            return Err(LoadError::NotSupported);
+        }
+        let token = self
+            .token
+            .read()
+            .clone()
+            .filter(|_| GITHUB_HOSTS.iter().any(|host| uri.starts_with(host)));
+
+        let mut downloads = self.downloads.lock();
+        match downloads.get(uri) {
+            Some(Poll::Ready(Ok(bytes))) => {
+                return Ok(BytesPoll::Ready {
+                    size: None,
+                    bytes: Bytes::Shared(bytes.clone()),
+                    mime: None,
+                });
+            }
+            Some(Poll::Ready(Err(err))) => return Err(LoadError::Loading(err.clone())),
+            Some(Poll::Pending) => return Ok(BytesPoll::Pending { size: None }),
+            None => {}
+        }
+        downloads.insert(uri.to_owned(), Poll::Pending);
+        drop(downloads);
+
+        let downloads = self.downloads.clone();
+        let ctx = ctx.clone();
+        let queued_uri = uri.to_owned();
+        let uri = uri.to_owned();
+        let start = move || {
+            // Forgotten while it was queued.
+            if !matches!(downloads.lock().get(&uri), Some(Poll::Pending)) {
+                return false;
+            }
+            hello_egui_utils::spawn(async move {
+                let result = download(&uri, token.as_deref()).await.map_err(|err| {
+                    tracing::warn!("Failed to download {uri}: {err}");
+                    err.to_string()
+                });
+                // Unless it was forgotten while we were downloading it.
+                if let Some(download) = downloads.lock().get_mut(&uri) {
+                    *download = Poll::Ready(result);
+                }
+                ctx.request_repaint();
+                finished();
+            });
+            true
+        };
+        {
+            let mut scheduler = SCHEDULER.lock();
+            // Still queued from before it was forgotten.
+            if !scheduler
+                .queue
+                .iter()
+                .any(|queued| queued.uri == queued_uri)
+            {
+                scheduler.queue.push_back(Queued {
+                    uri: queued_uri,
+                    start: Box::new(start),
+                });
+            }
+        }
+        start_queued();
+        Ok(BytesPoll::Pending { size: None })
+    }
+
+    fn forget(&self, uri: &str) {
+        self.downloads.lock().remove(uri);
+    }
+
+    fn forget_all(&self) {
+        self.downloads.lock().clear();
+    }
+
+    fn byte_size(&self) -> usize {
+        self.downloads
+            .lock()
+            .values()
+            .map(|download| match download {
+                Poll::Ready(Ok(bytes)) => bytes.len(),
+                _ => 0,
+            })
+            .sum()
+    }
+}
+
+async fn download(uri: &str, token: Option<&str>) -> reqwest::Result<Arc<[u8]>> {
+    let mut request = client().get(uri);
+    if let Some(token) = token {
+        request = request.bearer_auth(token);
+    }
+    let bytes = request.send().await?.error_for_status()?.bytes().await?;
+    Ok(bytes.as_ref().into())
+}
@@ -1,22 +1,65 @@
 use crate::github::auth::parse_github_artifact_url;
 use crate::github::model::{GithubArtifactLink, GithubPrLink};
-pub use crate::loaders::{DataReference, SnapshotLoader};
+pub use crate::loaders::{CancelFlag, DataReference, SnapshotLoader};
+use crate::path_filter::PathFilter;
 use crate::state::AppState;
 use eframe::egui::Context;
+pub use kitdiff_core::diff::{
+    DiffAlgorithm, DiffOptions, DiffPalette, DiffResult, diff_images, register_algorithm,
+};
+pub use kitdiff_core::path_filter;
+use kitdiff_core::platform;
 
+mod acknowledged;
+mod annotation;
 pub mod app;
+pub mod archive_index;
 mod bar;
+#[cfg(not(target_arch = "wasm32"))]
+pub mod baselines;
+#[cfg(not(target_arch = "wasm32"))]
+mod cache;
 pub mod config;
+mod confirm;
+#[cfg(not(target_arch = "wasm32"))]
+mod diff_cache;
 pub mod diff_image_loader;
+mod diff_precompute;
+pub mod events;
+#[cfg(not(target_arch = "wasm32"))]
+mod external;
+#[cfg(not(target_arch = "wasm32"))]
+pub mod file_association;
+#[cfg(not(target_arch = "wasm32"))]
+mod file_image_loader;
+mod flaky;
 pub mod github;
 mod home;
+pub mod http;
 pub mod loaders;
+pub mod logging;
+mod memory_budget;
 #[cfg(not(target_arch = "wasm32"))]
 pub mod native_loaders;
+mod notifications;
+mod prefetch;
+#[cfg(target_arch = "wasm32")]
+pub mod pwa;
+#[cfg(not(target_arch = "wasm32"))]
+pub mod remote_control;
+mod session;
 mod settings;
+mod settings_file;
+mod shared_bytes;
 pub mod snapshot;
 mod state;
+mod theme;
+pub mod thumbnail_loader;
+pub mod tile_loader;
 mod viewer;
+#[cfg(target_arch = "wasm32")]
+pub mod web_loaders;
+mod workspace;
 
 #[derive(Debug, Clone)]
 pub enum DiffSource {
@@ -24,43 +67,199 @@ pub enum DiffSource {
     Files(std::path::PathBuf),
     #[cfg(not(target_arch = "wasm32"))]
     Git(std::path::PathBuf),
+    /// Every snapshot in a folder as it currently renders, changed or not. One side of a
+    /// [`Self::Compare`].
+    #[cfg(not(target_arch = "wasm32"))]
+    Images(std::path::PathBuf),
+
+    /// A set of reference images saved with [`baselines::save`].
+    #[cfg(not(target_arch = "wasm32"))]
+    Baseline(baselines::SavedBaseline),
+
+    /// A prefix in an S3 or GCS bucket.
+    #[cfg(not(target_arch = "wasm32"))]
+    Bucket(native_loaders::bucket_loader::BucketLink),
+    /// A local folder picked in the browser.
+    #[cfg(target_arch = "wasm32")]
+    Directory(web_sys::FileSystemDirectoryHandle),
     Pr(GithubPrLink),
     GHArtifact(GithubArtifactLink),
+    Jenkins(loaders::jenkins_loader::JenkinsLink),
+    CircleCi(loaders::circleci_loader::CircleCiLink),
     Archive(DataReference),
+
+    /// Several sources shown as one, with paths prefixed by the given names.
+    Merged(Vec<(String, DiffSource)>),
+
+    /// `live` with the old side of every snapshot taken from `baseline`.
+    Pinned {
+        live: Box<DiffSource>,
+        baseline: std::sync::Arc<loaders::pinned_loader::Baseline>,
+    },
+
+    /// The current images of `new` against those of `old`, paired by path.
+    Compare {
+        old: Box<DiffSource>,
+        new: Box<DiffSource>,
+    },
+
+    /// A url claimed by a provider from [`loaders::registry`].
+    Registered(loaders::registry::RegisteredSource),
+}
+
+/// The url in a `kitdiff://open?url=...` link, e.g. printed by CI to open an artifact in the
+/// desktop app.
+pub fn deep_link_target(link: &str) -> Option<String> {
+    let query = link
+        .strip_prefix("kitdiff://open")?
+        .trim_start_matches('/')
+        .strip_prefix('?')?;
+    let params: Vec<(String, String)> = serde_urlencoded::from_str(query).ok()?;
+    params
+        .into_iter()
+        .find_map(|(key, value)| (key == "url").then_some(value))
+}
+
+/// Buckets need credentials from the environment, which browsers don't have.
+fn bucket_link(_url: &str) -> Option<DiffSource> {
+    #[cfg(not(target_arch = "wasm32"))]
+    return _url.parse().ok().map(DiffSource::Bucket);
+    #[cfg(target_arch = "wasm32")]
+    None
 }
 
 impl DiffSource {
     pub fn from_url(url: &str) -> Self {
-        if let Ok(link) = url.parse() {
+        if let Some(target) = deep_link_target(url) {
+            Self::from_url(&target)
+        } else if let Some(source) = loaders::registry::find(url) {
+            Self::Registered(source)
+        } else if let Ok(link) = url.parse() {
             Self::Pr(link)
+        } else if let Some(link) = bucket_link(url) {
+            link
         } else if let Some(link) = parse_github_artifact_url(url) {
             Self::GHArtifact(link)
+        } else if let Ok(link) = url.parse() {
+            Self::Jenkins(link)
+        } else if let Ok(link) = url.parse() {
+            Self::CircleCi(link)
         } else {
             // Try to load it as direct zip/tar.gz URL
             Self::Archive(DataReference::Url(url.to_owned()))
         }
     }
 
-    pub fn load(self, _ctx: &Context, state: &AppState) -> SnapshotLoader {
+    /// What to open for a path passed without a subcommand, e.g. by the file manager, or a url.
+    #[cfg(not(target_arch = "wasm32"))]
+    pub fn from_path(path: &str) -> Self {
+        if path.contains("://") {
+            Self::from_url(path)
+        } else if std::path::Path::new(path).is_dir() {
+            Self::Files(path.into())
+        } else {
+            Self::Archive(DataReference::Path(path.into()))
+        }
+    }
+
+    /// Setting `cancel` stops the load early.
+    pub fn load(self, ctx: &Context, state: &AppState, cancel: &CancelFlag) -> SnapshotLoader {
+        let filter = PathFilter::new(&state.settings.path_filter);
+        let cancel = cancel.clone();
         match self {
             #[cfg(not(target_arch = "wasm32"))]
-            Self::Files(path) => Box::new(native_loaders::file_loader::FileLoader::new(path)),
+            Self::Files(path) => Box::new(native_loaders::file_loader::FileLoader::new(
+                path, filter, cancel,
+            )),
             #[cfg(not(target_arch = "wasm32"))]
-            Self::Git(path) => Box::new(native_loaders::git_loader::GitLoader::new(path)),
-            Self::Pr(url) => Box::new(loaders::pr_loader::PrLoader::new(
-                url,
-                state.github_auth.client(),
-                state.github_auth.get_token().is_some(),
+            Self::Git(path) => Box::new(loaders::rename_loader::RenameLoader::new(
+                Box::new(native_loaders::git_loader::GitLoader::new(
+                    path,
+                    filter,
+                    cancel.clone(),
+                )),
+                cancel,
+            )),
+            #[cfg(not(target_arch = "wasm32"))]
+            Self::Images(path) => Box::new(native_loaders::images_loader::ImagesLoader::new(
+                path.clone(),
+                format!("Images in {}", path.display()),
+                filter,
+                cancel,
+            )),
+            #[cfg(not(target_arch = "wasm32"))]
+            Self::Baseline(baseline) => Box::new(native_loaders::images_loader::ImagesLoader::new(
+                baseline.images_dir(),
+                format!("Baseline: {}", baseline.manifest.name),
+                filter,
+                cancel,
+            )),
+            #[cfg(not(target_arch = "wasm32"))]
+            Self::Bucket(link) => Box::new(native_loaders::bucket_loader::BucketLoader::new(
+                link, filter, cancel,
+            )),
+            #[cfg(target_arch = "wasm32")]
+            Self::Directory(handle) => Box::new(
+                web_loaders::directory_loader::DirectoryLoader::new(handle, filter, cancel),
+            ),
+            Self::Pr(url) => Box::new(loaders::rename_loader::RenameLoader::new(
+                Box::new(loaders::pr_loader::PrLoader::new(
+                    url,
+                    state.github_auth.client(),
+                    state.github_auth.get_token().is_some(),
+                    filter,
+                    cancel.clone(),
+                )),
+                cancel,
             )),
             Self::GHArtifact(artifact) => {
                 Box::new(loaders::gh_archive_loader::GHArtifactLoader::new(
                     state.github_auth.client(),
                     artifact,
+                    filter,
+                    cancel,
                 ))
             }
-            Self::Archive(file_ref) => {
-                Box::new(loaders::archive_loader::ArchiveLoader::new(file_ref))
-            }
+            Self::Jenkins(link) => Box::new(loaders::jenkins_loader::JenkinsLoader::new(
+                link,
+                state.jenkins_credentials(),
+                filter,
+                cancel,
+            )),
+            Self::CircleCi(link) => Box::new(loaders::circleci_loader::CircleCiLoader::new(
+                link,
+                state
+                    .github_auth
+                    .circleci_token()
+                    .unwrap_or_default()
+                    .to_owned(),
+                filter,
+                cancel,
+            )),
+            Self::Archive(file_ref) => Box::new(loaders::archive_loader::ArchiveLoader::new(
+                file_ref, filter, cancel,
+            )),
+            Self::Merged(sources) => Box::new(loaders::merged_loader::MergedLoader::new(
+                sources
+                    .into_iter()
+                    .map(|(name, source)| (name, source.load(ctx, state, &cancel)))
+                    .collect(),
+            )),
+            Self::Pinned { live, baseline } => Box::new(loaders::pinned_loader::PinnedLoader::new(
+                live.load(ctx, state, &cancel),
+                baseline,
+            )),
+            Self::Compare { old, new } => Box::new(loaders::compare_loader::CompareLoader::new(
+                old.load(ctx, state, &cancel),
+                new.load(ctx, state, &cancel),
+            )),
+            Self::Registered(source) => source.load(loaders::registry::LoadContext {
+                egui_ctx: ctx,
+                client: state.github_auth.client(),
+                logged_in: state.github_auth.get_token().is_some(),
+                filter,
+                cancel,
+            }),
         }
     }
 }
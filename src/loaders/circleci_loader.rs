@@ -0,0 +1,259 @@
+//! Snapshot archives uploaded as artifacts of a CircleCI job.
+
+use crate::loaders::archive_loader::ArchiveLoader;
+use crate::loaders::download::{self, format_bytes};
+use crate::loaders::gh_archive_loader::{Download, DownloadEvent, LoaderState};
+use crate::loaders::{CancelFlag, LoadSnapshots, Progress};
+use crate::path_filter::PathFilter;
+use crate::snapshot::Snapshot;
+use anyhow::{Context as _, Error, Result};
+use bytes::Bytes;
+use eframe::egui::Context;
+use egui_inbox::{UiInbox, UiInboxSender};
+use octocrab::Octocrab;
+use reqwest::header::{HeaderMap, HeaderValue};
+use std::fmt;
+use std::str::FromStr;
+use std::task::Poll;
+
+const API_URL: &str = "https://circleci.com/api/v2";
+
+/// A CircleCI job, from a link like
+/// `https://app.circleci.com/pipelines/github/org/repo/123/workflows/<id>/jobs/456/artifacts`
+/// or `https://circleci.com/gh/org/repo/456`.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct CircleCiLink {
+    /// Like `gh/org/repo`.
+    pub project_slug: String,
+    pub job_number: u64,
+}
+
+impl FromStr for CircleCiLink {
+    type Err = Error;
+
+    fn from_str(url: &str) -> Result<Self> {
+        let url = url.split(['?', '#']).next().unwrap_or(url);
+        let segments: Vec<&str> =
+            if let Some(path) = url.strip_prefix("https://app.circleci.com/pipelines/") {
+                let segments: Vec<&str> = path.split('/').collect();
+                let job = segments
+                    .iter()
+                    .position(|segment| *segment == "jobs")
+                    .and_then(|index| segments.get(index + 1))
+                    .context("Not a link to a CircleCI job")?;
+                match segments.as_slice() {
+                    [vcs, org, repo, ..] => vec![*vcs, *org, *repo, *job],
+                    _ => anyhow::bail!("Not a link to a CircleCI job"),
+                }
+            } else if let Some(path) = url.strip_prefix("https://circleci.com/") {
+                path.split('/').take(4).collect()
+            } else {
+                anyhow::bail!("Not a CircleCI url");
+            };
+
+        let [vcs, org, repo, job] = segments.as_slice() else {
+            anyhow::bail!("Not a link to a CircleCI job");
+        };
+        let vcs = match *vcs {
+            "github" | "gh" => "gh",
+            "bitbucket" | "bb" => "bb",
+            "circleci" => "circleci",
+            other => anyhow::bail!("Unknown CircleCI project type {other:?}"),
+        };
+        Ok(Self {
+            project_slug: format!("{vcs}/{org}/{repo}"),
+            job_number: job.parse().context("Invalid CircleCI job number")?,
+        })
+    }
+}
+
+impl fmt::Display for CircleCiLink {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{} job {}", self.project_slug, self.job_number)
+    }
+}
+
+#[derive(serde::Deserialize)]
+struct ArtifactsPage {
+    items: Vec<Artifact>,
+    next_page_token: Option<String>,
+}
+
+#[derive(serde::Deserialize)]
+struct Artifact {
+    path: String,
+    url: String,
+}
+
+pub struct CircleCiLoader {
+    link: CircleCiLink,
+    token: String,
+    filter: PathFilter,
+    state: LoaderState,
+}
+
+impl CircleCiLoader {
+    /// `token` is a personal API token, and may be empty for public projects.
+    pub fn new(link: CircleCiLink, token: String, filter: PathFilter, cancel: CancelFlag) -> Self {
+        let mut inbox = UiInbox::new();
+        {
+            let link = link.clone();
+            let token = token.clone();
+            let cancel = cancel.clone();
+            inbox.spawn(move |tx| async move {
+                let result = download_archive(&link, &token, &cancel, &tx).await;
+                tx.send(DownloadEvent::Done(result)).ok();
+            });
+        }
+        Self {
+            link,
+            token,
+            filter,
+            state: LoaderState::LoadingData(Download::new(inbox, cancel)),
+        }
+    }
+}
+
+#[tracing::instrument(name = "download", skip_all, fields(job = %link))]
+async fn download_archive(
+    link: &CircleCiLink,
+    token: &str,
+    cancel: &CancelFlag,
+    tx: &UiInboxSender<DownloadEvent>,
+) -> Result<(Bytes, String)> {
+    let mut headers = HeaderMap::new();
+    if !token.is_empty() {
+        headers.insert("Circle-Token", HeaderValue::from_str(token)?);
+    }
+
+    let artifacts = list_artifacts(link, &headers).await?;
+    let archives: Vec<&Artifact> = artifacts
+        .iter()
+        .filter(|artifact| {
+            [".zip", ".tar.gz", ".tgz"]
+                .iter()
+                .any(|extension| artifact.path.ends_with(extension))
+        })
+        .collect();
+    // Prefer an archive that says it has snapshots, jobs may upload other archives too.
+    let archive = archives
+        .iter()
+        .find(|artifact| artifact.path.contains("snapshot"))
+        .or_else(|| archives.first())
+        .with_context(|| {
+            format!(
+                "{link} has no zip or tar.gz artifact, it has {} other artifacts",
+                artifacts.len()
+            )
+        })?;
+    tracing::info!("Downloading {}", archive.path);
+
+    let data = download::download_with_headers(&archive.url, headers, cancel, |progress| {
+        tx.send(DownloadEvent::Progress(progress)).ok();
+    })
+    .await?;
+    tracing::info!("Downloaded {}", format_bytes(data.len() as u64));
+    let name = archive.path.rsplit('/').next().unwrap_or(&archive.path);
+    Ok((data, name.to_owned()))
+}
+
+async fn list_artifacts(link: &CircleCiLink, headers: &HeaderMap) -> Result<Vec<Artifact>> {
+    let url = format!(
+        "{API_URL}/project/{}/{}/artifacts",
+        link.project_slug, link.job_number
+    );
+    let mut artifacts = Vec::new();
+    let mut page_token = None;
+    loop {
+        let page_url = match &page_token {
+            Some(page_token) => {
+                format!(
+                    "{url}?{}",
+                    serde_urlencoded::to_string([("page-token", page_token)])?
+                )
+            }
+            None => url.clone(),
+        };
+        let response = crate::http::client()
+            .get(page_url)
+            .headers(headers.clone())
+            .send()
+            .await?;
+        let status = response.status();
+        if status == reqwest::StatusCode::UNAUTHORIZED || status == reqwest::StatusCode::NOT_FOUND {
+            // CircleCI answers 404 for private projects without a token.
+            anyhow::bail!(
+                "CircleCI couldn't find {link} ({status}). For private projects, set an API \
+                token in Settings › CircleCI token"
+            );
+        }
+        let page: ArtifactsPage =
+            serde_json::from_slice(&response.error_for_status()?.bytes().await?)
+                .context("Unexpected response from the CircleCI api")?;
+        artifacts.extend(page.items);
+        page_token = page.next_page_token;
+        if page_token.is_none() {
+            return Ok(artifacts);
+        }
+    }
+}
+
+impl LoadSnapshots for CircleCiLoader {
+    fn update(&mut self, ctx: &Context) {
+        let mut new_state = None;
+        match &mut self.state {
+            LoaderState::LoadingData(download) => match download.update(ctx) {
+                Some(Ok((data, name))) => {
+                    new_state = Some(LoaderState::LoadingArchive(ArchiveLoader::new(
+                        crate::loaders::DataReference::Data(data, name),
+                        self.filter.clone(),
+                        download.cancel().clone(),
+                    )));
+                }
+                Some(Err(err)) => new_state = Some(LoaderState::Error(err)),
+                None => {}
+            },
+            LoaderState::LoadingArchive(loader) => loader.update(ctx),
+            LoaderState::Error(_) => {}
+        }
+        if let Some(new_state) = new_state {
+            self.state = new_state;
+        }
+    }
+
+    fn refresh(&mut self, _client: Octocrab, cancel: CancelFlag) {
+        *self = Self::new(
+            self.link.clone(),
+            self.token.clone(),
+            self.filter.clone(),
+            cancel,
+        );
+    }
+
+    fn snapshots(&self) -> &[Snapshot] {
+        match &self.state {
+            LoaderState::LoadingArchive(loader) => loader.snapshots(),
+            _ => &[],
+        }
+    }
+
+    fn state(&self) -> Poll<Result<(), &Error>> {
+        match &self.state {
+            LoaderState::LoadingData(_) => Poll::Pending,
+            LoaderState::LoadingArchive(loader) => loader.state(),
+            LoaderState::Error(err) => Poll::Ready(Err(err)),
+        }
+    }
+
+    fn progress(&self) -> Option<Progress> {
+        match &self.state {
+            LoaderState::LoadingData(download) => Some(download.progress()),
+            LoaderState::LoadingArchive(loader) => loader.progress(),
+            LoaderState::Error(_) => None,
+        }
+    }
+
+    fn files_header(&self) -> String {
+        format!("CircleCI: {}", self.link)
+    }
+}
@@ -0,0 +1,328 @@
+use crate::confirm::{self, Change, ChangeKind};
+use crate::diff_precompute::DiffPrecompute;
+use crate::github::git_data::{self, FileChange};
+use crate::github::model::GithubRepoLink;
+use crate::github::octokit::RepoClient;
+use crate::snapshot::{FileReference, Snapshot};
+use crate::state::{AppStateRef, PageRef, ReviewStatus};
+use anyhow::Context as _;
+use eframe::egui::{Button, Context, ImageSource, Ui};
+use egui_inbox::UiInbox;
+use octocrab::Octocrab;
+use std::task::Poll;
+
+/// Pushes the new images of accepted snapshots onto the PR branch, or into a pull request against
+/// it, for repos without an update workflow.
+pub struct CommitSnapshots {
+    client: Octocrab,
+    repo: GithubRepoLink,
+    branch: String,
+    inbox: UiInbox<Poll<anyhow::Result<Pushed>>>,
+    state: Option<Poll<anyhow::Result<Pushed>>>,
+}
+
+impl CommitSnapshots {
+    pub fn new(client: Octocrab, repo: GithubRepoLink, branch: String) -> Self {
+        Self {
+            client,
+            repo,
+            branch,
+            inbox: UiInbox::new(),
+            state: None,
+        }
+    }
+
+    pub fn update(&mut self, ctx: &Context) {
+        if let Some(state) = self.inbox.read(ctx).last() {
+            self.state = Some(state);
+        }
+    }
+
+    pub fn ui(&self, ui: &mut Ui, state: &AppStateRef<'_>, snapshots: &[Snapshot]) {
+        let accepted_snapshots = accepted(state, snapshots);
+        let enabled = !accepted_snapshots.is_empty() && !matches!(self.state, Some(Poll::Pending));
+
+        let mut target = None;
+        ui.horizontal_wrapped(|ui| {
+            let push = ui
+                .add_enabled(
+                    enabled,
+                    Button::new(format!(
+                        "Push {} accepted snapshots",
+                        accepted_snapshots.len()
+                    )),
+                )
+                .on_hover_text(confirm::hover_text(
+                    state,
+                    &format!(
+                        "Commit the new images of accepted snapshots to {} directly.",
+                        self.branch
+                    ),
+                ))
+                .on_disabled_hover_text("Accept snapshots to commit them");
+            if push.clicked() {
+                target = Some(Target::Branch);
+            }
+
+            let pull_request = ui
+                .add_enabled(enabled, Button::new("Open pull request"))
+                .on_hover_text(confirm::hover_text(
+                    state,
+                    &format!(
+                        "Commit the accepted snapshots to a new branch and open a pull request \
+                        into {}, for protected branches.",
+                        self.branch
+                    ),
+                ))
+                .on_disabled_hover_text("Accept snapshots to commit them");
+            if pull_request.clicked() {
+                target = Some(Target::PullRequest);
+            }
+        });
+
+        if let Some(target) = target {
+            let description = pull_request_description(state, &accepted_snapshots);
+            let root = state
+                .source_config()
+                .github
+                .snapshot_root
+                .clone()
+                .unwrap_or_default();
+            let preview = accepted_snapshots
+                .iter()
+                .map(|snapshot| {
+                    let kind = if snapshot.new.is_none() {
+                        ChangeKind::Delete
+                    } else if snapshot.added() {
+                        ChangeKind::Add
+                    } else {
+                        ChangeKind::Overwrite
+                    };
+                    Change::new(kind, repo_path(snapshot, &root))
+                })
+                .collect();
+            let snapshots: Vec<Snapshot> = accepted_snapshots.into_iter().cloned().collect();
+            let repo = RepoClient::new(self.client.clone(), self.repo.clone());
+            let branch = self.branch.clone();
+            let sender = self.inbox.sender();
+            let (title, location) = match target {
+                Target::Branch => (
+                    format!("Push {} snapshots", snapshots.len()),
+                    format!("on {branch}"),
+                ),
+                Target::PullRequest => (
+                    format!("Open a pull request with {} snapshots", snapshots.len()),
+                    format!("in a pull request into {branch}"),
+                ),
+            };
+            confirm::confirm(ui.ctx(), title, location, preview, move || {
+                sender.send(Poll::Pending).ok();
+                hello_egui_utils::spawn(async move {
+                    let result = push(&repo, &branch, &root, &snapshots, target, description).await;
+                    sender.send(Poll::Ready(result)).ok();
+                });
+            });
+        }
+
+        match &self.state {
+            Some(Poll::Pending) => {
+                ui.horizontal(|ui| {
+                    ui.spinner();
+                    ui.label("Pushing snapshots…");
+                });
+            }
+            Some(Poll::Ready(Ok(pushed))) => {
+                ui.hyperlink_to(&pushed.text, &pushed.url);
+            }
+            Some(Poll::Ready(Err(err))) => {
+                ui.colored_label(ui.visuals().error_fg_color, format!("Error: {err:#}"));
+            }
+            None => {}
+        }
+    }
+}
+
+#[derive(Clone, Copy)]
+enum Target {
+    /// Commit onto the PR branch.
+    Branch,
+
+    /// Commit onto a new branch and open a pull request into the PR branch.
+    PullRequest,
+}
+
+/// Where the snapshots went.
+struct Pushed {
+    text: String,
+    url: String,
+}
+
+async fn push(
+    repo: &RepoClient,
+    branch: &str,
+    root: &str,
+    snapshots: &[Snapshot],
+    target: Target,
+    description: String,
+) -> anyhow::Result<Pushed> {
+    let changes = snapshots
+        .iter()
+        .map(|snapshot| file_change(snapshot, root))
+        .collect::<anyhow::Result<Vec<_>>>()?;
+    let message = commit_message(&changes);
+    match target {
+        Target::Branch => push_to_branch(repo, branch, &message, &changes).await,
+        Target::PullRequest => {
+            open_pull_request(repo, branch, &message, &changes, description).await
+        }
+    }
+}
+
+/// Refuses changes to files that aren't in the branch, unless they are added next to existing
+/// ones. Artifact paths that don't start where the snapshots are in the repo end up there.
+async fn check_paths(
+    repo: &RepoClient,
+    head: &str,
+    branch: &str,
+    changes: &[FileChange],
+) -> anyhow::Result<()> {
+    let tree = git_data::tree(repo, head).await?;
+    for change in changes {
+        let dir = change.path.rsplit_once('/').map(|(dir, _)| dir);
+        let known = tree.files.contains(&change.path)
+            || (change.content.is_some() && dir.is_some_and(|dir| tree.dirs.contains(dir)));
+        anyhow::ensure!(
+            known,
+            "{} is not in {branch}. Set `snapshot_root` under `[github]` in kitdiff.toml to \
+            where the snapshots are in the repository",
+            change.path
+        );
+    }
+    Ok(())
+}
+
+async fn push_to_branch(
+    repo: &RepoClient,
+    branch: &str,
+    message: &str,
+    changes: &[FileChange],
+) -> anyhow::Result<Pushed> {
+    let head = git_data::branch_head(repo, branch).await?;
+    check_paths(repo, &head, branch, changes).await?;
+    let sha = git_data::create_commit(repo, &head, message, changes).await?;
+    git_data::update_branch(repo, branch, &sha).await?;
+    Ok(Pushed {
+        text: "Snapshots pushed, view commit".to_owned(),
+        url: format!(
+            "https://github.com/{}/{}/commit/{sha}",
+            repo.repo().owner,
+            repo.repo().repo
+        ),
+    })
+}
+
+async fn open_pull_request(
+    repo: &RepoClient,
+    branch: &str,
+    message: &str,
+    changes: &[FileChange],
+    description: String,
+) -> anyhow::Result<Pushed> {
+    let head = git_data::branch_head(repo, branch).await?;
+    check_paths(repo, &head, branch, changes).await?;
+    let sha = git_data::create_commit(repo, &head, message, changes).await?;
+    let new_branch = format!("kitdiff/update-snapshots-{}", sha.get(..7).unwrap_or(&sha));
+    git_data::create_branch(repo, &new_branch, &sha).await?;
+    let pr = repo
+        .pulls()
+        .create(format!("Update snapshots for {branch}"), new_branch, branch)
+        .body(description)
+        .send()
+        .await?;
+    Ok(Pushed {
+        text: format!("Opened pull request #{}", pr.number),
+        url: format!(
+            "https://github.com/{}/{}/pull/{}",
+            repo.repo().owner,
+            repo.repo().repo,
+            pr.number
+        ),
+    })
+}
+
+/// Accepted snapshots whose new image we have in memory, or that were deleted.
+fn accepted<'a>(state: &AppStateRef<'_>, snapshots: &'a [Snapshot]) -> Vec<&'a Snapshot> {
+    let PageRef::DiffViewer(viewer) = &state.page else {
+        return Vec::new();
+    };
+    snapshots
+        .iter()
+        .filter(|s| viewer.reviews.get(&s.path) == Some(&ReviewStatus::Accepted))
+        .filter(|s| {
+            matches!(
+                s.new,
+                None | Some(
+                    FileReference::Source(ImageSource::Bytes { .. }) | FileReference::Archive(_)
+                )
+            )
+        })
+        .collect()
+}
+
+fn file_change(snapshot: &Snapshot, root: &str) -> anyhow::Result<FileChange> {
+    let content = match &snapshot.new {
+        Some(FileReference::Source(ImageSource::Bytes { bytes, .. })) => Some(bytes.to_vec()),
+        Some(FileReference::Archive(entry)) => Some(
+            entry
+                .read()
+                .with_context(|| format!("Failed to extract {}", snapshot.path.display()))?,
+        ),
+        None => None,
+        Some(_) => anyhow::bail!("The new image of {} isn't loaded", snapshot.path.display()),
+    };
+    Ok(FileChange {
+        path: repo_path(snapshot, root),
+        content,
+    })
+}
+
+/// The artifact path of the snapshot, below the `snapshot_root` of the config.
+fn repo_path(snapshot: &Snapshot, root: &str) -> String {
+    let path = snapshot.path.to_string_lossy().replace('\\', "/");
+    match root.trim_matches('/') {
+        "" => path,
+        root => format!("{root}/{path}"),
+    }
+}
+
+fn commit_message(changes: &[FileChange]) -> String {
+    let mut message = format!(
+        "Update {} snapshots\n\nAccepted in kitdiff:\n",
+        changes.len()
+    );
+    for change in changes {
+        message += &format!("- {}\n", change.path);
+    }
+    message
+}
+
+/// Lists the changed files with their diff stats, as far as they are computed.
+fn pull_request_description(state: &AppStateRef<'_>, snapshots: &[&Snapshot]) -> String {
+    let mut description = String::from(
+        "Snapshot updates accepted in [kitdiff](https://github.com/rerun-io/kitdiff).\n\n\
+        | File | Change |\n| --- | --- |\n",
+    );
+    for snapshot in snapshots {
+        let change = if snapshot.added() {
+            "added".to_owned()
+        } else if snapshot.deleted() {
+            "deleted".to_owned()
+        } else {
+            DiffPrecompute::diff_uri(snapshot, state.settings.options)
+                .and_then(|uri| state.diff_image_loader.diff_pixels(&uri))
+                .map_or_else(|| "changed".to_owned(), |pixels| format!("{pixels} pixels"))
+        };
+        description += &format!("| `{}` | {change} |\n", snapshot.path.display());
+    }
+    description
+}
@@ -0,0 +1,163 @@
+use crate::config::Config;
+use crate::loaders::{CancelFlag, LoadSnapshots, Progress, SnapshotLoader, sort_snapshots};
+use crate::snapshot::{FileReference, Snapshot};
+use crate::state::AppStateRef;
+use eframe::egui::{CollapsingHeader, Context, Ui};
+use octocrab::Octocrab;
+use std::collections::HashMap;
+use std::ffi::OsStr;
+use std::path::Path;
+use std::task::Poll;
+
+/// Compares what two sources currently show, e.g. a CI artifact against the snapshots rendered
+/// on this machine.
+///
+/// Each side contributes its current image of every snapshot: the new image if there is one,
+/// otherwise the committed one. Snapshots are paired by relative path. As the sources are often
+/// rooted at different folders, e.g. `tests/snapshots/button.png` in an artifact and
+/// `button.png` in `./tests/snapshots`, a path also pairs with a path it ends with.
+pub struct CompareLoader {
+    old: SnapshotLoader,
+    new: SnapshotLoader,
+    snapshots: Vec<Snapshot>,
+
+    /// Number of snapshots of each side that are paired up in `snapshots`.
+    merged: (usize, usize),
+}
+
+impl CompareLoader {
+    pub fn new(old: SnapshotLoader, new: SnapshotLoader) -> Self {
+        Self {
+            old,
+            new,
+            snapshots: Vec::new(),
+            merged: (0, 0),
+        }
+    }
+
+    fn pair(&mut self) {
+        let old = self.old.snapshots();
+        let new = self.new.snapshots();
+
+        let mut old_by_name: HashMap<&OsStr, Vec<(&Path, Option<FileReference>)>> = HashMap::new();
+        for snapshot in old {
+            if let Some(name) = snapshot.path.file_name() {
+                old_by_name
+                    .entry(name)
+                    .or_default()
+                    .push((&snapshot.path, current(snapshot)));
+            }
+        }
+
+        let mut snapshots = Vec::with_capacity(new.len());
+        for snapshot in new {
+            let Some(name) = snapshot.path.file_name() else {
+                continue;
+            };
+            let old_file = old_by_name.get_mut(name).and_then(|candidates| {
+                let index = candidates.iter().position(|(old_path, _)| {
+                    old_path.ends_with(&snapshot.path) || snapshot.path.ends_with(old_path)
+                })?;
+                Some(candidates.swap_remove(index).1)
+            });
+            snapshots.push(Snapshot {
+                path: snapshot.path.clone(),
+                old: old_file.flatten(),
+                new: current(snapshot),
+                diff: None,
+                unchanged: false,
+                renamed_from: None,
+            });
+        }
+
+        // Whatever is left only exists on the old side.
+        snapshots.extend(
+            old_by_name
+                .into_values()
+                .flatten()
+                .map(|(path, file)| Snapshot {
+                    path: path.to_path_buf(),
+                    old: file,
+                    new: None,
+                    diff: None,
+                    unchanged: false,
+                    renamed_from: None,
+                }),
+        );
+
+        snapshots.retain(|snapshot| snapshot.old.is_some() || snapshot.new.is_some());
+        sort_snapshots(&mut snapshots);
+        self.snapshots = snapshots;
+    }
+}
+
+/// The image the source shows for the snapshot right now.
+fn current(snapshot: &Snapshot) -> Option<FileReference> {
+    snapshot.new.clone().or_else(|| snapshot.old.clone())
+}
+
+impl LoadSnapshots for CompareLoader {
+    fn update(&mut self, ctx: &Context) {
+        self.old.update(ctx);
+        self.new.update(ctx);
+
+        let counts = (self.old.snapshots().len(), self.new.snapshots().len());
+        if counts != self.merged {
+            self.pair();
+            self.merged = counts;
+        }
+    }
+
+    fn refresh(&mut self, client: Octocrab, cancel: CancelFlag) {
+        self.old.refresh(client.clone(), cancel.clone());
+        self.new.refresh(client, cancel);
+        self.snapshots.clear();
+        self.merged = (0, 0);
+    }
+
+    fn snapshots(&self) -> &[Snapshot] {
+        &self.snapshots
+    }
+
+    fn state(&self) -> Poll<Result<(), &anyhow::Error>> {
+        match (self.old.state(), self.new.state()) {
+            (Poll::Ready(Err(err)), _) | (_, Poll::Ready(Err(err))) => Poll::Ready(Err(err)),
+            (Poll::Ready(Ok(())), Poll::Ready(Ok(()))) => Poll::Ready(Ok(())),
+            _ => Poll::Pending,
+        }
+    }
+
+    fn progress(&self) -> Option<Progress> {
+        let (side, mut progress) = self
+            .old
+            .progress()
+            .map(|progress| ("old", progress))
+            .or_else(|| self.new.progress().map(|progress| ("new", progress)))?;
+        progress.detail = Some(match progress.detail {
+            Some(detail) => format!("{side}, {detail}"),
+            None => side.to_owned(),
+        });
+        Some(progress)
+    }
+
+    fn extra_ui(&self, ui: &mut Ui, state: &AppStateRef<'_>) {
+        for (name, loader) in [("Old", &self.old), ("New", &self.new)] {
+            CollapsingHeader::new(format!("{name}: {}", loader.files_header()))
+                .default_open(false)
+                .show(ui, |ui| loader.extra_ui(ui, state));
+        }
+    }
+
+    /// The local side is usually the new one, and has the repository's config.
+    fn config(&self) -> Option<&Config> {
+        self.new.config().or_else(|| self.old.config())
+    }
+
+    fn files_header(&self) -> String {
+        format!(
+            "Compare: {} vs {}",
+            self.old.files_header(),
+            self.new.files_header()
+        )
+    }
+}
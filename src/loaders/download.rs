@@ -0,0 +1,213 @@
+use crate::loaders::CancelFlag;
+use bytes::{Bytes, BytesMut};
+use futures::StreamExt as _;
+use reqwest::StatusCode;
+use reqwest::header::{
+    ACCEPT_RANGES, CONTENT_LENGTH, CONTENT_RANGE, HeaderMap, RANGE, RETRY_AFTER,
+};
+use std::time::Duration;
+
+/// How often a failed download is retried before giving up.
+const MAX_ATTEMPTS: u32 = 4;
+
+/// Wait before the first retry, doubled for every one after.
+const RETRY_DELAY: Duration = Duration::from_millis(500);
+
+/// Longest we wait before a retry, even if the server asks for more with `Retry-After`.
+const MAX_RETRY_DELAY: Duration = Duration::from_secs(120);
+
+#[derive(Debug, Clone, Copy, Default)]
+pub struct DownloadProgress {
+    pub downloaded: u64,
+    pub total: Option<u64>,
+}
+
+impl DownloadProgress {
+    pub fn fraction(&self) -> Option<f32> {
+        self.total
+            .filter(|total| *total > 0)
+            .map(|total| self.downloaded as f32 / total as f32)
+    }
+}
+
+/// Download `url` in chunks, reporting progress as it goes.
+///
+/// If the connection drops or the server has a problem, the download is retried with exponential
+/// backoff. When the server supports range requests we continue where we left off instead of
+/// starting over. Client errors other than timeouts and rate limits fail right away.
+pub async fn download(
+    url: &str,
+    cancel: &CancelFlag,
+    on_progress: impl FnMut(DownloadProgress),
+) -> anyhow::Result<Bytes> {
+    download_with_headers(url, HeaderMap::new(), cancel, on_progress).await
+}
+
+/// Like [`download`], sending `headers` with every request, e.g. for authentication.
+pub async fn download_with_headers(
+    url: &str,
+    headers: HeaderMap,
+    cancel: &CancelFlag,
+    mut on_progress: impl FnMut(DownloadProgress),
+) -> anyhow::Result<Bytes> {
+    let client = crate::http::client();
+    let mut data = BytesMut::new();
+    let mut total = None;
+    let mut supports_ranges = false;
+    let mut last_error = None;
+    let mut retry_after = None;
+
+    for attempt in 0..MAX_ATTEMPTS {
+        if cancel.is_cancelled() {
+            anyhow::bail!("Download cancelled");
+        }
+        if attempt > 0 {
+            let delay = retry_after
+                .take()
+                .unwrap_or(RETRY_DELAY * 2_u32.pow(attempt - 1))
+                .min(MAX_RETRY_DELAY);
+            tracing::warn!(
+                "Retrying download of {url} in {delay:?} (attempt {}), {} bytes so far",
+                attempt + 1,
+                data.len()
+            );
+            sleep(delay).await;
+            if cancel.is_cancelled() {
+                anyhow::bail!("Download cancelled");
+            }
+        }
+
+        let mut request = client.get(url).headers(headers.clone());
+        let resuming = supports_ranges && !data.is_empty();
+        if resuming {
+            request = request.header(RANGE, format!("bytes={}-", data.len()));
+        } else {
+            data.clear();
+        }
+
+        let response = match request.send().await {
+            Ok(response) => response,
+            Err(err) => {
+                last_error = Some(err.into());
+                continue;
+            }
+        };
+        let status = response.status();
+        if let Err(err) = response.error_for_status_ref() {
+            let retry = status.is_server_error()
+                || status == StatusCode::REQUEST_TIMEOUT
+                || status == StatusCode::TOO_MANY_REQUESTS;
+            if !retry {
+                return Err(err.into());
+            }
+            // Only the delay in seconds, not the http date form.
+            retry_after = response
+                .headers()
+                .get(RETRY_AFTER)
+                .and_then(|v| v.to_str().ok())
+                .and_then(|v| v.trim().parse::<u64>().ok())
+                .map(Duration::from_secs);
+            last_error = Some(err.into());
+            continue;
+        }
+
+        if resuming && response.status() != StatusCode::PARTIAL_CONTENT {
+            // The server ignored our range, start from scratch.
+            data.clear();
+        }
+        supports_ranges |= response
+            .headers()
+            .get(ACCEPT_RANGES)
+            .is_some_and(|v| v.as_bytes() == b"bytes")
+            || response.headers().contains_key(CONTENT_RANGE);
+
+        if total.is_none() {
+            total = response
+                .headers()
+                .get(CONTENT_LENGTH)
+                .and_then(|v| v.to_str().ok())
+                .and_then(|v| v.parse::<u64>().ok())
+                .map(|len| len + data.len() as u64);
+        }
+
+        let mut stream = response.bytes_stream();
+        let mut failed = false;
+        while let Some(chunk) = stream.next().await {
+            if cancel.is_cancelled() {
+                anyhow::bail!("Download cancelled");
+            }
+            match chunk {
+                Ok(chunk) => {
+                    data.extend_from_slice(&chunk);
+                    on_progress(DownloadProgress {
+                        downloaded: data.len() as u64,
+                        total,
+                    });
+                }
+                Err(err) => {
+                    last_error = Some(err.into());
+                    failed = true;
+                    break;
+                }
+            }
+        }
+
+        if failed {
+            continue;
+        }
+        match total {
+            Some(total) if data.len() as u64 != total => {
+                last_error = Some(anyhow::anyhow!(
+                    "Download of {url} ended after {} of {total} bytes",
+                    data.len()
+                ));
+                // Too much data can't be resumed.
+                if data.len() as u64 > total {
+                    data.clear();
+                }
+            }
+            _ => return Ok(data.freeze()),
+        }
+    }
+
+    Err(last_error.unwrap_or_else(|| anyhow::anyhow!("Download failed")))
+}
+
+#[cfg(not(target_arch = "wasm32"))]
+async fn sleep(duration: Duration) {
+    tokio::time::sleep(duration).await;
+}
+
+#[cfg(target_arch = "wasm32")]
+async fn sleep(duration: Duration) {
+    let promise = js_sys::Promise::new(&mut |resolve, _reject| {
+        let scheduled = web_sys::window().is_some_and(|window| {
+            window
+                .set_timeout_with_callback_and_timeout_and_arguments_0(
+                    &resolve,
+                    duration.as_millis() as i32,
+                )
+                .is_ok()
+        });
+        if !scheduled {
+            resolve.call0(&wasm_bindgen::JsValue::NULL).ok();
+        }
+    });
+    wasm_bindgen_futures::JsFuture::from(promise).await.ok();
+}
+
+/// Format a byte count for humans, e.g. `12.3 MB`.
+pub fn format_bytes(bytes: u64) -> String {
+    const UNITS: [&str; 4] = ["B", "KB", "MB", "GB"];
+    let mut value = bytes as f64;
+    let mut unit = 0;
+    while value >= 1000.0 && unit < UNITS.len() - 1 {
+        value /= 1000.0;
+        unit += 1;
+    }
+    if unit == 0 {
+        format!("{bytes} B")
+    } else {
+        format!("{value:.1} {}", UNITS[unit])
+    }
+}
@@ -0,0 +1,118 @@
+use crate::github::model::GithubArtifactLink;
+use crate::loaders::{CancelFlag, download};
+use eframe::egui::{Context, RichText, Ui};
+use egui_inbox::UiInbox;
+use kitdiff_core::test_logs::{self, FailedSnapshot};
+use octocrab::Octocrab;
+use octocrab::models::RunId;
+use std::path::Path;
+use std::task::Poll;
+
+/// Reads the logs of the workflow run that uploaded an artifact, to find out which of its
+/// snapshots made the snapshot tests fail.
+pub struct FailedTests {
+    inbox: UiInbox<anyhow::Result<Vec<FailedSnapshot>>>,
+    failed: Poll<anyhow::Result<Vec<FailedSnapshot>>>,
+}
+
+impl FailedTests {
+    pub fn new(client: Octocrab, artifact: GithubArtifactLink, cancel: CancelFlag) -> Self {
+        let mut inbox = UiInbox::new();
+        inbox.spawn(|tx| async move {
+            let result = failed_snapshots(&client, &artifact, &cancel).await;
+            if let Err(err) = &result {
+                // Not worth bothering the user about, the snapshots are there either way.
+                tracing::warn!("Couldn't read the workflow logs: {err:#}");
+            }
+            tx.send(result).ok();
+        });
+        Self {
+            inbox,
+            failed: Poll::Pending,
+        }
+    }
+
+    pub fn update(&mut self, ctx: &Context) {
+        if let Some(result) = self.inbox.read(ctx).last() {
+            self.failed = Poll::Ready(result);
+        }
+    }
+
+    /// The failure of the snapshot at `path`, if one was reported.
+    pub fn get(&self, path: &Path) -> Option<&FailedSnapshot> {
+        match &self.failed {
+            Poll::Ready(Ok(failed)) => failed.iter().find(|failure| failure.matches(path)),
+            _ => None,
+        }
+    }
+
+    pub fn ui(&self, ui: &mut Ui) {
+        let Poll::Ready(Ok(failed)) = &self.failed else {
+            return;
+        };
+        if failed.is_empty() {
+            return;
+        }
+        let text = match failed.len() {
+            1 => "✖ 1 snapshot failed in CI".to_owned(),
+            count => format!("✖ {count} snapshots failed in CI"),
+        };
+        ui.label(RichText::new(text).color(ui.visuals().error_fg_color))
+            .on_hover_ui(|ui| {
+                for failure in failed {
+                    match &failure.test {
+                        Some(test) => ui.label(format!("{} in {test}", failure.name)),
+                        None => ui.label(&failure.name),
+                    };
+                }
+            });
+    }
+}
+
+async fn failed_snapshots(
+    client: &Octocrab,
+    artifact: &GithubArtifactLink,
+    cancel: &CancelFlag,
+) -> anyhow::Result<Vec<FailedSnapshot>> {
+    let run_id = match artifact.run_id {
+        Some(run_id) => run_id,
+        None => artifact_run(client, artifact).await?,
+    };
+
+    // Like artifacts, the logs redirect to blob storage that we download from ourselves.
+    let route = format!(
+        "/repos/{}/{}/actions/runs/{run_id}/logs",
+        artifact.repo.owner, artifact.repo.repo
+    );
+    let response = client._get(route).await?;
+    let location = response
+        .headers()
+        .get(reqwest::header::LOCATION)
+        .and_then(|l| l.to_str().ok())
+        .map(ToOwned::to_owned);
+    let data = if let Some(url) = location {
+        download::download(&url, cancel, |_| {}).await?
+    } else {
+        client
+            .actions()
+            .download_workflow_run_logs(&artifact.repo.owner, &artifact.repo.repo, run_id)
+            .await?
+    };
+
+    let failed = test_logs::failed_snapshots_in_zip(&data)?;
+    tracing::info!("Found {} failed snapshots in the logs", failed.len());
+    Ok(failed)
+}
+
+/// Artifacts opened by url don't say which run uploaded them.
+async fn artifact_run(client: &Octocrab, artifact: &GithubArtifactLink) -> anyhow::Result<RunId> {
+    let route = format!(
+        "/repos/{}/{}/actions/artifacts/{}",
+        artifact.repo.owner, artifact.repo.repo, artifact.artifact_id
+    );
+    let response: serde_json::Value = client.get(route, None::<&()>).await?;
+    response["workflow_run"]["id"]
+        .as_u64()
+        .map(RunId)
+        .ok_or_else(|| anyhow::anyhow!("The artifact doesn't belong to a workflow run"))
+}
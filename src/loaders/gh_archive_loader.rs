@@ -1,106 +1,254 @@
 use crate::github::model::GithubArtifactLink;
-use crate::loaders::LoadSnapshots;
 use crate::loaders::archive_loader::ArchiveLoader;
+use crate::loaders::commit_snapshots::CommitSnapshots;
+use crate::loaders::download::{self, DownloadProgress, format_bytes};
+use crate::loaders::failed_tests::FailedTests;
+use crate::loaders::update_workflow::{DEFAULT_WORKFLOW, UpdateWorkflow};
+use crate::loaders::{CancelFlag, LoadSnapshots, Phase, Progress};
+use crate::path_filter::PathFilter;
 use crate::snapshot::Snapshot;
 use crate::state::AppStateRef;
 use anyhow::Error;
 use bytes::Bytes;
 use eframe::egui::{Context, Ui};
-use egui_inbox::UiInbox;
+use egui_inbox::{UiInbox, UiInboxSender};
+use kitdiff_core::test_logs::FailedSnapshot;
 use octocrab::Octocrab;
 use octocrab::params::actions::ArchiveFormat;
-use serde_json::json;
+use std::path::Path;
 use std::task::Poll;
 
-enum PipelineState {
-    Loading,
-    Triggered { workflow_link: String },
-    Error(anyhow::Error),
+pub struct GHArtifactLoader {
+    state: LoaderState,
+    artifact: GithubArtifactLink,
+    update_workflow: UpdateWorkflow,
+
+    /// Only if we know the PR branch.
+    commit_snapshots: Option<CommitSnapshots>,
+    failed_tests: FailedTests,
+    filter: PathFilter,
+}
+
+/// GitHub refused to hand out the artifact. Downloading artifacts needs a signed in user, so
+/// that's what the ui suggests if nobody is. Signing in refreshes the loader, which downloads
+/// again.
+#[derive(Debug)]
+pub struct ArtifactAccessDenied;
+
+impl std::fmt::Display for ArtifactAccessDenied {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str("GitHub refused to download the artifact")
+    }
 }
 
-enum Event {
-    PipelineState(PipelineState),
+fn is_access_denied(err: &Error) -> bool {
+    err.chain().any(|cause| {
+        cause
+            .downcast_ref::<octocrab::GitHubError>()
+            .is_some_and(|err| {
+                matches!(
+                    err.status_code,
+                    reqwest::StatusCode::UNAUTHORIZED | reqwest::StatusCode::FORBIDDEN
+                )
+            })
+    })
 }
 
-pub struct GHArtifactLoader {
-    state: LoaderState,
-    artifact: GithubArtifactLink,
-    pipeline_state: Option<PipelineState>,
-    inbox: UiInbox<Event>,
+#[derive(Debug)]
+pub enum DownloadEvent {
+    Progress(DownloadProgress),
+    Done(anyhow::Result<(Bytes, String)>),
+}
+
+#[derive(Debug)]
+pub struct Download {
+    inbox: UiInbox<DownloadEvent>,
+    progress: DownloadProgress,
+    cancel: CancelFlag,
+    /// `egui` time of the first progress event, used to compute the download speed.
+    started_at: Option<f64>,
+    bytes_per_second: Option<f64>,
+}
+
+impl Download {
+    pub fn new(inbox: UiInbox<DownloadEvent>, cancel: CancelFlag) -> Self {
+        Self {
+            inbox,
+            progress: DownloadProgress::default(),
+            cancel,
+            started_at: None,
+            bytes_per_second: None,
+        }
+    }
+
+    pub fn cancel(&self) -> &CancelFlag {
+        &self.cancel
+    }
+
+    /// Tracks the progress, and returns the result once the download is done.
+    pub fn update(&mut self, ctx: &Context) -> Option<anyhow::Result<(Bytes, String)>> {
+        let now = ctx.input(|i| i.time);
+        let mut result = None;
+        for event in self.inbox.read(ctx) {
+            match event {
+                DownloadEvent::Progress(progress) => {
+                    let started_at = *self.started_at.get_or_insert(now);
+                    if now > started_at {
+                        self.bytes_per_second =
+                            Some(progress.downloaded as f64 / (now - started_at));
+                    }
+                    self.progress = progress;
+                }
+                DownloadEvent::Done(done) => result = Some(done),
+            }
+        }
+        result
+    }
+
+    pub fn progress(&self) -> Progress {
+        let progress = self.progress;
+        let mut text = match progress.total {
+            Some(total) => format!(
+                "{} / {}",
+                format_bytes(progress.downloaded),
+                format_bytes(total)
+            ),
+            None => format_bytes(progress.downloaded),
+        };
+        if let Some(speed) = self.bytes_per_second {
+            text += &format!(", {}/s", format_bytes(speed as u64));
+        }
+        Progress::new(Phase::Downloading)
+            .with_fraction(progress.fraction())
+            .with_detail(text)
+    }
 }
 
 #[derive(Debug)]
 pub enum LoaderState {
-    LoadingData(UiInbox<anyhow::Result<(Bytes, String)>>),
+    LoadingData(Download),
     LoadingArchive(ArchiveLoader),
     Error(anyhow::Error),
 }
 
 impl GHArtifactLoader {
-    pub fn new(client: Octocrab, artifact: GithubArtifactLink) -> Self {
+    pub fn new(
+        client: Octocrab,
+        artifact: GithubArtifactLink,
+        filter: PathFilter,
+        cancel: CancelFlag,
+    ) -> Self {
         let mut data_inbox = UiInbox::new();
 
         {
+            let client = client.clone();
             let artifact = artifact.clone();
+            let cancel = cancel.clone();
             data_inbox.spawn(move |tx| async move {
-                tx.send(download_artifact(&client, &artifact).await).ok();
+                let result = download_artifact(&client, &artifact, &cancel, &tx).await;
+                tx.send(DownloadEvent::Done(result)).ok();
             });
         }
 
-        let inbox = UiInbox::new();
+        let failed_tests = FailedTests::new(client.clone(), artifact.clone(), cancel.clone());
 
         Self {
-            state: LoaderState::LoadingData(data_inbox),
+            state: LoaderState::LoadingData(Download::new(data_inbox, cancel)),
+            commit_snapshots: artifact
+                .branch_name
+                .clone()
+                .map(|branch| CommitSnapshots::new(client.clone(), artifact.repo.clone(), branch)),
+            failed_tests,
+            update_workflow: UpdateWorkflow::new(client, artifact.clone()),
             artifact,
-            pipeline_state: None,
-            inbox,
+            filter,
         }
     }
 }
 
+#[tracing::instrument(name = "download", skip_all, fields(artifact = %artifact.artifact_id))]
 pub async fn download_artifact(
     client: &Octocrab,
     artifact: &GithubArtifactLink,
+    cancel: &CancelFlag,
+    tx: &UiInboxSender<DownloadEvent>,
 ) -> anyhow::Result<(Bytes, String)> {
-    let data = client
-        .actions()
-        .download_artifact(
-            &artifact.repo.owner,
-            &artifact.repo.repo,
-            artifact.artifact_id,
-            ArchiveFormat::Zip,
-        )
-        .await?;
     let name = artifact.name();
+
+    #[cfg(target_arch = "wasm32")]
+    let cache_key = crate::web_loaders::opfs_cache::artifact_key(
+        &artifact.repo.owner,
+        &artifact.repo.repo,
+        &artifact.artifact_id.to_string(),
+    );
+    #[cfg(target_arch = "wasm32")]
+    if let Some(data) = crate::web_loaders::opfs_cache::get(&cache_key).await {
+        return Ok((data, name));
+    }
+
+    // The artifact endpoint redirects to a short-lived blob storage url. Resolve that ourselves
+    // so we can stream it with progress and range requests.
+    let route = format!(
+        "/repos/{}/{}/actions/artifacts/{}/zip",
+        artifact.repo.owner, artifact.repo.repo, artifact.artifact_id
+    );
+    let response = client._get(route).await?;
+    let location = response
+        .headers()
+        .get(reqwest::header::LOCATION)
+        .and_then(|l| l.to_str().ok())
+        .map(ToOwned::to_owned);
+
+    let data = if let Some(url) = location {
+        download::download(&url, cancel, |progress| {
+            tx.send(DownloadEvent::Progress(progress)).ok();
+        })
+        .await?
+    } else {
+        client
+            .actions()
+            .download_artifact(
+                &artifact.repo.owner,
+                &artifact.repo.repo,
+                artifact.artifact_id,
+                ArchiveFormat::Zip,
+            )
+            .await?
+    };
+    tracing::info!("Downloaded {}", download::format_bytes(data.len() as u64));
+    #[cfg(target_arch = "wasm32")]
+    crate::web_loaders::opfs_cache::put(&cache_key, &name, &data).await;
     Ok((data, name))
 }
 
 impl LoadSnapshots for GHArtifactLoader {
     fn update(&mut self, ctx: &Context) {
-        for event in self.inbox.read(ctx) {
-            match event {
-                Event::PipelineState(state) => {
-                    self.pipeline_state = Some(state);
-                }
-            }
+        self.update_workflow.update(ctx);
+        self.failed_tests.update(ctx);
+        if let Some(commit_snapshots) = &mut self.commit_snapshots {
+            commit_snapshots.update(ctx);
         }
 
         let mut new_state = None;
         match &mut self.state {
-            LoaderState::LoadingData(inbox) => {
-                if let Some(result) = inbox.read(ctx).last() {
-                    match result {
-                        Ok((data, name)) => {
-                            new_state = Some(LoaderState::LoadingArchive(ArchiveLoader::new(
-                                crate::loaders::DataReference::Data(data.clone(), name),
-                            )));
-                        }
-                        Err(e) => {
-                            new_state = Some(LoaderState::Error(e));
-                        }
-                    }
+            LoaderState::LoadingData(download) => match download.update(ctx) {
+                Some(Ok((data, name))) => {
+                    new_state = Some(LoaderState::LoadingArchive(ArchiveLoader::new(
+                        crate::loaders::DataReference::Data(data, name),
+                        self.filter.clone(),
+                        download.cancel().clone(),
+                    )));
                 }
-            }
+                Some(Err(e)) => {
+                    let e = if is_access_denied(&e) {
+                        e.context(ArtifactAccessDenied)
+                    } else {
+                        e
+                    };
+                    new_state = Some(LoaderState::Error(e));
+                }
+                None => {}
+            },
             LoaderState::LoadingArchive(loader) => {
                 loader.update(ctx);
             }
@@ -126,6 +274,18 @@ impl LoadSnapshots for GHArtifactLoader {
         }
     }
 
+    fn progress(&self) -> Option<Progress> {
+        match &self.state {
+            LoaderState::LoadingData(download) => Some(download.progress()),
+            LoaderState::LoadingArchive(loader) => loader.progress(),
+            LoaderState::Error(_) => None,
+        }
+    }
+
+    fn failed_snapshot(&self, path: &Path) -> Option<&FailedSnapshot> {
+        self.failed_tests.get(path)
+    }
+
     fn files_header(&self) -> String {
         match &self.state {
             LoaderState::LoadingData(_) | LoaderState::Error(_) => "Github Artifact".to_owned(),
@@ -134,75 +294,21 @@ impl LoadSnapshots for GHArtifactLoader {
     }
 
     fn extra_ui(&self, ui: &mut Ui, state: &AppStateRef<'_>) {
-        if let Some((git_ref, run_id)) = self.artifact.branch_name.clone().zip(self.artifact.run_id)
-        {
-            let response = ui.button("Commit the updated snapshots").on_hover_text(
-                "This will create a commit on the PR branch with the updated snapshots.",
-            );
-            if response.clicked() {
-                let client = state.github_auth.client();
-                let artifact = self.artifact.clone();
-                let sender = self.inbox.sender();
-                sender
-                    .send(Event::PipelineState(PipelineState::Loading))
-                    .ok();
-                hello_egui_utils::spawn(async move {
-                    let workflow_name = "update_kittest_snapshots.yml";
-                    let result = client
-                        .actions()
-                        .create_workflow_dispatch(
-                            artifact.repo.owner.clone(),
-                            artifact.repo.repo.clone(),
-                            workflow_name,
-                            git_ref.clone(),
-                        )
-                        .inputs(json!({
-                            "run_id": run_id.to_string(),
-                        }))
-                        .send()
-                        .await;
-
-                    let workflow_link = format!(
-                        "https://github.com/{}/{}/actions/workflows/{workflow_name}",
-                        artifact.repo.owner, artifact.repo.repo
-                    );
-
-                    match result {
-                        Ok(()) => {
-                            sender
-                                .send(Event::PipelineState(PipelineState::Triggered {
-                                    workflow_link,
-                                }))
-                                .ok();
-                        }
-                        Err(err) => {
-                            sender
-                                .send(Event::PipelineState(PipelineState::Error(err.into())))
-                                .ok();
-                        }
-                    }
-                });
-            }
+        let workflow = state
+            .source_config()
+            .github
+            .update_snapshot_workflow_name
+            .as_deref()
+            .unwrap_or(DEFAULT_WORKFLOW);
+        self.update_workflow.ui(ui, workflow);
+        self.failed_tests.ui(ui);
 
-            match &self.pipeline_state {
-                Some(PipelineState::Loading) => {
-                    ui.label("Triggering pipeline...");
-                }
-                Some(PipelineState::Triggered { workflow_link }) => {
-                    ui.horizontal(|ui| {
-                        ui.label("Pipeline triggered!");
-                        ui.hyperlink_to("View workflows", workflow_link);
-                    });
-                }
-                Some(PipelineState::Error(err)) => {
-                    ui.colored_label(ui.visuals().error_fg_color, format!("Error: {err}"));
-                }
-                None => {}
-            }
+        if let Some(commit_snapshots) = &self.commit_snapshots {
+            commit_snapshots.ui(ui, state, self.snapshots());
         }
     }
 
-    fn refresh(&mut self, client: Octocrab) {
-        *self = Self::new(client, self.artifact.clone());
+    fn refresh(&mut self, client: Octocrab, cancel: CancelFlag) {
+        *self = Self::new(client, self.artifact.clone(), self.filter.clone(), cancel);
     }
 }
@@ -0,0 +1,288 @@
+//! Snapshots in the artifacts of a Jenkins build, either a single archive or the png files of an
+//! artifact folder.
+
+use crate::loaders::archive_loader::run_discovery;
+use crate::loaders::{CancelFlag, LoadSnapshots, Phase, Progress, extend_sorted};
+use crate::path_filter::PathFilter;
+use crate::snapshot::Snapshot;
+use anyhow::{Context as _, Error, Result};
+use eframe::egui::Context;
+use egui_inbox::{UiInbox, UiInboxSender};
+use futures::{StreamExt as _, TryStreamExt as _};
+use kitdiff_core::archive::{Files, snapshot_path};
+use octocrab::Octocrab;
+use std::fmt;
+use std::path::{Path, PathBuf};
+use std::str::FromStr;
+use std::task::Poll;
+
+/// How many png files are downloaded at once.
+const CONCURRENT_DOWNLOADS: usize = 8;
+
+/// An artifact of a Jenkins build, like
+/// `https://jenkins.example/job/app/job/main/123/artifact/tests/snapshots/`.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct JenkinsLink {
+    /// The build, ending with a slash, e.g. `https://jenkins.example/job/app/job/main/123/`.
+    pub build_url: String,
+
+    /// The artifact path within the build, empty for all artifacts.
+    pub path: String,
+}
+
+impl FromStr for JenkinsLink {
+    type Err = Error;
+
+    fn from_str(url: &str) -> Result<Self> {
+        anyhow::ensure!(
+            url.starts_with("https://") || url.starts_with("http://"),
+            "Not a url"
+        );
+        anyhow::ensure!(url.contains("/job/"), "Not a Jenkins job url");
+        let url = url.split(['?', '#']).next().unwrap_or(url);
+        let (build, path) = url
+            .split_once("/artifact/")
+            .or_else(|| url.strip_suffix("/artifact").map(|build| (build, "")))
+            .context("Not a Jenkins artifact url")?;
+        Ok(Self {
+            build_url: format!("{build}/"),
+            path: path.trim_matches('/').to_owned(),
+        })
+    }
+}
+
+impl fmt::Display for JenkinsLink {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}artifact/{}", self.build_url, self.path)
+    }
+}
+
+impl JenkinsLink {
+    fn is_archive(&self) -> bool {
+        [".zip", ".tar.gz", ".tgz"]
+            .iter()
+            .any(|extension| self.path.ends_with(extension))
+    }
+
+    fn artifact_url(&self, path: &str) -> String {
+        format!("{}artifact/{path}", self.build_url)
+    }
+}
+
+/// Basic auth for one Jenkins server.
+#[derive(Debug, Clone, Default)]
+pub struct JenkinsCredentials {
+    /// The server from [`crate::settings::JenkinsSettings::url`].
+    pub url: String,
+    pub user: String,
+    pub api_token: String,
+}
+
+impl JenkinsCredentials {
+    /// Whether the login may be sent to `url`, only true on the configured server so a link
+    /// elsewhere can't collect it.
+    pub fn applies_to(&self, url: &str) -> bool {
+        let origin = |url: &str| reqwest::Url::parse(url).ok().map(|url| url.origin());
+        let server = origin(&self.url);
+        !self.user.is_empty() && server.is_some() && origin(url) == server
+    }
+}
+
+#[derive(serde::Deserialize)]
+struct Build {
+    artifacts: Vec<Artifact>,
+}
+
+#[derive(serde::Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct Artifact {
+    relative_path: String,
+}
+
+enum Event {
+    /// This many files are going to be downloaded.
+    Listed(usize),
+    Downloaded,
+    Snapshots(Vec<Snapshot>),
+    Done(Result<()>),
+}
+
+pub struct JenkinsLoader {
+    link: JenkinsLink,
+    credentials: JenkinsCredentials,
+    filter: PathFilter,
+    inbox: UiInbox<Event>,
+    snapshots: Vec<Snapshot>,
+    state: Poll<Result<()>>,
+
+    /// Downloaded and total files, once the artifacts are listed.
+    downloads: Option<(usize, usize)>,
+}
+
+impl JenkinsLoader {
+    pub fn new(
+        link: JenkinsLink,
+        credentials: JenkinsCredentials,
+        filter: PathFilter,
+        cancel: CancelFlag,
+    ) -> Self {
+        let mut inbox = UiInbox::new();
+        {
+            let link = link.clone();
+            let credentials = credentials.clone();
+            let filter = filter.clone();
+            inbox.spawn(|tx| async move {
+                let result = load(&link, &credentials, &filter, cancel, &tx).await;
+                tx.send(Event::Done(result)).ok();
+            });
+        }
+        Self {
+            link,
+            credentials,
+            filter,
+            inbox,
+            snapshots: Vec::new(),
+            state: Poll::Pending,
+            downloads: None,
+        }
+    }
+}
+
+async fn load(
+    link: &JenkinsLink,
+    credentials: &JenkinsCredentials,
+    filter: &PathFilter,
+    cancel: CancelFlag,
+    tx: &UiInboxSender<Event>,
+) -> Result<()> {
+    if link.is_archive() {
+        tx.send(Event::Listed(1)).ok();
+        let bytes = get(&link.artifact_url(&link.path), credentials).await?;
+        tx.send(Event::Downloaded).ok();
+        let snapshots = run_discovery(bytes, filter, cancel).await?;
+        tx.send(Event::Snapshots(snapshots)).ok();
+        return Ok(());
+    }
+
+    // The artifact pages are html, the api lists every artifact of the build instead.
+    let build: Build = serde_json::from_slice(
+        &get(
+            &format!("{}api/json?tree=artifacts[relativePath]", link.build_url),
+            credentials,
+        )
+        .await?,
+    )
+    .context("Unexpected response from the Jenkins api")?;
+
+    let folder = if link.path.is_empty() {
+        String::new()
+    } else {
+        format!("{}/", link.path)
+    };
+    let files: Vec<(PathBuf, String)> = build
+        .artifacts
+        .into_iter()
+        .filter_map(|artifact| {
+            let relative = artifact.relative_path.strip_prefix(&folder)?;
+            let wanted =
+                relative.ends_with(".png") && filter.matches(&snapshot_path(Path::new(relative)));
+            wanted.then(|| (relative.into(), link.artifact_url(&artifact.relative_path)))
+        })
+        .collect();
+    tracing::info!("Found {} png files in {link}", files.len());
+    tx.send(Event::Listed(files.len())).ok();
+
+    let mut downloads = futures::stream::iter(files)
+        .map(|(path, url)| {
+            let cancel = cancel.clone();
+            async move {
+                anyhow::ensure!(!cancel.is_cancelled(), "Cancelled");
+                anyhow::Ok((path, get(&url, credentials).await?))
+            }
+        })
+        .buffer_unordered(CONCURRENT_DOWNLOADS);
+    let mut files = Files::new();
+    while let Some((path, bytes)) = downloads.try_next().await? {
+        tx.send(Event::Downloaded).ok();
+        files.insert(path, bytes.as_ref().into());
+    }
+
+    let snapshots = kitdiff_core::archive::snapshots_from_files(&files)
+        .into_iter()
+        .filter(|snapshot| filter.matches(&snapshot.path))
+        .map(Snapshot::from)
+        .collect();
+    tx.send(Event::Snapshots(snapshots)).ok();
+    Ok(())
+}
+
+async fn get(url: &str, credentials: &JenkinsCredentials) -> Result<bytes::Bytes> {
+    let mut request = crate::http::client().get(url);
+    if credentials.applies_to(url) {
+        request = request.basic_auth(&credentials.user, Some(&credentials.api_token));
+    }
+    let response = request.send().await?;
+    let status = response.status();
+    if status == reqwest::StatusCode::UNAUTHORIZED || status == reqwest::StatusCode::FORBIDDEN {
+        anyhow::bail!(
+            "Jenkins refused to download {url} ({status}), check the server, user and API token \
+            in Settings › Jenkins login"
+        );
+    }
+    Ok(response.error_for_status()?.bytes().await?)
+}
+
+impl LoadSnapshots for JenkinsLoader {
+    fn update(&mut self, ctx: &Context) {
+        for event in self.inbox.read(ctx) {
+            match event {
+                Event::Listed(total) => self.downloads = Some((0, total)),
+                Event::Downloaded => {
+                    if let Some((done, _)) = &mut self.downloads {
+                        *done += 1;
+                    }
+                }
+                Event::Snapshots(snapshots) => extend_sorted(&mut self.snapshots, snapshots),
+                Event::Done(result) => self.state = Poll::Ready(result),
+            }
+        }
+    }
+
+    fn refresh(&mut self, _client: Octocrab, cancel: CancelFlag) {
+        *self = Self::new(
+            self.link.clone(),
+            self.credentials.clone(),
+            self.filter.clone(),
+            cancel,
+        );
+    }
+
+    fn snapshots(&self) -> &[Snapshot] {
+        &self.snapshots
+    }
+
+    fn state(&self) -> Poll<Result<(), &Error>> {
+        match &self.state {
+            Poll::Ready(Ok(())) => Poll::Ready(Ok(())),
+            Poll::Ready(Err(err)) => Poll::Ready(Err(err)),
+            Poll::Pending => Poll::Pending,
+        }
+    }
+
+    fn progress(&self) -> Option<Progress> {
+        if self.state.is_ready() {
+            return None;
+        }
+        Some(match self.downloads {
+            None => Progress::new(Phase::Discovering).with_detail("listing artifacts"),
+            Some((done, total)) if done == total && total > 0 => Progress::new(Phase::Extracting),
+            Some((done, total)) => Progress::new(Phase::Downloading)
+                .with_fraction((total > 0).then(|| done as f32 / total as f32))
+                .with_detail(format!("{done} of {total} files")),
+        })
+    }
+
+    fn files_header(&self) -> String {
+        format!("Jenkins: {}", self.link)
+    }
+}
@@ -0,0 +1,155 @@
+use crate::config::Config;
+use crate::github::model::GithubPrLink;
+use crate::loaders::{CancelFlag, LoadSnapshots, Progress, SnapshotLoader, extend_sorted};
+use crate::snapshot::Snapshot;
+use crate::state::AppStateRef;
+use eframe::egui::{CollapsingHeader, Context, Ui};
+use kitdiff_core::test_logs::FailedSnapshot;
+use octocrab::Octocrab;
+use std::path::Path;
+use std::task::Poll;
+
+/// Combines several sources into one snapshot list, e.g. the per-platform artifacts of a PR.
+///
+/// Paths are prefixed with the name of the part they came from.
+pub struct MergedLoader {
+    parts: Vec<Part>,
+    snapshots: Vec<Snapshot>,
+}
+
+struct Part {
+    name: String,
+    loader: SnapshotLoader,
+
+    /// Number of snapshots of this part that are in the merged list.
+    merged: usize,
+}
+
+impl MergedLoader {
+    pub fn new(parts: Vec<(String, SnapshotLoader)>) -> Self {
+        Self {
+            parts: parts
+                .into_iter()
+                .map(|(name, loader)| Part {
+                    name,
+                    loader,
+                    merged: 0,
+                })
+                .collect(),
+            snapshots: Vec::new(),
+        }
+    }
+}
+
+impl LoadSnapshots for MergedLoader {
+    fn update(&mut self, ctx: &Context) {
+        for part in &mut self.parts {
+            part.loader.update(ctx);
+
+            // Loaders only ever append while loading, but may sort what they have, so we can't
+            // just take the new ones from the end.
+            let snapshots = part.loader.snapshots();
+            if snapshots.len() == part.merged {
+                continue;
+            }
+            let prefix = Path::new(&part.name);
+            self.snapshots.retain(|s| !s.path.starts_with(prefix));
+            extend_sorted(
+                &mut self.snapshots,
+                snapshots
+                    .iter()
+                    .map(|snapshot| prefixed(&part.name, snapshot)),
+            );
+            part.merged = snapshots.len();
+        }
+    }
+
+    fn refresh(&mut self, client: Octocrab, cancel: CancelFlag) {
+        for part in &mut self.parts {
+            part.loader.refresh(client.clone(), cancel.clone());
+            part.merged = 0;
+        }
+        self.snapshots.clear();
+    }
+
+    fn snapshots(&self) -> &[Snapshot] {
+        &self.snapshots
+    }
+
+    fn state(&self) -> Poll<Result<(), &anyhow::Error>> {
+        let mut pending = false;
+        for part in &self.parts {
+            match part.loader.state() {
+                Poll::Ready(Err(err)) => return Poll::Ready(Err(err)),
+                Poll::Pending => pending = true,
+                Poll::Ready(Ok(())) => {}
+            }
+        }
+        if pending {
+            Poll::Pending
+        } else {
+            Poll::Ready(Ok(()))
+        }
+    }
+
+    fn extra_ui(&self, ui: &mut Ui, state: &AppStateRef<'_>) {
+        for part in &self.parts {
+            CollapsingHeader::new(&part.name)
+                .default_open(part.loader.state().is_pending())
+                .show(ui, |ui| part.loader.extra_ui(ui, state));
+        }
+    }
+
+    fn failed_snapshot(&self, path: &Path) -> Option<&FailedSnapshot> {
+        self.parts.iter().find_map(|part| {
+            let path = path.strip_prefix(&part.name).ok()?;
+            part.loader.failed_snapshot(path)
+        })
+    }
+
+    fn review_url(&self) -> Option<String> {
+        self.parts.iter().find_map(|part| part.loader.review_url())
+    }
+
+    fn pull_request(&self) -> Option<&GithubPrLink> {
+        self.parts
+            .iter()
+            .find_map(|part| part.loader.pull_request())
+    }
+
+    fn config(&self) -> Option<&Config> {
+        self.parts.iter().find_map(|part| part.loader.config())
+    }
+
+    /// The first part that is still loading.
+    fn progress(&self) -> Option<Progress> {
+        self.parts.iter().find_map(|part| {
+            let mut progress = part.loader.progress()?;
+            progress.detail = Some(match progress.detail {
+                Some(detail) => format!("{}, {detail}", part.name),
+                None => part.name.clone(),
+            });
+            Some(progress)
+        })
+    }
+
+    fn files_header(&self) -> String {
+        let names: Vec<&str> = self.parts.iter().map(|part| part.name.as_str()).collect();
+        format!("Merged: {}", names.join(" + "))
+    }
+}
+
+/// Moves the snapshot below `prefix`. In-memory images keep their uris, those are by content.
+fn prefixed(prefix: &str, snapshot: &Snapshot) -> Snapshot {
+    Snapshot {
+        path: Path::new(prefix).join(&snapshot.path),
+        old: snapshot.old.clone(),
+        new: snapshot.new.clone(),
+        diff: snapshot.diff.clone(),
+        unchanged: snapshot.unchanged,
+        renamed_from: snapshot
+            .renamed_from
+            .as_ref()
+            .map(|path| Path::new(prefix).join(path)),
+    }
+}
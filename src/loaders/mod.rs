@@ -1,30 +1,178 @@
+use crate::config::Config;
+use crate::github::model::GithubPrLink;
+use crate::loaders::pinned_loader::Baseline;
 use crate::snapshot::Snapshot;
 use crate::state::AppStateRef;
 use eframe::egui;
+use kitdiff_core::test_logs::FailedSnapshot;
 use octocrab::Octocrab;
-use std::path::PathBuf;
+use std::cmp::Ordering;
+use std::ffi::OsStr;
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
 use std::task::Poll;
 
+pub use kitdiff_core::CancelFlag;
+
 pub mod archive_loader;
+pub mod circleci_loader;
+pub mod commit_snapshots;
+pub mod compare_loader;
+pub mod download;
+pub mod failed_tests;
 pub mod gh_archive_loader;
+pub mod jenkins_loader;
+pub mod merged_loader;
+pub mod pinned_loader;
+pub mod pr_history;
 pub mod pr_loader;
+pub mod registry;
+pub mod rename_loader;
+pub mod update_workflow;
 
 pub trait LoadSnapshots {
     fn update(&mut self, ctx: &egui::Context);
 
-    fn refresh(&mut self, client: Octocrab);
+    /// Start loading again. `cancel` replaces the flag the loader was created with.
+    fn refresh(&mut self, client: Octocrab, cancel: CancelFlag);
 
     fn snapshots(&self) -> &[Snapshot];
 
+    /// Replaces the baseline of a [`PinnedLoader`], other loaders return false.
+    ///
+    /// [`PinnedLoader`]: pinned_loader::PinnedLoader
+    #[expect(unused_variables)]
+    fn set_baseline(&mut self, baseline: Option<Arc<Baseline>>) -> bool {
+        false
+    }
+
     /// State is separate so that snapshots can be streamed in
     fn state(&self) -> Poll<Result<(), &anyhow::Error>>;
 
+    /// What the loader is doing while [`Self::state`] is pending.
+    fn progress(&self) -> Option<Progress> {
+        None
+    }
+
     #[expect(unused_variables)]
     fn extra_ui(&self, ui: &mut egui::Ui, state: &AppStateRef<'_>) {}
 
+    /// Extra ui for the active snapshot, shown below the view options.
+    #[expect(unused_variables)]
+    fn snapshot_ui(&self, ui: &mut egui::Ui, state: &AppStateRef<'_>, snapshot: &Snapshot) {}
+
+    /// The commit that last changed the snapshot, for sources that know about commits.
+    ///
+    /// May start looking it up in the background and return [`Poll::Pending`] until it's found.
+    #[expect(unused_variables)]
+    fn blame(&self, snapshot: &Snapshot) -> Poll<Option<&BlameInfo>> {
+        Poll::Ready(None)
+    }
+
+    /// Whether the snapshot at `path` made a snapshot test fail in CI, for sources that come
+    /// from a workflow run.
+    #[expect(unused_variables)]
+    fn failed_snapshot(&self, path: &Path) -> Option<&FailedSnapshot> {
+        None
+    }
+
+    /// Where to leave review comments, e.g. the files tab of a pull request.
+    fn review_url(&self) -> Option<String> {
+        None
+    }
+
+    /// The pull request the snapshots belong to, e.g. to post a review summary on.
+    fn pull_request(&self) -> Option<&GithubPrLink> {
+        None
+    }
+
+    /// Config that came with the source, e.g. a `kitdiff.toml` in the repository.
+    fn config(&self) -> Option<&Config> {
+        None
+    }
+
     fn files_header(&self) -> String;
 }
 
+/// See [`LoadSnapshots::progress`].
+#[derive(Debug, Clone)]
+pub struct Progress {
+    pub phase: Phase,
+
+    /// `None` if we can't tell how far along we are.
+    pub fraction: Option<f32>,
+
+    /// E.g. the downloaded size, shown after the phase.
+    pub detail: Option<String>,
+}
+
+impl Progress {
+    pub fn new(phase: Phase) -> Self {
+        Self {
+            phase,
+            fraction: None,
+            detail: None,
+        }
+    }
+
+    #[must_use]
+    pub fn with_fraction(mut self, fraction: Option<f32>) -> Self {
+        self.fraction = fraction;
+        self
+    }
+
+    #[must_use]
+    pub fn with_detail(mut self, detail: impl Into<String>) -> Self {
+        self.detail = Some(detail.into());
+        self
+    }
+}
+
+impl std::fmt::Display for Progress {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}", self.phase)?;
+        if let Some(fraction) = self.fraction {
+            write!(f, " {:.0}%", fraction * 100.0)?;
+        }
+        if let Some(detail) = &self.detail {
+            write!(f, " ({detail})")?;
+        }
+        Ok(())
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Phase {
+    Downloading,
+    Extracting,
+
+    /// Looking for snapshots, e.g. walking a directory or a git diff.
+    Discovering,
+    Diffing,
+}
+
+impl std::fmt::Display for Phase {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str(match self {
+            Self::Downloading => "Downloading",
+            Self::Extracting => "Extracting",
+            Self::Discovering => "Discovering",
+            Self::Diffing => "Diffing",
+        })
+    }
+}
+
+/// The commit that last changed a snapshot.
+#[derive(Debug, Clone)]
+pub struct BlameInfo {
+    pub sha: String,
+    pub summary: String,
+    pub author: String,
+
+    /// Link to the commit on GitHub, if we know the repository.
+    pub url: Option<String>,
+}
+
 pub type SnapshotLoader = Box<dyn LoadSnapshots + Send + Sync>;
 
 #[derive(Debug, Clone)]
@@ -49,8 +197,18 @@ impl DataReference {
     pub async fn into_bytes(self) -> anyhow::Result<bytes::Bytes> {
         match self {
             Self::Url(url) => {
-                let resp = reqwest::get(&url).await?;
+                #[cfg(target_arch = "wasm32")]
+                let cache_key = crate::web_loaders::opfs_cache::url_key(&url);
+                #[cfg(target_arch = "wasm32")]
+                if let Some(bytes) = crate::web_loaders::opfs_cache::get(&cache_key).await {
+                    return Ok(bytes);
+                }
+
+                let resp = crate::http::client().get(&url).send().await?;
                 let bytes = resp.bytes().await?;
+
+                #[cfg(target_arch = "wasm32")]
+                crate::web_loaders::opfs_cache::put(&cache_key, &url, &bytes).await;
                 Ok(bytes)
             }
             Self::Data(data, _) => Ok(data),
@@ -69,18 +227,68 @@ impl DataReference {
 
 /// Sort the snapshots. It'll sort them so folders come first and then files.
 pub fn sort_snapshots(snapshots: &mut [Snapshot]) {
-    snapshots.sort_by_key(|s| {
-        let parent = s
-            .path
-            .parent()
-            .map(|p| p.to_string_lossy().to_lowercase())
-            .unwrap_or_default();
-        let depth = s.path.components().count();
-        let name = s
-            .path
-            .file_name()
-            .map(|n| n.to_string_lossy().to_lowercase())
+    snapshots.sort_by_cached_key(sort_key);
+}
+
+/// Add a batch of streamed in snapshots, keeping `snapshots` sorted as per [`sort_snapshots`].
+///
+/// Re-sorting everything, or inserting the snapshots one by one, gets slow for sources with
+/// thousands of files. The batch is sorted on its own and merged in with a single pass instead.
+pub fn extend_sorted(snapshots: &mut Vec<Snapshot>, batch: impl IntoIterator<Item = Snapshot>) {
+    let mut batch: Vec<Snapshot> = batch.into_iter().collect();
+    if batch.is_empty() {
+        return;
+    }
+    batch.sort_by_cached_key(sort_key);
+
+    let existing = std::mem::take(snapshots);
+    snapshots.reserve(existing.len() + batch.len());
+    let mut existing = existing.into_iter().peekable();
+    for snapshot in batch {
+        // New snapshots go after the ones that sort the same.
+        while let Some(old) = existing.next_if(|old| compare(old, &snapshot).is_le()) {
+            snapshots.push(old);
+        }
+        snapshots.push(snapshot);
+    }
+    snapshots.extend(existing);
+}
+
+fn sort_key(snapshot: &Snapshot) -> (String, usize, String) {
+    let parent = snapshot
+        .path
+        .parent()
+        .map(|p| p.to_string_lossy().to_lowercase())
+        .unwrap_or_default();
+    let depth = snapshot.path.components().count();
+    let name = snapshot
+        .path
+        .file_name()
+        .map(|n| n.to_string_lossy().to_lowercase())
+        .unwrap_or_default();
+    (parent, depth, name)
+}
+
+/// The order of [`sort_key`], without allocating the keys.
+fn compare(a: &Snapshot, b: &Snapshot) -> Ordering {
+    let lowercase = |part: Option<&OsStr>, other: Option<&OsStr>| {
+        let part = part.map(|part| part.to_string_lossy()).unwrap_or_default();
+        let other = other
+            .map(|other| other.to_string_lossy())
             .unwrap_or_default();
-        (parent, depth, name)
-    });
+        part.chars()
+            .flat_map(char::to_lowercase)
+            .cmp(other.chars().flat_map(char::to_lowercase))
+    };
+    lowercase(
+        a.path.parent().map(Path::as_os_str),
+        b.path.parent().map(Path::as_os_str),
+    )
+    .then_with(|| {
+        a.path
+            .components()
+            .count()
+            .cmp(&b.path.components().count())
+    })
+    .then_with(|| lowercase(a.path.file_name(), b.path.file_name()))
 }
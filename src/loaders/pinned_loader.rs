@@ -0,0 +1,211 @@
+use crate::config::Config;
+use crate::github::model::GithubPrLink;
+use crate::loaders::merged_loader::MergedLoader;
+use crate::loaders::{BlameInfo, CancelFlag, LoadSnapshots, Progress, SnapshotLoader};
+use crate::snapshot::{FileReference, Snapshot};
+use crate::state::AppStateRef;
+use eframe::egui::{Context, Ui};
+use kitdiff_core::test_logs::FailedSnapshot;
+use octocrab::Octocrab;
+use std::collections::HashMap;
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+use std::task::Poll;
+
+/// Which side of the loaded snapshots becomes the baseline.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum BaselineSide {
+    /// Keep comparing against what the old side shows right now.
+    Old,
+
+    /// Compare future loads against what the new side shows right now, e.g. the output of a
+    /// specific CI run.
+    New,
+}
+
+/// Images captured from a loaded source, used as the old side of every snapshot.
+#[derive(Debug)]
+pub struct Baseline {
+    /// Where the images came from, e.g. the files header of the source.
+    pub name: String,
+    images: HashMap<PathBuf, FileReference>,
+}
+
+impl Baseline {
+    /// Takes the images of `side`, copying anything that could change under us.
+    ///
+    /// Files on disk and in archives are read into memory. In-memory images have uris by their
+    /// content, a reload can't change them.
+    pub fn capture(name: String, snapshots: &[Snapshot], side: BaselineSide) -> Self {
+        let mut images = HashMap::new();
+        for snapshot in snapshots {
+            let file = match side {
+                BaselineSide::Old => &snapshot.old,
+                BaselineSide::New => &snapshot.new,
+            };
+            let Some(file) = file else {
+                continue;
+            };
+            let name = snapshot.path.to_string_lossy();
+            let pinned = match file {
+                FileReference::Path(path) => match std::fs::read(path) {
+                    Ok(bytes) => FileReference::from_bytes(&name, bytes.into()),
+                    Err(err) => {
+                        tracing::warn!("Failed to pin {}: {err}", path.display());
+                        continue;
+                    }
+                },
+                FileReference::Archive(entry) => match entry.read() {
+                    Ok(bytes) => FileReference::from_bytes(&name, bytes.into()),
+                    Err(err) => {
+                        tracing::warn!("Failed to pin {}: {err:#}", snapshot.path.display());
+                        continue;
+                    }
+                },
+                // Remote images are addressed by commit and in-memory ones by their content, they
+                // don't change.
+                FileReference::Source(_) => file.clone(),
+            };
+            images.insert(snapshot.path.clone(), pinned);
+        }
+        Self { name, images }
+    }
+
+    pub fn len(&self) -> usize {
+        self.images.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.images.is_empty()
+    }
+
+    /// The snapshot with the pinned old side. Snapshots the baseline doesn't know keep their own.
+    fn apply(&self, snapshot: &Snapshot) -> Snapshot {
+        match self.images.get(&snapshot.path) {
+            Some(old) => Snapshot {
+                path: snapshot.path.clone(),
+                old: Some(old.clone()),
+                new: snapshot.new.clone(),
+                // A diff file shipped with the source compares against the wrong image.
+                diff: None,
+                unchanged: false,
+                renamed_from: snapshot.renamed_from.clone(),
+            },
+            None => snapshot.clone(),
+        }
+    }
+}
+
+/// Pins the snapshots of an already running loader, so they don't have to be loaded again.
+pub fn pin(loader: &mut SnapshotLoader, baseline: Option<Arc<Baseline>>) {
+    let Some(baseline) = baseline else {
+        // Without a baseline a pinned loader shows the live snapshots as they are.
+        loader.set_baseline(None);
+        return;
+    };
+    if !loader.set_baseline(Some(Arc::clone(&baseline))) {
+        // The empty loader only holds the place until the live one is moved into the pinned one.
+        let live = std::mem::replace(loader, Box::new(MergedLoader::new(Vec::new())));
+        *loader = Box::new(PinnedLoader::new(live, baseline));
+    }
+}
+
+/// Shows the snapshots of `live` against a pinned [`Baseline`].
+pub struct PinnedLoader {
+    live: SnapshotLoader,
+
+    /// `None` once unpinned, until the source is loaded again.
+    baseline: Option<Arc<Baseline>>,
+    snapshots: Vec<Snapshot>,
+
+    /// Number of live snapshots that are in `snapshots`.
+    merged: usize,
+}
+
+impl PinnedLoader {
+    pub fn new(live: SnapshotLoader, baseline: Arc<Baseline>) -> Self {
+        let mut loader = Self {
+            live,
+            baseline: Some(baseline),
+            snapshots: Vec::new(),
+            merged: 0,
+        };
+        loader.merge();
+        loader
+    }
+
+    fn merge(&mut self) {
+        let live = self.live.snapshots();
+        self.snapshots = match &self.baseline {
+            Some(baseline) => live.iter().map(|s| baseline.apply(s)).collect(),
+            None => live.to_vec(),
+        };
+        self.merged = live.len();
+    }
+}
+
+impl LoadSnapshots for PinnedLoader {
+    fn update(&mut self, ctx: &Context) {
+        self.live.update(ctx);
+
+        if self.live.snapshots().len() != self.merged {
+            self.merge();
+        }
+    }
+
+    fn set_baseline(&mut self, baseline: Option<Arc<Baseline>>) -> bool {
+        self.baseline = baseline;
+        self.merge();
+        true
+    }
+
+    fn refresh(&mut self, client: Octocrab, cancel: CancelFlag) {
+        self.live.refresh(client, cancel);
+        self.snapshots.clear();
+        self.merged = 0;
+    }
+
+    fn snapshots(&self) -> &[Snapshot] {
+        &self.snapshots
+    }
+
+    fn state(&self) -> Poll<Result<(), &anyhow::Error>> {
+        self.live.state()
+    }
+
+    fn progress(&self) -> Option<Progress> {
+        self.live.progress()
+    }
+
+    fn extra_ui(&self, ui: &mut Ui, state: &AppStateRef<'_>) {
+        self.live.extra_ui(ui, state);
+    }
+
+    fn snapshot_ui(&self, ui: &mut Ui, state: &AppStateRef<'_>, snapshot: &Snapshot) {
+        self.live.snapshot_ui(ui, state, snapshot);
+    }
+
+    fn blame(&self, snapshot: &Snapshot) -> Poll<Option<&BlameInfo>> {
+        self.live.blame(snapshot)
+    }
+
+    fn failed_snapshot(&self, path: &Path) -> Option<&FailedSnapshot> {
+        self.live.failed_snapshot(path)
+    }
+
+    fn review_url(&self) -> Option<String> {
+        self.live.review_url()
+    }
+
+    fn pull_request(&self) -> Option<&GithubPrLink> {
+        self.live.pull_request()
+    }
+
+    fn config(&self) -> Option<&Config> {
+        self.live.config()
+    }
+
+    fn files_header(&self) -> String {
+        self.live.files_header()
+    }
+}
@@ -0,0 +1,225 @@
+use crate::github::model::GithubRepoLink;
+use crate::github::octokit::RepoClient;
+use crate::github::pr::GithubPr;
+use crate::loaders::BlameInfo;
+use crate::loaders::pr_loader::resolve_url;
+use crate::snapshot::Snapshot;
+use eframe::egui::{Button, Context, Image, Slider, Ui};
+use egui_inbox::UiInbox;
+use futures::StreamExt as _;
+use octocrab::Octocrab;
+use std::collections::HashMap;
+use std::collections::hash_map::Entry;
+use std::path::{Path, PathBuf};
+use std::task::Poll;
+
+/// A snapshot's image at one of the PR's commits.
+#[derive(Debug, Clone)]
+pub struct HistoryEntry {
+    pub sha: String,
+    pub message: String,
+
+    /// `None` if the file doesn't exist at this commit.
+    pub uri: Option<String>,
+}
+
+enum HistoryCommand {
+    Fetch(PathBuf),
+    Fetched(PathBuf, Vec<HistoryEntry>),
+    FetchBlame(PathBuf),
+    FetchedBlame(PathBuf, Option<BlameInfo>),
+}
+
+/// Per-snapshot history across all commits of a PR, and the commit that last changed each
+/// snapshot. Both are fetched on demand.
+pub struct PrHistory {
+    client: Octocrab,
+    repo: GithubRepoLink,
+    logged_in: bool,
+    inbox: UiInbox<HistoryCommand>,
+    history: HashMap<PathBuf, Poll<Vec<HistoryEntry>>>,
+    blame: HashMap<PathBuf, Poll<Option<BlameInfo>>>,
+}
+
+impl PrHistory {
+    pub fn new(client: Octocrab, repo: GithubRepoLink, logged_in: bool) -> Self {
+        Self {
+            client,
+            repo,
+            logged_in,
+            inbox: UiInbox::new(),
+            history: HashMap::new(),
+            blame: HashMap::new(),
+        }
+    }
+
+    pub fn update(&mut self, ctx: &Context, pr: &GithubPr) {
+        for command in self.inbox.read(ctx) {
+            match command {
+                HistoryCommand::Fetch(path) => {
+                    // Wait for the commit list, the ui will ask again.
+                    let Some(commits) = pr.commits() else {
+                        continue;
+                    };
+                    let Entry::Vacant(entry) = self.history.entry(path.clone()) else {
+                        continue;
+                    };
+                    entry.insert(Poll::Pending);
+
+                    let repo_client = RepoClient::new(self.client.clone(), self.repo.clone());
+                    let logged_in = self.logged_in;
+                    self.inbox.spawn(move |tx| async move {
+                        let file_path = path.to_string_lossy().into_owned();
+                        let entries = futures::stream::iter(commits)
+                            .map(|(sha, message)| {
+                                let repo_client = &repo_client;
+                                let file_path = &file_path;
+                                async move {
+                                    let uri =
+                                        resolve_url(repo_client, &sha, file_path, logged_in).await;
+                                    HistoryEntry { sha, message, uri }
+                                }
+                            })
+                            .buffered(4)
+                            .collect()
+                            .await;
+                        tx.send(HistoryCommand::Fetched(path, entries)).ok();
+                    });
+                }
+                HistoryCommand::Fetched(path, entries) => {
+                    self.history.insert(path, Poll::Ready(entries));
+                }
+                HistoryCommand::FetchBlame(path) => {
+                    let Some((head_sha, _)) = pr.commits().and_then(|c| c.last().cloned()) else {
+                        continue;
+                    };
+                    let Entry::Vacant(entry) = self.blame.entry(path.clone()) else {
+                        continue;
+                    };
+                    entry.insert(Poll::Pending);
+
+                    let repo_client = RepoClient::new(self.client.clone(), self.repo.clone());
+                    self.inbox.spawn(move |tx| async move {
+                        let info = match fetch_blame(&repo_client, &head_sha, &path).await {
+                            Ok(info) => info,
+                            Err(err) => {
+                                tracing::warn!(
+                                    "Failed to find commit for {}: {err}",
+                                    path.display()
+                                );
+                                None
+                            }
+                        };
+                        tx.send(HistoryCommand::FetchedBlame(path, info)).ok();
+                    });
+                }
+                HistoryCommand::FetchedBlame(path, info) => {
+                    self.blame.insert(path, Poll::Ready(info));
+                }
+            }
+        }
+    }
+
+    pub fn blame(&self, snapshot: &Snapshot) -> Poll<Option<&BlameInfo>> {
+        match self.blame.get(&snapshot.path) {
+            Some(Poll::Ready(info)) => Poll::Ready(info.as_ref()),
+            Some(Poll::Pending) => Poll::Pending,
+            None => {
+                self.inbox
+                    .sender()
+                    .send(HistoryCommand::FetchBlame(snapshot.path.clone()))
+                    .ok();
+                Poll::Pending
+            }
+        }
+    }
+
+    /// Timeline with a slider to scrub through the snapshot's versions.
+    pub fn ui(&self, ui: &mut Ui, snapshot: &Snapshot) {
+        ui.group(|ui| {
+            ui.heading("History");
+
+            let entries = match self.history.get(&snapshot.path) {
+                None => {
+                    self.inbox
+                        .sender()
+                        .send(HistoryCommand::Fetch(snapshot.path.clone()))
+                        .ok();
+                    ui.spinner();
+                    return;
+                }
+                Some(Poll::Pending) => {
+                    ui.spinner();
+                    return;
+                }
+                Some(Poll::Ready(entries)) => entries,
+            };
+
+            let Some(last) = entries.len().checked_sub(1) else {
+                ui.label("No commits found.");
+                return;
+            };
+
+            let id = ui.id().with(("history", &snapshot.path));
+            let mut index = ui
+                .data(|d| d.get_temp::<usize>(id))
+                .unwrap_or(last)
+                .min(last);
+
+            ui.horizontal(|ui| {
+                if ui.add_enabled(index > 0, Button::new("⏴")).clicked() {
+                    index -= 1;
+                }
+                ui.add(Slider::new(&mut index, 0..=last).show_value(false));
+                if ui.add_enabled(index < last, Button::new("⏵")).clicked() {
+                    index += 1;
+                }
+            });
+            ui.data_mut(|d| d.insert_temp(id, index));
+
+            let entry = &entries[index];
+            ui.label(format!(
+                "{}/{} {} {}",
+                index + 1,
+                entries.len(),
+                entry.sha.get(..7).unwrap_or(&entry.sha),
+                entry.message
+            ));
+
+            if let Some(uri) = &entry.uri {
+                ui.add(Image::new(uri).max_width(ui.available_width()));
+            } else {
+                ui.weak("The file doesn't exist at this commit.");
+            }
+        });
+    }
+}
+
+/// The newest commit up to `head_sha` that changed `path`.
+async fn fetch_blame(
+    repo_client: &RepoClient,
+    head_sha: &str,
+    path: &Path,
+) -> octocrab::Result<Option<BlameInfo>> {
+    let page = repo_client
+        .repos()
+        .list_commits()
+        .sha(head_sha)
+        .path(path.to_string_lossy())
+        .per_page(1)
+        .send()
+        .await?;
+
+    Ok(page.items.into_iter().next().map(|commit| BlameInfo {
+        summary: commit
+            .commit
+            .message
+            .lines()
+            .next()
+            .unwrap_or_default()
+            .to_owned(),
+        author: commit.author.map(|author| author.login).unwrap_or_default(),
+        url: Some(commit.html_url),
+        sha: commit.sha,
+    }))
+}
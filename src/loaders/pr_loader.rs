@@ -1,7 +1,9 @@
 use crate::github::model::{GithubPrLink, GithubRepoLink};
 use crate::github::octokit::RepoClient;
 use crate::github::pr::{GithubPr, pr_ui};
-use crate::loaders::{LoadSnapshots, sort_snapshots};
+use crate::loaders::pr_history::PrHistory;
+use crate::loaders::{BlameInfo, CancelFlag, LoadSnapshots, Phase, Progress, extend_sorted};
+use crate::path_filter::PathFilter;
 use crate::snapshot::{FileReference, Snapshot};
 use crate::state::AppStateRef;
 use eframe::egui::{Context, Ui};
@@ -9,6 +11,7 @@ use egui_inbox::{UiInbox, UiInboxSender};
 use futures::{StreamExt as _, TryStreamExt as _};
 use octocrab::models::repos::DiffEntryStatus;
 use octocrab::{Octocrab, Result};
+use std::path::Path;
 use std::pin::pin;
 use std::task::Poll;
 
@@ -20,16 +23,34 @@ pub struct PrLoader {
     state: Poll<anyhow::Result<()>>,
     link: GithubPrLink,
     pr_info: GithubPr,
+    history: PrHistory,
     logged_in: bool,
+    filter: PathFilter,
 }
 
 impl PrLoader {
-    pub fn new(link: GithubPrLink, client: Octocrab, logged_in: bool) -> Self {
+    pub fn new(
+        link: GithubPrLink,
+        client: Octocrab,
+        logged_in: bool,
+        filter: PathFilter,
+        cancel: CancelFlag,
+    ) -> Self {
         let mut inbox = UiInbox::new();
         let repo_client = RepoClient::new(client.clone(), link.repo.clone());
 
-        inbox.spawn(|tx| async move {
-            let result = stream_files(repo_client, link.pr_number, tx.clone(), logged_in).await;
+        let pr_number = link.pr_number;
+        let file_filter = filter.clone();
+        inbox.spawn(move |tx| async move {
+            let result = stream_files(
+                repo_client,
+                pr_number,
+                tx.clone(),
+                logged_in,
+                &file_filter,
+                &cancel,
+            )
+            .await;
             match result {
                 Ok(()) => {
                     tx.send(None).ok();
@@ -44,9 +65,11 @@ impl PrLoader {
             snapshots: Vec::new(),
             inbox,
             state: Poll::Pending,
-            pr_info: GithubPr::new(link.clone(), client),
+            pr_info: GithubPr::new(link.clone(), client.clone()),
+            history: PrHistory::new(client, link.repo.clone(), logged_in),
             link,
             logged_in,
+            filter,
         }
     }
 }
@@ -56,6 +79,8 @@ async fn stream_files(
     pr_number: u64,
     sender: Sender,
     logged_in: bool,
+    filter: &PathFilter,
+    cancel: &CancelFlag,
 ) -> octocrab::Result<()> {
     let pr = repo_client.pulls().get(pr_number).await?;
 
@@ -64,7 +89,12 @@ async fn stream_files(
     let stream = file.into_stream(&repo_client);
 
     let results = stream
-        .try_filter_map(|file| async move { Ok(file.filename.ends_with(".png").then_some(file)) })
+        .try_filter_map(|file| async move {
+            Ok(
+                (file.filename.ends_with(".png") && filter.matches(Path::new(&file.filename)))
+                    .then_some(file),
+            )
+        })
         .map_ok(|file| {
             let repo_client = &repo_client;
             let pr = &pr;
@@ -92,6 +122,12 @@ async fn stream_files(
                     old: old_url.map(|url| FileReference::Source(url.into())),
                     new: new_url.map(|url| FileReference::Source(url.into())),
                     diff: None,
+                    unchanged: false,
+                    renamed_from: file
+                        .previous_filename
+                        .clone()
+                        .filter(|_| file.status == DiffEntryStatus::Renamed)
+                        .map(Into::into),
                 })
             }
         })
@@ -99,6 +135,9 @@ async fn stream_files(
     let mut results = pin!(results);
 
     while let Some(snapshot) = results.next().await.transpose()? {
+        if cancel.is_cancelled() {
+            break;
+        }
         sender.send(Some(Ok(snapshot))).ok();
     }
 
@@ -108,7 +147,7 @@ async fn stream_files(
 /// When logged in, uses the GitHub contents API to get a signed download URL
 /// that works for private repos. Otherwise, falls back to the public
 /// media.githubusercontent.com URL to avoid burning API rate limit.
-async fn resolve_url(
+pub(crate) async fn resolve_url(
     repo_client: &RepoClient,
     commit_sha: &str,
     file_path: &str,
@@ -138,11 +177,11 @@ fn create_media_url(repo: &GithubRepoLink, commit_sha: &str, file_path: &str) ->
 
 impl LoadSnapshots for PrLoader {
     fn update(&mut self, ctx: &Context) {
+        let mut batch = Vec::new();
         for snapshot in self.inbox.read(ctx) {
             match snapshot {
                 Some(Ok(s)) => {
-                    self.snapshots.push(s);
-                    sort_snapshots(&mut self.snapshots);
+                    batch.push(s);
                 }
                 Some(Err(e)) => {
                     self.state = Poll::Ready(Err(e.into()));
@@ -152,11 +191,19 @@ impl LoadSnapshots for PrLoader {
                 }
             }
         }
+        extend_sorted(&mut self.snapshots, batch);
         self.pr_info.update(ctx);
+        self.history.update(ctx, &self.pr_info);
     }
 
-    fn refresh(&mut self, client: Octocrab) {
-        *self = Self::new(self.link.clone(), client, self.logged_in);
+    fn refresh(&mut self, client: Octocrab, cancel: CancelFlag) {
+        *self = Self::new(
+            self.link.clone(),
+            client,
+            self.logged_in,
+            self.filter.clone(),
+            cancel,
+        );
     }
 
     fn snapshots(&self) -> &[Snapshot] {
@@ -171,10 +218,32 @@ impl LoadSnapshots for PrLoader {
         }
     }
 
+    fn progress(&self) -> Option<Progress> {
+        self.state().is_pending().then(|| {
+            Progress::new(Phase::Discovering).with_detail(format!("{} found", self.snapshots.len()))
+        })
+    }
+
     fn extra_ui(&self, ui: &mut Ui, state: &AppStateRef<'_>) {
         pr_ui(ui, state, &self.pr_info);
     }
 
+    fn snapshot_ui(&self, ui: &mut Ui, _state: &AppStateRef<'_>, snapshot: &Snapshot) {
+        self.history.ui(ui, snapshot);
+    }
+
+    fn blame(&self, snapshot: &Snapshot) -> Poll<Option<&BlameInfo>> {
+        self.history.blame(snapshot)
+    }
+
+    fn review_url(&self) -> Option<String> {
+        Some(format!("https://github.com/{}/files", self.link))
+    }
+
+    fn pull_request(&self) -> Option<&GithubPrLink> {
+        Some(&self.link)
+    }
+
     fn files_header(&self) -> String {
         format!("{}", self.link)
     }
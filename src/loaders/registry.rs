@@ -0,0 +1,95 @@
+//! Sources added from outside of kitdiff, e.g. an internal artifact store.
+//!
+//! Register a [`SourceProvider`] before creating the [`crate::app::App`]. Urls typed on the home
+//! page or passed on the command line are offered to the registered providers first, and only
+//! fall back to the built-in sources if none of them accepts the url.
+
+use crate::SnapshotLoader;
+use crate::loaders::CancelFlag;
+use crate::path_filter::PathFilter;
+use eframe::egui::Context;
+use eframe::egui::mutex::RwLock;
+use octocrab::Octocrab;
+use std::fmt;
+use std::sync::{Arc, LazyLock};
+
+static PROVIDERS: LazyLock<RwLock<Vec<Arc<dyn SourceProvider>>>> =
+    LazyLock::new(|| RwLock::new(Vec::new()));
+
+/// Creates loaders for the urls it recognizes.
+pub trait SourceProvider: Send + Sync {
+    /// Shown in logs, e.g. `artifact-store`.
+    fn name(&self) -> &str;
+
+    /// Whether this provider should load `url`.
+    fn matches(&self, url: &str) -> bool;
+
+    /// Start loading `url`. Only called with urls [`Self::matches`] accepted.
+    fn load(&self, url: &str, load: LoadContext<'_>) -> SnapshotLoader;
+}
+
+/// What a [`SourceProvider`] gets to create its loader.
+pub struct LoadContext<'a> {
+    pub egui_ctx: &'a Context,
+
+    /// Authenticated if the user is logged in to GitHub.
+    pub client: Octocrab,
+    pub logged_in: bool,
+
+    /// The path filter from the settings. Loaders should skip files it doesn't match.
+    pub filter: PathFilter,
+
+    /// Set when the user cancels the load, or the viewer is closed.
+    pub cancel: CancelFlag,
+}
+
+/// Adds a provider. Later registrations take precedence over earlier ones.
+pub fn register(provider: impl SourceProvider + 'static) {
+    tracing::debug!("Registered source provider {}", provider.name());
+    PROVIDERS.write().insert(0, Arc::new(provider));
+}
+
+/// The registered provider for `url`, if any.
+pub(crate) fn find(url: &str) -> Option<RegisteredSource> {
+    let provider = PROVIDERS
+        .read()
+        .iter()
+        .find(|provider| provider.matches(url))
+        .cloned()?;
+    Some(RegisteredSource {
+        provider,
+        url: url.to_owned(),
+    })
+}
+
+/// A url claimed by a registered [`SourceProvider`].
+#[derive(Clone)]
+pub struct RegisteredSource {
+    provider: Arc<dyn SourceProvider>,
+    url: String,
+}
+
+impl RegisteredSource {
+    pub fn url(&self) -> &str {
+        &self.url
+    }
+
+    /// See [`SourceProvider::name`].
+    pub fn provider_name(&self) -> &str {
+        self.provider.name()
+    }
+
+    pub(crate) fn load(&self, load: LoadContext<'_>) -> SnapshotLoader {
+        tracing::debug!("Loading {} with {}", self.url, self.provider.name());
+        self.provider.load(&self.url, load)
+    }
+}
+
+impl fmt::Debug for RegisteredSource {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("RegisteredSource")
+            .field("provider", &self.provider.name())
+            .field("url", &self.url)
+            .finish()
+    }
+}
@@ -0,0 +1,247 @@
+use crate::config::Config;
+use crate::github::model::GithubPrLink;
+use crate::loaders::{
+    BlameInfo, CancelFlag, LoadSnapshots, Phase, Progress, SnapshotLoader, sort_snapshots,
+};
+use crate::snapshot::Snapshot;
+use crate::state::AppStateRef;
+use eframe::egui::load::{Bytes, BytesPoll};
+use eframe::egui::{Context, Ui};
+use egui_inbox::UiInbox;
+use kitdiff_core::phash;
+use kitdiff_core::test_logs::FailedSnapshot;
+use octocrab::Octocrab;
+use std::collections::{HashMap, HashSet};
+use std::path::Path;
+use std::task::Poll;
+
+enum Detection {
+    /// Waiting for the inner loader to find all snapshots.
+    Waiting,
+
+    /// Loading the images of added and deleted snapshots, by uri.
+    Loading(Vec<String>),
+    Hashing(UiInbox<Option<Vec<Snapshot>>>),
+    Done,
+}
+
+/// Pairs snapshots that were deleted at one path and added at another with a similar image into a
+/// single renamed snapshot, so a moved test shows its pixel changes instead of two unrelated
+/// entries.
+///
+/// Images are compared by [`phash::dhash`], once the inner loader found every snapshot.
+pub struct RenameLoader {
+    inner: SnapshotLoader,
+    detection: Detection,
+    cancel: CancelFlag,
+
+    /// The inner snapshots with the renames paired up, once any were found.
+    paired: Option<Vec<Snapshot>>,
+}
+
+impl RenameLoader {
+    pub fn new(inner: SnapshotLoader, cancel: CancelFlag) -> Self {
+        Self {
+            inner,
+            detection: Detection::Waiting,
+            cancel,
+            paired: None,
+        }
+    }
+
+    fn detect(&mut self, ctx: &Context) {
+        match &mut self.detection {
+            Detection::Waiting => {
+                if !self.inner.state().is_ready() {
+                    return;
+                }
+                let snapshots = self.inner.snapshots();
+                let has_added = snapshots.iter().any(Snapshot::added);
+                let has_deleted = snapshots.iter().any(Snapshot::deleted);
+                if self.cancel.is_cancelled() || !has_added || !has_deleted {
+                    self.detection = Detection::Done;
+                    return;
+                }
+                let uris = snapshots
+                    .iter()
+                    .filter_map(|snapshot| {
+                        if snapshot.added() {
+                            snapshot.new_uri()
+                        } else if snapshot.deleted() {
+                            snapshot.old_uri()
+                        } else {
+                            None
+                        }
+                    })
+                    .collect();
+                self.detection = Detection::Loading(uris);
+            }
+            Detection::Loading(uris) => {
+                // Goes through the egui loaders, which know how to authenticate with GitHub and
+                // cache the images for when they are shown.
+                let mut images = HashMap::new();
+                for uri in uris.iter() {
+                    match ctx.try_load_bytes(uri) {
+                        Ok(BytesPoll::Ready { bytes, .. }) => {
+                            images.insert(uri.clone(), bytes);
+                        }
+                        Ok(BytesPoll::Pending { .. }) => return,
+                        Err(err) => tracing::warn!("Can't check {uri} for renames: {err}"),
+                    }
+                }
+                let snapshots = self.inner.snapshots().to_vec();
+                let mut inbox = UiInbox::new();
+                inbox.spawn(|tx| async move {
+                    tx.send(pair_renames(snapshots, &images)).ok();
+                });
+                self.detection = Detection::Hashing(inbox);
+            }
+            Detection::Hashing(inbox) => {
+                if let Some(paired) = inbox.read(ctx).last() {
+                    self.paired = paired;
+                    self.detection = Detection::Done;
+                }
+            }
+            Detection::Done => {}
+        }
+    }
+}
+
+/// The snapshots with similar deleted and added images merged, `None` if there are no renames.
+fn pair_renames(
+    snapshots: Vec<Snapshot>,
+    images: &HashMap<String, Bytes>,
+) -> Option<Vec<Snapshot>> {
+    let hash = |uri: Option<String>| {
+        let bytes = images.get(&uri?)?;
+        phash::dhash_bytes(bytes)
+            .inspect_err(|err| tracing::warn!("Can't check for renames: {err}"))
+            .ok()
+    };
+    let deleted: Vec<(usize, u64)> = snapshots
+        .iter()
+        .enumerate()
+        .filter(|(_, snapshot)| snapshot.deleted())
+        .filter_map(|(index, snapshot)| Some((index, hash(snapshot.old_uri())?)))
+        .collect();
+    let added: Vec<(usize, u64)> = snapshots
+        .iter()
+        .enumerate()
+        .filter(|(_, snapshot)| snapshot.added())
+        .filter_map(|(index, snapshot)| Some((index, hash(snapshot.new_uri())?)))
+        .collect();
+
+    let mut candidates = Vec::new();
+    for (deleted_index, deleted_hash) in &deleted {
+        for (added_index, added_hash) in &added {
+            let distance = phash::distance(*deleted_hash, *added_hash);
+            if distance <= phash::SIMILAR_DISTANCE {
+                candidates.push((distance, *deleted_index, *added_index));
+            }
+        }
+    }
+    if candidates.is_empty() {
+        return None;
+    }
+
+    // Closest pairs first, every snapshot is used once.
+    candidates.sort_unstable();
+    let mut used = HashSet::new();
+    let mut renamed_from = HashMap::new();
+    for (_, deleted_index, added_index) in candidates {
+        if !used.contains(&deleted_index) && !used.contains(&added_index) {
+            used.insert(deleted_index);
+            used.insert(added_index);
+            renamed_from.insert(added_index, deleted_index);
+        }
+    }
+
+    let mut paired: Vec<Snapshot> = snapshots
+        .iter()
+        .enumerate()
+        .filter_map(|(index, snapshot)| {
+            if let Some(old_index) = renamed_from.get(&index) {
+                let old = &snapshots[*old_index];
+                Some(Snapshot {
+                    path: snapshot.path.clone(),
+                    old: old.old.clone(),
+                    new: snapshot.new.clone(),
+                    diff: None,
+                    unchanged: false,
+                    renamed_from: Some(old.path.clone()),
+                })
+            } else {
+                (!used.contains(&index)).then(|| snapshot.clone())
+            }
+        })
+        .collect();
+    sort_snapshots(&mut paired);
+    tracing::info!("Paired {} renamed snapshots", renamed_from.len());
+    Some(paired)
+}
+
+impl LoadSnapshots for RenameLoader {
+    fn update(&mut self, ctx: &Context) {
+        self.inner.update(ctx);
+        self.detect(ctx);
+    }
+
+    fn refresh(&mut self, client: Octocrab, cancel: CancelFlag) {
+        self.inner.refresh(client, cancel.clone());
+        self.cancel = cancel;
+        self.detection = Detection::Waiting;
+        self.paired = None;
+    }
+
+    fn snapshots(&self) -> &[Snapshot] {
+        match &self.paired {
+            Some(paired) => paired,
+            None => self.inner.snapshots(),
+        }
+    }
+
+    fn state(&self) -> Poll<Result<(), &anyhow::Error>> {
+        self.inner.state()
+    }
+
+    fn progress(&self) -> Option<Progress> {
+        self.inner.progress().or_else(|| match self.detection {
+            Detection::Loading(_) | Detection::Hashing(_) => {
+                Some(Progress::new(Phase::Diffing).with_detail("looking for renamed snapshots"))
+            }
+            Detection::Waiting | Detection::Done => None,
+        })
+    }
+
+    fn extra_ui(&self, ui: &mut Ui, state: &AppStateRef<'_>) {
+        self.inner.extra_ui(ui, state);
+    }
+
+    fn snapshot_ui(&self, ui: &mut Ui, state: &AppStateRef<'_>, snapshot: &Snapshot) {
+        self.inner.snapshot_ui(ui, state, snapshot);
+    }
+
+    fn blame(&self, snapshot: &Snapshot) -> Poll<Option<&BlameInfo>> {
+        self.inner.blame(snapshot)
+    }
+
+    fn failed_snapshot(&self, path: &Path) -> Option<&FailedSnapshot> {
+        self.inner.failed_snapshot(path)
+    }
+
+    fn review_url(&self) -> Option<String> {
+        self.inner.review_url()
+    }
+
+    fn pull_request(&self) -> Option<&GithubPrLink> {
+        self.inner.pull_request()
+    }
+
+    fn config(&self) -> Option<&Config> {
+        self.inner.config()
+    }
+
+    fn files_header(&self) -> String {
+        self.inner.files_header()
+    }
+}
@@ -0,0 +1,350 @@
+use crate::github::model::GithubArtifactLink;
+use eframe::egui::{Context, Ui};
+use egui_inbox::UiInbox;
+use octocrab::Octocrab;
+use octocrab::models::RunId;
+use octocrab::models::workflows::Run;
+use serde_json::json;
+
+/// Used if the config doesn't name a workflow.
+pub const DEFAULT_WORKFLOW: &str = "update_kittest_snapshots.yml";
+
+/// Seconds between checks on the dispatched workflow run.
+const POLL_INTERVAL: f64 = 5.0;
+
+/// Triggers the workflow that commits updated snapshots to the PR branch, and follows the run it
+/// started until the commit lands.
+pub struct UpdateWorkflow {
+    client: Octocrab,
+    artifact: GithubArtifactLink,
+    state: Option<WorkflowState>,
+    inbox: UiInbox<Event>,
+    next_poll: f64,
+    polling: bool,
+}
+
+struct Dispatch {
+    workflow: String,
+    git_ref: String,
+}
+
+enum WorkflowState {
+    Dispatching,
+
+    /// Dispatched, waiting for the run to show up. GitHub doesn't tell us which run a dispatch
+    /// started, so we look for one that wasn't there before.
+    Dispatched {
+        dispatch: Dispatch,
+        known_runs: Vec<RunId>,
+    },
+    Running {
+        dispatch: Dispatch,
+        run: RunInfo,
+    },
+    Completed {
+        run: RunInfo,
+        commit: Option<CommitLink>,
+    },
+    Error(anyhow::Error),
+}
+
+#[derive(Clone)]
+struct RunInfo {
+    id: RunId,
+    url: String,
+    status: String,
+    conclusion: Option<String>,
+    head_sha: String,
+}
+
+impl From<Run> for RunInfo {
+    fn from(run: Run) -> Self {
+        Self {
+            id: run.id,
+            url: run.html_url.to_string(),
+            status: run.status,
+            conclusion: run.conclusion,
+            head_sha: run.head_sha,
+        }
+    }
+}
+
+struct CommitLink {
+    sha: String,
+    url: String,
+}
+
+enum Event {
+    State(WorkflowState),
+
+    /// A poll finished, with a new state if anything changed.
+    Polled(anyhow::Result<Option<WorkflowState>>),
+}
+
+impl UpdateWorkflow {
+    pub fn new(client: Octocrab, artifact: GithubArtifactLink) -> Self {
+        Self {
+            client,
+            artifact,
+            state: None,
+            inbox: UiInbox::new(),
+            next_poll: 0.0,
+            polling: false,
+        }
+    }
+
+    pub fn update(&mut self, ctx: &Context) {
+        for event in self.inbox.read(ctx) {
+            match event {
+                Event::State(state) => self.state = Some(state),
+                Event::Polled(result) => {
+                    self.polling = false;
+                    match result {
+                        Ok(Some(state)) => self.state = Some(state),
+                        Ok(None) => {}
+                        Err(err) => self.state = Some(WorkflowState::Error(err)),
+                    }
+                }
+            }
+        }
+
+        let now = ctx.input(|i| i.time);
+        let poll = match &self.state {
+            Some(WorkflowState::Dispatched {
+                dispatch,
+                known_runs,
+            }) => Some((dispatch, None, known_runs.clone())),
+            Some(WorkflowState::Running { dispatch, run }) => {
+                Some((dispatch, Some(run.clone()), Vec::new()))
+            }
+            _ => None,
+        };
+        let Some((dispatch, run, known_runs)) = poll else {
+            return;
+        };
+
+        if self.polling {
+            return;
+        }
+        if now < self.next_poll {
+            ctx.request_repaint_after_secs((self.next_poll - now) as f32);
+            return;
+        }
+        self.next_poll = now + POLL_INTERVAL;
+        self.polling = true;
+
+        let client = self.client.clone();
+        let artifact = self.artifact.clone();
+        let workflow = dispatch.workflow.clone();
+        let git_ref = dispatch.git_ref.clone();
+        self.inbox.spawn(move |tx| async move {
+            let dispatch = Dispatch { workflow, git_ref };
+            let result = match run {
+                None => find_run(&client, &artifact, dispatch, &known_runs).await,
+                Some(run) => check_run(&client, &artifact, dispatch, run.id).await,
+            };
+            tx.send(Event::Polled(result)).ok();
+        });
+    }
+
+    fn in_progress(&self) -> bool {
+        matches!(
+            self.state,
+            Some(
+                WorkflowState::Dispatching
+                    | WorkflowState::Dispatched { .. }
+                    | WorkflowState::Running { .. }
+            )
+        )
+    }
+
+    pub fn ui(&self, ui: &mut Ui, workflow: &str) {
+        let Some((git_ref, run_id)) = self.artifact.branch_name.clone().zip(self.artifact.run_id)
+        else {
+            return;
+        };
+
+        let response = ui
+            .add_enabled(
+                !self.in_progress(),
+                eframe::egui::Button::new("Commit the updated snapshots"),
+            )
+            .on_hover_text(format!(
+                "This will run the {workflow} workflow, which creates a commit on the PR branch \
+                with the updated snapshots."
+            ));
+        if response.clicked() {
+            let client = self.client.clone();
+            let artifact = self.artifact.clone();
+            let dispatch = Dispatch {
+                workflow: workflow.to_owned(),
+                git_ref,
+            };
+            let sender = self.inbox.sender();
+            sender.send(Event::State(WorkflowState::Dispatching)).ok();
+            hello_egui_utils::spawn(async move {
+                let state = match dispatch_workflow(&client, &artifact, dispatch, run_id).await {
+                    Ok(state) => state,
+                    Err(err) => WorkflowState::Error(err),
+                };
+                sender.send(Event::State(state)).ok();
+            });
+        }
+
+        match &self.state {
+            Some(WorkflowState::Dispatching) => {
+                ui.horizontal(|ui| {
+                    ui.spinner();
+                    ui.label("Triggering workflow…");
+                });
+            }
+            Some(WorkflowState::Dispatched { dispatch, .. }) => {
+                ui.horizontal(|ui| {
+                    ui.spinner();
+                    ui.label("Waiting for the run to start…");
+                });
+                ui.hyperlink_to("View workflow", self.workflow_url(&dispatch.workflow));
+            }
+            Some(WorkflowState::Running { run, .. }) => {
+                ui.horizontal(|ui| {
+                    ui.spinner();
+                    ui.label(format!("Workflow {}", run.status.replace('_', " ")));
+                });
+                ui.hyperlink_to("View run", &run.url);
+            }
+            Some(WorkflowState::Completed { run, commit }) => {
+                if run.conclusion.as_deref() == Some("success") {
+                    if let Some(commit) = commit {
+                        ui.horizontal(|ui| {
+                            ui.label("Snapshots committed:");
+                            ui.hyperlink_to(
+                                commit.sha.get(..7).unwrap_or(&commit.sha),
+                                &commit.url,
+                            );
+                        });
+                    } else {
+                        ui.label("Workflow finished without a new commit.");
+                    }
+                } else {
+                    ui.colored_label(
+                        ui.visuals().error_fg_color,
+                        format!("Workflow {}", run.conclusion.as_deref().unwrap_or("failed")),
+                    );
+                }
+                ui.hyperlink_to("View run", &run.url);
+            }
+            Some(WorkflowState::Error(err)) => {
+                ui.colored_label(ui.visuals().error_fg_color, format!("Error: {err}"));
+            }
+            None => {}
+        }
+    }
+
+    fn workflow_url(&self, workflow: &str) -> String {
+        format!(
+            "https://github.com/{}/{}/actions/workflows/{workflow}",
+            self.artifact.repo.owner, self.artifact.repo.repo
+        )
+    }
+}
+
+async fn list_runs(
+    client: &Octocrab,
+    artifact: &GithubArtifactLink,
+    dispatch: &Dispatch,
+) -> anyhow::Result<Vec<Run>> {
+    Ok(client
+        .workflows(&artifact.repo.owner, &artifact.repo.repo)
+        .list_runs(&dispatch.workflow)
+        .branch(&dispatch.git_ref)
+        .event("workflow_dispatch")
+        .per_page(20)
+        .send()
+        .await?
+        .items)
+}
+
+async fn dispatch_workflow(
+    client: &Octocrab,
+    artifact: &GithubArtifactLink,
+    dispatch: Dispatch,
+    run_id: u64,
+) -> anyhow::Result<WorkflowState> {
+    let known_runs = list_runs(client, artifact, &dispatch)
+        .await?
+        .into_iter()
+        .map(|run| run.id)
+        .collect();
+
+    client
+        .actions()
+        .create_workflow_dispatch(
+            artifact.repo.owner.clone(),
+            artifact.repo.repo.clone(),
+            dispatch.workflow.clone(),
+            dispatch.git_ref.clone(),
+        )
+        .inputs(json!({
+            "run_id": run_id.to_string(),
+        }))
+        .send()
+        .await?;
+
+    Ok(WorkflowState::Dispatched {
+        dispatch,
+        known_runs,
+    })
+}
+
+async fn find_run(
+    client: &Octocrab,
+    artifact: &GithubArtifactLink,
+    dispatch: Dispatch,
+    known_runs: &[RunId],
+) -> anyhow::Result<Option<WorkflowState>> {
+    let run = list_runs(client, artifact, &dispatch)
+        .await?
+        .into_iter()
+        .find(|run| !known_runs.contains(&run.id));
+    Ok(run.map(|run| WorkflowState::Running {
+        dispatch,
+        run: run.into(),
+    }))
+}
+
+async fn check_run(
+    client: &Octocrab,
+    artifact: &GithubArtifactLink,
+    dispatch: Dispatch,
+    run_id: RunId,
+) -> anyhow::Result<Option<WorkflowState>> {
+    let run: RunInfo = client
+        .workflows(&artifact.repo.owner, &artifact.repo.repo)
+        .get(run_id)
+        .await?
+        .into();
+
+    if run.status != "completed" {
+        return Ok(Some(WorkflowState::Running { dispatch, run }));
+    }
+
+    // The workflow pushes to the branch, so a new head means the commit landed.
+    let head = client
+        .repos(&artifact.repo.owner, &artifact.repo.repo)
+        .list_commits()
+        .sha(dispatch.git_ref)
+        .per_page(1)
+        .send()
+        .await?
+        .items
+        .into_iter()
+        .next();
+    let commit = head
+        .filter(|commit| commit.sha != run.head_sha)
+        .map(|commit| CommitLink {
+            url: commit.html_url,
+            sha: commit.sha,
+        });
+
+    Ok(Some(WorkflowState::Completed { run, commit }))
+}
@@ -0,0 +1,193 @@
+//! Logging setup, and a copy of recent log messages for the in-app log panel.
+
+use eframe::egui::mutex::Mutex;
+use eframe::egui::{self, Color32, RichText, ScrollArea, TextEdit, Ui};
+use std::collections::VecDeque;
+use std::fmt::Write as _;
+use std::sync::LazyLock;
+use tracing::field::{Field, Visit};
+use tracing::{Event, Level, Subscriber};
+use tracing_subscriber::layer::{Context, SubscriberExt as _};
+use tracing_subscriber::registry::LookupSpan;
+use tracing_subscriber::util::SubscriberInitExt as _;
+use tracing_subscriber::{EnvFilter, Layer};
+
+/// Only the most recent messages are kept for the log panel.
+const MAX_ENTRIES: usize = 2000;
+
+static ENTRIES: LazyLock<Mutex<VecDeque<LogEntry>>> = LazyLock::new(|| Mutex::new(VecDeque::new()));
+
+#[derive(Clone)]
+struct LogEntry {
+    level: Level,
+    target: String,
+
+    /// The spans the event happened in, outermost first, e.g. `download:extract`.
+    spans: String,
+    message: String,
+}
+
+/// Log to stderr (on native) and to the log panel.
+///
+/// `RUST_LOG` overrides the default filter. With `verbose`, debug messages from kitdiff are
+/// included.
+pub fn init(verbose: bool) {
+    let default = if verbose {
+        "info,kitdiff=debug"
+    } else {
+        "info"
+    };
+    let filter = EnvFilter::try_from_default_env().unwrap_or_else(|_| EnvFilter::new(default));
+
+    let registry = tracing_subscriber::registry().with(filter).with(PanelLayer);
+
+    #[cfg(not(target_arch = "wasm32"))]
+    let registry = registry.with(tracing_subscriber::fmt::layer().with_writer(std::io::stderr));
+
+    if let Err(err) = registry.try_init() {
+        eprintln!("Failed to set up logging: {err}");
+    }
+}
+
+struct PanelLayer;
+
+impl<S> Layer<S> for PanelLayer
+where
+    S: Subscriber + for<'a> LookupSpan<'a>,
+{
+    fn on_event(&self, event: &Event<'_>, ctx: Context<'_, S>) {
+        let mut message = MessageVisitor::default();
+        event.record(&mut message);
+
+        let spans = ctx
+            .event_scope(event)
+            .map(|scope| {
+                scope
+                    .from_root()
+                    .map(|span| span.name())
+                    .collect::<Vec<_>>()
+                    .join(":")
+            })
+            .unwrap_or_default();
+
+        let mut entries = ENTRIES.lock();
+        entries.push_back(LogEntry {
+            level: *event.metadata().level(),
+            target: event.metadata().target().to_owned(),
+            spans,
+            message: message.0,
+        });
+        if entries.len() > MAX_ENTRIES {
+            entries.pop_front();
+        }
+    }
+}
+
+/// The `message` field followed by any other fields as `key=value`.
+#[derive(Default)]
+struct MessageVisitor(String);
+
+impl Visit for MessageVisitor {
+    fn record_debug(&mut self, field: &Field, value: &dyn std::fmt::Debug) {
+        if field.name() == "message" {
+            write!(self.0, "{value:?}").ok();
+        } else {
+            write!(self.0, " {}={value:?}", field.name()).ok();
+        }
+    }
+
+    fn record_str(&mut self, field: &Field, value: &str) {
+        if field.name() == "message" {
+            self.0.push_str(value);
+        } else {
+            write!(self.0, " {}={value}", field.name()).ok();
+        }
+    }
+}
+
+fn level_color(ui: &Ui, level: Level) -> Color32 {
+    match level {
+        Level::ERROR => ui.visuals().error_fg_color,
+        Level::WARN => ui.visuals().warn_fg_color,
+        Level::INFO => ui.visuals().text_color(),
+        Level::DEBUG | Level::TRACE => ui.visuals().weak_text_color(),
+    }
+}
+
+/// Recent log messages, with a level and text filter.
+pub fn log_ui(ui: &mut Ui) {
+    let id = ui.id().with("log_filter");
+    let (mut max_level, mut filter): (Level, String) = ui
+        .data(|d| d.get_temp(id))
+        .unwrap_or((Level::INFO, String::new()));
+
+    ui.horizontal(|ui| {
+        egui::ComboBox::from_id_salt("log_level")
+            .selected_text(max_level.as_str())
+            .show_ui(ui, |ui| {
+                for level in [
+                    Level::ERROR,
+                    Level::WARN,
+                    Level::INFO,
+                    Level::DEBUG,
+                    Level::TRACE,
+                ] {
+                    ui.selectable_value(&mut max_level, level, level.as_str());
+                }
+            });
+        TextEdit::singleline(&mut filter)
+            .hint_text("Filter")
+            .desired_width(200.0)
+            .show(ui);
+
+        if ui.button("Copy").clicked() {
+            let text = visible_entries(max_level, &filter)
+                .iter()
+                .map(|e| format!("{} {} {}: {}", e.level, e.target, e.spans, e.message))
+                .collect::<Vec<_>>()
+                .join("\n");
+            ui.ctx().copy_text(text);
+        }
+        if ui.button("Clear").clicked() {
+            ENTRIES.lock().clear();
+        }
+    });
+
+    let entries = visible_entries(max_level, &filter);
+    let row_height = ui.text_style_height(&egui::TextStyle::Monospace);
+    ScrollArea::vertical()
+        .auto_shrink(false)
+        .stick_to_bottom(true)
+        .show_rows(ui, row_height, entries.len(), |ui, rows| {
+            for entry in &entries[rows] {
+                let mut text = format!("{:5} {}", entry.level, entry.target);
+                if !entry.spans.is_empty() {
+                    text += &format!(" [{}]", entry.spans);
+                }
+                text += &format!(" {}", entry.message);
+                ui.label(
+                    RichText::new(text)
+                        .monospace()
+                        .color(level_color(ui, entry.level)),
+                );
+            }
+        });
+
+    ui.data_mut(|d| d.insert_temp(id, (max_level, filter)));
+}
+
+fn visible_entries(max_level: Level, filter: &str) -> Vec<LogEntry> {
+    let filter = filter.to_lowercase();
+    ENTRIES
+        .lock()
+        .iter()
+        .filter(|e| e.level <= max_level)
+        .filter(|e| {
+            filter.is_empty()
+                || e.message.to_lowercase().contains(&filter)
+                || e.target.contains(&filter)
+                || e.spans.contains(&filter)
+        })
+        .cloned()
+        .collect()
+}
@@ -1,35 +1,133 @@
 #[cfg(not(target_arch = "wasm32"))]
 mod cli;
+#[cfg(not(target_arch = "wasm32"))]
+mod diff_server;
 
 #[cfg(not(target_arch = "wasm32"))]
 use eframe::NativeOptions;
 use kitdiff::app::App;
 use kitdiff::config::Config;
+#[cfg(not(target_arch = "wasm32"))]
+use kitdiff::remote_control::{Access, DEFAULT_PORT};
 
 #[cfg(not(target_arch = "wasm32"))]
 fn main() -> eframe::Result<()> {
-    env_logger::init();
-
     let rt = tokio::runtime::Builder::new_multi_thread()
         .enable_all()
         .build()
         .expect("Failed to create Tokio runtime");
     let _guard = rt.enter();
 
-    use clap::Parser as _;
-    let mode = cli::Cli::parse();
+    let mode = cli::Cli::parse_args();
+    kitdiff::logging::init(mode.verbose);
+    if let Some(cli::EventStream::Json) = mode.event_stream {
+        kitdiff::events::enable();
+    }
+    if let Some(ca_bundle) = mode.ca_bundle.clone() {
+        kitdiff::http::set_ca_bundle(ca_bundle);
+    }
+
+    if matches!(mode.command, Some(cli::Commands::RegisterFileTypes)) {
+        if let Err(err) = kitdiff::file_association::register() {
+            tracing::error!("{err:#}");
+        }
+        return Ok(());
+    }
+
+    if matches!(mode.command, Some(cli::Commands::Baselines)) {
+        cli::list_baselines();
+        return Ok(());
+    }
+
+    if let Some(cli::Commands::Export {
+        source,
+        output,
+        profile,
+        min_changed_pixels,
+        min_changed_percent,
+    }) = &mode.command
+    {
+        let min_change = kitdiff_core::diff::MinChange {
+            pixels: *min_changed_pixels,
+            percent: *min_changed_percent,
+        };
+        if let Err(err) = cli::export(source, output, profile.as_deref(), min_change) {
+            tracing::error!("{err:#}");
+            #[expect(clippy::exit, reason = "scripts need to notice a failed export")]
+            std::process::exit(1);
+        }
+        return Ok(());
+    }
+
+    if let Some(cli::Commands::DiffServer { profile }) = &mode.command {
+        if let Err(err) = diff_server::run(profile.as_deref()) {
+            tracing::error!("{err:#}");
+            #[expect(clippy::exit, reason = "the test framework needs to notice")]
+            std::process::exit(1);
+        }
+        return Ok(());
+    }
+
+    if let Some(cli::Commands::Auth { action }) = &mode.command {
+        if let Err(err) = rt.block_on(cli::auth(action, mode.token.as_deref())) {
+            tracing::error!("{err:#}");
+            #[expect(clippy::exit, reason = "setup scripts need to notice a failed login")]
+            std::process::exit(1);
+        }
+        return Ok(());
+    }
+
+    let directory = if mode.command.is_none() && mode.path.is_none() {
+        match mode.default_directory() {
+            Ok(directory) => directory,
+            Err(err) => {
+                tracing::error!("{err:#}");
+                #[expect(clippy::exit, reason = "cargo needs to notice a failed subcommand")]
+                std::process::exit(1);
+            }
+        }
+    } else {
+        ".".into()
+    };
 
-    let source = mode
-        .command
-        .unwrap_or(cli::Commands::Files {
-            directory: Some(".".into()),
-        })
-        .to_source();
+    let token = mode.token;
+    let port = mode.remote_control.unwrap_or(DEFAULT_PORT);
+    let access = if mode.remote_control.is_some() {
+        Access::All
+    } else {
+        Access::Forward
+    };
+    let new_window = mode.new_window;
+    let source = match (mode.command, mode.path) {
+        (Some(command), _) => command.to_source(),
+        (None, Some(path)) => Some(kitdiff::DiffSource::from_path(&path)),
+        (None, None) => Some(kitdiff::DiffSource::Files(directory.clone())),
+    };
+
+    if !new_window {
+        match rt.block_on(kitdiff::remote_control::forward(port, source.as_ref())) {
+            Ok(()) => {
+                tracing::info!("Opened in the kitdiff that is already running");
+                return Ok(());
+            }
+            Err(err) => tracing::debug!("Not handed to another kitdiff: {err:#}"),
+        }
+    }
+    // The port is taken when another kitdiff is running.
+    let remote_control = (!new_window || access == Access::All).then_some((port, access));
+
+    let config = Config::discover(&directory).unwrap_or_default();
 
     eframe::run_native(
         "kitdiff",
         NativeOptions::default(),
-        Box::new(move |cc| Ok(Box::new(App::new(cc, source, Config::default())))),
+        Box::new(move |cc| {
+            Ok(Box::new(
+                App::new(cc, source, config)
+                    .with_github_token(token)
+                    .with_remote_control(remote_control),
+            ))
+        }),
     )
 }
 
@@ -59,6 +157,9 @@ fn main() {
     use wasm_bindgen::JsCast;
     use web_sys::HtmlCanvasElement;
 
+    kitdiff::logging::init(false);
+    kitdiff::pwa::init();
+
     let web_options = eframe::WebOptions::default();
     wasm_bindgen_futures::spawn_local(async {
         let document = web_sys::window().unwrap().document().unwrap();
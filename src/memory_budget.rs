@@ -0,0 +1,164 @@
+use crate::diff_image_loader::DiffOptions;
+use crate::diff_precompute::{DiffPrecompute, NEIGHBORHOOD};
+use crate::snapshot::Snapshot;
+use eframe::egui::Context;
+use std::collections::{HashMap, HashSet};
+use std::path::PathBuf;
+
+/// How often (in seconds) we sum up the loaders' memory usage. Summing takes a lock on every
+/// loader, so we don't want to do it every frame.
+const CHECK_INTERVAL: f64 = 1.0;
+
+/// Bytes held by egui's loaders, split up by kind.
+#[derive(Debug, Clone, Copy, Default)]
+pub struct MemoryUsage {
+    /// Raw file contents, including the in-memory images of archives.
+    pub bytes: usize,
+
+    /// Decoded images, diffs, thumbnails and tiles.
+    pub images: usize,
+
+    /// Textures uploaded to the GPU.
+    pub textures: usize,
+}
+
+impl MemoryUsage {
+    pub fn read(ctx: &Context) -> Self {
+        let loaders = ctx.loaders();
+        Self {
+            bytes: loaders.bytes.lock().iter().map(|l| l.byte_size()).sum(),
+            images: loaders.image.lock().iter().map(|l| l.byte_size()).sum(),
+            textures: loaders.texture.lock().iter().map(|l| l.byte_size()).sum(),
+        }
+    }
+
+    pub fn total(&self) -> usize {
+        self.bytes + self.images + self.textures
+    }
+}
+
+/// Keeps the memory held by loaded snapshots below [`crate::settings::Settings::memory_budget_mb`].
+///
+/// We remember when each snapshot was last viewed, and when we're over budget we forget the
+/// least recently viewed ones first. Snapshots near the active one are never evicted, so
+/// navigating back and forth stays fast.
+#[derive(Default)]
+pub struct MemoryBudget {
+    last_viewed: HashMap<PathBuf, f64>,
+    last_check: Option<f64>,
+    usage: MemoryUsage,
+}
+
+impl MemoryBudget {
+    /// Memory usage as of the last check.
+    pub fn usage(&self) -> MemoryUsage {
+        self.usage
+    }
+
+    /// `priority` are indices into `snapshots`, most important first.
+    pub fn update(
+        &mut self,
+        ctx: &Context,
+        snapshots: &[Snapshot],
+        priority: &[usize],
+        active: Option<&Snapshot>,
+        budget_bytes: usize,
+        options: DiffOptions,
+    ) {
+        let now = ctx.input(|i| i.time);
+        if let Some(active) = active {
+            self.last_viewed.insert(active.path.clone(), now);
+        }
+
+        if self
+            .last_check
+            .is_some_and(|last| now - last < CHECK_INTERVAL)
+        {
+            return;
+        }
+        self.last_check = Some(now);
+        self.usage = MemoryUsage::read(ctx);
+
+        if self.usage.total() <= budget_bytes {
+            return;
+        }
+
+        let protected: HashSet<usize> = priority
+            .iter()
+            .take(NEIGHBORHOOD * 2 + 1)
+            .copied()
+            .collect();
+        // Identical images share their uri, one may be on screen under another snapshot.
+        let protected_uris: HashSet<String> = protected
+            .iter()
+            .filter_map(|index| snapshots.get(*index))
+            .flat_map(|snapshot| uris(snapshot, options))
+            .flatten()
+            .collect();
+
+        // Snapshots that were only loaded for their diff stats are already unloaded by
+        // `DiffPrecompute`, so only the viewed ones need to be considered.
+        let mut candidates: Vec<(f64, &Snapshot)> = snapshots
+            .iter()
+            .enumerate()
+            .filter(|(index, _)| !protected.contains(index))
+            .filter_map(|(_, snapshot)| {
+                let last_viewed = self.last_viewed.get(&snapshot.path)?;
+                Some((*last_viewed, snapshot))
+            })
+            .collect();
+        candidates.sort_by(|a, b| a.0.total_cmp(&b.0));
+
+        let mut evicted = 0;
+        for (_, snapshot) in candidates {
+            if MemoryUsage::read(ctx).total() <= budget_bytes {
+                break;
+            }
+            evict(ctx, snapshot, options, &protected_uris);
+            self.last_viewed.remove(&snapshot.path);
+            evicted += 1;
+        }
+
+        if evicted > 0 {
+            self.usage = MemoryUsage::read(ctx);
+            tracing::debug!(
+                "Evicted {evicted} snapshots, now using {} bytes",
+                self.usage.total()
+            );
+        }
+    }
+}
+
+/// Everything the loaders may hold for this snapshot.
+fn uris(snapshot: &Snapshot, options: DiffOptions) -> [Option<String>; 6] {
+    [
+        snapshot.old_uri(),
+        snapshot.new_uri(),
+        snapshot.file_diff_uri(),
+        DiffPrecompute::diff_uri(snapshot, options),
+        snapshot.old_thumbnail_uri(),
+        snapshot.new_thumbnail_uri(),
+    ]
+}
+
+/// Forget everything the loaders hold for this snapshot, except the `protected` uris. It is
+/// loaded again when viewed.
+fn evict(ctx: &Context, snapshot: &Snapshot, options: DiffOptions, protected: &HashSet<String>) {
+    let loaders = ctx.loaders();
+    for uri in uris(snapshot, options)
+        .iter()
+        .flatten()
+        .filter(|uri| !protected.contains(*uri))
+    {
+        for loader in loaders.texture.lock().iter() {
+            loader.forget(uri);
+        }
+        for loader in loaders.image.lock().iter() {
+            loader.forget(uri);
+        }
+        // The bytes of in-memory images belong to the snapshot, forgetting them is a no-op.
+        for loader in loaders.bytes.lock().iter() {
+            loader.forget(uri);
+        }
+    }
+}
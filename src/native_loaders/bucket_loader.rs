@@ -0,0 +1,301 @@
+//! Snapshots uploaded to an S3 or Google Cloud Storage bucket by CI, as `.zip`/`.tar.gz`
+//! archives or as loose png files.
+//!
+//! Credentials come from the environment like for the cloud CLIs: `AWS_ACCESS_KEY_ID`,
+//! `AWS_REGION`, web identity or the instance metadata for S3, and
+//! `GOOGLE_APPLICATION_CREDENTIALS` or the `gcloud` application default credentials for GCS.
+
+use crate::loaders::{CancelFlag, LoadSnapshots, Phase, Progress, extend_sorted};
+use crate::path_filter::PathFilter;
+use crate::snapshot::Snapshot;
+use anyhow::{Context as _, Error, Result};
+use eframe::egui::Context;
+use egui_inbox::{UiInbox, UiInboxSender};
+use futures::{StreamExt as _, TryStreamExt as _};
+use kitdiff_core::archive::{Files, snapshot_path};
+use object_store::path::Path as ObjectPath;
+use object_store::{ObjectMeta, ObjectStore};
+use octocrab::Octocrab;
+use std::fmt;
+use std::path::{Path, PathBuf};
+use std::str::FromStr;
+use std::sync::Arc;
+use std::task::Poll;
+
+/// How many objects are downloaded at once.
+const CONCURRENT_DOWNLOADS: usize = 8;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum BucketService {
+    S3,
+    Gcs,
+}
+
+/// A prefix in a bucket, like `s3://bucket/runs/123` or `gs://bucket/runs/123`.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct BucketLink {
+    pub service: BucketService,
+    pub bucket: String,
+
+    /// Without leading or trailing slashes, empty for the whole bucket.
+    pub prefix: String,
+}
+
+impl FromStr for BucketLink {
+    type Err = Error;
+
+    fn from_str(url: &str) -> Result<Self> {
+        let (service, rest) = if let Some(rest) = url.strip_prefix("s3://") {
+            (BucketService::S3, rest)
+        } else if let Some(rest) = url.strip_prefix("gs://") {
+            (BucketService::Gcs, rest)
+        } else {
+            anyhow::bail!("Not an s3:// or gs:// url");
+        };
+        let (bucket, prefix) = rest.split_once('/').unwrap_or((rest, ""));
+        anyhow::ensure!(!bucket.is_empty(), "The url has no bucket name");
+        Ok(Self {
+            service,
+            bucket: bucket.to_owned(),
+            prefix: prefix.trim_matches('/').to_owned(),
+        })
+    }
+}
+
+impl fmt::Display for BucketLink {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let scheme = match self.service {
+            BucketService::S3 => "s3",
+            BucketService::Gcs => "gs",
+        };
+        write!(f, "{scheme}://{}", self.bucket)?;
+        if !self.prefix.is_empty() {
+            write!(f, "/{}", self.prefix)?;
+        }
+        Ok(())
+    }
+}
+
+impl BucketLink {
+    fn store(&self) -> Result<Arc<dyn ObjectStore>> {
+        let options = client_options()?;
+        Ok(match self.service {
+            BucketService::S3 => Arc::new(
+                object_store::aws::AmazonS3Builder::from_env()
+                    .with_bucket_name(&self.bucket)
+                    .with_client_options(options)
+                    .build()?,
+            ),
+            BucketService::Gcs => Arc::new(
+                object_store::gcp::GoogleCloudStorageBuilder::from_env()
+                    .with_bucket_name(&self.bucket)
+                    .with_client_options(options)
+                    .build()?,
+            ),
+        })
+    }
+}
+
+/// The certificates from `--ca-bundle`, which object_store's own http client needs too.
+fn client_options() -> Result<object_store::ClientOptions> {
+    let mut options = object_store::ClientOptions::new();
+    if let Some(path) = crate::http::ca_bundle() {
+        let pem = std::fs::read(path)
+            .with_context(|| format!("Failed to read the CA bundle {}", path.display()))?;
+        for certificate in object_store::Certificate::from_pem_bundle(&pem)? {
+            options = options.with_root_certificate(certificate);
+        }
+    }
+    Ok(options)
+}
+
+enum Event {
+    /// This many objects are going to be downloaded.
+    Listed(usize),
+    Downloaded,
+    Snapshots(Vec<Snapshot>),
+    Done(Result<()>),
+}
+
+pub struct BucketLoader {
+    link: BucketLink,
+    filter: PathFilter,
+    inbox: UiInbox<Event>,
+    snapshots: Vec<Snapshot>,
+    state: Poll<Result<()>>,
+
+    /// Downloaded and total objects, once the listing is done.
+    downloads: Option<(usize, usize)>,
+}
+
+impl BucketLoader {
+    pub fn new(link: BucketLink, filter: PathFilter, cancel: CancelFlag) -> Self {
+        let mut inbox = UiInbox::new();
+        {
+            let link = link.clone();
+            let filter = filter.clone();
+            inbox.spawn(|tx| async move {
+                let result = load(&link, &filter, &cancel, &tx).await;
+                tx.send(Event::Done(result)).ok();
+            });
+        }
+        Self {
+            link,
+            filter,
+            inbox,
+            snapshots: Vec::new(),
+            state: Poll::Pending,
+            downloads: None,
+        }
+    }
+}
+
+async fn load(
+    link: &BucketLink,
+    filter: &PathFilter,
+    cancel: &CancelFlag,
+    tx: &UiInboxSender<Event>,
+) -> Result<()> {
+    let store = link.store()?;
+    let prefix = (!link.prefix.is_empty()).then(|| ObjectPath::from(link.prefix.as_str()));
+    let objects: Vec<ObjectMeta> = store
+        .list(prefix.as_ref())
+        .try_collect()
+        .await
+        .with_context(|| format!("Failed to list {link}"))?;
+
+    let objects: Vec<(PathBuf, ObjectMeta)> = objects
+        .into_iter()
+        .filter_map(|object| {
+            let key = object.location.as_ref();
+            let relative = key
+                .strip_prefix(link.prefix.as_str())
+                .unwrap_or(key)
+                .trim_start_matches('/');
+            let wanted = is_archive(relative)
+                || (relative.ends_with(".png")
+                    && filter.matches(&snapshot_path(Path::new(relative))));
+            wanted.then(|| (PathBuf::from(relative), object))
+        })
+        .collect();
+    tracing::info!("Found {} archives and png files in {link}", objects.len());
+    tx.send(Event::Listed(objects.len())).ok();
+
+    let mut loose_files = Files::new();
+    let mut downloads = futures::stream::iter(objects)
+        .map(|(path, object)| {
+            let store = store.clone();
+            async move {
+                anyhow::ensure!(!cancel.is_cancelled(), "Cancelled");
+                let bytes = store
+                    .get(&object.location)
+                    .await?
+                    .bytes()
+                    .await
+                    .with_context(|| format!("Failed to download {}", object.location))?;
+                Ok((path, bytes))
+            }
+        })
+        .buffer_unordered(CONCURRENT_DOWNLOADS);
+
+    while let Some((path, bytes)) = downloads.try_next().await? {
+        tx.send(Event::Downloaded).ok();
+        if is_archive(&path.to_string_lossy()) {
+            let snapshots = archive_snapshots(path, bytes, filter, cancel).await?;
+            tx.send(Event::Snapshots(snapshots)).ok();
+        } else {
+            loose_files.insert(path, bytes.as_ref().into());
+        }
+    }
+
+    let snapshots = kitdiff_core::archive::snapshots_from_files(&loose_files)
+        .into_iter()
+        .filter(|snapshot| filter.matches(&snapshot.path))
+        .map(Snapshot::from)
+        .collect();
+    tx.send(Event::Snapshots(snapshots)).ok();
+    Ok(())
+}
+
+/// The snapshots of an archive in the bucket, with paths starting at the archive's path, so the
+/// snapshots of several archives don't collide.
+async fn archive_snapshots(
+    path: PathBuf,
+    bytes: bytes::Bytes,
+    filter: &PathFilter,
+    cancel: &CancelFlag,
+) -> Result<Vec<Snapshot>> {
+    let cancel = cancel.clone();
+    let snapshots = {
+        let path = path.clone();
+        tokio::task::spawn_blocking(move || {
+            kitdiff_core::archive::discover(&bytes, &cancel)
+                .with_context(|| format!("Failed to extract {}", path.display()))
+        })
+        .await??
+    };
+    Ok(snapshots
+        .into_iter()
+        .map(|mut snapshot| {
+            snapshot.path = path.join(&snapshot.path);
+            snapshot
+        })
+        .filter(|snapshot| filter.matches(&snapshot.path))
+        .map(Snapshot::from)
+        .collect())
+}
+
+fn is_archive(path: &str) -> bool {
+    [".zip", ".tar.gz", ".tgz"]
+        .iter()
+        .any(|extension| path.ends_with(extension))
+}
+
+impl LoadSnapshots for BucketLoader {
+    fn update(&mut self, ctx: &Context) {
+        for event in self.inbox.read(ctx) {
+            match event {
+                Event::Listed(total) => self.downloads = Some((0, total)),
+                Event::Downloaded => {
+                    if let Some((done, _)) = &mut self.downloads {
+                        *done += 1;
+                    }
+                }
+                Event::Snapshots(snapshots) => extend_sorted(&mut self.snapshots, snapshots),
+                Event::Done(result) => self.state = Poll::Ready(result),
+            }
+        }
+    }
+
+    fn refresh(&mut self, _client: Octocrab, cancel: CancelFlag) {
+        *self = Self::new(self.link.clone(), self.filter.clone(), cancel);
+    }
+
+    fn snapshots(&self) -> &[Snapshot] {
+        &self.snapshots
+    }
+
+    fn state(&self) -> Poll<Result<(), &Error>> {
+        match &self.state {
+            Poll::Ready(Ok(())) => Poll::Ready(Ok(())),
+            Poll::Ready(Err(err)) => Poll::Ready(Err(err)),
+            Poll::Pending => Poll::Pending,
+        }
+    }
+
+    fn progress(&self) -> Option<Progress> {
+        if self.state.is_ready() {
+            return None;
+        }
+        Some(match self.downloads {
+            None => Progress::new(Phase::Discovering).with_detail("listing objects"),
+            Some((done, total)) => Progress::new(Phase::Downloading)
+                .with_fraction((total > 0).then(|| done as f32 / total as f32))
+                .with_detail(format!("{done} of {total} objects")),
+        })
+    }
+
+    fn files_header(&self) -> String {
+        format!("Bucket: {}", self.link)
+    }
+}
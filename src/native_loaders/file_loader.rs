@@ -1,47 +1,55 @@
-use crate::loaders::LoadSnapshots;
-use crate::snapshot::{FileReference, Snapshot};
+use crate::config::Config;
+use crate::loaders::{CancelFlag, LoadSnapshots, Phase, Progress, extend_sorted};
+use crate::path_filter::PathFilter;
+use crate::snapshot::Snapshot;
 use anyhow::Error;
 use eframe::egui::Context;
 use egui_inbox::UiInbox;
-use ignore::WalkBuilder;
-use ignore::types::TypesBuilder;
 use octocrab::Octocrab;
-use std::path::{Path, PathBuf};
+use std::path::PathBuf;
 use std::task::Poll;
 
 pub struct FileLoader {
     base_path: PathBuf,
-    inbox: UiInbox<Option<Snapshot>>,
+    config: Option<Config>,
+    filter: PathFilter,
+    inbox: UiInbox<Option<Vec<Snapshot>>>,
     loading: bool,
     snapshots: Vec<Snapshot>,
 }
 
 impl FileLoader {
-    pub fn new(base_path: impl Into<PathBuf>) -> Self {
+    pub fn new(base_path: impl Into<PathBuf>, filter: PathFilter, cancel: CancelFlag) -> Self {
         let base_path = base_path.into();
 
         let (sender, inbox) = UiInbox::channel();
 
         {
             let base_path = base_path.clone();
+            let filter = filter.clone();
             std::thread::Builder::new()
                 .name(format!("File loader {}", base_path.display()))
                 .spawn(move || {
-                    let mut types_builder = TypesBuilder::new();
-                    types_builder
-                        .add("png", "*.png")
-                        .expect("Failed to add png type");
-                    types_builder.select("png");
-                    let types = types_builder.build().expect("Failed to build types");
-
-                    for entry in WalkBuilder::new(&base_path).types(types).build().flatten() {
-                        if entry.file_type().is_some_and(|ft| ft.is_file())
-                            && let Some(snapshot) = try_create_snapshot(entry.path(), &base_path)
-                            && sender.send(Some(snapshot)).is_err()
-                        {
-                            break;
+                    let (batches, receiver) = std::sync::mpsc::channel();
+                    std::thread::scope(|scope| {
+                        std::thread::Builder::new()
+                            .name(format!("File walker {}", base_path.display()))
+                            .spawn_scoped(scope, || {
+                                kitdiff_core::files::discover(
+                                    &base_path, &filter, &cancel, &batches,
+                                );
+                                drop(batches);
+                            })
+                            .expect("Failed to spawn file walker thread");
+
+                        for batch in receiver {
+                            let batch = batch.into_iter().map(Snapshot::from).collect();
+                            if sender.send(Some(batch)).is_err() {
+                                // The loader was dropped, stop walking.
+                                cancel.cancel();
+                            }
                         }
-                    }
+                    });
 
                     // Signal completion
                     sender.send(None).ok();
@@ -50,7 +58,9 @@ impl FileLoader {
         }
 
         Self {
+            config: Config::discover(&base_path),
             base_path,
+            filter,
             inbox,
             snapshots: Vec::new(),
             loading: true,
@@ -60,17 +70,18 @@ impl FileLoader {
 
 impl LoadSnapshots for FileLoader {
     fn update(&mut self, ctx: &Context) {
-        for snapshot in self.inbox.read(ctx) {
-            if let Some(snapshot) = snapshot {
-                self.snapshots.push(snapshot);
+        for batch in self.inbox.read(ctx) {
+            if let Some(batch) = batch {
+                // The parallel walk delivers files in no particular order.
+                extend_sorted(&mut self.snapshots, batch);
             } else {
                 self.loading = false;
             }
         }
     }
 
-    fn refresh(&mut self, _client: Octocrab) {
-        *self = Self::new(self.base_path.clone());
+    fn refresh(&mut self, _client: Octocrab, cancel: CancelFlag) {
+        *self = Self::new(self.base_path.clone(), self.filter.clone(), cancel);
     }
 
     fn snapshots(&self) -> &[Snapshot] {
@@ -85,54 +96,17 @@ impl LoadSnapshots for FileLoader {
         }
     }
 
-    fn files_header(&self) -> String {
-        format!("Files in {}", self.base_path.display())
-    }
-}
-
-fn try_create_snapshot(png_path: &Path, base_path: &Path) -> Option<Snapshot> {
-    let file_name = png_path.file_name()?.to_str()?;
-
-    // Skip files that are already variants (.old.png, .new.png, .diff.png)
-    if file_name.ends_with(".old.png")
-        || file_name.ends_with(".new.png")
-        || file_name.ends_with(".diff.png")
-    {
-        return None;
+    fn progress(&self) -> Option<Progress> {
+        self.state().is_pending().then(|| {
+            Progress::new(Phase::Discovering).with_detail(format!("{} found", self.snapshots.len()))
+        })
     }
 
-    // Get base path without .png extension
-    let file_base_path = png_path.with_extension("");
-    let old_path = file_base_path.with_extension("old.png");
-    let new_path = file_base_path.with_extension("new.png");
-    let diff_path = file_base_path.with_extension("diff.png");
-
-    // Only create snapshot if diff exists
-    if !diff_path.exists() {
-        return None;
+    fn config(&self) -> Option<&Config> {
+        self.config.as_ref()
     }
 
-    // Create relative path from the base directory
-    let relative_path = png_path.strip_prefix(base_path).unwrap_or(png_path);
-
-    if old_path.exists() {
-        // old.png exists, use original as new and old.png as old
-        Some(Snapshot {
-            path: relative_path.to_path_buf(),
-            old: Some(FileReference::Path(old_path)),
-            new: Some(FileReference::Path(png_path.to_path_buf())),
-            diff: Some(FileReference::Path(diff_path)),
-        })
-    } else if new_path.exists() {
-        // new.png exists, use original as old and new.png as new
-        Some(Snapshot {
-            path: relative_path.to_path_buf(),
-            old: Some(FileReference::Path(png_path.to_path_buf())),
-            new: Some(FileReference::Path(new_path)),
-            diff: Some(FileReference::Path(diff_path)),
-        })
-    } else {
-        // No old or new variant, skip this snapshot
-        None
+    fn files_header(&self) -> String {
+        format!("Files in {}", self.base_path.display())
     }
 }
@@ -1,20 +1,29 @@
-use crate::loaders::{LoadSnapshots, sort_snapshots};
+use crate::config::Config;
+use crate::loaders::{BlameInfo, CancelFlag, LoadSnapshots, Phase, Progress, extend_sorted};
+use crate::path_filter::PathFilter;
 use crate::snapshot::{FileReference, Snapshot};
-use eframe::egui::load::Bytes;
 use eframe::egui::{Context, ImageSource};
 use egui_inbox::{UiInbox, UiInboxSender};
 use gix::Repository;
 use gix::bstr::ByteSlice as _;
 use octocrab::Octocrab;
 use std::borrow::Cow;
+use std::collections::HashMap;
 use std::path::{Path, PathBuf};
 use std::str;
 use std::task::Poll;
 
+/// Don't walk further back than this when looking for the commit that changed a snapshot.
+const MAX_BLAME_COMMITS: usize = 1000;
+
 enum Command {
     Snapshot(Snapshot),
+    Blame(PathBuf, BlameInfo),
     Error(anyhow::Error),
+
+    /// All snapshots are discovered, blame is still being looked up.
     Done,
+    BlameDone,
     GitInfo(GitInfo),
 }
 
@@ -28,41 +37,44 @@ struct GitInfo {
 
 pub struct GitLoader {
     base_path: PathBuf,
+    config: Option<Config>,
+    filter: PathFilter,
     inbox: UiInbox<Command>,
     git_info: Option<GitInfo>,
     snapshots: Vec<Snapshot>,
+    blame: HashMap<PathBuf, BlameInfo>,
+    blame_done: bool,
     state: Poll<Result<(), anyhow::Error>>,
 }
 
 impl GitLoader {
-    pub fn new(base_path: PathBuf) -> Self {
+    pub fn new(base_path: PathBuf, filter: PathFilter, cancel: CancelFlag) -> Self {
         let (sender, inbox) = UiInbox::channel();
 
         {
             let base_path = base_path.clone();
+            let filter = filter.clone();
             std::thread::Builder::new()
                 .name(format!("Git loader {}", base_path.display()))
                 .spawn(move || {
-                    let result = run_git_discovery(&sender, &base_path);
-                    match result {
-                        Ok(()) => {
-                            // Signal done
-                            sender.send(Command::Done).ok();
-                        }
-                        Err(e) => {
-                            // Send error
-                            sender.send(Command::Error(e)).ok();
-                        }
+                    let result = run_git_discovery(&sender, &base_path, &filter, &cancel);
+                    if let Err(e) = result {
+                        sender.send(Command::Error(e)).ok();
                     }
+                    sender.send(Command::BlameDone).ok();
                 })
                 .expect("Failed to spawn git loader thread");
         }
 
         Self {
+            config: Config::discover(&base_path),
             base_path,
+            filter,
             inbox,
             git_info: None,
             snapshots: Vec::new(),
+            blame: HashMap::new(),
+            blame_done: false,
             state: Poll::Pending,
         }
     }
@@ -70,11 +82,14 @@ impl GitLoader {
 
 impl LoadSnapshots for GitLoader {
     fn update(&mut self, ctx: &Context) {
+        let mut batch = Vec::new();
         for new_data in self.inbox.read(ctx) {
             match new_data {
                 Command::Snapshot(snapshot) => {
-                    self.snapshots.push(snapshot);
-                    sort_snapshots(&mut self.snapshots);
+                    batch.push(snapshot);
+                }
+                Command::Blame(path, info) => {
+                    self.blame.insert(path, info);
                 }
                 Command::Error(e) => {
                     self.state = Poll::Ready(Err(e));
@@ -85,12 +100,16 @@ impl LoadSnapshots for GitLoader {
                 Command::Done => {
                     self.state = Poll::Ready(Ok(()));
                 }
+                Command::BlameDone => {
+                    self.blame_done = true;
+                }
             }
         }
+        extend_sorted(&mut self.snapshots, batch);
     }
 
-    fn refresh(&mut self, _client: Octocrab) {
-        *self = Self::new(self.base_path.clone());
+    fn refresh(&mut self, _client: Octocrab, cancel: CancelFlag) {
+        *self = Self::new(self.base_path.clone(), self.filter.clone(), cancel);
     }
 
     fn snapshots(&self) -> &[Snapshot] {
@@ -105,6 +124,25 @@ impl LoadSnapshots for GitLoader {
         }
     }
 
+    fn progress(&self) -> Option<Progress> {
+        self.state().is_pending().then(|| {
+            Progress::new(Phase::Discovering).with_detail(format!("{} found", self.snapshots.len()))
+        })
+    }
+
+    fn blame(&self, snapshot: &Snapshot) -> Poll<Option<&BlameInfo>> {
+        match self.blame.get(&snapshot.path) {
+            Some(info) => Poll::Ready(Some(info)),
+            // Blame is looked up after all snapshots are discovered.
+            None if !self.blame_done => Poll::Pending,
+            None => Poll::Ready(None),
+        }
+    }
+
+    fn config(&self) -> Option<&Config> {
+        self.config.as_ref()
+    }
+
     fn files_header(&self) -> String {
         match &self.git_info {
             Some(info) => format!(
@@ -116,7 +154,13 @@ impl LoadSnapshots for GitLoader {
     }
 }
 
-fn run_git_discovery(sender: &Sender, base_path: &Path) -> anyhow::Result<()> {
+#[tracing::instrument(name = "discover", skip_all, fields(path = %base_path.display()))]
+fn run_git_discovery(
+    sender: &Sender,
+    base_path: &Path,
+    filter: &PathFilter,
+    cancel: &CancelFlag,
+) -> anyhow::Result<()> {
     // Open git repository in current directory
     let repo =
         gix::open(base_path).map_err(|e| anyhow::anyhow!("Git repository not found: {e}"))?;
@@ -148,9 +192,12 @@ fn run_git_discovery(sender: &Sender, base_path: &Path) -> anyhow::Result<()> {
         }))
         .ok();
 
+    tracing::info!("Comparing {current_branch} against {default_branch}");
+
     // Don't compare branch with itself
     if current_branch == default_branch {
-        log::warn!("Current branch is the same as default branch ({current_branch})");
+        tracing::warn!("Current branch is the same as default branch ({current_branch})");
+        sender.send(Command::Done).ok();
         return Ok(());
     }
 
@@ -182,6 +229,8 @@ fn run_git_discovery(sender: &Sender, base_path: &Path) -> anyhow::Result<()> {
 
     let base_tree = base_commit.tree()?;
 
+    let mut snapshot_paths = Vec::new();
+
     // Use gix diff to find changed PNG files between merge base and current HEAD
     base_tree.changes()?
         .for_each_to_obtain_tree(
@@ -190,6 +239,10 @@ fn run_git_discovery(sender: &Sender, base_path: &Path) -> anyhow::Result<()> {
                 gix::object::tree::diff::Action,
                 Box<dyn std::error::Error + Send + Sync>,
             > {
+                if cancel.is_cancelled() {
+                    return Ok(gix::object::tree::diff::Action::Break(()));
+                }
+
                 // Check the file path
                 let file_path = change.location();
                 let path_str = file_path.to_str().unwrap_or("");
@@ -198,6 +251,7 @@ fn run_git_discovery(sender: &Sender, base_path: &Path) -> anyhow::Result<()> {
                 // Check if this is a PNG file
                 if let Some(extension) = path_obj.extension()
                     && extension == "png"
+                    && filter.matches(path_obj)
                 {
                     // Create snapshot for this changed PNG file
                     match base_commit.tree() {
@@ -211,18 +265,19 @@ fn run_git_discovery(sender: &Sender, base_path: &Path) -> anyhow::Result<()> {
                                 base_path,
                             ) {
                                 Ok(Some(snapshot)) => {
+                                    snapshot_paths.push(snapshot.path.clone());
                                     sender.send(Command::Snapshot(snapshot)).ok();
                                 }
                                 Ok(None) => {
-                                    log::info!("No snapshot created for {}", path_obj.display());
+                                    tracing::info!("No snapshot created for {}", path_obj.display());
                                 }
                                 Err(err) => {
-                                    log::error!("Failed to create snapshot for {}: {err}", path_obj.display());
+                                    tracing::error!("Failed to create snapshot for {}: {err}", path_obj.display());
                                 }
                             }
                         }
                         Err(err) => {
-                            log::error!("Failed to get base tree: {err}");
+                            tracing::error!("Failed to get base tree: {err}");
                         }
                     }
                 }
@@ -230,9 +285,99 @@ fn run_git_discovery(sender: &Sender, base_path: &Path) -> anyhow::Result<()> {
             },
         )?;
 
+    tracing::info!("Found {} changed snapshots", snapshot_paths.len());
+    sender.send(Command::Done).ok();
+
+    if let Err(err) = find_blame(
+        sender,
+        &head_commit,
+        base_commit.id,
+        snapshot_paths,
+        &github_repo_info,
+        cancel,
+    ) {
+        tracing::warn!("Failed to find the commits that changed the snapshots: {err:#}");
+    }
+
+    Ok(())
+}
+
+/// Find the newest commit on the current branch that changed each of `paths`, like
+/// `git log -1 <default branch>..HEAD -- <path>`, in one walk over the branch.
+fn find_blame(
+    sender: &Sender,
+    head_commit: &gix::Commit<'_>,
+    base_commit_id: gix::ObjectId,
+    mut paths: Vec<PathBuf>,
+    github_repo_info: &Option<(String, String)>,
+    cancel: &CancelFlag,
+) -> anyhow::Result<()> {
+    let blob_id =
+        |commit: &gix::Commit<'_>, path: &Path| -> anyhow::Result<Option<gix::ObjectId>> {
+            let mut tree = commit.tree()?;
+            Ok(tree
+                .peel_to_entry_by_path(path)?
+                .map(|entry| entry.oid().to_owned()))
+        };
+
+    // Commits of the default branch are hidden, so the walk stops at the merge base.
+    let walk = head_commit
+        .ancestors()
+        .with_hidden([base_commit_id])
+        .sorting(gix::revision::walk::Sorting::ByCommitTime(
+            gix::traverse::commit::simple::CommitTimeOrder::NewestFirst,
+        ))
+        .all()?;
+    for info in walk.take(MAX_BLAME_COMMITS) {
+        if paths.is_empty() || cancel.is_cancelled() {
+            break;
+        }
+        let info = info?;
+        let commit = info.object()?;
+        let parents = info
+            .parent_ids()
+            .map(|id| head_commit.repo.find_commit(id))
+            .collect::<Result<Vec<_>, _>>()?;
+
+        let mut unchanged = Vec::with_capacity(paths.len());
+        for path in paths {
+            let id = blob_id(&commit, &path)?;
+            // Like `git log`, a merge that kept the file of one of its parents didn't change it.
+            let mut changed = true;
+            for parent in &parents {
+                if blob_id(parent, &path)? == id {
+                    changed = false;
+                    break;
+                }
+            }
+            if changed {
+                let info = blame_info(&commit, github_repo_info)?;
+                sender.send(Command::Blame(path, info)).ok();
+            } else {
+                unchanged.push(path);
+            }
+        }
+        paths = unchanged;
+    }
     Ok(())
 }
 
+fn blame_info(
+    commit: &gix::Commit<'_>,
+    github_repo_info: &Option<(String, String)>,
+) -> anyhow::Result<BlameInfo> {
+    let sha = commit.id.to_string();
+    let url = github_repo_info
+        .as_ref()
+        .map(|(org, repo)| format!("https://github.com/{org}/{repo}/commit/{sha}"));
+    Ok(BlameInfo {
+        summary: commit.message()?.summary().to_string(),
+        author: commit.author()?.name.to_string(),
+        sha,
+        url,
+    })
+}
+
 fn find_default_branch(repo: &Repository) -> anyhow::Result<String> {
     // Try common default branch names
     for branch_name in ["main", "master"] {
@@ -292,40 +437,37 @@ fn create_git_snapshot(
     let head_tree = head_commit.tree()?;
 
     // Compare git object content (both should be LFS pointers if using LFS)
-    if let Ok(current_content) = get_file_from_tree(repo, &head_tree, relative_path)
-        && default_file_content == current_content
-    {
+    let current_content = get_file_from_tree(repo, &head_tree, relative_path).ok();
+    if current_content.as_ref() == Some(&default_file_content) {
         return Ok(None);
     }
 
     // Check if this is an LFS pointer file
-    let default_image_source = if is_lfs_pointer(&default_file_content) {
+    let name = relative_path.to_string_lossy();
+    let default_file = if is_lfs_pointer(&default_file_content) {
         // If we have GitHub repo info, create media URL
         if let Some((org, repo_name)) = github_repo_info {
             let media_url = create_lfs_media_url(org, repo_name, commit_sha, relative_path);
-            ImageSource::Uri(Cow::Owned(media_url))
+            FileReference::Source(ImageSource::Uri(Cow::Owned(media_url)))
         } else {
             // Fallback to bytes (will likely fail to load but better than nothing)
-            ImageSource::Bytes {
-                uri: Cow::Owned(format!("bytes://{}", relative_path.display())),
-                bytes: Bytes::Shared(default_file_content.into()),
-            }
+            FileReference::from_bytes(&name, default_file_content.into())
         }
     } else {
         // Regular file content
-        ImageSource::Bytes {
-            uri: Cow::Owned(format!("bytes://{}", relative_path.display())),
-            bytes: Bytes::Shared(default_file_content.into()),
-        }
+        FileReference::from_bytes(&name, default_file_content.into())
     };
 
-    let full_path = base_path.join(relative_path);
+    // Removed on the current branch, keep it around as a deleted snapshot
+    let new = current_content.map(|_| FileReference::Path(base_path.join(relative_path)));
 
     Ok(Some(Snapshot {
         path: relative_path.to_path_buf(),
-        old: Some(FileReference::Source(default_image_source)), // Default branch version as ImageSource
-        new: Some(FileReference::Path(full_path)), // Current working tree version with full path
-        diff: None,                                // Always None for git mode
+        old: Some(default_file), // Default branch version
+        new,                     // Current working tree version with full path
+        diff: None,              // Always None for git mode
+        unchanged: false,
+        renamed_from: None,
     }))
 }
 
@@ -0,0 +1,99 @@
+use crate::loaders::{CancelFlag, LoadSnapshots, Phase, Progress, sort_snapshots};
+use crate::path_filter::PathFilter;
+use crate::snapshot::Snapshot;
+use anyhow::Error;
+use eframe::egui::Context;
+use egui_inbox::UiInbox;
+use octocrab::Octocrab;
+use std::path::PathBuf;
+use std::task::Poll;
+
+/// Every snapshot image in a folder as it currently renders, changed or not, see
+/// [`kitdiff_core::files::current_images`]. Meant to be one side of a
+/// [`crate::loaders::compare_loader::CompareLoader`].
+pub struct ImagesLoader {
+    base_path: PathBuf,
+    header: String,
+    filter: PathFilter,
+    inbox: UiInbox<anyhow::Result<Vec<Snapshot>>>,
+    snapshots: Poll<anyhow::Result<Vec<Snapshot>>>,
+}
+
+impl ImagesLoader {
+    pub fn new(base_path: PathBuf, header: String, filter: PathFilter, cancel: CancelFlag) -> Self {
+        let mut inbox = UiInbox::new();
+        {
+            let base_path = base_path.clone();
+            let filter = filter.clone();
+            inbox.spawn(|tx| async move {
+                let result = tokio::task::spawn_blocking(move || {
+                    anyhow::ensure!(
+                        base_path.is_dir(),
+                        "{} is not a folder",
+                        base_path.display()
+                    );
+                    let mut snapshots: Vec<Snapshot> =
+                        kitdiff_core::files::current_images(&base_path, &filter, &cancel)
+                            .into_iter()
+                            .map(Snapshot::from)
+                            .collect();
+                    sort_snapshots(&mut snapshots);
+                    Ok(snapshots)
+                })
+                .await
+                .map_err(Error::from)
+                .flatten();
+                tx.send(result).ok();
+            });
+        }
+        Self {
+            base_path,
+            header,
+            filter,
+            inbox,
+            snapshots: Poll::Pending,
+        }
+    }
+}
+
+impl LoadSnapshots for ImagesLoader {
+    fn update(&mut self, ctx: &Context) {
+        if let Some(result) = self.inbox.read(ctx).last() {
+            self.snapshots = Poll::Ready(result);
+        }
+    }
+
+    fn refresh(&mut self, _client: Octocrab, cancel: CancelFlag) {
+        *self = Self::new(
+            self.base_path.clone(),
+            self.header.clone(),
+            self.filter.clone(),
+            cancel,
+        );
+    }
+
+    fn snapshots(&self) -> &[Snapshot] {
+        match &self.snapshots {
+            Poll::Ready(Ok(snapshots)) => snapshots,
+            _ => &[],
+        }
+    }
+
+    fn state(&self) -> Poll<Result<(), &Error>> {
+        match &self.snapshots {
+            Poll::Ready(Ok(_)) => Poll::Ready(Ok(())),
+            Poll::Ready(Err(err)) => Poll::Ready(Err(err)),
+            Poll::Pending => Poll::Pending,
+        }
+    }
+
+    fn progress(&self) -> Option<Progress> {
+        self.snapshots
+            .is_pending()
+            .then(|| Progress::new(Phase::Discovering))
+    }
+
+    fn files_header(&self) -> String {
+        self.header.clone()
+    }
+}
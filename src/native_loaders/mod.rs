@@ -1,2 +1,4 @@
+pub mod bucket_loader;
 pub mod file_loader;
 pub mod git_loader;
+pub mod images_loader;
@@ -0,0 +1,204 @@
+use crate::state::{AppStateRef, SystemCommand};
+use eframe::egui::{self, Align2, CollapsingHeader, Context, Frame, Id, RichText, ScrollArea, Ui};
+
+/// Toasts disappear after this many seconds, they stay in the log.
+const TOAST_SECONDS: f64 = 8.0;
+
+/// Older entries are dropped from the log.
+const MAX_ENTRIES: usize = 200;
+
+/// An error worth telling the user about, optionally with the command that can be retried.
+pub struct Notification {
+    pub message: String,
+    pub retry: Option<SystemCommand>,
+
+    /// Notifications with the same key are only shown once, for errors that are reported every
+    /// frame, like a failed loader.
+    key: Option<String>,
+}
+
+impl Notification {
+    pub fn error(context: &str, err: impl std::fmt::Display) -> Self {
+        Self {
+            message: format!("{context}: {err:#}"),
+            retry: None,
+            key: None,
+        }
+    }
+
+    #[must_use]
+    pub fn with_retry(mut self, command: impl Into<SystemCommand>) -> Self {
+        self.retry = Some(command.into());
+        self
+    }
+
+    #[must_use]
+    pub fn with_key(mut self, key: impl Into<String>) -> Self {
+        self.key = Some(key.into());
+        self
+    }
+}
+
+pub enum NotificationCommand {
+    Notify(Notification),
+    Dismiss(u64),
+    Retry(u64),
+    Clear,
+    ToggleLog,
+}
+
+impl From<NotificationCommand> for SystemCommand {
+    fn from(value: NotificationCommand) -> Self {
+        Self::Notification(value)
+    }
+}
+
+impl From<Notification> for SystemCommand {
+    fn from(value: Notification) -> Self {
+        Self::Notification(NotificationCommand::Notify(value))
+    }
+}
+
+struct Entry {
+    id: u64,
+
+    /// `egui` time when it was reported.
+    time: f64,
+    notification: Notification,
+    dismissed: bool,
+}
+
+#[derive(Default)]
+pub struct Notifications {
+    entries: Vec<Entry>,
+    next_id: u64,
+    pub log_open: bool,
+}
+
+impl Notifications {
+    /// Returns a command to run, for retries.
+    pub fn handle(&mut self, ctx: &Context, command: NotificationCommand) -> Option<SystemCommand> {
+        match command {
+            NotificationCommand::Notify(notification) => {
+                self.push(ctx.input(|i| i.time), notification);
+            }
+            NotificationCommand::Dismiss(id) => {
+                if let Some(entry) = self.entries.iter_mut().find(|e| e.id == id) {
+                    entry.dismissed = true;
+                }
+            }
+            NotificationCommand::Retry(id) => {
+                // Removed, so the retried command can report the same error again.
+                let index = self.entries.iter().position(|e| e.id == id)?;
+                return self.entries.remove(index).notification.retry;
+            }
+            NotificationCommand::Clear => self.entries.clear(),
+            NotificationCommand::ToggleLog => self.log_open = !self.log_open,
+        }
+        None
+    }
+
+    fn push(&mut self, time: f64, notification: Notification) {
+        if notification.key.is_some()
+            && self
+                .entries
+                .iter()
+                .any(|e| e.notification.key == notification.key)
+        {
+            return;
+        }
+
+        tracing::error!("{}", notification.message);
+        self.entries.push(Entry {
+            id: self.next_id,
+            time,
+            notification,
+            dismissed: false,
+        });
+        self.next_id += 1;
+
+        if self.entries.len() > MAX_ENTRIES {
+            self.entries.drain(..self.entries.len() - MAX_ENTRIES);
+        }
+    }
+
+    pub fn len(&self) -> usize {
+        self.entries.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.entries.is_empty()
+    }
+
+    /// Recent notifications in the bottom right corner.
+    pub fn toasts_ui(&self, ctx: &Context, state: &AppStateRef<'_>) {
+        let now = ctx.input(|i| i.time);
+        let toasts: Vec<&Entry> = self
+            .entries
+            .iter()
+            .filter(|e| !e.dismissed && now - e.time < TOAST_SECONDS)
+            .collect();
+        let Some(oldest) = toasts.first() else {
+            return;
+        };
+        ctx.request_repaint_after_secs((oldest.time + TOAST_SECONDS - now) as f32);
+
+        egui::Area::new(Id::new("notification_toasts"))
+            .anchor(Align2::RIGHT_BOTTOM, [-8.0, -8.0])
+            .show(ctx, |ui| {
+                ui.set_max_width(400.0);
+                for entry in toasts {
+                    Frame::popup(ui.style()).show(ui, |ui| {
+                        entry_ui(ui, state, entry, now);
+                    });
+                }
+            });
+    }
+
+    /// All notifications, newest first, and the log below them.
+    pub fn log_ui(&self, ui: &mut Ui, state: &AppStateRef<'_>) {
+        let now = ui.input(|i| i.time);
+        ui.horizontal(|ui| {
+            ui.strong("Log");
+            if ui.button("Close").clicked() {
+                state.send(NotificationCommand::ToggleLog);
+            }
+        });
+        ScrollArea::vertical().show(ui, |ui| {
+            CollapsingHeader::new(format!("Errors ({})", self.entries.len()))
+                .default_open(!self.entries.is_empty())
+                .show(ui, |ui| {
+                    if self.entries.is_empty() {
+                        ui.weak("Nothing to report");
+                    } else if ui.button("Clear").clicked() {
+                        state.send(NotificationCommand::Clear);
+                    }
+                    for entry in self.entries.iter().rev() {
+                        entry_ui(ui, state, entry, now);
+                        ui.separator();
+                    }
+                });
+            CollapsingHeader::new("Messages")
+                .default_open(true)
+                .show(ui, crate::logging::log_ui);
+        });
+    }
+}
+
+fn entry_ui(ui: &mut Ui, state: &AppStateRef<'_>, entry: &Entry, now: f64) {
+    ui.horizontal_wrapped(|ui| {
+        ui.label(RichText::new(&entry.notification.message).color(ui.visuals().error_fg_color));
+    });
+    ui.horizontal(|ui| {
+        ui.weak(format!("{:.0}s ago", now - entry.time));
+        if entry.notification.retry.is_some() && ui.button("Retry").clicked() {
+            state.send(NotificationCommand::Retry(entry.id));
+        }
+        if ui.small_button("Copy").clicked() {
+            ui.ctx().copy_text(entry.notification.message.clone());
+        }
+        if !entry.dismissed && ui.small_button("Dismiss").clicked() {
+            state.send(NotificationCommand::Dismiss(entry.id));
+        }
+    });
+}
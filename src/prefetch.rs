@@ -0,0 +1,145 @@
+//! Loading the snapshots around the active one ahead of time, so navigating to them is instant.
+
+use crate::settings::{PrefetchSettings, Settings};
+use crate::snapshot::Snapshot;
+use eframe::egui::load::ImagePoll;
+use eframe::egui::{Context, SizeHint};
+use std::collections::HashSet;
+use std::path::PathBuf;
+
+/// Counters to tune [`PrefetchSettings`] with.
+#[derive(Debug, Clone, Copy, Default)]
+pub struct PrefetchStats {
+    /// Navigated to a snapshot whose images were all loaded already.
+    pub hits: u32,
+
+    /// Navigated to a snapshot that had to wait for some of its images.
+    pub misses: u32,
+
+    /// Images loaded ahead of time.
+    pub prefetched: u32,
+}
+
+impl PrefetchStats {
+    /// Share of the navigations that were hits, from 0 to 1.
+    pub fn hit_rate(&self) -> Option<f32> {
+        let total = self.hits + self.misses;
+        (total > 0).then(|| self.hits as f32 / total as f32)
+    }
+}
+
+/// Loads the old, new and diff images of the snapshots around the active one in the filtered
+/// list, closest first, once the active one is loaded.
+#[derive(Default)]
+pub struct Prefetch {
+    /// Uris we started loading that aren't done yet.
+    in_flight: HashSet<String>,
+    active: Option<PathBuf>,
+    stats: PrefetchStats,
+}
+
+impl Prefetch {
+    pub fn stats(&self) -> PrefetchStats {
+        self.stats
+    }
+
+    pub fn in_flight(&self) -> usize {
+        self.in_flight.len()
+    }
+
+    /// `filtered` are indices into `snapshots`.
+    pub fn update(
+        &mut self,
+        ctx: &Context,
+        snapshots: &[Snapshot],
+        filtered: &[usize],
+        active_filtered_index: usize,
+        settings: &Settings,
+    ) {
+        self.in_flight.retain(|uri| {
+            matches!(
+                ctx.try_load_image(uri, SizeHint::default()),
+                Ok(ImagePoll::Pending { .. })
+            )
+        });
+
+        let Some(active) = filtered
+            .get(active_filtered_index)
+            .and_then(|index| snapshots.get(*index))
+        else {
+            return;
+        };
+        let active_loaded = uris(active, settings).iter().all(|uri| {
+            !matches!(
+                ctx.try_load_image(uri, SizeHint::default()),
+                Ok(ImagePoll::Pending { .. })
+            )
+        });
+        if self.active.as_ref() != Some(&active.path) {
+            // The first snapshot of a load had no chance to be prefetched.
+            if self.active.is_some() {
+                if active_loaded {
+                    self.stats.hits += 1;
+                } else {
+                    self.stats.misses += 1;
+                }
+            }
+            self.active = Some(active.path.clone());
+        }
+
+        // The active snapshot goes first, it's what the user is waiting for.
+        if !active_loaded || !enabled(&settings.prefetch) {
+            return;
+        }
+
+        let max_in_flight = settings.prefetch.max_in_flight as usize;
+        for distance in 1..=settings.prefetch.window as usize {
+            let around = [
+                active_filtered_index.checked_add(distance),
+                active_filtered_index.checked_sub(distance),
+            ];
+            for snapshot in around
+                .into_iter()
+                .flatten()
+                .filter_map(|i| snapshots.get(*filtered.get(i)?))
+            {
+                for uri in uris(snapshot, settings) {
+                    if self.in_flight.len() >= max_in_flight {
+                        return;
+                    }
+                    if self.in_flight.contains(&uri) {
+                        continue;
+                    }
+                    if let Ok(ImagePoll::Pending { .. }) =
+                        ctx.try_load_image(&uri, SizeHint::default())
+                    {
+                        self.in_flight.insert(uri);
+                        self.stats.prefetched += 1;
+                    }
+                }
+            }
+        }
+    }
+}
+
+/// The images the viewer may show for the snapshot.
+fn uris(snapshot: &Snapshot, settings: &Settings) -> Vec<String> {
+    [
+        snapshot.old_uri(),
+        snapshot.new_uri(),
+        snapshot.diff_uri(settings.use_original_diff, settings.options),
+    ]
+    .into_iter()
+    .flatten()
+    .collect()
+}
+
+#[cfg(not(target_arch = "wasm32"))]
+fn enabled(settings: &PrefetchSettings) -> bool {
+    settings.window > 0
+}
+
+#[cfg(target_arch = "wasm32")]
+fn enabled(settings: &PrefetchSettings) -> bool {
+    settings.window > 0 && settings.on_web && (settings.on_metered || !crate::pwa::is_metered())
+}
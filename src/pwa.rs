@@ -0,0 +1,80 @@
+//! Installing the web version as an app, and using it offline.
+//!
+//! The service worker (`assets/sw.js`) keeps the app itself around, downloads are kept by
+//! [`crate::web_loaders::opfs_cache`].
+
+use std::cell::RefCell;
+use wasm_bindgen::JsCast as _;
+use wasm_bindgen::JsValue;
+use wasm_bindgen::closure::Closure;
+
+thread_local! {
+    /// The `beforeinstallprompt` event, if the browser offered to install us.
+    static INSTALL_PROMPT: RefCell<Option<JsValue>> = const { RefCell::new(None) };
+}
+
+/// Register the service worker and listen for the install prompt. Call once on startup.
+pub fn init() {
+    let Some(window) = web_sys::window() else {
+        return;
+    };
+
+    let registration = window.navigator().service_worker().register("./sw.js");
+    wasm_bindgen_futures::spawn_local(async move {
+        if let Err(err) = wasm_bindgen_futures::JsFuture::from(registration).await {
+            tracing::warn!("Failed to register the service worker: {err:?}");
+        }
+    });
+
+    let on_prompt = Closure::<dyn FnMut(web_sys::Event)>::new(|event: web_sys::Event| {
+        // Show our own button instead of the browser's mini-infobar.
+        event.prevent_default();
+        INSTALL_PROMPT.with(|prompt| *prompt.borrow_mut() = Some(event.into()));
+    });
+    window
+        .add_event_listener_with_callback("beforeinstallprompt", on_prompt.as_ref().unchecked_ref())
+        .ok();
+    // Lives as long as the page.
+    on_prompt.forget();
+}
+
+/// Whether the browser would let us install the app right now.
+pub fn can_install() -> bool {
+    INSTALL_PROMPT.with(|prompt| prompt.borrow().is_some())
+}
+
+/// Show the browser's install dialog. The prompt can only be used once.
+pub fn install() {
+    let Some(event) = INSTALL_PROMPT.with(|prompt| prompt.borrow_mut().take()) else {
+        return;
+    };
+    let prompt = js_sys::Reflect::get(&event, &"prompt".into())
+        .ok()
+        .and_then(|prompt| prompt.dyn_into::<js_sys::Function>().ok());
+    if let Some(prompt) = prompt
+        && let Err(err) = prompt.call0(&event)
+    {
+        tracing::warn!("Failed to show the install prompt: {err:?}");
+    }
+}
+
+pub fn is_offline() -> bool {
+    web_sys::window().is_some_and(|window| !window.navigator().on_line())
+}
+
+/// Whether the browser reports a metered connection, or the user asked it to save data. Only some
+/// browsers tell, the others count as unmetered.
+pub fn is_metered() -> bool {
+    let Some(window) = web_sys::window() else {
+        return false;
+    };
+    let Some(connection) = js_sys::Reflect::get(&window.navigator(), &"connection".into())
+        .ok()
+        .filter(|connection| connection.is_object())
+    else {
+        return false;
+    };
+    let field = |name: &str| js_sys::Reflect::get(&connection, &name.into()).ok();
+    field("saveData").and_then(|save_data| save_data.as_bool()) == Some(true)
+        || field("type").and_then(|kind| kind.as_string()).as_deref() == Some("cellular")
+}
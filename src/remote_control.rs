@@ -0,0 +1,289 @@
+//! Lets other programs drive a running kitdiff over HTTP on localhost, e.g. to open the artifact
+//! of a CI run in the window that is already open instead of starting another one.
+//!
+//! Every request needs the token that the running kitdiff writes to a file only the user can
+//! read, see [`token_path`], so neither other users nor web pages (through DNS rebinding) can
+//! send commands. The `Host` has to be localhost for the same reason.
+
+use crate::DiffSource;
+use crate::session::SavedSource;
+use crate::state::{ReviewStatus, SystemCommand, View, ViewerSystemCommand};
+use anyhow::Context as _;
+use axum::Json;
+use axum::extract::State;
+use axum::http::{HeaderMap, StatusCode, header};
+use egui_inbox::UiInboxSender;
+use std::fmt::Debug;
+use std::net::{Ipv4Addr, SocketAddrV4};
+use std::path::PathBuf;
+use std::sync::Arc;
+use std::time::Duration;
+
+/// Every window listens here unless it's told otherwise, so a second `kitdiff` can hand its
+/// source to the first one instead of opening another window.
+pub const DEFAULT_PORT: u16 = 7474;
+
+/// The request header with the contents of the [`token_path`] file.
+pub const TOKEN_HEADER: &str = "x-kitdiff-token";
+
+/// Which commands the endpoint accepts.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Access {
+    /// Only what [`forward`] sends, which is all single-instance mode needs.
+    Forward,
+
+    /// Every command, for tools driving kitdiff. Only with `--remote-control`.
+    All,
+}
+
+/// The body of a `POST /command`, e.g. `{"command": "select", "path": "tests/snapshots/a.png"}`.
+#[derive(serde::Serialize, serde::Deserialize)]
+#[serde(tag = "command", rename_all = "snake_case")]
+enum RemoteCommand {
+    /// A folder, archive or url, like on the command line.
+    Open {
+        source: String,
+    },
+
+    /// What another kitdiff was started with, see [`forward`].
+    OpenSaved {
+        source: SavedSource,
+    },
+    Select {
+        path: PathBuf,
+    },
+
+    /// `blend_all`, `old`, `new`, `diff` or `flip`.
+    View {
+        view: String,
+    },
+
+    /// `accepted`, `rejected`, `flagged`, or `null` to clear the decision.
+    Review {
+        path: PathBuf,
+        status: Option<String>,
+    },
+    Filter {
+        filter: String,
+    },
+    Refresh,
+
+    /// Bring the window to the front.
+    Focus,
+}
+
+impl RemoteCommand {
+    fn is_forwarded(&self) -> bool {
+        matches!(self, Self::OpenSaved { .. } | Self::Focus)
+    }
+
+    fn into_system_command(self) -> Result<SystemCommand, String> {
+        Ok(match self {
+            Self::Open { source } => SystemCommand::Open(DiffSource::from_path(&source)),
+            Self::OpenSaved { source } => SystemCommand::Open(
+                source
+                    .source()
+                    .ok_or("The source can't be opened anymore")?,
+            ),
+            Self::Select { path } => ViewerSystemCommand::SelectPath(path).into(),
+            Self::View { view } => ViewerSystemCommand::SetView(parse(&View::ALL, &view)?).into(),
+            Self::Review { path, status } => {
+                let statuses = [
+                    ReviewStatus::Accepted,
+                    ReviewStatus::Rejected,
+                    ReviewStatus::Flagged,
+                ];
+                let status = status.map(|status| parse(&statuses, &status)).transpose()?;
+                ViewerSystemCommand::SetReview(path, status).into()
+            }
+            Self::Filter { filter } => ViewerSystemCommand::SetFilter(filter).into(),
+            Self::Refresh => SystemCommand::Refresh,
+            Self::Focus => SystemCommand::Focus,
+        })
+    }
+}
+
+/// Finds the option named like `name` in snake case, e.g. `blend_all` for `BlendAll`.
+fn parse<T: Copy + Debug>(options: &[T], name: &str) -> Result<T, String> {
+    let snake_case = |option: &T| {
+        let mut snake = String::new();
+        for (index, char) in format!("{option:?}").chars().enumerate() {
+            if char.is_uppercase() && index > 0 {
+                snake.push('_');
+            }
+            snake.push(char.to_ascii_lowercase());
+        }
+        snake
+    };
+    options
+        .iter()
+        .find(|option| snake_case(option) == name)
+        .copied()
+        .ok_or_else(|| {
+            let names: Vec<String> = options.iter().map(snake_case).collect();
+            format!("Unknown value {name}, expected one of {}", names.join(", "))
+        })
+}
+
+/// The file with the token of the kitdiff listening on `port`, in the runtime dir where there is
+/// one, e.g. `$XDG_RUNTIME_DIR/kitdiff` on Linux, else the cache dir.
+pub fn token_path(port: u16) -> Option<PathBuf> {
+    let dirs = directories::ProjectDirs::from("io", "rerun", "kitdiff")?;
+    let dir = dirs.runtime_dir().unwrap_or(dirs.cache_dir());
+    Some(dir.join(format!("remote-control-{port}.token")))
+}
+
+/// A new random token, written to [`token_path`] so only the user can read it.
+fn write_token(port: u16) -> anyhow::Result<String> {
+    use std::io::Write as _;
+
+    let mut random = [0_u8; 32];
+    getrandom::fill(&mut random).map_err(|err| anyhow::anyhow!("No randomness: {err}"))?;
+    let token: String = random.iter().map(|byte| format!("{byte:02x}")).collect();
+
+    let path = token_path(port).context("No directory for the token")?;
+    if let Some(dir) = path.parent() {
+        std::fs::create_dir_all(dir)?;
+    }
+    // Created anew, a file left behind may be readable by others.
+    std::fs::remove_file(&path).ok();
+    let mut options = std::fs::OpenOptions::new();
+    options.write(true).create_new(true);
+    #[cfg(unix)]
+    std::os::unix::fs::OpenOptionsExt::mode(&mut options, 0o600);
+    options
+        .open(&path)
+        .and_then(|mut file| file.write_all(token.as_bytes()))
+        .with_context(|| format!("Failed to write {}", path.display()))?;
+    Ok(token)
+}
+
+#[derive(Clone)]
+struct Remote {
+    tx: UiInboxSender<SystemCommand>,
+    port: u16,
+    token: Arc<str>,
+    access: Access,
+}
+
+impl Remote {
+    /// Rejects requests without the token or for another host.
+    fn check(&self, headers: &HeaderMap) -> Result<(), (StatusCode, String)> {
+        let host = headers
+            .get(header::HOST)
+            .and_then(|host| host.to_str().ok());
+        let hosts = [
+            format!("127.0.0.1:{}", self.port),
+            format!("localhost:{}", self.port),
+        ];
+        if !host.is_some_and(|host| hosts.iter().any(|allowed| allowed == host)) {
+            return Err((StatusCode::FORBIDDEN, "Unexpected host".to_owned()));
+        }
+        let token = headers
+            .get(TOKEN_HEADER)
+            .map(|token| token.as_bytes())
+            .unwrap_or_default();
+        if !constant_time_eq(token, self.token.as_bytes()) {
+            return Err((
+                StatusCode::UNAUTHORIZED,
+                format!("Missing or wrong {TOKEN_HEADER} header"),
+            ));
+        }
+        Ok(())
+    }
+}
+
+/// Doesn't tell how much of the token was right by how long it took.
+fn constant_time_eq(a: &[u8], b: &[u8]) -> bool {
+    a.len() == b.len() && a.iter().zip(b).fold(0, |diff, (a, b)| diff | (a ^ b)) == 0
+}
+
+/// Listens on `port` of localhost and forwards the commands `access` allows to the app.
+pub fn serve(port: u16, access: Access, tx: UiInboxSender<SystemCommand>) -> anyhow::Result<()> {
+    // Bound here so a port in use is reported right away.
+    let listener = std::net::TcpListener::bind(SocketAddrV4::new(Ipv4Addr::LOCALHOST, port))?;
+    listener.set_nonblocking(true)?;
+    let listener = tokio::net::TcpListener::from_std(listener)?;
+    let token = write_token(port)?;
+    tracing::info!(
+        "Accepting remote commands on http://{}",
+        listener.local_addr()?
+    );
+
+    let remote = Remote {
+        tx,
+        port,
+        token: token.into(),
+        access,
+    };
+    let router = axum::Router::new()
+        .route("/command", axum::routing::post(command_route))
+        .with_state(remote);
+    tokio::spawn(async move {
+        if let Err(err) = axum::serve(listener, router).await {
+            tracing::error!("Remote control stopped: {err}");
+        }
+    });
+    Ok(())
+}
+
+async fn command_route(
+    State(remote): State<Remote>,
+    headers: HeaderMap,
+    Json(command): Json<RemoteCommand>,
+) -> Result<&'static str, (StatusCode, String)> {
+    remote.check(&headers)?;
+    if remote.access == Access::Forward && !command.is_forwarded() {
+        return Err((
+            StatusCode::FORBIDDEN,
+            "Start kitdiff with --remote-control to accept this command".to_owned(),
+        ));
+    }
+    let command = command
+        .into_system_command()
+        .map_err(|err| (StatusCode::BAD_REQUEST, err))?;
+    remote.tx.send(command).map_err(|_err| {
+        (
+            StatusCode::SERVICE_UNAVAILABLE,
+            "kitdiff is shutting down".to_owned(),
+        )
+    })?;
+    Ok("ok")
+}
+
+/// Hands `source` to the kitdiff listening on `port` and brings its window to the front. Fails if
+/// there is none, or it can't open the source.
+pub async fn forward(port: u16, source: Option<&DiffSource>) -> anyhow::Result<()> {
+    let open = source
+        .map(|source| {
+            let saved = SavedSource::new(source).context("The source can't be forwarded")?;
+            // The other kitdiff may run in another folder.
+            anyhow::Ok(RemoteCommand::OpenSaved {
+                source: saved.absolute()?,
+            })
+        })
+        .transpose()?;
+    let path = token_path(port).context("No directory for the token")?;
+    let token = std::fs::read_to_string(&path)
+        .with_context(|| format!("Failed to read {}", path.display()))?;
+
+    // Commands for localhost must not go through a proxy.
+    let client = reqwest::Client::builder()
+        .no_proxy()
+        .timeout(Duration::from_secs(2))
+        .build()?;
+    let url = format!(
+        "http://{}/command",
+        SocketAddrV4::new(Ipv4Addr::LOCALHOST, port)
+    );
+    for command in open.iter().chain([&RemoteCommand::Focus]) {
+        client
+            .post(&url)
+            .header(TOKEN_HEADER, token.trim())
+            .json(command)
+            .send()
+            .await?
+            .error_for_status()?;
+    }
+    Ok(())
+}
@@ -0,0 +1,169 @@
+//! The open source and how far its review got, saved with the settings so a closed window can be
+//! brought back from the home page.
+
+use crate::DiffSource;
+use crate::loaders::DataReference;
+use crate::snapshot::SnapshotVersion;
+use crate::state::{ReviewStatus, View, ViewerState};
+use std::collections::HashMap;
+use std::path::{Path, PathBuf};
+
+/// A [`DiffSource`] that can be written to storage. Sources that only live in memory, like a
+/// dropped archive or a pinned baseline, aren't saved.
+#[derive(Clone, Debug, PartialEq, serde::Serialize, serde::Deserialize)]
+pub enum SavedSource {
+    /// Anything [`DiffSource::from_url`] opens.
+    Url(String),
+    Files(PathBuf),
+    Git(PathBuf),
+    Images(PathBuf),
+    Archive(PathBuf),
+
+    /// By name, see [`crate::baselines::find`].
+    Baseline(String),
+    Merged(Vec<(String, SavedSource)>),
+    Compare {
+        old: Box<SavedSource>,
+        new: Box<SavedSource>,
+    },
+}
+
+impl SavedSource {
+    pub fn new(source: &DiffSource) -> Option<Self> {
+        Some(match source {
+            #[cfg(not(target_arch = "wasm32"))]
+            DiffSource::Files(path) => Self::Files(path.clone()),
+            #[cfg(not(target_arch = "wasm32"))]
+            DiffSource::Git(path) => Self::Git(path.clone()),
+            #[cfg(not(target_arch = "wasm32"))]
+            DiffSource::Images(path) => Self::Images(path.clone()),
+            #[cfg(not(target_arch = "wasm32"))]
+            DiffSource::Baseline(baseline) => Self::Baseline(baseline.manifest.name.clone()),
+            #[cfg(not(target_arch = "wasm32"))]
+            DiffSource::Bucket(link) => Self::Url(link.to_string()),
+            #[cfg(target_arch = "wasm32")]
+            DiffSource::Directory(_) => return None,
+            DiffSource::Pr(link) => Self::Url(format!("https://github.com/{link}")),
+            DiffSource::GHArtifact(link) => Self::Url(format!(
+                "https://github.com/{}/{}/actions/runs/{}/artifacts/{}",
+                link.repo.owner,
+                link.repo.repo,
+                // Not needed to download the artifact, only to make the url parse.
+                link.run_id
+                    .map_or_else(|| "0".to_owned(), |run| run.to_string()),
+                link.artifact_id,
+            )),
+            DiffSource::Jenkins(link) => Self::Url(link.to_string()),
+            DiffSource::CircleCi(link) => Self::Url(format!(
+                "https://circleci.com/{}/{}",
+                link.project_slug, link.job_number
+            )),
+            DiffSource::Archive(DataReference::Url(url)) => Self::Url(url.clone()),
+            DiffSource::Archive(DataReference::Path(path)) => Self::Archive(path.clone()),
+            DiffSource::Archive(DataReference::Data(..)) => return None,
+            DiffSource::Merged(sources) => Self::Merged(
+                sources
+                    .iter()
+                    .map(|(name, source)| Some((name.clone(), Self::new(source)?)))
+                    .collect::<Option<_>>()?,
+            ),
+            // The pinned images are only in memory, the live source is the next best thing.
+            DiffSource::Pinned { live, .. } => Self::new(live)?,
+            DiffSource::Compare { old, new } => Self::Compare {
+                old: Box::new(Self::new(old)?),
+                new: Box::new(Self::new(new)?),
+            },
+            DiffSource::Registered(source) => Self::Url(source.url().to_owned()),
+        })
+    }
+
+    /// With relative paths resolved against the current folder, to open it from another one.
+    #[cfg(not(target_arch = "wasm32"))]
+    pub fn absolute(self) -> std::io::Result<Self> {
+        Ok(match self {
+            Self::Files(path) => Self::Files(std::path::absolute(path)?),
+            Self::Git(path) => Self::Git(std::path::absolute(path)?),
+            Self::Images(path) => Self::Images(std::path::absolute(path)?),
+            Self::Archive(path) => Self::Archive(std::path::absolute(path)?),
+            Self::Url(_) | Self::Baseline(_) => self,
+            Self::Merged(sources) => Self::Merged(
+                sources
+                    .into_iter()
+                    .map(|(name, source)| Ok((name, source.absolute()?)))
+                    .collect::<std::io::Result<_>>()?,
+            ),
+            Self::Compare { old, new } => Self::Compare {
+                old: Box::new(old.absolute()?),
+                new: Box::new(new.absolute()?),
+            },
+        })
+    }
+
+    pub fn source(&self) -> Option<DiffSource> {
+        Some(match self {
+            Self::Url(url) => DiffSource::from_url(url),
+            #[cfg(not(target_arch = "wasm32"))]
+            Self::Files(path) => DiffSource::Files(path.clone()),
+            #[cfg(not(target_arch = "wasm32"))]
+            Self::Git(path) => DiffSource::Git(path.clone()),
+            #[cfg(not(target_arch = "wasm32"))]
+            Self::Images(path) => DiffSource::Images(path.clone()),
+            #[cfg(not(target_arch = "wasm32"))]
+            Self::Baseline(name) => DiffSource::Baseline(crate::baselines::find(name).ok()?),
+            #[cfg(target_arch = "wasm32")]
+            Self::Files(_) | Self::Git(_) | Self::Images(_) | Self::Baseline(_) => return None,
+            Self::Archive(path) => DiffSource::Archive(DataReference::Path(path.clone())),
+            Self::Merged(sources) => DiffSource::Merged(
+                sources
+                    .iter()
+                    .map(|(name, source)| Some((name.clone(), source.source()?)))
+                    .collect::<Option<_>>()?,
+            ),
+            Self::Compare { old, new } => DiffSource::Compare {
+                old: Box::new(old.source()?),
+                new: Box::new(new.source()?),
+            },
+        })
+    }
+}
+
+/// What the viewer showed when kitdiff was closed.
+#[derive(Clone, Debug, serde::Serialize, serde::Deserialize)]
+pub struct Session {
+    source: SavedSource,
+
+    /// The files header of the source, to say on the home page what will be opened.
+    pub title: String,
+    pub selected: Option<PathBuf>,
+    pub filter: String,
+    pub scope: Option<PathBuf>,
+    pub view: View,
+    pub flaky_only: bool,
+    pub reviews: HashMap<PathBuf, ReviewStatus>,
+
+    /// See [`ViewerState::review_versions`], reviews without one are dropped on restore.
+    #[serde(default)]
+    pub review_versions: HashMap<PathBuf, SnapshotVersion>,
+}
+
+impl Session {
+    /// `None` if the source can't be opened again after a restart.
+    pub fn capture(viewer: &ViewerState) -> Option<Self> {
+        Some(Self {
+            source: SavedSource::new(&viewer.source)?,
+            title: viewer.loader.files_header(),
+            selected: viewer.selected_path().map(Path::to_path_buf),
+            filter: viewer.filter.clone(),
+            scope: viewer.scope.clone(),
+            view: viewer.view,
+            flaky_only: viewer.flaky_only,
+            reviews: viewer.reviews.clone(),
+            review_versions: viewer.review_versions.clone(),
+        })
+    }
+
+    /// `None` if it's gone since, e.g. a deleted baseline.
+    pub fn source(&self) -> Option<DiffSource> {
+        self.source.source()
+    }
+}
@@ -1,6 +1,10 @@
+use crate::config::Profile;
 use crate::diff_image_loader::DiffOptions;
-use crate::github::auth::AuthState;
-use eframe::egui::TextureFilter;
+use crate::path_filter::PathFilterSettings;
+use crate::workspace::Workspace;
+use eframe::egui::{Color32, TextureFilter};
+use kitdiff_core::diff::MinChange;
+use std::collections::BTreeMap;
 
 #[derive(Debug, Clone, PartialEq, Eq, serde::Serialize, serde::Deserialize)]
 pub enum ImageMode {
@@ -8,16 +12,116 @@ pub enum ImageMode {
     Fit,
 }
 
+/// What is shown behind transparent parts of snapshots.
+#[derive(Debug, Clone, Copy, Default, PartialEq, serde::Serialize, serde::Deserialize)]
+pub enum Background {
+    /// The panel color of the ui theme.
+    #[default]
+    Theme,
+
+    /// Alternating light and dark squares, `cell_size` points wide.
+    Checkerboard {
+        cell_size: f32,
+    },
+    Color(Color32),
+}
+
+/// The Jenkins server to log in to, see [`crate::loaders::jenkins_loader`]. The API token is kept
+/// with the other secrets in [`crate::github::auth::AuthState`].
+#[derive(Debug, Clone, Default, PartialEq, Eq, serde::Serialize, serde::Deserialize)]
+#[serde(default)]
+pub struct JenkinsSettings {
+    /// e.g. `https://jenkins.example/`, the login is only sent to links on this server.
+    pub url: String,
+
+    pub user: String,
+
+    /// Where the API token used to be stored, moved to the keychain on startup.
+    #[serde(rename = "api_token", skip_serializing)]
+    pub legacy_api_token: String,
+}
+
+/// How the snapshots around the active one are loaded ahead of time, see [`crate::prefetch`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq, serde::Serialize, serde::Deserialize)]
+#[serde(default)]
+pub struct PrefetchSettings {
+    /// Snapshots before and after the active one.
+    pub window: u32,
+
+    /// Images loading ahead of time at once.
+    pub max_in_flight: u32,
+
+    /// Prefetch in the browser, where every image may be a download.
+    pub on_web: bool,
+
+    /// Prefetch when the browser reports a metered connection or data saver.
+    pub on_metered: bool,
+}
+
+impl Default for PrefetchSettings {
+    fn default() -> Self {
+        Self {
+            window: 10,
+            max_in_flight: 8,
+            on_web: true,
+            on_metered: false,
+        }
+    }
+}
+
 #[derive(Debug, Clone, PartialEq, serde::Serialize, serde::Deserialize)]
+#[serde(default)]
 pub struct Settings {
     pub new_opacity: f32,
     pub diff_opacity: f32,
     pub mode: ImageMode,
     pub texture_magnification: TextureFilter,
+    pub background: Background,
     pub use_original_diff: bool,
     pub options: DiffOptions,
-    #[serde(default)]
-    pub auth: AuthState,
+
+    /// Once loaded images take more than this, the least recently viewed snapshots are unloaded.
+    pub memory_budget_mb: u32,
+
+    pub prefetch: PrefetchSettings,
+
+    /// Command to compare two files with, e.g. `code --diff {old} {new}`.
+    pub external_diff_tool: String,
+
+    /// Seconds between switching images in [`crate::state::View::Flip`].
+    pub flip_interval: f32,
+
+    /// Named profiles, see [`crate::config::Config::profile`].
+    pub profiles: BTreeMap<String, Profile>,
+
+    /// Which snapshots are discovered when loading a source.
+    pub path_filter: PathFilterSettings,
+
+    /// Sources that are opened together, by name.
+    pub workspaces: BTreeMap<String, Workspace>,
+
+    /// Show `linux/…`, `macos/…` and `windows/…` variants of a snapshot as one entry.
+    pub group_platforms: bool,
+
+    /// List snapshots whose old and new images are identical, see
+    /// [`crate::snapshot::Snapshot::unchanged`].
+    pub show_unchanged: bool,
+
+    /// Leave snapshots with smaller diffs out of the list.
+    pub min_change: MinChange,
+
+    /// Stronger text and borders, see [`crate::theme`].
+    pub high_contrast: bool,
+
+    /// Actions that write or delete files only list what they would change, see
+    /// [`crate::confirm`].
+    pub read_only: bool,
+
+    pub jenkins: JenkinsSettings,
+
+    /// Where the CircleCI token used to be stored, moved to the keychain on startup.
+    #[serde(rename = "circleci_token", skip_serializing)]
+    pub legacy_circleci_token: String,
 }
 
 impl Default for Settings {
@@ -27,9 +131,43 @@ impl Default for Settings {
             diff_opacity: 0.25,
             mode: ImageMode::Fit,
             texture_magnification: TextureFilter::Nearest,
+            background: Background::default(),
             use_original_diff: true,
             options: DiffOptions::default(),
-            auth: Default::default(),
+            memory_budget_mb: 2048,
+            prefetch: PrefetchSettings::default(),
+            external_diff_tool: String::new(),
+            flip_interval: 0.5,
+            profiles: BTreeMap::new(),
+            path_filter: PathFilterSettings::default(),
+            workspaces: BTreeMap::new(),
+            group_platforms: true,
+            show_unchanged: false,
+            min_change: MinChange::default(),
+            high_contrast: false,
+            read_only: false,
+            jenkins: JenkinsSettings::default(),
+            legacy_circleci_token: String::new(),
         }
     }
 }
+
+impl Settings {
+    /// The current values of the settings that can be set per profile.
+    pub fn profile(&self) -> Profile {
+        Profile {
+            options: self.options,
+            use_original_diff: self.use_original_diff,
+        }
+    }
+
+    pub fn apply_profile(&mut self, profile: &Profile) {
+        // The palette is a personal preference, profiles shipped with a repository shouldn't
+        // change it.
+        self.options = DiffOptions {
+            palette: self.options.palette,
+            ..profile.options
+        };
+        self.use_original_diff = profile.use_original_diff;
+    }
+}
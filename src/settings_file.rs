@@ -0,0 +1,76 @@
+//! Sharing settings as a JSON file, e.g. so a team can agree on thresholds and profiles.
+//!
+//! Exported files never contain the GitHub login or the Jenkins and CircleCI API tokens, they
+//! aren't part of [`Settings`]. Importing keeps the local Jenkins server, external diff tool and
+//! memory budget.
+
+use crate::notifications::Notification;
+use crate::settings::Settings;
+use crate::state::{AppStateRef, SystemCommand};
+
+const FILE_NAME: &str = "kitdiff-settings.json";
+
+pub fn export(state: &AppStateRef<'_>) {
+    let settings = state.persist();
+    let json = match serde_json::to_vec_pretty(&settings) {
+        Ok(json) => json,
+        Err(err) => {
+            state.send(Notification::error("Failed to export settings", err));
+            return;
+        }
+    };
+
+    #[cfg(not(target_arch = "wasm32"))]
+    {
+        let tx = state.tx.clone();
+        tokio::spawn(async move {
+            let Some(file) = rfd::AsyncFileDialog::new()
+                .set_title("Export settings")
+                .set_file_name(FILE_NAME)
+                .add_filter("JSON", &["json"])
+                .save_file()
+                .await
+            else {
+                return;
+            };
+            if let Err(err) = tokio::fs::write(file.path(), json).await {
+                tx.send(Notification::error("Failed to export settings", err).into())
+                    .ok();
+            }
+        });
+    }
+
+    #[cfg(target_arch = "wasm32")]
+    if let Err(err) = crate::web_loaders::download(FILE_NAME, "application/json", &json) {
+        state.send(Notification::error("Failed to export settings", err));
+    }
+}
+
+pub fn import(state: &AppStateRef<'_>) {
+    let tx = state.tx.clone();
+    let import = async move {
+        let Some(file) = rfd::AsyncFileDialog::new()
+            .set_title("Import settings")
+            .add_filter("JSON", &["json"])
+            .pick_file()
+            .await
+        else {
+            return;
+        };
+        let command = match parse(&file.read().await) {
+            Ok(settings) => SystemCommand::ImportSettings(settings),
+            Err(err) => Notification::error("Failed to import settings", err).into(),
+        };
+        tx.send(command).ok();
+    };
+
+    #[cfg(not(target_arch = "wasm32"))]
+    tokio::spawn(import);
+    #[cfg(target_arch = "wasm32")]
+    wasm_bindgen_futures::spawn_local(import);
+}
+
+/// Missing fields get their defaults, so files from older versions still import.
+fn parse(json: &[u8]) -> serde_json::Result<Settings> {
+    serde_json::from_slice(json)
+}
@@ -0,0 +1,90 @@
+//! One copy of every in-memory image, however many snapshots have it.
+//!
+//! Artifacts are full of identical images, e.g. the unchanged screens of every platform. In-memory
+//! files get a `bytes://` uri by their content, so besides the bytes, the decoded image and the
+//! texture are only kept once too.
+//!
+//! [`SharedBytesLoader`] serves those uris for as long as a snapshot has the bytes, so closing a
+//! source frees them, unlike `include_bytes`, which keeps them in the context for good.
+
+use eframe::egui::Context;
+use eframe::egui::load::{Bytes, BytesLoadResult, BytesLoader, BytesPoll, LoadError};
+use eframe::egui::mutex::Mutex;
+use eframe::epaint::ahash::HashMap;
+use kitdiff_core::archive::content_hash;
+use std::sync::{Arc, LazyLock, Weak};
+
+const PREFIX: &str = "bytes://";
+
+/// By content hash. Weak, the snapshots keep the bytes alive.
+static SHARED: LazyLock<Mutex<HashMap<u64, Weak<[u8]>>>> = LazyLock::new(Mutex::default);
+
+/// The uri for `bytes`, and the copy of them to keep. `extension` tells the image loaders the
+/// format.
+pub fn share(bytes: Arc<[u8]>, extension: &str) -> (String, Arc<[u8]>) {
+    let mut hash = content_hash(&bytes);
+    let mut shared = SHARED.lock();
+    if shared.len() == shared.capacity() {
+        // Make room from the images nothing has anymore before growing the map.
+        shared.retain(|_, weak| weak.strong_count() > 0);
+    }
+    let bytes = loop {
+        match shared.get(&hash).and_then(Weak::upgrade) {
+            // Compares the length, then the bytes.
+            Some(existing) if existing == bytes => break existing,
+            // Another image with the same hash keeps its uri, this one takes the next free one.
+            Some(_) => hash = hash.wrapping_add(1),
+            None => {
+                shared.insert(hash, Arc::downgrade(&bytes));
+                break bytes;
+            }
+        }
+    };
+    (format!("{PREFIX}{hash:016x}.{extension}"), bytes)
+}
+
+/// The bytes behind a uri from [`share`], if a snapshot still has them.
+fn shared(uri: &str) -> Option<Arc<[u8]>> {
+    let (hash, _extension) = uri.strip_prefix(PREFIX)?.split_once('.')?;
+    let hash = u64::from_str_radix(hash, 16).ok()?;
+    SHARED.lock().get(&hash)?.upgrade()
+}
+
+/// Loads the `bytes://` uris of [`share`]. It doesn't own the bytes, there's nothing to forget.
+pub struct SharedBytesLoader;
+
+impl BytesLoader for SharedBytesLoader {
+    fn id(&self) -> &'static str {
+        "SharedBytesLoader"
+    }
+
+    fn load(&self, _ctx: &Context, uri: &str) -> BytesLoadResult {
+        if !uri.starts_with(PREFIX) {
+            return Err(LoadError::NotSupported);
+        }
+        match shared(uri) {
+            Some(bytes) => Ok(BytesPoll::Ready {
+                size: None,
+                bytes: Bytes::Shared(bytes),
+                mime: None,
+            }),
+            None => Err(LoadError::Loading(format!(
+                "{uri} isn't part of an open source anymore"
+            ))),
+        }
+    }
+
+    fn forget(&self, _uri: &str) {}
+
+    fn forget_all(&self) {}
+
+    /// The in-memory images of the open sources.
+    fn byte_size(&self) -> usize {
+        SHARED
+            .lock()
+            .values()
+            .filter_map(Weak::upgrade)
+            .map(|bytes| bytes.len())
+            .sum()
+    }
+}
@@ -1,9 +1,13 @@
+use crate::archive_index::ArchiveEntry;
 use crate::diff_image_loader::DiffOptions;
 use crate::state::{AppStateRef, PageRef};
-use crate::{diff_image_loader, state::View};
+use crate::thumbnail_loader::thumbnail_uri;
+use crate::{diff_image_loader, shared_bytes, state::View};
 use eframe::egui;
 use eframe::egui::{Color32, ImageSource};
-use std::path::PathBuf;
+use kitdiff_core::diff::DiffPalette;
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
 
 #[derive(Debug, Clone)]
 pub struct Snapshot {
@@ -13,15 +17,71 @@ pub struct Snapshot {
     /// If only new is set, the file was added.
     pub new: Option<FileReference>,
     pub diff: Option<FileReference>,
+
+    /// See [`kitdiff_core::Snapshot::unchanged`].
+    pub unchanged: bool,
+
+    /// Where the old image was, if the snapshot was moved here from another path.
+    pub renamed_from: Option<PathBuf>,
+}
+
+/// The images a snapshot had when it was reviewed, see [`Snapshot::version`].
+#[derive(Debug, Clone, PartialEq, Eq, serde::Serialize, serde::Deserialize)]
+pub struct SnapshotVersion {
+    old: Option<String>,
+    new: Option<String>,
 }
 
 #[derive(Debug, Clone)]
 pub enum FileReference {
     Path(PathBuf),
     Source(ImageSource<'static>),
+
+    /// In an archive opened from its index, extracted when it's loaded.
+    Archive(ArchiveEntry),
+}
+
+impl From<kitdiff_core::FileReference> for FileReference {
+    fn from(file: kitdiff_core::FileReference) -> Self {
+        match file {
+            kitdiff_core::FileReference::Path(path) => Self::Path(path),
+            kitdiff_core::FileReference::Url(url) => match ArchiveEntry::from_uri(&url) {
+                Some(entry) => Self::Archive(entry),
+                None => Self::Source(ImageSource::Uri(url.into())),
+            },
+            kitdiff_core::FileReference::Bytes { name, bytes } => Self::from_bytes(&name, bytes),
+        }
+    }
+}
+
+impl From<kitdiff_core::Snapshot> for Snapshot {
+    fn from(snapshot: kitdiff_core::Snapshot) -> Self {
+        Self {
+            path: snapshot.path,
+            old: snapshot.old.map(Into::into),
+            new: snapshot.new.map(Into::into),
+            diff: snapshot.diff.map(Into::into),
+            unchanged: snapshot.unchanged,
+            renamed_from: None,
+        }
+    }
 }
 
 impl FileReference {
+    /// An in-memory file, with a uri by its content, see [`crate::shared_bytes`]. `name` is only
+    /// used for its extension.
+    pub fn from_bytes(name: &str, bytes: Arc<[u8]>) -> Self {
+        let extension = Path::new(name)
+            .extension()
+            .and_then(|extension| extension.to_str())
+            .unwrap_or("png");
+        let (uri, bytes) = shared_bytes::share(bytes, extension);
+        Self::Source(ImageSource::Bytes {
+            uri: uri.into(),
+            bytes: egui::load::Bytes::Shared(bytes),
+        })
+    }
+
     pub fn to_uri(&self) -> String {
         match self {
             Self::Path(path) => format!("file://{}", path.display()),
@@ -29,10 +89,60 @@ impl FileReference {
                 ImageSource::Bytes { uri, .. } | ImageSource::Uri(uri) => uri.to_string(),
                 ImageSource::Texture(_) => "unknown://unknown".to_owned(),
             },
+            Self::Archive(entry) => entry.uri(),
+        }
+    }
+
+    /// Changes with the content of the file: in-memory and archive uris are made from it, local
+    /// files add their size and modification time.
+    fn version(&self) -> String {
+        let uri = self.to_uri();
+        #[cfg(not(target_arch = "wasm32"))]
+        if let Some(path) = uri.strip_prefix("file://")
+            && let Ok(metadata) = std::fs::metadata(path)
+        {
+            let modified = metadata
+                .modified()
+                .ok()
+                .and_then(|time| time.duration_since(std::time::UNIX_EPOCH).ok())
+                .unwrap_or_default();
+            return format!("{uri}#{}-{}", metadata.len(), modified.as_nanos());
         }
+        uri
+    }
+
+    /// The encoded image, `None` for images that only exist as a texture.
+    pub async fn read(&self) -> anyhow::Result<Option<Vec<u8>>> {
+        Ok(Some(match self {
+            Self::Path(path) => read_file(path).await?,
+            Self::Source(ImageSource::Bytes { bytes, .. }) => bytes.to_vec(),
+            Self::Source(ImageSource::Uri(uri)) => match uri.strip_prefix("file://") {
+                Some(path) => read_file(std::path::Path::new(path)).await?,
+                None => crate::http::client()
+                    .get(uri.as_ref())
+                    .send()
+                    .await?
+                    .error_for_status()?
+                    .bytes()
+                    .await?
+                    .to_vec(),
+            },
+            Self::Source(ImageSource::Texture(_)) => return Ok(None),
+            Self::Archive(entry) => entry.read()?,
+        }))
     }
 }
 
+#[cfg(not(target_arch = "wasm32"))]
+async fn read_file(path: &std::path::Path) -> anyhow::Result<Vec<u8>> {
+    Ok(tokio::fs::read(path).await?)
+}
+
+#[cfg(target_arch = "wasm32")]
+async fn read_file(path: &std::path::Path) -> anyhow::Result<Vec<u8>> {
+    anyhow::bail!("Can't read {} in the browser", path.display())
+}
+
 impl Snapshot {
     pub fn file_name(&self) -> std::borrow::Cow<'_, str> {
         self.path
@@ -49,6 +159,14 @@ impl Snapshot {
         self.old.is_some() && self.new.is_none()
     }
 
+    /// A review is only valid for the images it was made with, this tells them apart.
+    pub fn version(&self) -> SnapshotVersion {
+        SnapshotVersion {
+            old: self.old.as_ref().map(FileReference::version),
+            new: self.new.as_ref().map(FileReference::version),
+        }
+    }
+
     pub fn old_uri(&self) -> Option<String> {
         self.old.as_ref().map(|p| p.to_uri())
     }
@@ -57,16 +175,12 @@ impl Snapshot {
         self.new.as_ref().map(|p| p.to_uri())
     }
 
-    pub fn register_bytes(&self, ctx: &egui::Context) {
-        if let Some(FileReference::Source(ImageSource::Bytes { bytes, uri })) = &self.old {
-            ctx.include_bytes(uri.clone(), bytes.clone());
-        }
-        if let Some(FileReference::Source(ImageSource::Bytes { bytes, uri })) = &self.new {
-            ctx.include_bytes(uri.clone(), bytes.clone());
-        }
-        if let Some(FileReference::Source(ImageSource::Bytes { bytes, uri })) = &self.diff {
-            ctx.include_bytes(uri.clone(), bytes.clone());
-        }
+    pub fn old_thumbnail_uri(&self) -> Option<String> {
+        self.old_uri().map(|uri| thumbnail_uri(&uri))
+    }
+
+    pub fn new_thumbnail_uri(&self) -> Option<String> {
+        self.new_uri().map(|uri| thumbnail_uri(&uri))
     }
 
     pub fn file_diff_uri(&self) -> Option<String> {
@@ -74,13 +188,16 @@ impl Snapshot {
     }
 
     pub fn diff_uri(&self, use_file_if_available: bool, options: DiffOptions) -> Option<String> {
-        use_file_if_available
+        // Original diffs come in their own colors, so they're only used with the default palette.
+        let use_file = use_file_if_available && options.palette == DiffPalette::default();
+        use_file
             .then(|| self.file_diff_uri())
             .flatten()
             .or_else(|| {
-                self.old_uri()
-                    .zip(self.new_uri())
-                    .map(|(old, new)| diff_image_loader::DiffUri { old, new, options }.to_uri())
+                self.old_uri().zip(self.new_uri()).map(|(old, new)| {
+                    let options = diff_image_loader::options_for(&self.path, options);
+                    diff_image_loader::DiffUri { old, new, options }.to_uri()
+                })
             })
     }
 
@@ -114,8 +231,9 @@ impl Snapshot {
         let PageRef::DiffViewer(vs) = &state.page else {
             return None;
         };
-        let blend_all = vs.view == View::BlendAll;
-        let show_old = vs.view == View::Old;
+        let view = vs.shown_view();
+        let blend_all = view == View::BlendAll;
+        let show_old = view == View::Old;
         (blend_all || show_old)
             .then(|| self.old_uri())
             .flatten()
@@ -126,8 +244,9 @@ impl Snapshot {
         let PageRef::DiffViewer(vs) = &state.page else {
             return None;
         };
-        let blend_all = vs.view == View::BlendAll;
-        let show_new = vs.view == View::New;
+        let view = vs.shown_view();
+        let blend_all = view == View::BlendAll;
+        let show_new = view == View::New;
         (blend_all || show_new)
             .then(|| self.new_uri())
             .flatten()
@@ -138,8 +257,9 @@ impl Snapshot {
         let PageRef::DiffViewer(vs) = &state.page else {
             return None;
         };
-        let blend_all = vs.view == View::BlendAll;
-        let show_diff = vs.view == View::Diff;
+        let view = vs.shown_view();
+        let blend_all = view == View::BlendAll;
+        let show_diff = view == View::Diff;
         (blend_all || show_diff)
             .then(|| self.diff_uri(state.settings.use_original_diff, state.settings.options))
             .flatten()
@@ -1,15 +1,30 @@
-use crate::config::Config;
+use crate::acknowledged::Acknowledgements;
+use crate::annotation::{Annotation, AnnotationTool};
+use crate::config::{Config, Profile};
 use crate::diff_image_loader::DiffImageLoader;
-use crate::github::auth::{GitHubAuth, GithubAuthCommand};
+use crate::diff_precompute::{DiffPrecompute, priority_order};
+use crate::events::{self, Event, LoadEvents};
+use crate::flaky::FlakyTracker;
+use crate::github::auth::{AuthState, GitHubAuth, GithubAuthCommand};
 use crate::github::model::GithubPrLink;
 use crate::github::pr::GithubPr;
-use crate::loaders::SnapshotLoader;
+use crate::loaders::jenkins_loader::JenkinsCredentials;
+use crate::loaders::pinned_loader::{self, Baseline, BaselineSide};
+use crate::loaders::{CancelFlag, SnapshotLoader};
+use crate::memory_budget::MemoryBudget;
+use crate::notifications::{Notification, NotificationCommand, Notifications};
+use crate::prefetch::Prefetch;
+use crate::session::Session;
 use crate::settings::Settings;
-use crate::snapshot::Snapshot;
+use crate::snapshot::{Snapshot, SnapshotVersion};
 use eframe::egui::{self, Context};
 use egui_inbox::UiInboxSender;
 use octocrab::Octocrab;
+use std::collections::{HashMap, HashSet};
 use std::ops::Deref;
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+use std::task::Poll;
 
 pub struct AppState {
     pub github_auth: GitHubAuth,
@@ -17,6 +32,16 @@ pub struct AppState {
     pub settings: Settings,
     pub config: Config,
     pub page: Page,
+    pub notifications: Notifications,
+
+    /// Name of the profile whose values are currently in [`Self::settings`].
+    pub active_profile: Option<String>,
+
+    /// The global values, while they are overridden by a profile.
+    global_profile: Option<Profile>,
+
+    /// What the viewer showed when kitdiff was last closed, offered on the home page.
+    pub previous_session: Option<Session>,
 }
 
 pub enum Page {
@@ -25,22 +50,221 @@ pub enum Page {
 }
 
 pub struct ViewerState {
+    /// What the loader was created from, so it can be recreated with different settings.
+    pub source: crate::DiffSource,
     pub loader: SnapshotLoader,
+
+    /// Cancels the running load, replaced whenever the loader is recreated.
+    pub cancel: CancelFlag,
     pub index: usize,
 
     /// If true, this item will scroll into view.
     pub index_just_selected: bool,
     pub filter: String,
+
+    /// Only show snapshots below this directory.
+    pub scope: Option<PathBuf>,
     pub view: View,
+    pub flip: FlipState,
+    pub precompute: DiffPrecompute,
+    pub memory: MemoryBudget,
+    pub prefetch: Prefetch,
+
+    /// Review decisions, by snapshot path.
+    pub reviews: HashMap<PathBuf, ReviewStatus>,
+
+    /// The images the snapshots in [`Self::reviews`] were reviewed with.
+    pub review_versions: HashMap<PathBuf, SnapshotVersion>,
+
+    pub flaky: FlakyTracker,
+
+    /// Only list the snapshots [`Self::flaky`] knows to be flaky.
+    pub flaky_only: bool,
+
+    /// Diffs acknowledged as known, see [`crate::acknowledged`].
+    pub acknowledged: Acknowledgements,
+
+    /// Leave the snapshots [`Self::acknowledged`] knows out of the list.
+    pub hide_acknowledged: bool,
+    pub events: LoadEvents,
+
+    /// Snapshots shown in their own window, by path so they survive a refresh.
+    pub popouts: Vec<Popout>,
+
+    /// Markup drawn on snapshots, by snapshot path.
+    pub annotations: HashMap<PathBuf, Vec<Annotation>>,
+
+    /// Changes to [`Self::reviews`] and [`Self::annotations`], for undo and redo.
+    pub history: EditHistory,
+
+    /// While set, dragging or clicking on the image draws instead of panning.
+    pub annotation_tool: Option<AnnotationTool>,
+
+    /// Like [`Self::annotation_tool`], only one of them is active at a time.
+    pub measure_tool: Option<MeasureTool>,
+
+    /// Set by [`Self::refresh`] until the new snapshot list is complete.
+    refreshing: Option<Refreshing>,
+}
+
+/// A snapshot in its own window, e.g. to put two snapshots side by side on different monitors.
+pub struct Popout {
+    pub path: PathBuf,
+    pub view: PopoutView,
+}
+
+#[derive(Clone, Copy, Default, PartialEq, Eq)]
+pub enum PopoutView {
+    #[default]
+    SideBySide,
+    Old,
+    New,
+    Diff,
+}
+
+impl PopoutView {
+    pub const ALL: [Self; 4] = [Self::SideBySide, Self::Old, Self::New, Self::Diff];
+}
+
+impl std::fmt::Display for PopoutView {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str(match self {
+            Self::SideBySide => "Old | New",
+            Self::Old => "Old",
+            Self::New => "New",
+            Self::Diff => "Diff",
+        })
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum MeasureTool {
+    /// Drag to measure distances, in pixels of the snapshot.
+    Ruler,
+
+    /// Show the color of the hovered pixel in old, new and diff.
+    ColorPicker,
+}
+
+impl MeasureTool {
+    pub const ALL: [Self; 2] = [Self::Ruler, Self::ColorPicker];
+
+    pub fn icon(self) -> &'static str {
+        match self {
+            Self::Ruler => "📏",
+            Self::ColorPicker => "🎨",
+        }
+    }
+}
+
+impl std::fmt::Display for MeasureTool {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str(match self {
+            Self::Ruler => "Ruler",
+            Self::ColorPicker => "Color picker",
+        })
+    }
+}
+
+/// Undone edits are forgotten beyond this many.
+const MAX_UNDO: usize = 100;
+
+/// A change to the review of a snapshot, with what it replaced.
+enum Edit {
+    Review {
+        path: PathBuf,
+        before: Option<ReviewStatus>,
+        after: Option<ReviewStatus>,
+    },
+    Annotations {
+        path: PathBuf,
+        before: Vec<Annotation>,
+        after: Vec<Annotation>,
+    },
+}
+
+impl Edit {
+    fn path(&self) -> &Path {
+        match self {
+            Self::Review { path, .. } | Self::Annotations { path, .. } => path,
+        }
+    }
+}
+
+/// Review decisions and annotations that can be undone with Ctrl+Z and redone with Ctrl+Shift+Z,
+/// so a snapshot accepted by accident is one key away from where it was.
+#[derive(Default)]
+pub struct EditHistory {
+    undo: Vec<Edit>,
+    redo: Vec<Edit>,
+}
+
+/// What we knew before a refresh, to restore it as the new snapshots come in.
+struct Refreshing {
+    /// Selected again once it shows up, unless the user picks something else first.
+    selected: Option<PathBuf>,
+    previous: HashSet<PathBuf>,
+}
+
+/// Which image [`View::Flip`] currently shows.
+#[derive(Clone, Copy, Default)]
+pub struct FlipState {
+    pub showing_new: bool,
+    last_flip: f64,
 }
 
 impl ViewerState {
-    fn filtered_snapshots(&self) -> Vec<FilteredSnapshot<'_>> {
+    /// The view that is actually shown right now, resolving [`View::Flip`] to old or new.
+    pub fn shown_view(&self) -> View {
+        match self.view {
+            View::Flip if self.flip.showing_new => View::New,
+            View::Flip => View::Old,
+            view => view,
+        }
+    }
+
+    fn update_flip(&mut self, ctx: &Context, interval: f32) {
+        if self.view != View::Flip {
+            return;
+        }
+
+        let (now, pointer_down) = ctx.input(|i| (i.time, i.pointer.any_down()));
+        if pointer_down {
+            // Hold the current image while the user is looking closely.
+            self.flip.last_flip = now;
+            return;
+        }
+
+        let interval = f64::from(interval.max(0.05));
+        let elapsed = now - self.flip.last_flip;
+        if elapsed >= interval {
+            self.flip.showing_new = !self.flip.showing_new;
+            self.flip.last_flip = now;
+            ctx.request_repaint_after_secs(interval as f32);
+        } else {
+            ctx.request_repaint_after_secs((interval - elapsed) as f32);
+        }
+    }
+
+    fn filtered_snapshots(
+        &self,
+        settings: &Settings,
+        diff_image_loader: &DiffImageLoader,
+    ) -> Vec<FilteredSnapshot<'_>> {
         let filter = self.filter.to_lowercase();
         self.loader
             .snapshots()
             .iter()
             .enumerate()
+            .filter(|(_, s)| settings.show_unchanged || !s.unchanged)
+            .filter(|(_, s)| !below_min_change(s, settings, diff_image_loader))
+            .filter(|(_, s)| !self.flaky_only || self.flaky.is_flaky(&s.path))
+            .filter(|(_, s)| !self.hide_acknowledged || !self.acknowledged.is_acknowledged(&s.path))
+            .filter(|(_, s)| {
+                self.scope
+                    .as_ref()
+                    .is_none_or(|scope| s.path.starts_with(scope))
+            })
             .filter(|(_, s)| {
                 if filter.is_empty() {
                     true
@@ -52,7 +276,40 @@ impl ViewerState {
     }
 }
 
-#[derive(Copy, Clone, Default, PartialEq, Eq)]
+/// Whether the snapshot's diff is known to be smaller than [`Settings::min_change`]. Until it's
+/// computed, the snapshot stays in the list.
+fn below_min_change(
+    snapshot: &Snapshot,
+    settings: &Settings,
+    diff_image_loader: &DiffImageLoader,
+) -> bool {
+    settings.min_change.is_active()
+        && !snapshot.unchanged
+        && DiffPrecompute::diff_uri(snapshot, settings.options)
+            .and_then(|uri| diff_image_loader.changed_pixels(&uri))
+            .is_some_and(|changed| settings.min_change.hides(changed.count, changed.fraction))
+}
+
+#[derive(Copy, Clone, Debug, PartialEq, Eq, serde::Serialize, serde::Deserialize)]
+pub enum ReviewStatus {
+    Accepted,
+    Rejected,
+
+    /// Needs a second opinion, e.g. from a designer.
+    Flagged,
+}
+
+impl ReviewStatus {
+    pub fn icon(self) -> &'static str {
+        match self {
+            Self::Accepted => "✔",
+            Self::Rejected => "✖",
+            Self::Flagged => "⚑",
+        }
+    }
+}
+
+#[derive(Copy, Clone, Debug, Default, PartialEq, Eq, serde::Serialize, serde::Deserialize)]
 pub enum View {
     /// View all stacked on each other, with opacity settings.
     #[default]
@@ -66,6 +323,9 @@ pub enum View {
 
     /// View diff
     Diff,
+
+    /// Alternate between old and new, flicker makes small changes easy to spot.
+    Flip,
 }
 
 impl std::fmt::Display for View {
@@ -75,12 +335,13 @@ impl std::fmt::Display for View {
             Self::Old => write!(f, "Old"),
             Self::New => write!(f, "New"),
             Self::Diff => write!(f, "Diff"),
+            Self::Flip => write!(f, "Flip old/new"),
         }
     }
 }
 
 impl View {
-    pub const ALL: [Self; 4] = [Self::BlendAll, Self::Old, Self::New, Self::Diff];
+    pub const ALL: [Self; 5] = [Self::BlendAll, Self::Old, Self::New, Self::Diff, Self::Flip];
 
     pub fn key(self) -> egui::Key {
         match self {
@@ -88,27 +349,110 @@ impl View {
             Self::Old => egui::Key::Num2,
             Self::New => egui::Key::Num3,
             Self::Diff => egui::Key::Num4,
+            Self::Flip => egui::Key::Num5,
         }
     }
 }
 
 impl AppState {
-    pub fn new(settings: Settings, config: Config, sender: UiInboxSender<SystemCommand>) -> Self {
+    pub fn new(
+        settings: Settings,
+        auth: AuthState,
+        config: Config,
+        sender: UiInboxSender<SystemCommand>,
+    ) -> Self {
         Self {
-            github_auth: GitHubAuth::new(settings.auth.clone(), sender),
+            github_auth: GitHubAuth::new(auth, sender),
             github_pr: None,
             settings,
             config,
             page: Page::Home,
+            notifications: Notifications::default(),
+            active_profile: None,
+            global_profile: None,
+            previous_session: None,
+        }
+    }
+
+    /// The session to save, the previous one while nothing that can be restored is open.
+    pub fn session(&self) -> Option<Session> {
+        match &self.page {
+            Page::DiffViewer(viewer) => Session::capture(viewer),
+            Page::Home => None,
         }
+        .or_else(|| self.previous_session.clone())
     }
 
     pub fn persist(&self) -> Settings {
         let mut settings = self.settings.clone();
-        settings.auth = self.github_auth.get_auth_state().clone();
+        if let Some(global) = &self.global_profile {
+            settings.apply_profile(global);
+        }
         settings
     }
 
+    pub fn jenkins_credentials(&self) -> JenkinsCredentials {
+        JenkinsCredentials {
+            url: self.settings.jenkins.url.clone(),
+            user: self.settings.jenkins.user.clone(),
+            api_token: self
+                .github_auth
+                .jenkins_api_token()
+                .unwrap_or_default()
+                .to_owned(),
+        }
+    }
+
+    /// All known profile names, from the settings and the config.
+    pub fn profile_names(&self) -> Vec<&str> {
+        let mut names: Vec<&str> = self
+            .settings
+            .profiles
+            .keys()
+            .chain(self.source_config().profiles.keys())
+            .map(String::as_str)
+            .collect();
+        names.sort_unstable();
+        names.dedup();
+        names
+    }
+
+    /// Config of the open source, falling back to the global config.
+    pub fn source_config(&self) -> &Config {
+        match &self.page {
+            Page::DiffViewer(viewer) => viewer.loader.config().unwrap_or(&self.config),
+            Page::Home => &self.config,
+        }
+    }
+
+    fn set_profile(&mut self, name: Option<String>) {
+        if self.active_profile == name {
+            return;
+        }
+
+        if let Some(global) = self.global_profile.take() {
+            self.settings.apply_profile(&global);
+        }
+        self.active_profile = None;
+
+        let Some(name) = name else {
+            return;
+        };
+        let profile = self
+            .settings
+            .profiles
+            .get(&name)
+            .or_else(|| self.source_config().profiles.get(&name))
+            .cloned();
+        if let Some(profile) = profile {
+            self.global_profile = Some(self.settings.profile());
+            self.settings.apply_profile(&profile);
+            self.active_profile = Some(name);
+        } else {
+            tracing::warn!("Unknown settings profile {name:?}");
+        }
+    }
+
     pub fn reference<'a>(
         &'a self,
         ctx: &'a Context,
@@ -118,7 +462,8 @@ impl AppState {
         let page = match &self.page {
             Page::Home => PageRef::Home,
             Page::DiffViewer(viewer) => {
-                let filtered_snapshots = viewer.filtered_snapshots();
+                let filtered_snapshots =
+                    viewer.filtered_snapshots(&self.settings, diff_image_loader);
 
                 let active_filtered_index = filtered_snapshots
                     .iter()
@@ -215,14 +560,56 @@ pub enum SystemCommand {
     GithubAuth(GithubAuthCommand),
     LoadPrDetails(GithubPrLink),
     UpdateSettings(Settings),
+
+    /// Replace all settings, e.g. with ones shared by a teammate.
+    ImportSettings(Settings),
+    SetProfile(Option<String>),
+    SaveProfile(String),
     ViewerCommand(ViewerSystemCommand),
+    Notification(NotificationCommand),
     Refresh,
+
+    /// Pin the old side of every snapshot to what `side` shows right now, or unpin with `None`.
+    PinBaseline(Option<BaselineSide>),
+
+    /// Open the source of the session and pick up where it was left.
+    RestoreSession(Session),
+
+    /// Close the source and go back to the home page, freeing everything its images hold.
+    Close,
+
+    /// Bring the window to the front, e.g. when another kitdiff handed us its source.
+    Focus,
 }
 
 pub enum ViewerSystemCommand {
     SetFilter(String),
+    SetScope(Option<PathBuf>),
     SelectSnapshot(usize),
+
+    /// Select the snapshot, or select it once it's loaded.
+    SelectPath(PathBuf),
     SetView(View),
+    SetReview(PathBuf, Option<ReviewStatus>),
+    SetFlakyOnly(bool),
+    SetHideAcknowledged(bool),
+
+    /// Show the snapshot in its own window, or focus that window if it is already open.
+    PopOut(PathBuf),
+    SetPopoutView(PathBuf, PopoutView),
+    ClosePopout(PathBuf),
+
+    SetAnnotationTool(Option<AnnotationTool>),
+    SetMeasureTool(Option<MeasureTool>),
+    Annotate(PathBuf, Annotation),
+
+    /// Remove the most recent annotation of the snapshot.
+    UndoAnnotation(PathBuf),
+    ClearAnnotations(PathBuf),
+
+    /// Revert the last review decision or annotation change, see [`EditHistory`].
+    Undo,
+    Redo,
 }
 
 impl From<ViewerSystemCommand> for SystemCommand {
@@ -234,15 +621,83 @@ impl From<ViewerSystemCommand> for SystemCommand {
 impl AppState {
     pub fn handle(&mut self, ctx: &Context, command: SystemCommand) {
         match command {
-            SystemCommand::Open(source) => {
-                let loader = source.load(ctx, self);
+            SystemCommand::Open(mut source) => {
+                // A pinned baseline stays until it's unpinned, so it can be compared against
+                // other sources.
+                if let Page::DiffViewer(viewer) = &self.page
+                    && let crate::DiffSource::Pinned { baseline, .. } = &viewer.source
+                    && !matches!(source, crate::DiffSource::Pinned { .. })
+                {
+                    source = crate::DiffSource::Pinned {
+                        live: Box::new(source),
+                        baseline: baseline.clone(),
+                    };
+                }
+
+                let cancel = CancelFlag::default();
+                let loader = source.clone().load(ctx, self, &cancel);
                 self.page = Page::DiffViewer(ViewerState {
+                    source,
+                    cancel,
                     filter: String::new(),
+                    scope: None,
                     index: 0,
                     index_just_selected: true,
                     loader,
                     view: View::default(),
+                    flip: FlipState::default(),
+                    precompute: DiffPrecompute::default(),
+                    memory: MemoryBudget::default(),
+                    prefetch: Prefetch::default(),
+                    reviews: HashMap::new(),
+                    review_versions: HashMap::new(),
+                    flaky: FlakyTracker::default(),
+                    flaky_only: false,
+                    acknowledged: Acknowledgements::default(),
+                    hide_acknowledged: false,
+                    events: LoadEvents::default(),
+                    popouts: Vec::new(),
+                    annotations: HashMap::new(),
+                    history: EditHistory::default(),
+                    annotation_tool: None,
+                    measure_tool: None,
+                    refreshing: None,
                 });
+
+                let profile = self.source_config().profile.clone();
+                self.set_profile(profile);
+            }
+            SystemCommand::RestoreSession(session) => {
+                let Some(source) = session.source() else {
+                    self.notifications.handle(
+                        ctx,
+                        NotificationCommand::Notify(Notification::error(
+                            "Failed to restore the session",
+                            format!("{} can't be opened anymore", session.title),
+                        )),
+                    );
+                    return;
+                };
+                self.handle(ctx, SystemCommand::Open(source));
+                if let Page::DiffViewer(viewer) = &mut self.page {
+                    viewer.restore(session);
+                }
+            }
+            SystemCommand::Close => {
+                let Page::DiffViewer(viewer) = &self.page else {
+                    return;
+                };
+                // Offered on the home page, so the review can be picked up again.
+                if let Some(session) = Session::capture(viewer) {
+                    self.previous_session = Some(session);
+                }
+                // Dropping the viewer cancels its loading.
+                self.page = Page::Home;
+                self.github_pr = None;
+                ctx.forget_all_images();
+
+                let profile = self.source_config().profile.clone();
+                self.set_profile(profile);
             }
             SystemCommand::GithubAuth(auth) => {
                 self.github_auth.handle(ctx, auth);
@@ -250,17 +705,86 @@ impl AppState {
             SystemCommand::LoadPrDetails(url) => {
                 self.github_pr = Some(GithubPr::new(url, self.github_auth.client()));
             }
-            SystemCommand::UpdateSettings(settings) => {
+            SystemCommand::ImportSettings(mut settings) => {
+                // The Jenkins login stays ours, a shared file shouldn't pick where it's sent. Nor
+                // which command we run, or how much memory this machine can spare.
+                settings.jenkins.clone_from(&self.settings.jenkins);
+                settings
+                    .external_diff_tool
+                    .clone_from(&self.settings.external_diff_tool);
+                settings.memory_budget_mb = self.settings.memory_budget_mb;
+                let filter_changed = settings.path_filter != self.settings.path_filter;
+                self.settings = settings;
+                self.active_profile = None;
+                self.global_profile = None;
+                if filter_changed {
+                    self.reload(ctx);
+                }
+            }
+            SystemCommand::UpdateSettings(mut settings) => {
+                // Changes to profile values go to the profile, not the global defaults.
+                if let Some(name) = &self.active_profile {
+                    settings.profiles.insert(name.clone(), settings.profile());
+                }
+                let filter_changed = settings.path_filter != self.settings.path_filter;
                 self.settings = settings;
+
+                // Discovery has to run again to pick up the new globs.
+                if filter_changed {
+                    self.reload(ctx);
+                }
+            }
+            SystemCommand::PinBaseline(side) => {
+                if let Page::DiffViewer(viewer) = &mut self.page {
+                    let baseline = side.map(|side| {
+                        Arc::new(Baseline::capture(
+                            viewer.loader.files_header(),
+                            viewer.loader.snapshots(),
+                            side,
+                        ))
+                    });
+                    let live = match viewer.source.clone() {
+                        crate::DiffSource::Pinned { live, .. } => *live,
+                        source => source,
+                    };
+                    viewer.source = match &baseline {
+                        Some(baseline) => crate::DiffSource::Pinned {
+                            live: Box::new(live),
+                            baseline: Arc::clone(baseline),
+                        },
+                        None => live,
+                    };
+                    viewer.pin(baseline);
+                }
+            }
+            SystemCommand::SetProfile(name) => {
+                self.set_profile(name);
+            }
+            SystemCommand::SaveProfile(name) => {
+                let profile = self.settings.profile();
+                self.settings.profiles.insert(name.clone(), profile.clone());
+                if self.global_profile.is_none() {
+                    self.global_profile = Some(profile);
+                }
+                self.active_profile = Some(name);
             }
 
             SystemCommand::ViewerCommand(command) => {
                 if let Page::DiffViewer(viewer) = &mut self.page {
                     viewer.handle(ctx, command);
                 } else {
-                    log::warn!("Received ViewerCommand but not in DiffViewer page");
+                    tracing::warn!("Received ViewerCommand but not in DiffViewer page");
+                }
+            }
+            SystemCommand::Notification(command) => {
+                if let Some(retry) = self.notifications.handle(ctx, command) {
+                    self.handle(ctx, retry);
                 }
             }
+            SystemCommand::Focus => {
+                ctx.send_viewport_cmd(egui::ViewportCommand::Minimized(false));
+                ctx.send_viewport_cmd(egui::ViewportCommand::Focus);
+            }
             SystemCommand::Refresh => match &mut self.page {
                 Page::Home => {}
                 Page::DiffViewer(viewer) => {
@@ -271,14 +795,97 @@ impl AppState {
         }
     }
 
+    /// Creates the viewer's loader again from its source, e.g. after the path filter changed.
+    fn reload(&mut self, ctx: &Context) {
+        let Page::DiffViewer(viewer) = &self.page else {
+            return;
+        };
+        let cancel = CancelFlag::default();
+        let loader = viewer.source.clone().load(ctx, self, &cancel);
+        if let Page::DiffViewer(viewer) = &mut self.page {
+            viewer.replace_loader(loader, cancel);
+        }
+    }
+
     pub fn update(&mut self, ctx: &Context) {
         if let Page::DiffViewer(viewer) = &mut self.page {
             viewer.loader.update(ctx);
             viewer.index_just_selected = false;
+            viewer.update_refreshing();
+
+            // Errors from cancelling are expected.
+            if let Poll::Ready(Err(err)) = viewer.loader.state()
+                && !viewer.cancel.is_cancelled()
+            {
+                let notification = Notification::error("Failed to load snapshots", err)
+                    .with_key(format!("loader: {err:#}"))
+                    .with_retry(SystemCommand::Open(viewer.source.clone()));
+                self.notifications
+                    .handle(ctx, NotificationCommand::Notify(notification));
+            }
+            viewer.update_flip(ctx, self.settings.flip_interval);
         }
+        crate::diff_image_loader::set_overrides(&self.source_config().overrides);
 
         self.github_auth.update(ctx);
     }
+
+    /// Compute diff stats for the loaded snapshots in the background, load the ones around the
+    /// active one ahead of time and unload the least recently viewed ones when over the memory
+    /// budget.
+    pub fn update_background_work(&mut self, ctx: &Context, diff_image_loader: &DiffImageLoader) {
+        let Page::DiffViewer(viewer) = &mut self.page else {
+            return;
+        };
+
+        let filtered: Vec<usize> = viewer
+            .filtered_snapshots(&self.settings, diff_image_loader)
+            .iter()
+            .map(|(i, _)| *i)
+            .collect();
+        let active_filtered_index = filtered
+            .iter()
+            .position(|i| *i == viewer.index)
+            .unwrap_or(0);
+        let snapshots = viewer.loader.snapshots();
+        let priority = priority_order(&filtered, active_filtered_index, snapshots.len());
+
+        viewer.precompute.update(
+            ctx,
+            diff_image_loader,
+            snapshots,
+            &priority,
+            self.settings.options,
+        );
+        viewer.prefetch.update(
+            ctx,
+            snapshots,
+            &filtered,
+            active_filtered_index,
+            &self.settings,
+        );
+        viewer.flaky.update(
+            &*viewer.loader,
+            &viewer.precompute,
+            diff_image_loader,
+            self.settings.options,
+        );
+        viewer.acknowledged.update(ctx, &*viewer.loader);
+        viewer.events.update(
+            &*viewer.loader,
+            &viewer.precompute,
+            diff_image_loader,
+            self.settings.options,
+        );
+        viewer.memory.update(
+            ctx,
+            snapshots,
+            &priority,
+            snapshots.get(viewer.index),
+            self.settings.memory_budget_mb as usize * 1_000_000,
+            self.settings.options,
+        );
+    }
 }
 
 impl ViewerState {
@@ -288,20 +895,318 @@ impl ViewerState {
                 self.filter = filter;
                 self.index_just_selected = true;
             }
+            ViewerSystemCommand::SetScope(scope) => {
+                self.scope = scope;
+                self.index_just_selected = true;
+            }
             ViewerSystemCommand::SelectSnapshot(index) => {
-                if index < self.loader.snapshots().len() {
+                if let Some(refreshing) = &mut self.refreshing {
+                    refreshing.selected = None;
+                }
+                if let Some(snapshot) = self.loader.snapshots().get(index) {
+                    if index != self.index {
+                        events::emit(&Event::Selected {
+                            path: &snapshot.path,
+                        });
+                    }
                     self.index = index;
                     self.index_just_selected = true;
                 }
             }
+            ViewerSystemCommand::SelectPath(path) => {
+                if self.loader.state().is_pending() {
+                    let previous = self.loader.snapshots().iter();
+                    self.refreshing
+                        .get_or_insert_with(|| Refreshing {
+                            selected: None,
+                            previous: previous.map(|s| s.path.clone()).collect(),
+                        })
+                        .selected = Some(path.clone());
+                }
+                self.select_path(&path);
+            }
             ViewerSystemCommand::SetView(view_filter) => {
                 self.view = view_filter;
             }
+            ViewerSystemCommand::SetReview(path, status) => {
+                let before = self.reviews.get(&path).copied();
+                if before != status {
+                    self.edit(Edit::Review {
+                        path,
+                        before,
+                        after: status,
+                    });
+                }
+            }
+            ViewerSystemCommand::PopOut(path) => {
+                if !self.popouts.iter().any(|popout| popout.path == path) {
+                    self.popouts.push(Popout {
+                        path,
+                        view: PopoutView::default(),
+                    });
+                }
+            }
+            ViewerSystemCommand::SetPopoutView(path, view) => {
+                if let Some(popout) = self.popouts.iter_mut().find(|popout| popout.path == path) {
+                    popout.view = view;
+                }
+            }
+            ViewerSystemCommand::ClosePopout(path) => {
+                self.popouts.retain(|popout| popout.path != path);
+            }
+            ViewerSystemCommand::SetAnnotationTool(tool) => {
+                if tool.is_some() {
+                    self.measure_tool = None;
+                }
+                self.annotation_tool = tool;
+            }
+            ViewerSystemCommand::SetMeasureTool(tool) => {
+                if tool.is_some() {
+                    self.annotation_tool = None;
+                }
+                self.measure_tool = tool;
+            }
+            ViewerSystemCommand::Annotate(path, annotation) => {
+                let before = self.annotations.get(&path).cloned().unwrap_or_default();
+                let mut after = before.clone();
+                after.push(annotation);
+                self.edit(Edit::Annotations {
+                    path,
+                    before,
+                    after,
+                });
+            }
+            ViewerSystemCommand::UndoAnnotation(path) => {
+                let before = self.annotations.get(&path).cloned().unwrap_or_default();
+                if !before.is_empty() {
+                    let after = before[..before.len() - 1].to_vec();
+                    self.edit(Edit::Annotations {
+                        path,
+                        before,
+                        after,
+                    });
+                }
+            }
+            ViewerSystemCommand::ClearAnnotations(path) => {
+                let before = self.annotations.get(&path).cloned().unwrap_or_default();
+                if !before.is_empty() {
+                    self.edit(Edit::Annotations {
+                        path,
+                        before,
+                        after: Vec::new(),
+                    });
+                }
+            }
+            ViewerSystemCommand::Undo => {
+                if let Some(edit) = self.history.undo.pop() {
+                    self.apply(&edit, true);
+                    self.select_path(edit.path());
+                    self.history.redo.push(edit);
+                }
+            }
+            ViewerSystemCommand::Redo => {
+                if let Some(edit) = self.history.redo.pop() {
+                    self.apply(&edit, false);
+                    self.select_path(edit.path());
+                    self.history.undo.push(edit);
+                }
+            }
+            ViewerSystemCommand::SetFlakyOnly(flaky_only) => {
+                self.flaky_only = flaky_only;
+                self.index_just_selected = true;
+            }
+            ViewerSystemCommand::SetHideAcknowledged(hide) => {
+                self.hide_acknowledged = hide;
+                self.index_just_selected = true;
+            }
+        }
+    }
+
+    fn edit(&mut self, edit: Edit) {
+        self.apply(&edit, false);
+        self.history.undo.push(edit);
+        if self.history.undo.len() > MAX_UNDO {
+            self.history.undo.remove(0);
+        }
+        self.history.redo.clear();
+    }
+
+    /// Sets what `edit` changed to its state before (`undo`) or after it.
+    fn apply(&mut self, edit: &Edit, undo: bool) {
+        match edit {
+            Edit::Review {
+                path,
+                before,
+                after,
+            } => {
+                let status = if undo { *before } else { *after };
+                match status {
+                    Some(status) => {
+                        self.reviews.insert(path.clone(), status);
+                        if let Some(snapshot) =
+                            self.loader.snapshots().iter().find(|s| &s.path == path)
+                        {
+                            self.review_versions
+                                .insert(path.clone(), snapshot.version());
+                        }
+                    }
+                    None => {
+                        self.reviews.remove(path);
+                        self.review_versions.remove(path);
+                    }
+                }
+                events::emit(&Event::Reviewed { path, status });
+            }
+            Edit::Annotations {
+                path,
+                before,
+                after,
+            } => {
+                let annotations = if undo { before } else { after };
+                if annotations.is_empty() {
+                    self.annotations.remove(path);
+                } else {
+                    self.annotations.insert(path.clone(), annotations.clone());
+                }
+            }
         }
     }
 
+    /// Selects the snapshot of an undone or redone edit, so it doesn't happen out of sight.
+    fn select_path(&mut self, path: &Path) {
+        if let Some(index) = self
+            .loader
+            .snapshots()
+            .iter()
+            .position(|snapshot| snapshot.path == path)
+            && index != self.index
+        {
+            events::emit(&Event::Selected { path });
+            self.index = index;
+            self.index_just_selected = true;
+        }
+    }
+
+    /// The selected snapshot, or the one that will be selected again once a refresh finds it.
+    pub fn selected_path(&self) -> Option<&Path> {
+        self.refreshing
+            .as_ref()
+            .and_then(|refreshing| refreshing.selected.as_deref())
+            .or_else(|| {
+                self.loader
+                    .snapshots()
+                    .get(self.index)
+                    .map(|snapshot| snapshot.path.as_path())
+            })
+    }
+
+    /// Applies a saved session to the freshly opened viewer of its source.
+    fn restore(&mut self, session: Session) {
+        self.filter = session.filter;
+        self.scope = session.scope;
+        self.view = session.view;
+        self.flaky_only = session.flaky_only;
+        self.reviews = session.reviews;
+        self.review_versions = session.review_versions;
+        // The snapshots are still loading, selected once the saved one shows up. Reviews of
+        // images that changed since are dropped once they are all there.
+        self.refreshing = Some(Refreshing {
+            selected: session.selected,
+            previous: HashSet::new(),
+        });
+    }
+
+    /// Loads the snapshots again, keeping the selection and reviews of snapshots that are still
+    /// there with the same images.
     pub fn refresh(&mut self, client: Octocrab) {
-        self.loader.refresh(client);
+        self.start_refreshing();
+        self.cancel.cancel();
+        self.cancel = CancelFlag::default();
+        self.loader.refresh(client, self.cancel.clone());
+    }
+
+    /// Like [`Self::refresh`], with a loader created from a different source or settings.
+    pub fn replace_loader(&mut self, loader: SnapshotLoader, cancel: CancelFlag) {
+        self.start_refreshing();
+        self.cancel.cancel();
+        self.loader = loader;
+        self.cancel = cancel;
+    }
+
+    /// Shows the snapshots against `baseline`, or unpins them with `None`, without loading them
+    /// again.
+    pub fn pin(&mut self, baseline: Option<Arc<Baseline>>) {
+        self.start_refreshing();
+        pinned_loader::pin(&mut self.loader, baseline);
+    }
+
+    fn start_refreshing(&mut self) {
+        let snapshots = self.loader.snapshots();
+        self.refreshing = Some(Refreshing {
+            selected: snapshots.get(self.index).map(|s| s.path.clone()),
+            previous: snapshots.iter().map(|s| s.path.clone()).collect(),
+        });
+
         self.index = 0;
+        self.precompute = DiffPrecompute::default();
+        self.flaky.reset();
+        self.acknowledged.reset();
+        self.events.reset();
+    }
+
+    fn update_refreshing(&mut self) {
+        let Some(refreshing) = &mut self.refreshing else {
+            return;
+        };
+        let snapshots = self.loader.snapshots();
+
+        // Snapshots stream in sorted, so the index of the selected one can still move.
+        if let Some(path) = &refreshing.selected
+            && let Some(index) = snapshots.iter().position(|s| &s.path == path)
+            && index != self.index
+        {
+            self.index = index;
+            self.index_just_selected = true;
+        }
+
+        if self.loader.state().is_ready() {
+            let current: HashSet<&Path> = snapshots.iter().map(|s| s.path.as_path()).collect();
+            let added = current
+                .iter()
+                .filter(|path| !refreshing.previous.contains(**path))
+                .count();
+            let removed = refreshing
+                .previous
+                .iter()
+                .filter(|path| !current.contains(path.as_path()))
+                .count();
+            // Reviews of snapshots that are gone would count towards the totals forever, those
+            // of images that changed since were about something else.
+            let versions: HashMap<&Path, SnapshotVersion> = snapshots
+                .iter()
+                .filter(|s| self.reviews.contains_key(&s.path))
+                .map(|s| (s.path.as_path(), s.version()))
+                .collect();
+            let reviewed = self.reviews.len();
+            self.reviews.retain(|path, _| {
+                versions
+                    .get(path.as_path())
+                    .is_some_and(|version| self.review_versions.get(path) == Some(version))
+            });
+            self.review_versions
+                .retain(|path, _| self.reviews.contains_key(path));
+            tracing::info!(
+                "Refreshed: {added} new snapshots, {removed} gone, {} reviews reset",
+                reviewed - self.reviews.len()
+            );
+            self.refreshing = None;
+        }
+    }
+}
+
+impl Drop for ViewerState {
+    /// Stop loading when the viewer is closed or replaced by another source.
+    fn drop(&mut self) {
+        self.cancel.cancel();
     }
 }
@@ -0,0 +1,72 @@
+//! A high contrast variant of the re_ui style, for reviewers who have trouble with its muted
+//! grays.
+
+use eframe::egui::{Color32, Context, Stroke, Style, Theme};
+use std::sync::Arc;
+
+/// Switches between the re_ui style and its high contrast variant.
+pub struct Themes {
+    /// The styles as re_ui set them up, for the dark and light theme.
+    base: [Arc<Style>; 2],
+    high_contrast: bool,
+}
+
+impl Themes {
+    /// Call after the re_ui style is applied.
+    pub fn new(ctx: &Context) -> Self {
+        Self {
+            base: [ctx.style_of(Theme::Dark), ctx.style_of(Theme::Light)],
+            high_contrast: false,
+        }
+    }
+
+    pub fn update(&mut self, ctx: &Context, high_contrast: bool) {
+        if high_contrast == self.high_contrast {
+            return;
+        }
+        self.high_contrast = high_contrast;
+
+        for (theme, base) in [Theme::Dark, Theme::Light].into_iter().zip(&self.base) {
+            let mut style = (**base).clone();
+            if high_contrast {
+                make_high_contrast(&mut style, theme);
+            }
+            ctx.set_style_of(theme, style);
+        }
+    }
+}
+
+fn make_high_contrast(style: &mut Style, theme: Theme) {
+    let (background, foreground, border) = match theme {
+        Theme::Dark => (Color32::BLACK, Color32::WHITE, Color32::from_gray(200)),
+        Theme::Light => (Color32::WHITE, Color32::BLACK, Color32::from_gray(40)),
+    };
+    let visuals = &mut style.visuals;
+
+    visuals.override_text_color = Some(foreground);
+    visuals.panel_fill = background;
+    visuals.window_fill = background;
+    visuals.extreme_bg_color = background;
+    visuals.faint_bg_color = background;
+    visuals.window_stroke = Stroke::new(1.0, border);
+    visuals.hyperlink_color = match theme {
+        Theme::Dark => Color32::from_rgb(120, 200, 255),
+        Theme::Light => Color32::from_rgb(0, 60, 200),
+    };
+    visuals.selection.bg_fill = Color32::from_rgb(0, 90, 200);
+    visuals.selection.stroke = Stroke::new(1.0, Color32::WHITE);
+
+    for widget in [
+        &mut visuals.widgets.noninteractive,
+        &mut visuals.widgets.inactive,
+        &mut visuals.widgets.hovered,
+        &mut visuals.widgets.active,
+        &mut visuals.widgets.open,
+    ] {
+        widget.fg_stroke.color = foreground;
+        widget.bg_stroke = Stroke::new(widget.bg_stroke.width.max(1.0), border);
+    }
+    // Hovered and pressed widgets need to stand out without relying on subtle shades.
+    visuals.widgets.hovered.bg_stroke = Stroke::new(2.0, foreground);
+    visuals.widgets.active.bg_stroke = Stroke::new(2.0, foreground);
+}
@@ -0,0 +1,186 @@
+use crate::diff_image_loader::DiffImageLoader;
+#[cfg(not(target_arch = "wasm32"))]
+use crate::file_image_loader::{file_path, read_file};
+use eframe::egui::load::{BytesPoll, ImageLoadResult, ImageLoader, ImagePoll, LoadError};
+use eframe::egui::mutex::Mutex;
+use eframe::egui::{ColorImage, Context, SizeHint};
+use eframe::epaint::ahash::HashMap;
+use std::sync::Arc;
+use std::task::Poll;
+
+/// Thumbnails are at most this many pixels wide or high.
+pub const THUMBNAIL_SIZE: u32 = 256;
+
+const PREFIX: &str = "thumb://";
+
+type ThumbnailMap = HashMap<String, Result<Poll<Arc<ColorImage>>, LoadError>>;
+
+/// Serves downscaled versions of images via `thumb://<uri>`.
+///
+/// Only the small image is kept around, so previews of thousands of snapshots don't hold on to
+/// the full resolution textures. Images that are decoded already, e.g. the snapshot on screen,
+/// are scaled down without decoding the file again.
+pub struct ThumbnailLoader {
+    diff_loader: Arc<DiffImageLoader>,
+    thumbnails: Arc<Mutex<ThumbnailMap>>,
+}
+
+impl ThumbnailLoader {
+    pub fn new(diff_loader: Arc<DiffImageLoader>) -> Self {
+        Self {
+            diff_loader,
+            thumbnails: Arc::default(),
+        }
+    }
+}
+
+pub fn thumbnail_uri(uri: &str) -> String {
+    format!("{PREFIX}{uri}")
+}
+
+impl ImageLoader for ThumbnailLoader {
+    fn id(&self) -> &'static str {
+        "ThumbnailLoader"
+    }
+
+    fn load(&self, ctx: &Context, uri: &str, _size_hint: SizeHint) -> ImageLoadResult {
+        let Some(source_uri) = uri.strip_prefix(PREFIX) else {
+            return Err(LoadError::NotSupported);
+        };
+
+        if let Some(entry) = self.thumbnails.lock().get(uri) {
+            return match entry {
+                Ok(Poll::Ready(image)) => Ok(ImagePoll::Ready {
+                    image: image.clone(),
+                }),
+                Ok(Poll::Pending) => Ok(ImagePoll::Pending { size: None }),
+                Err(err) => Err(err.clone()),
+            };
+        }
+
+        let mut source = self.diff_loader.decoded(source_uri).map(Source::Decoded);
+        #[cfg(not(target_arch = "wasm32"))]
+        if source.is_none()
+            && let Some(path) = file_path(source_uri)
+            && image::ImageFormat::from_path(&path).is_ok()
+        {
+            source = Some(Source::File(path));
+        }
+        let source = match source {
+            Some(source) => source,
+            None => match ctx.try_load_bytes(source_uri)? {
+                BytesPoll::Pending { .. } => return Ok(ImagePoll::Pending { size: None }),
+                BytesPoll::Ready { bytes, .. } => Source::Bytes(bytes.to_vec()),
+            },
+        };
+
+        self.thumbnails
+            .lock()
+            .insert(uri.to_owned(), Ok(Poll::Pending));
+
+        let thumbnails = self.thumbnails.clone();
+        let ctx = ctx.clone();
+        let uri = uri.to_owned();
+        let work = move |source: Result<Source, LoadError>| {
+            let result = source
+                .and_then(make_thumbnail)
+                .map(|image| Poll::Ready(Arc::new(image)));
+            thumbnails.lock().insert(uri, result);
+            ctx.request_repaint();
+        };
+
+        #[cfg(not(target_arch = "wasm32"))]
+        match source {
+            // Reading blocks, so it's done on tokio's blocking threads and only the decoding on
+            // rayon's pool.
+            Source::File(path) => {
+                tokio::task::spawn_blocking(move || {
+                    let source = read_file(&path)
+                        .map(Source::Bytes)
+                        .map_err(|err| LoadError::Loading(format!("{err:#}")));
+                    rayon::spawn(move || work(source));
+                });
+            }
+            source => rayon::spawn(move || work(Ok(source))),
+        }
+        #[cfg(target_arch = "wasm32")]
+        work(Ok(source));
+
+        Ok(ImagePoll::Pending { size: None })
+    }
+
+    fn forget(&self, uri: &str) {
+        self.thumbnails.lock().remove(uri);
+    }
+
+    fn forget_all(&self) {
+        self.thumbnails.lock().clear();
+    }
+
+    fn byte_size(&self) -> usize {
+        self.thumbnails
+            .lock()
+            .values()
+            .map(|entry| match entry {
+                Ok(Poll::Ready(image)) => image.as_raw().len(),
+                _ => 0,
+            })
+            .sum()
+    }
+}
+
+/// What a thumbnail is made from.
+enum Source {
+    /// The pixels egui decoded, premultiplied by alpha.
+    Decoded(Arc<ColorImage>),
+
+    /// A local file, read on a blocking thread before the thumbnail is made.
+    #[cfg(not(target_arch = "wasm32"))]
+    File(std::path::PathBuf),
+
+    /// The encoded file.
+    Bytes(Vec<u8>),
+}
+
+fn make_thumbnail(source: Source) -> Result<ColorImage, LoadError> {
+    match source {
+        Source::Decoded(image) => {
+            let [width, height] = image.size;
+            let image =
+                image::RgbaImage::from_raw(width as u32, height as u32, image.as_raw().to_vec())
+                    .ok_or_else(|| LoadError::Loading("Unexpected image size".to_owned()))?;
+            // Scaling premultiplied colors keeps transparent pixels from bleeding into the rest.
+            let image = downscale(image);
+            Ok(ColorImage::from_rgba_premultiplied(
+                [image.width() as usize, image.height() as usize],
+                image.as_raw(),
+            ))
+        }
+        #[cfg(not(target_arch = "wasm32"))]
+        Source::File(path) => {
+            let bytes = read_file(&path).map_err(|err| LoadError::Loading(format!("{err:#}")))?;
+            make_thumbnail(Source::Bytes(bytes))
+        }
+        Source::Bytes(bytes) => {
+            let image = image::load_from_memory(&bytes)
+                .map_err(|err| LoadError::Loading(err.to_string()))?
+                .to_rgba8();
+            let image = downscale(image);
+            Ok(ColorImage::from_rgba_unmultiplied(
+                [image.width() as usize, image.height() as usize],
+                image.as_raw(),
+            ))
+        }
+    }
+}
+
+fn downscale(image: image::RgbaImage) -> image::RgbaImage {
+    if image.width() > THUMBNAIL_SIZE || image.height() > THUMBNAIL_SIZE {
+        let scale = THUMBNAIL_SIZE as f32 / image.width().max(image.height()) as f32;
+        let width = ((image.width() as f32 * scale).round() as u32).max(1);
+        let height = ((image.height() as f32 * scale).round() as u32).max(1);
+        image::imageops::thumbnail(&image, width, height)
+    } else {
+        image
+    }
+}
@@ -0,0 +1,101 @@
+use crate::diff_image_loader::DiffImageLoader;
+use eframe::egui::load::{ImageLoadResult, ImageLoader, ImagePoll, LoadError};
+use eframe::egui::mutex::Mutex;
+use eframe::egui::{ColorImage, Context, SizeHint};
+use eframe::epaint::ahash::HashMap;
+use std::sync::Arc;
+
+/// Edge length of a tile in image pixels.
+pub const TILE_SIZE: usize = 1024;
+
+const PREFIX: &str = "tile://";
+
+/// Serves square pieces of a large image via `tile://<x>/<y>/<uri>`, where `x` and `y` are tile
+/// indices.
+///
+/// Very large snapshots exceed the GPU texture size limit and are slow to upload in one piece, so
+/// the viewer only requests the tiles that are actually visible.
+pub struct TileLoader {
+    diff_loader: Arc<DiffImageLoader>,
+    tiles: Mutex<HashMap<String, Arc<ColorImage>>>,
+}
+
+impl TileLoader {
+    pub fn new(diff_loader: Arc<DiffImageLoader>) -> Self {
+        Self {
+            diff_loader,
+            tiles: Mutex::default(),
+        }
+    }
+}
+
+pub fn tile_uri(uri: &str, x: usize, y: usize) -> String {
+    format!("{PREFIX}{x}/{y}/{uri}")
+}
+
+fn parse_tile_uri(uri: &str) -> Option<(usize, usize, &str)> {
+    let mut parts = uri.strip_prefix(PREFIX)?.splitn(3, '/');
+    let x = parts.next()?.parse().ok()?;
+    let y = parts.next()?.parse().ok()?;
+    Some((x, y, parts.next()?))
+}
+
+impl ImageLoader for TileLoader {
+    fn id(&self) -> &'static str {
+        "TileLoader"
+    }
+
+    fn load(&self, ctx: &Context, uri: &str, _size_hint: SizeHint) -> ImageLoadResult {
+        let Some((x, y, source_uri)) = parse_tile_uri(uri) else {
+            return Err(LoadError::NotSupported);
+        };
+
+        if let Some(tile) = self.tiles.lock().get(uri) {
+            return Ok(ImagePoll::Ready {
+                image: tile.clone(),
+            });
+        }
+
+        match self
+            .diff_loader
+            .load_source(ctx, source_uri, SizeHint::default())?
+        {
+            ImagePoll::Pending { size } => Ok(ImagePoll::Pending { size }),
+            ImagePoll::Ready { image } => {
+                let min = [x * TILE_SIZE, y * TILE_SIZE];
+                if min[0] >= image.width() || min[1] >= image.height() {
+                    return Err(LoadError::Loading(format!("Tile {x}/{y} is out of bounds")));
+                }
+                let size = [
+                    TILE_SIZE.min(image.width() - min[0]),
+                    TILE_SIZE.min(image.height() - min[1]),
+                ];
+                let tile = Arc::new(image.region_by_pixels(min, size));
+                self.tiles.lock().insert(uri.to_owned(), tile.clone());
+                Ok(ImagePoll::Ready { image: tile })
+            }
+        }
+    }
+
+    fn forget(&self, uri: &str) {
+        let mut tiles = self.tiles.lock();
+        if parse_tile_uri(uri).is_some() {
+            tiles.remove(uri);
+        } else {
+            // Forgetting the source image also drops all of its tiles.
+            tiles.retain(|tile_uri, _| parse_tile_uri(tile_uri).is_none_or(|(_, _, s)| s != uri));
+        }
+    }
+
+    fn forget_all(&self) {
+        self.tiles.lock().clear();
+    }
+
+    fn byte_size(&self) -> usize {
+        self.tiles
+            .lock()
+            .values()
+            .map(|tile| tile.as_raw().len())
+            .sum()
+    }
+}
@@ -0,0 +1,306 @@
+use crate::annotation::{self, Annotation, AnnotationTool, Shape};
+use crate::snapshot::Snapshot;
+use crate::state::{ViewerAppStateRef, ViewerSystemCommand};
+#[cfg(not(target_arch = "wasm32"))]
+use crate::viewer::export_view;
+use eframe::egui::emath::RectTransform;
+use eframe::egui::{
+    self, Color32, CursorIcon, Event, Id, Key, OpenUrl, Order, Pos2, Rect, Sense, TextEdit, Ui,
+    UserData, Vec2, ViewportCommand,
+};
+
+/// What to do with the annotated image once the screenshot arrives.
+#[derive(Clone)]
+enum Export {
+    Copy,
+
+    /// Ask where to save it, suggesting the file name.
+    #[cfg(not(target_arch = "wasm32"))]
+    Save(String),
+
+    /// Copy, then open the pull request so it can be pasted into a review comment. GitHub has no
+    /// api to upload images.
+    ReviewComment,
+}
+
+/// Sent along with the screenshot request, to know which part of the screen to keep.
+struct ExportRequest {
+    export: Export,
+    rect: Rect,
+}
+
+/// A note that is being typed.
+#[derive(Clone)]
+struct PendingText {
+    pos: Pos2,
+    text: String,
+}
+
+fn color_id() -> Id {
+    Id::new("annotation_color")
+}
+
+/// Where the image was shown last frame, for exporting.
+fn image_rect_id() -> Id {
+    Id::new("annotation_image_rect")
+}
+
+pub fn toolbar(ui: &mut Ui, state: &ViewerAppStateRef<'_>, snapshot: &Snapshot) {
+    ui.horizontal_wrapped(|ui| {
+        ui.label("Annotate:");
+        for tool in AnnotationTool::ALL {
+            let selected = state.annotation_tool == Some(tool);
+            if ui
+                .selectable_label(selected, tool.icon())
+                .on_hover_text(tool.to_string())
+                .clicked()
+            {
+                state.app.send(ViewerSystemCommand::SetAnnotationTool(
+                    (!selected).then_some(tool),
+                ));
+            }
+        }
+
+        let mut color = ui.data(|d| d.get_temp(color_id())).unwrap_or(Color32::RED);
+        if ui.color_edit_button_srgba(&mut color).changed() {
+            ui.data_mut(|d| d.insert_temp(color_id(), color));
+        }
+
+        let has_annotations = state
+            .annotations
+            .get(&snapshot.path)
+            .is_some_and(|annotations| !annotations.is_empty());
+        ui.add_enabled_ui(has_annotations, |ui| {
+            if ui.button("Undo").clicked() {
+                state
+                    .app
+                    .send(ViewerSystemCommand::UndoAnnotation(snapshot.path.clone()));
+            }
+            if ui.button("Clear").clicked() {
+                state
+                    .app
+                    .send(ViewerSystemCommand::ClearAnnotations(snapshot.path.clone()));
+            }
+        });
+
+        ui.menu_button("Export", |ui| {
+            if ui.button("Copy image").clicked() {
+                request_export(ui, Export::Copy);
+                ui.close();
+            }
+            #[cfg(not(target_arch = "wasm32"))]
+            if ui.button("Save as PNG…").clicked() {
+                let file_name = snapshot
+                    .path
+                    .file_stem()
+                    .map(|stem| format!("{}.annotated.png", stem.to_string_lossy()))
+                    .unwrap_or_else(|| "annotated.png".to_owned());
+                request_export(ui, Export::Save(file_name));
+                ui.close();
+            }
+            if state.loader.review_url().is_some()
+                && ui
+                    .button("Copy for review comment")
+                    .on_hover_text("Copies the image and opens the pull request to paste it into")
+                    .clicked()
+            {
+                request_export(ui, Export::ReviewComment);
+                ui.close();
+            }
+        });
+    });
+}
+
+/// Draw the annotations of `snapshot` over the image, and let the active tool add new ones.
+///
+/// `image_rect` is where the image is shown on screen, `clip_rect` the part of it that's visible.
+pub fn annotation_layer(
+    ui: &Ui,
+    state: &ViewerAppStateRef<'_>,
+    snapshot: &Snapshot,
+    image_rect: Rect,
+    clip_rect: Rect,
+    image_size: Vec2,
+) {
+    let to_screen = RectTransform::from_to(Rect::from_min_size(Pos2::ZERO, image_size), image_rect);
+    let visible = image_rect.intersect(clip_rect);
+    ui.data_mut(|d| d.insert_temp(image_rect_id(), visible));
+
+    let mut preview = None;
+    if let Some(tool) = state.annotation_tool {
+        preview = draw(ui, state, snapshot, tool, visible, to_screen);
+    }
+
+    let painter = ui.painter_at(visible);
+    if let Some(annotations) = state.annotations.get(&snapshot.path) {
+        annotation::paint(&painter, to_screen, annotations);
+    }
+    if let Some(preview) = preview {
+        annotation::paint(&painter, to_screen, &[preview]);
+    }
+
+    handle_screenshots(ui, state);
+}
+
+/// Handle the input of the active tool. Returns the shape that is being dragged out, if any.
+fn draw(
+    ui: &Ui,
+    state: &ViewerAppStateRef<'_>,
+    snapshot: &Snapshot,
+    tool: AnnotationTool,
+    rect: Rect,
+    to_screen: RectTransform,
+) -> Option<Annotation> {
+    let from_screen = to_screen.inverse();
+    let id = ui.id().with("annotate");
+    let response = ui
+        .interact(rect, id, Sense::click_and_drag())
+        .on_hover_cursor(CursorIcon::Crosshair);
+    let color = ui.data(|d| d.get_temp(color_id())).unwrap_or(Color32::RED);
+    let pointer = ui
+        .input(|i| i.pointer.latest_pos())
+        .map(|pos| from_screen * pos);
+
+    if tool == AnnotationTool::Text {
+        if response.clicked()
+            && let Some(pos) = pointer
+        {
+            let pending = PendingText {
+                pos,
+                text: String::new(),
+            };
+            ui.data_mut(|d| d.insert_temp(id, pending));
+        }
+        text_input(ui, state, snapshot, id, color, to_screen);
+        return None;
+    }
+
+    if ui.input(|i| i.key_pressed(Key::Escape)) {
+        state.app.send(ViewerSystemCommand::SetAnnotationTool(None));
+    }
+
+    if response.drag_started() {
+        ui.data_mut(|d| d.insert_temp(id, pointer));
+    }
+    let start = ui.data(|d| d.get_temp::<Option<Pos2>>(id)).flatten();
+    if response.drag_stopped() {
+        ui.data_mut(|d| d.remove::<Option<Pos2>>(id));
+    }
+
+    let (start, end) = (start?, pointer?);
+    let shape = match tool {
+        AnnotationTool::Rectangle => Shape::Rectangle(Rect::from_two_pos(start, end)),
+        AnnotationTool::Arrow | AnnotationTool::Text => Shape::Arrow {
+            from: start,
+            to: end,
+        },
+    };
+    let annotation = Annotation { shape, color };
+
+    if response.drag_stopped() {
+        state.app.send(ViewerSystemCommand::Annotate(
+            snapshot.path.clone(),
+            annotation,
+        ));
+        None
+    } else {
+        response.dragged().then_some(annotation)
+    }
+}
+
+fn text_input(
+    ui: &Ui,
+    state: &ViewerAppStateRef<'_>,
+    snapshot: &Snapshot,
+    id: Id,
+    color: Color32,
+    to_screen: RectTransform,
+) {
+    let Some(mut pending) = ui.data(|d| d.get_temp::<PendingText>(id)) else {
+        if ui.input(|i| i.key_pressed(Key::Escape)) {
+            state.app.send(ViewerSystemCommand::SetAnnotationTool(None));
+        }
+        return;
+    };
+
+    let (submit, cancel) = egui::Area::new(id.with("text"))
+        .fixed_pos(to_screen * pending.pos)
+        .order(Order::Foreground)
+        .show(ui.ctx(), |ui| {
+            ui.add(
+                TextEdit::singleline(&mut pending.text)
+                    .hint_text("Note, Enter to add")
+                    .desired_width(200.0),
+            )
+            .request_focus();
+            ui.input(|i| (i.key_pressed(Key::Enter), i.key_pressed(Key::Escape)))
+        })
+        .inner;
+
+    if submit && !pending.text.trim().is_empty() {
+        let annotation = Annotation {
+            shape: Shape::Text {
+                pos: pending.pos,
+                text: pending.text.trim().to_owned(),
+            },
+            color,
+        };
+        state.app.send(ViewerSystemCommand::Annotate(
+            snapshot.path.clone(),
+            annotation,
+        ));
+    }
+    if submit || cancel {
+        ui.data_mut(|d| d.remove::<PendingText>(id));
+    } else {
+        ui.data_mut(|d| d.insert_temp(id, pending));
+    }
+}
+
+/// Screenshot the viewport, the image is cut out once it arrives in [`handle_screenshots`].
+///
+/// A screenshot exports what is on screen, annotations included, without rendering them again.
+fn request_export(ui: &Ui, export: Export) {
+    let Some(rect) = ui.data(|d| d.get_temp::<Rect>(image_rect_id())) else {
+        return;
+    };
+    let request = ExportRequest { export, rect };
+    ui.ctx()
+        .send_viewport_cmd(ViewportCommand::Screenshot(UserData::new(request)));
+}
+
+fn handle_screenshots(ui: &Ui, state: &ViewerAppStateRef<'_>) {
+    let screenshots: Vec<_> = ui.input(|i| {
+        i.events
+            .iter()
+            .filter_map(|event| {
+                let Event::Screenshot {
+                    image, user_data, ..
+                } = event
+                else {
+                    return None;
+                };
+                let request = user_data.data.as_ref()?.downcast_ref::<ExportRequest>()?;
+                let image = image.region(&request.rect, Some(i.pixels_per_point));
+                Some((request.export.clone(), image))
+            })
+            .collect()
+    });
+
+    for (export, image) in screenshots {
+        match export {
+            Export::Copy => ui.ctx().copy_image(image),
+            #[cfg(not(target_arch = "wasm32"))]
+            Export::Save(file_name) => {
+                export_view::save_png(state, &image, file_name, "Save annotated image");
+            }
+            Export::ReviewComment => {
+                ui.ctx().copy_image(image);
+                if let Some(url) = state.loader.review_url() {
+                    tracing::info!("Copied the annotated image, paste it into a review comment");
+                    ui.ctx().open_url(OpenUrl::new_tab(url));
+                }
+            }
+        }
+    }
+}
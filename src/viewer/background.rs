@@ -0,0 +1,68 @@
+use crate::settings::Background;
+use eframe::egui::{Color32, Mesh, Rect, Shape, Slider, Ui, Vec2, vec2};
+
+pub fn background_ui(ui: &mut Ui, background: &mut Background) {
+    ui.horizontal_wrapped(|ui| {
+        ui.label("Background:");
+        ui.selectable_value(background, Background::Theme, "Theme");
+        let checkerboard = matches!(background, Background::Checkerboard { .. });
+        if ui.selectable_label(checkerboard, "Checkerboard").clicked() && !checkerboard {
+            *background = Background::Checkerboard { cell_size: 8.0 };
+        }
+        ui.selectable_value(background, Background::Color(Color32::BLACK), "Black");
+        ui.selectable_value(background, Background::Color(Color32::WHITE), "White");
+        let custom = matches!(background, Background::Color(color)
+            if *color != Color32::BLACK && *color != Color32::WHITE);
+        if ui.selectable_label(custom, "Custom").clicked() && !custom {
+            *background = Background::Color(Color32::from_rgb(255, 0, 255));
+        }
+    });
+
+    match background {
+        Background::Theme => {}
+        Background::Checkerboard { cell_size } => {
+            ui.add(
+                Slider::new(cell_size, 2.0..=64.0)
+                    .logarithmic(true)
+                    .text("Cell size"),
+            );
+        }
+        Background::Color(color) => {
+            ui.color_edit_button_srgba(color);
+        }
+    }
+}
+
+/// The background of an image shown at `image_rect`, clipped to `clip_rect`. `None` for the theme
+/// background, which is already there.
+///
+/// Checkerboard cells start at the corner of the image, so they move along with it.
+pub fn shape(background: Background, image_rect: Rect, clip_rect: Rect) -> Option<Shape> {
+    let rect = image_rect.intersect(clip_rect);
+    if !rect.is_positive() {
+        return None;
+    }
+    match background {
+        Background::Theme => None,
+        Background::Color(color) => Some(Shape::rect_filled(rect, 0.0, color)),
+        Background::Checkerboard { cell_size } => {
+            let cell = cell_size.max(2.0);
+            let mut mesh = Mesh::default();
+            mesh.add_colored_rect(rect, Color32::from_gray(204));
+
+            let first = ((rect.min - image_rect.min) / cell).floor();
+            let last = ((rect.max - image_rect.min) / cell).ceil();
+            for y in first.y as i64..last.y as i64 {
+                for x in first.x as i64..last.x as i64 {
+                    if (x + y) % 2 == 0 {
+                        continue;
+                    }
+                    let min = image_rect.min + vec2(x as f32, y as f32) * cell;
+                    let cell_rect = Rect::from_min_size(min, Vec2::splat(cell)).intersect(rect);
+                    mesh.add_colored_rect(cell_rect, Color32::from_gray(153));
+                }
+            }
+            Some(Shape::mesh(mesh))
+        }
+    }
+}
@@ -0,0 +1,229 @@
+//! Saving the images of every listed snapshot at once, e.g. to archive review evidence before
+//! the CI artifacts expire. See [`kitdiff_core::export`] for the layout.
+
+use crate::confirm::{self, Change, ChangeKind};
+use crate::diff_image_loader::DiffOptions;
+use crate::notifications::Notification;
+use crate::snapshot::{FileReference, Snapshot};
+use crate::state::{SystemCommand, ViewerAppStateRef};
+use eframe::egui::load::Bytes;
+use eframe::egui::{ImageSource, Popup, Ui};
+use egui_inbox::UiInboxSender;
+use kitdiff_core::CancelFlag;
+use kitdiff_core::diff::MinChange;
+
+#[derive(Clone, Copy)]
+enum Target {
+    #[cfg(not(target_arch = "wasm32"))]
+    Folder,
+    Zip,
+}
+
+pub fn export_button(ui: &mut Ui, state: &ViewerAppStateRef<'_>) {
+    let response = ui.button("Export…").on_hover_text(confirm::hover_text(
+        state.app,
+        "Save the old, new and diff images of every changed snapshot in the list",
+    ));
+    Popup::menu(&response).show(|ui| {
+        let mut target = None;
+        #[cfg(not(target_arch = "wasm32"))]
+        if ui.button("To a folder…").clicked() {
+            target = Some(Target::Folder);
+        }
+        if ui.button("As a zip archive…").clicked() {
+            target = Some(Target::Zip);
+        }
+
+        if let Some(target) = target {
+            let snapshots: Vec<Snapshot> = state
+                .filtered_snapshots
+                .iter()
+                .map(|(_, snapshot)| (*snapshot).clone())
+                .collect();
+            let changes = snapshots.iter().flat_map(planned_changes).collect();
+            let location = match target {
+                #[cfg(not(target_arch = "wasm32"))]
+                Target::Folder => "in the folder you pick next",
+                Target::Zip => "in the zip archive",
+            };
+            let options = state.app.settings.options;
+            let min_change = state.app.settings.min_change;
+            let tx = state.app.tx.clone();
+            confirm::confirm(
+                ui.ctx(),
+                format!("Export {} snapshots", snapshots.len()),
+                location,
+                changes,
+                move || {
+                    let task = export(snapshots, target, options, min_change, tx);
+                    #[cfg(not(target_arch = "wasm32"))]
+                    tokio::spawn(task);
+                    #[cfg(target_arch = "wasm32")]
+                    wasm_bindgen_futures::spawn_local(task);
+                },
+            );
+            ui.close();
+        }
+    });
+}
+
+/// The files the export may write for a snapshot. Those whose diff turns out to be below the
+/// threshold are skipped.
+fn planned_changes(snapshot: &Snapshot) -> Vec<Change> {
+    if snapshot.unchanged {
+        return Vec::new();
+    }
+    let folder = kitdiff_core::export::folder(&snapshot.path);
+    let diff = snapshot.diff.is_some() || (snapshot.old.is_some() && snapshot.new.is_some());
+    [
+        ("old", snapshot.old.is_some()),
+        ("new", snapshot.new.is_some()),
+        ("diff", diff),
+    ]
+    .into_iter()
+    .filter(|(_, written)| *written)
+    .map(|(name, _)| Change::new(ChangeKind::Add, format!("{folder}/{name}.png")))
+    .collect()
+}
+
+async fn export(
+    snapshots: Vec<Snapshot>,
+    target: Target,
+    options: DiffOptions,
+    min_change: MinChange,
+    tx: UiInboxSender<SystemCommand>,
+) {
+    let result = async {
+        #[cfg(not(target_arch = "wasm32"))]
+        let Some(output) = pick_output(target).await else {
+            return Ok(None);
+        };
+
+        let mut core_snapshots = Vec::with_capacity(snapshots.len());
+        for snapshot in snapshots {
+            core_snapshots.push(to_core(snapshot).await?);
+        }
+
+        let cancel = CancelFlag::default();
+        #[cfg(not(target_arch = "wasm32"))]
+        let summary = tokio::task::spawn_blocking(move || match target {
+            Target::Folder => kitdiff_core::export::export_dir(
+                &core_snapshots,
+                &output,
+                &options,
+                min_change,
+                &cancel,
+            ),
+            Target::Zip => {
+                let file = std::fs::File::create(&output)?;
+                kitdiff_core::export::export_zip(
+                    &core_snapshots,
+                    file,
+                    &options,
+                    min_change,
+                    &cancel,
+                )
+            }
+        })
+        .await??;
+
+        #[cfg(target_arch = "wasm32")]
+        let summary = {
+            let Target::Zip = target;
+            let mut zip = std::io::Cursor::new(Vec::new());
+            let summary = kitdiff_core::export::export_zip(
+                &core_snapshots,
+                &mut zip,
+                &options,
+                min_change,
+                &cancel,
+            )?;
+            crate::web_loaders::download("snapshots.zip", "application/zip", zip.get_ref())?;
+            summary
+        };
+
+        anyhow::Ok(Some(summary))
+    };
+
+    match result.await {
+        Ok(Some(summary)) if summary.failed > 0 => {
+            tx.send(Notification::error("Some snapshots couldn't be exported", summary).into())
+                .ok();
+        }
+        Ok(_) => {}
+        Err(err) => {
+            tx.send(Notification::error("Failed to export snapshots", err).into())
+                .ok();
+        }
+    }
+}
+
+#[cfg(not(target_arch = "wasm32"))]
+async fn pick_output(target: Target) -> Option<std::path::PathBuf> {
+    let dialog = rfd::AsyncFileDialog::new().set_title("Export snapshots");
+    let handle = match target {
+        Target::Folder => dialog.pick_folder().await,
+        Target::Zip => {
+            dialog
+                .set_file_name("snapshots.zip")
+                .add_filter("Zip archive", &["zip"])
+                .save_file()
+                .await
+        }
+    };
+    handle.map(|handle| handle.path().to_path_buf())
+}
+
+/// Core snapshots can only read local files, so anything remote is downloaded first.
+async fn to_core(snapshot: Snapshot) -> anyhow::Result<kitdiff_core::Snapshot> {
+    Ok(kitdiff_core::Snapshot {
+        old: file(snapshot.old).await?,
+        new: file(snapshot.new).await?,
+        diff: file(snapshot.diff).await?,
+        path: snapshot.path,
+        unchanged: snapshot.unchanged,
+    })
+}
+
+async fn file(file: Option<FileReference>) -> anyhow::Result<Option<kitdiff_core::FileReference>> {
+    let Some(file) = file else {
+        return Ok(None);
+    };
+    Ok(Some(match file {
+        FileReference::Path(path) => kitdiff_core::FileReference::Path(path),
+        FileReference::Source(ImageSource::Bytes { uri, bytes }) => {
+            let bytes = match bytes {
+                Bytes::Static(bytes) => bytes.into(),
+                Bytes::Shared(bytes) => bytes,
+            };
+            kitdiff_core::FileReference::Bytes {
+                name: uri.into_owned(),
+                bytes,
+            }
+        }
+        FileReference::Source(ImageSource::Uri(uri)) => {
+            if let Some(path) = uri.strip_prefix("file://") {
+                kitdiff_core::FileReference::Path(path.into())
+            } else {
+                let bytes = crate::http::client()
+                    .get(uri.as_ref())
+                    .send()
+                    .await?
+                    .error_for_status()?
+                    .bytes()
+                    .await?;
+                kitdiff_core::FileReference::Bytes {
+                    name: uri.into_owned(),
+                    bytes: bytes.as_ref().into(),
+                }
+            }
+        }
+        FileReference::Source(ImageSource::Texture(_)) => {
+            anyhow::bail!("Can't export an image that only exists as a texture")
+        }
+        FileReference::Archive(entry) => kitdiff_core::FileReference::Bytes {
+            name: entry.uri(),
+            bytes: entry.read()?.into(),
+        },
+    }))
+}
@@ -0,0 +1,68 @@
+use crate::notifications::Notification;
+use crate::snapshot::Snapshot;
+use crate::state::{View, ViewerAppStateRef};
+use eframe::egui::load::ImagePoll;
+use eframe::egui::{Context, Event, SizeHint, Ui};
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum CopyImage {
+    New,
+    Diff,
+}
+
+/// Copy the decoded image to the system clipboard, so it can be pasted into a chat or issue.
+pub fn copy_image(
+    ctx: &Context,
+    state: &ViewerAppStateRef<'_>,
+    snapshot: &Snapshot,
+    which: CopyImage,
+) {
+    let settings = &state.app.settings;
+    let uri = match which {
+        CopyImage::New => snapshot.new_uri(),
+        CopyImage::Diff => snapshot.diff_uri(settings.use_original_diff, settings.options),
+    };
+    let Some(uri) = uri else {
+        tracing::warn!("{} has no {which:?} image", snapshot.path.display());
+        return;
+    };
+
+    match state
+        .app
+        .diff_image_loader
+        .load_source(ctx, &uri, SizeHint::default())
+    {
+        Ok(ImagePoll::Ready { image }) => ctx.copy_image((*image).clone()),
+        Ok(ImagePoll::Pending { .. }) => {
+            tracing::warn!("The image is still loading, try again in a moment");
+        }
+        Err(err) => state
+            .app
+            .send(Notification::error("Failed to copy image", err)),
+    }
+}
+
+pub fn copy_path(ctx: &Context, snapshot: &Snapshot) {
+    ctx.copy_text(snapshot.path.to_string_lossy().into_owned());
+}
+
+/// Cmd+C copies the image that is currently shown: the diff in diff view, the new image
+/// otherwise.
+pub fn handle_shortcuts(ui: &Ui, state: &ViewerAppStateRef<'_>) {
+    if ui.ctx().wants_keyboard_input() {
+        return;
+    }
+    let Some(snapshot) = state.active_snapshot else {
+        return;
+    };
+
+    let copy = ui.input(|i| i.events.iter().any(|e| matches!(e, Event::Copy)));
+    if copy {
+        let which = if state.view == View::Diff {
+            CopyImage::Diff
+        } else {
+            CopyImage::New
+        };
+        copy_image(ui.ctx(), state, snapshot, which);
+    }
+}
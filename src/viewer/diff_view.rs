@@ -1,8 +1,18 @@
+use crate::http;
 use crate::state::ViewerAppStateRef;
-use eframe::egui::{Image, RichText, SizeHint, Ui};
+use crate::viewer::tiled_image::{display_rect, needs_tiling, paint_preview, paint_tiled};
+use crate::viewer::{annotate, background, measure};
+use eframe::egui::load::ImagePoll;
+use eframe::egui::{
+    Align2, FontId, Image, Rect, RichText, Shape, SizeHint, Stroke, StrokeKind, TextureOptions, Ui,
+    vec2,
+};
 
 pub fn diff_view(ui: &mut Ui, state: &ViewerAppStateRef<'_>) {
-    ui.label("Use 1/2/3 to only show old / new / diff at 100% opacity. Arrow keys to navigate.");
+    ui.label(
+        "Use 2/3/4 to only show old / new / diff at 100% opacity, 5 to flip between old and new. \
+        Arrow keys to navigate.",
+    );
 
     if let Some(snapshot) = state.active_snapshot {
         let diff_uri = snapshot.diff_uri(
@@ -10,7 +20,12 @@ pub fn diff_view(ui: &mut Ui, state: &ViewerAppStateRef<'_>) {
             state.app.settings.options,
         );
 
-        if let Some(info) =
+        if snapshot.deleted() {
+            ui.label(
+                RichText::new("Deleted: this snapshot no longer exists, showing the old image.")
+                    .color(ui.visuals().warn_fg_color),
+            );
+        } else if let Some(info) =
             diff_uri.and_then(|diff_uri| state.app.diff_image_loader.diff_info(&diff_uri))
         {
             if info.diff == 0 {
@@ -25,57 +40,135 @@ pub fn diff_view(ui: &mut Ui, state: &ViewerAppStateRef<'_>) {
             ui.label("No diff info yet...");
         }
 
+        annotate::toolbar(ui, state, snapshot);
+        measure::toolbar(ui, state);
+
         let rect = ui.available_rect_before_wrap();
 
         let old = snapshot.old_image(state.app);
         let new = snapshot.new_image(state.app);
         let diff = snapshot.diff_image(state.app);
 
-        let is_loading = |maybe_image: &Option<Image<'_>>| {
-            maybe_image
-                .as_ref()
-                .map(|img| {
-                    img.load_for_size(ui.ctx(), rect.size())
-                        .is_ok_and(|poll| poll.is_pending())
-                })
-                .unwrap_or(false)
+        // Check the decoded images rather than the textures, so large images don't get uploaded
+        // in one piece just to find out whether they're loaded.
+        let decoded = |maybe_image: &Option<Image<'_>>| {
+            maybe_image.as_ref().and_then(|img| {
+                state
+                    .app
+                    .diff_image_loader
+                    .load_source(ui.ctx(), img.uri()?, SizeHint::default())
+                    .ok()
+            })
         };
-
-        let any_loading = is_loading(&old) || is_loading(&new) || is_loading(&diff);
-
-        if let Some(old) = old {
-            ui.place(rect, old);
+        let polls = [decoded(&old), decoded(&new), decoded(&diff)];
+        // The images on screen skip the queue of downloads the preloading started.
+        for uri in [
+            snapshot.old_uri(),
+            snapshot.new_uri(),
+            snapshot.file_diff_uri(),
+        ]
+        .iter()
+        .flatten()
+        {
+            http::prioritize(uri);
         }
 
-        if let Some(new) = new {
-            ui.place(rect, new);
+        // Painted behind the images once we know where they ended up.
+        let background_index = ui.painter().add(Shape::Noop);
+        let mut images_rect = Rect::NOTHING;
+        let mut shown = None;
+        for (image, poll) in [old, new, diff].into_iter().zip(polls) {
+            if let Some(image) = image {
+                let image_rect = show_image(ui, rect, image, poll.as_ref(), state);
+                if let Some(ImagePoll::Ready { image }) = poll {
+                    images_rect = images_rect.union(image_rect);
+                    shown = Some((image_rect, image.size));
+                }
+            }
+        }
+        if let Some(shape) = background::shape(state.app.settings.background, images_rect, rect) {
+            ui.painter().set(background_index, shape);
         }
 
-        if let Some(diff) = diff {
-            ui.place(rect, diff);
+        if snapshot.deleted() {
+            deleted_placeholder(ui, shown.map_or(rect, |(image_rect, _)| image_rect));
         }
 
-        // Preload surrounding snapshots once our image is loaded
-        if !any_loading {
-            for i in -10..=10 {
-                if let Some((_, surrounding_snapshot)) = state
-                    .filtered_snapshots
-                    .get((state.active_filtered_index as isize + i) as usize)
-                {
-                    if let Some(old_uri) = surrounding_snapshot.old_uri() {
-                        ui.ctx().try_load_image(&old_uri, SizeHint::default()).ok();
-                    }
-                    if let Some(new_uri) = surrounding_snapshot.new_uri() {
-                        ui.ctx().try_load_image(&new_uri, SizeHint::default()).ok();
-                    }
-                    if let Some(diff_uri) = surrounding_snapshot.diff_uri(
-                        state.app.settings.use_original_diff,
-                        state.app.settings.options,
-                    ) {
-                        ui.ctx().try_load_image(&diff_uri, SizeHint::default()).ok();
-                    }
-                }
-            }
+        // Sizes of old and new may differ, annotate the last one, as it's drawn on top.
+        if let Some((image_rect, [width, height])) = shown {
+            let size = vec2(width as f32, height as f32);
+            annotate::annotation_layer(ui, state, snapshot, image_rect, rect, size);
+            measure::measure_layer(ui, state, snapshot, image_rect, rect, size);
         }
     }
 }
+
+fn show_image(
+    ui: &mut Ui,
+    rect: Rect,
+    image: Image<'_>,
+    poll: Option<&ImagePoll>,
+    state: &ViewerAppStateRef<'_>,
+) -> Rect {
+    if let (Some(ImagePoll::Ready { image: decoded }), Some(uri)) = (poll, image.uri())
+        && needs_tiling(ui, decoded.size)
+    {
+        paint_tiled(
+            ui,
+            rect,
+            uri,
+            decoded.size,
+            image.image_options().tint,
+            &state.app.settings.mode,
+            TextureOptions {
+                magnification: state.app.settings.texture_magnification,
+                ..TextureOptions::default()
+            },
+        )
+    } else if let (Some(ImagePoll::Pending { .. }), Some(uri)) = (poll, image.uri())
+        && let Some(target) = state
+            .app
+            .diff_image_loader
+            .image_size(uri)
+            .map(|size| display_rect(ui, rect, size, &state.app.settings.mode))
+        && paint_preview(
+            ui,
+            rect,
+            target,
+            uri,
+            image.image_options().tint,
+            TextureOptions::LINEAR,
+        )
+    {
+        // The blurry thumbnail of an image that was decoded before, until it's decoded again.
+        target
+    } else {
+        ui.place(rect, image).rect
+    }
+}
+
+/// A badge over the old image of a deleted snapshot, or centered in `rect` in views that don't
+/// show the old image.
+fn deleted_placeholder(ui: &Ui, rect: Rect) {
+    let painter = ui.painter_at(rect);
+    let galley = painter.layout_no_wrap(
+        "Deleted".to_owned(),
+        FontId::proportional(24.0),
+        ui.visuals().strong_text_color(),
+    );
+    let badge = Align2::CENTER_CENTER
+        .align_size_within_rect(galley.size(), rect)
+        .expand(8.0);
+    painter.rect(
+        badge,
+        4.0,
+        ui.visuals().panel_fill.gamma_multiply(0.9),
+        Stroke::new(2.0, ui.visuals().warn_fg_color),
+        StrokeKind::Inside,
+    );
+    painter.galley(
+        badge.shrink(8.0).min,
+        galley,
+        ui.visuals().strong_text_color(),
+    );
+}
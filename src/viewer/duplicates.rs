@@ -0,0 +1,284 @@
+//! Finds snapshots whose new images are the same as, or look like, those of other snapshots.
+//! Usually a test that was copied and not changed enough, or one that renders the wrong thing.
+
+use crate::snapshot::Snapshot;
+use crate::state::ViewerAppStateRef;
+use crate::viewer::set_diff::path_link;
+use eframe::egui::mutex::Mutex;
+use eframe::egui::{Id, Modal, ProgressBar, ScrollArea, Ui};
+use futures::StreamExt as _;
+use kitdiff_core::phash;
+use std::collections::HashMap;
+use std::hash::{DefaultHasher, Hash as _, Hasher as _};
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+use std::sync::atomic::{AtomicUsize, Ordering};
+
+/// Stricter than [`phash::SIMILAR_DISTANCE`], different tests of one widget often look alike.
+const MAX_DISTANCE: u32 = 4;
+
+/// How many images are read at once.
+const CONCURRENT_READS: usize = 8;
+
+struct Analysis {
+    checked: AtomicUsize,
+    total: usize,
+    result: Mutex<Option<Vec<Group>>>,
+}
+
+/// Snapshots with the same or near-identical new images.
+struct Group {
+    /// Every pixel is the same, not only the look.
+    identical: bool,
+    paths: Vec<PathBuf>,
+}
+
+/// One analysis per source, so the flags don't stick to another one.
+fn id(state: &ViewerAppStateRef<'_>) -> Id {
+    Id::new("duplicates").with(state.loader.files_header())
+}
+
+fn open_id() -> Id {
+    Id::new("duplicates_open")
+}
+
+pub fn duplicates_button(ui: &mut Ui, state: &ViewerAppStateRef<'_>) {
+    if !ui
+        .button("Duplicates…")
+        .on_hover_text("Find snapshots whose new image is the same as another one's")
+        .clicked()
+    {
+        return;
+    }
+    ui.data_mut(|d| d.insert_temp(open_id(), true));
+    if ui
+        .data(|d| d.get_temp::<Arc<Analysis>>(id(state)))
+        .is_some()
+    {
+        return;
+    }
+
+    let snapshots: Vec<Snapshot> = state
+        .loader
+        .snapshots()
+        .iter()
+        .filter(|snapshot| snapshot.new.is_some())
+        .cloned()
+        .collect();
+    let analysis = Arc::new(Analysis {
+        checked: AtomicUsize::new(0),
+        total: snapshots.len(),
+        result: Mutex::new(None),
+    });
+    ui.data_mut(|d| d.insert_temp(id(state), analysis.clone()));
+
+    let ctx = ui.ctx().clone();
+    hello_egui_utils::spawn(async move {
+        let result = find_duplicates(snapshots, &analysis, &ctx).await;
+        *analysis.result.lock() = Some(result);
+        ctx.request_repaint();
+    });
+}
+
+/// Whether the analysis found the snapshot to look like another one, with a hover text saying
+/// which.
+pub fn duplicate_of(ui: &Ui, state: &ViewerAppStateRef<'_>, path: &Path) -> Option<String> {
+    let analysis = ui.data(|d| d.get_temp::<Arc<Analysis>>(id(state)))?;
+    let result = analysis.result.lock();
+    let group = result
+        .as_ref()?
+        .iter()
+        .find(|group| group.paths.iter().any(|other| other == path))?;
+    let others: Vec<String> = group
+        .paths
+        .iter()
+        .filter(|other| *other != path)
+        .map(|other| other.display().to_string())
+        .collect();
+    Some(format!(
+        "{} {}",
+        if group.identical {
+            "Same pixels as"
+        } else {
+            "Looks like"
+        },
+        others.join(", ")
+    ))
+}
+
+/// The report window, while it is open.
+pub fn duplicates_modal(ui: &Ui, state: &ViewerAppStateRef<'_>) {
+    if !ui
+        .data(|d| d.get_temp::<bool>(open_id()))
+        .unwrap_or_default()
+    {
+        return;
+    }
+    let Some(analysis) = ui.data(|d| d.get_temp::<Arc<Analysis>>(id(state))) else {
+        ui.data_mut(|d| d.remove::<bool>(open_id()));
+        return;
+    };
+
+    let mut close = false;
+    let mut clear = false;
+    let modal = Modal::new(Id::new("duplicates_modal")).show(ui.ctx(), |ui| {
+        ui.set_width(600.0);
+        ui.heading("Duplicate snapshots");
+
+        match &*analysis.result.lock() {
+            None => {
+                let checked = analysis.checked.load(Ordering::Relaxed);
+                ui.add(
+                    ProgressBar::new(checked as f32 / analysis.total.max(1) as f32)
+                        .text(format!("{checked} of {} images", analysis.total)),
+                );
+            }
+            Some(groups) if groups.is_empty() => {
+                ui.label(format!(
+                    "None of the {} new images look like another one.",
+                    analysis.total
+                ));
+            }
+            Some(groups) => {
+                ui.label(format!(
+                    "{} groups of snapshots look alike. They are marked with ⧉ in the list.",
+                    groups.len()
+                ));
+                ScrollArea::vertical().max_height(400.0).show(ui, |ui| {
+                    for group in groups {
+                        ui.separator();
+                        ui.weak(if group.identical {
+                            "Same pixels"
+                        } else {
+                            "Nearly the same"
+                        });
+                        for path in &group.paths {
+                            path_link(ui, state, path);
+                        }
+                    }
+                });
+            }
+        }
+
+        ui.horizontal(|ui| {
+            if ui.button("Close").clicked() {
+                close = true;
+            }
+            if ui
+                .button("Clear")
+                .on_hover_text("Close and remove the marks from the list")
+                .clicked()
+            {
+                clear = true;
+            }
+        });
+    });
+
+    if close || clear || modal.should_close() {
+        ui.data_mut(|d| d.remove::<bool>(open_id()));
+    }
+    if clear {
+        ui.data_mut(|d| d.remove::<Arc<Analysis>>(id(state)));
+    }
+}
+
+/// What is compared of an image.
+struct Hashes {
+    /// Of the decoded pixels, so differently encoded files of one image match.
+    pixels: u64,
+    size: (u32, u32),
+    look: u64,
+}
+
+async fn find_duplicates(
+    snapshots: Vec<Snapshot>,
+    analysis: &Analysis,
+    ctx: &eframe::egui::Context,
+) -> Vec<Group> {
+    let mut hashed: Vec<(PathBuf, Hashes)> = futures::stream::iter(snapshots)
+        .map(|snapshot| async move {
+            let bytes = match &snapshot.new {
+                Some(file) => file.read().await,
+                None => Ok(None),
+            };
+            (snapshot.path, bytes)
+        })
+        .buffer_unordered(CONCURRENT_READS)
+        .filter_map(|(path, bytes)| async move {
+            analysis.checked.fetch_add(1, Ordering::Relaxed);
+            ctx.request_repaint();
+            let result = bytes.and_then(|bytes| bytes.map(|bytes| hashes(&bytes)).transpose());
+            match result {
+                Ok(hashes) => Some((path, hashes?)),
+                Err(err) => {
+                    tracing::warn!("Skipping {}: {err:#}", path.display());
+                    None
+                }
+            }
+        })
+        .collect()
+        .await;
+    hashed.sort_by(|(a, _), (b, _)| a.cmp(b));
+    group(hashed)
+}
+
+fn hashes(bytes: &[u8]) -> anyhow::Result<Hashes> {
+    let image = image::load_from_memory(bytes)?;
+    let mut hasher = DefaultHasher::new();
+    image.to_rgba8().as_raw().hash(&mut hasher);
+    Ok(Hashes {
+        pixels: hasher.finish(),
+        size: (image.width(), image.height()),
+        look: phash::dhash(&image),
+    })
+}
+
+/// Groups images of the same size whose hashes are at most [`MAX_DISTANCE`] apart, also through
+/// other images of the group.
+fn group(hashed: Vec<(PathBuf, Hashes)>) -> Vec<Group> {
+    // Union-find over the indices of `hashed`.
+    let mut parents: Vec<usize> = (0..hashed.len()).collect();
+    for (a, (_, a_hashes)) in hashed.iter().enumerate() {
+        for (b, (_, b_hashes)) in hashed.iter().enumerate().skip(a + 1) {
+            if a_hashes.size == b_hashes.size
+                && phash::distance(a_hashes.look, b_hashes.look) <= MAX_DISTANCE
+            {
+                let (a_root, b_root) = (root(&mut parents, a), root(&mut parents, b));
+                parents[b_root] = a_root;
+            }
+        }
+    }
+
+    let mut members: HashMap<usize, Vec<usize>> = HashMap::new();
+    for index in 0..hashed.len() {
+        members
+            .entry(root(&mut parents, index))
+            .or_default()
+            .push(index);
+    }
+    let mut groups: Vec<Group> = members
+        .into_values()
+        .filter(|indices| indices.len() > 1)
+        .map(|indices| Group {
+            identical: indices
+                .iter()
+                .all(|index| hashed[*index].1.pixels == hashed[indices[0]].1.pixels),
+            paths: indices
+                .iter()
+                .map(|index| hashed[*index].0.clone())
+                .collect(),
+        })
+        .collect();
+    // Exact copies first, they are the most likely mistakes.
+    groups.sort_by(|a, b| b.identical.cmp(&a.identical).then(a.paths.cmp(&b.paths)));
+    groups
+}
+
+/// The representative of the set `index` is in.
+fn root(parents: &mut [usize], mut index: usize) -> usize {
+    while parents[index] != index {
+        parents[index] = parents[parents[index]];
+        index = parents[index];
+    }
+    index
+}
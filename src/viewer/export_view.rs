@@ -0,0 +1,387 @@
+use crate::settings::Background;
+use crate::snapshot::Snapshot;
+use crate::state::{View, ViewerAppStateRef};
+use eframe::egui::load::ImagePoll;
+use eframe::egui::{Color32, ColorImage, Id, SizeHint, Ui};
+use std::sync::Arc;
+use std::time::Duration;
+
+/// Copy or save what the central panel shows, at the resolution of the snapshot rather than the
+/// screen, e.g. for changelogs and release notes.
+pub fn export_menu(ui: &mut Ui, state: &ViewerAppStateRef<'_>, snapshot: &Snapshot) {
+    ui.menu_button("Export view", |ui| {
+        let Some(layers) = layers(ui, state, snapshot) else {
+            ui.weak("Loading images…");
+            return;
+        };
+        let panel_fill = ui.visuals().panel_fill;
+        let composite = || compose(state.app.settings.background, panel_fill, &layers);
+
+        if ui.button("Copy image").clicked() {
+            if let Some(image) = composite() {
+                ui.ctx().copy_image(image);
+            }
+            ui.close();
+        }
+        #[cfg(not(target_arch = "wasm32"))]
+        if ui.button("Save as PNG…").clicked() {
+            if let Some(image) = composite() {
+                let view = state
+                    .shown_view()
+                    .to_string()
+                    .to_lowercase()
+                    .replace(' ', "_");
+                let file_name = snapshot
+                    .path
+                    .file_stem()
+                    .map(|stem| format!("{}.{view}.png", stem.to_string_lossy()))
+                    .unwrap_or_else(|| format!("{view}.png"));
+                save_png(state, &image, file_name, "Export view");
+            }
+            ui.close();
+        }
+
+        ui.separator();
+        animation_menu(ui, state, snapshot);
+    });
+}
+
+/// Old and new alternating in a loop, which shows a change better than any still image.
+fn animation_menu(ui: &mut Ui, state: &ViewerAppStateRef<'_>, snapshot: &Snapshot) {
+    let id = Id::new("export_animation");
+    let (mut format, mut flash_diff): (AnimationFormat, bool) = ui
+        .data(|d| d.get_temp(id))
+        .unwrap_or((AnimationFormat::Gif, false));
+
+    ui.strong("Old ↔ new animation");
+    ui.horizontal(|ui| {
+        for f in AnimationFormat::ALL {
+            ui.selectable_value(&mut format, f, f.name());
+        }
+    });
+    ui.checkbox(&mut flash_diff, "Flash the diff");
+    ui.data_mut(|d| d.insert_temp(id, (format, flash_diff)));
+
+    let settings = &state.app.settings;
+    let mut uris = vec![snapshot.old_uri(), snapshot.new_uri()];
+    if flash_diff {
+        uris.push(snapshot.diff_uri(settings.use_original_diff, settings.options));
+    }
+    let Some(uris) = uris.into_iter().collect::<Option<Vec<_>>>() else {
+        ui.weak("Needs both an old and a new image");
+        return;
+    };
+    let Some(images) = uris
+        .iter()
+        .map(|uri| loaded(ui, state, uri))
+        .collect::<Option<Vec<_>>>()
+    else {
+        ui.weak("Loading images…");
+        return;
+    };
+
+    let label = if cfg!(target_arch = "wasm32") {
+        format!("Download {}", format.name())
+    } else {
+        format!("Save as {}…", format.name())
+    };
+    if ui.button(label).clicked() {
+        let Some(size) = canvas_size(images.iter()) else {
+            return;
+        };
+        let panel_fill = ui.visuals().panel_fill;
+        let frames = images
+            .iter()
+            .map(|image| {
+                let mut frame = fill(settings.background, panel_fill, size);
+                draw(&mut frame, image, 1.0);
+                frame
+            })
+            .collect();
+        let file_name = snapshot
+            .path
+            .file_stem()
+            .map(|stem| format!("{}.flip.{}", stem.to_string_lossy(), format.extension()))
+            .unwrap_or_else(|| format!("flip.{}", format.extension()));
+        let delay = Duration::from_secs_f32(settings.flip_interval.max(0.05));
+        save_animation(state, frames, delay, format, file_name);
+        ui.close();
+    }
+}
+
+/// The decoded images of the shown view with their opacity, bottom to top. `None` while loading.
+fn layers(
+    ui: &Ui,
+    state: &ViewerAppStateRef<'_>,
+    snapshot: &Snapshot,
+) -> Option<Vec<(Arc<ColorImage>, f32)>> {
+    let settings = &state.app.settings;
+    let diff_uri = snapshot.diff_uri(settings.use_original_diff, settings.options);
+    let uris = match state.shown_view() {
+        View::BlendAll => vec![
+            (snapshot.old_uri(), 1.0),
+            (snapshot.new_uri(), settings.new_opacity),
+            (diff_uri, settings.diff_opacity),
+        ],
+        View::Old => vec![(snapshot.old_uri(), 1.0)],
+        View::New => vec![(snapshot.new_uri(), 1.0)],
+        View::Diff => vec![(diff_uri, 1.0)],
+        // Resolved to old or new by shown_view.
+        View::Flip => Vec::new(),
+    };
+
+    let mut layers = Vec::new();
+    for (uri, opacity) in uris {
+        let Some(uri) = uri else {
+            continue;
+        };
+        layers.push((loaded(ui, state, &uri)?, opacity));
+    }
+    Some(layers)
+}
+
+fn loaded(ui: &Ui, state: &ViewerAppStateRef<'_>, uri: &str) -> Option<Arc<ColorImage>> {
+    match state
+        .app
+        .diff_image_loader
+        .load_source(ui.ctx(), uri, SizeHint::default())
+    {
+        Ok(ImagePoll::Ready { image }) => Some(image),
+        Ok(ImagePoll::Pending { .. }) | Err(_) => None,
+    }
+}
+
+/// Blends the layers the same way the central panel does. Images of different sizes are centered,
+/// the result is as large as the largest of them.
+fn compose(
+    background: Background,
+    panel_fill: Color32,
+    layers: &[(Arc<ColorImage>, f32)],
+) -> Option<ColorImage> {
+    let size = canvas_size(layers.iter().map(|(image, _)| image))?;
+    let mut composite = fill(background, panel_fill, size);
+    for (image, opacity) in layers {
+        draw(&mut composite, image, *opacity);
+    }
+    Some(composite)
+}
+
+fn canvas_size<'a>(images: impl Iterator<Item = &'a Arc<ColorImage>>) -> Option<[usize; 2]> {
+    images
+        .map(|image| image.size)
+        .reduce(|[w1, h1], [w2, h2]| [w1.max(w2), h1.max(h2)])
+}
+
+fn fill(background: Background, panel_fill: Color32, [width, height]: [usize; 2]) -> ColorImage {
+    match background {
+        Background::Theme => ColorImage::new([width, height], vec![panel_fill; width * height]),
+        Background::Color(color) => ColorImage::new([width, height], vec![color; width * height]),
+        Background::Checkerboard { cell_size } => {
+            let cell = cell_size.max(2.0) as usize;
+            let pixels = (0..height)
+                .flat_map(|y| {
+                    (0..width).map(move |x| {
+                        if (x / cell + y / cell) % 2 == 0 {
+                            Color32::from_gray(204)
+                        } else {
+                            Color32::from_gray(153)
+                        }
+                    })
+                })
+                .collect();
+            ColorImage::new([width, height], pixels)
+        }
+    }
+}
+
+/// Blend `image` centered onto `target`, with `opacity` like the tint of the on-screen image.
+fn draw(target: &mut ColorImage, image: &ColorImage, opacity: f32) {
+    let [width, height] = image.size;
+    let offset_x = (target.size[0] - width) / 2;
+    let offset_y = (target.size[1] - height) / 2;
+    for y in 0..height {
+        for x in 0..width {
+            let pixel = image.pixels[y * width + x].gamma_multiply(opacity);
+            let index = (y + offset_y) * target.size[0] + x + offset_x;
+            target.pixels[index] = target.pixels[index].blend(pixel);
+        }
+    }
+}
+
+#[cfg(not(target_arch = "wasm32"))]
+pub fn save_png(
+    state: &ViewerAppStateRef<'_>,
+    image: &ColorImage,
+    file_name: String,
+    title: &'static str,
+) {
+    use crate::notifications::Notification;
+
+    let png = match encode_png(image) {
+        Ok(png) => png,
+        Err(err) => {
+            state
+                .app
+                .send(Notification::error("Failed to export image", err));
+            return;
+        }
+    };
+
+    let tx = state.app.tx.clone();
+    tokio::spawn(async move {
+        let Some(file) = rfd::AsyncFileDialog::new()
+            .set_title(title)
+            .set_file_name(file_name)
+            .add_filter("PNG", &["png"])
+            .save_file()
+            .await
+        else {
+            return;
+        };
+        if let Err(err) = tokio::fs::write(file.path(), png).await {
+            tx.send(Notification::error("Failed to save image", err).into())
+                .ok();
+        }
+    });
+}
+
+#[derive(Clone, Copy, PartialEq, Eq)]
+enum AnimationFormat {
+    Gif,
+
+    /// Animated png, without the 256 color limit of gif.
+    Apng,
+}
+
+impl AnimationFormat {
+    const ALL: [Self; 2] = [Self::Gif, Self::Apng];
+
+    fn name(self) -> &'static str {
+        match self {
+            Self::Gif => "GIF",
+            Self::Apng => "APNG",
+        }
+    }
+
+    fn extension(self) -> &'static str {
+        match self {
+            Self::Gif => "gif",
+            Self::Apng => "png",
+        }
+    }
+
+    #[cfg_attr(not(target_arch = "wasm32"), expect(dead_code))]
+    fn mime(self) -> &'static str {
+        match self {
+            Self::Gif => "image/gif",
+            Self::Apng => "image/apng",
+        }
+    }
+}
+
+/// Encoding takes a while for large snapshots, so it runs in the background on native.
+fn save_animation(
+    state: &ViewerAppStateRef<'_>,
+    frames: Vec<ColorImage>,
+    delay: Duration,
+    format: AnimationFormat,
+    file_name: String,
+) {
+    use crate::notifications::Notification;
+
+    #[cfg(not(target_arch = "wasm32"))]
+    {
+        let tx = state.app.tx.clone();
+        tokio::spawn(async move {
+            let Some(file) = rfd::AsyncFileDialog::new()
+                .set_title("Export animation")
+                .set_file_name(file_name)
+                .add_filter(format.name(), &[format.extension()])
+                .save_file()
+                .await
+            else {
+                return;
+            };
+            let result = async {
+                let data =
+                    tokio::task::spawn_blocking(move || encode_animation(&frames, delay, format))
+                        .await??;
+                tokio::fs::write(file.path(), data).await?;
+                anyhow::Ok(())
+            };
+            if let Err(err) = result.await {
+                tx.send(Notification::error("Failed to export animation", err).into())
+                    .ok();
+            }
+        });
+    }
+
+    #[cfg(target_arch = "wasm32")]
+    if let Err(err) = encode_animation(&frames, delay, format)
+        .and_then(|data| crate::web_loaders::download(&file_name, format.mime(), &data))
+    {
+        state
+            .app
+            .send(Notification::error("Failed to export animation", err));
+    }
+}
+
+fn encode_animation(
+    frames: &[ColorImage],
+    delay: Duration,
+    format: AnimationFormat,
+) -> anyhow::Result<Vec<u8>> {
+    let mut data = Vec::new();
+    match format {
+        AnimationFormat::Gif => {
+            use image::codecs::gif::{GifEncoder, Repeat};
+
+            let mut encoder = GifEncoder::new_with_speed(&mut data, 10);
+            encoder.set_repeat(Repeat::Infinite)?;
+            for frame in frames {
+                let [width, height] = frame.size;
+                let rgba = image::RgbaImage::from_raw(
+                    width as u32,
+                    height as u32,
+                    frame.as_raw().to_vec(),
+                )
+                .ok_or_else(|| anyhow::anyhow!("Frame has an unexpected size"))?;
+                encoder.encode_frame(image::Frame::from_parts(
+                    rgba,
+                    0,
+                    0,
+                    image::Delay::from_saturating_duration(delay),
+                ))?;
+            }
+        }
+        AnimationFormat::Apng => {
+            let Some([width, height]) = frames.first().map(|frame| frame.size) else {
+                anyhow::bail!("No frames to export");
+            };
+            let mut encoder = png::Encoder::new(&mut data, width as u32, height as u32);
+            encoder.set_color(png::ColorType::Rgba);
+            encoder.set_depth(png::BitDepth::Eight);
+            encoder.set_animated(frames.len() as u32, 0)?;
+            let millis = u16::try_from(delay.as_millis()).unwrap_or(u16::MAX);
+            encoder.set_frame_delay(millis, 1000)?;
+            let mut writer = encoder.write_header()?;
+            for frame in frames {
+                writer.write_image_data(frame.as_raw())?;
+            }
+            writer.finish()?;
+        }
+    }
+    Ok(data)
+}
+
+#[cfg(not(target_arch = "wasm32"))]
+fn encode_png(image: &ColorImage) -> anyhow::Result<Vec<u8>> {
+    use anyhow::Context as _;
+
+    let [width, height] = image.size;
+    let rgba = image::RgbaImage::from_raw(width as u32, height as u32, image.as_raw().to_vec())
+        .context("Image has an unexpected size")?;
+    let mut png = Vec::new();
+    rgba.write_to(&mut std::io::Cursor::new(&mut png), image::ImageFormat::Png)?;
+    Ok(png)
+}
@@ -1,9 +1,28 @@
-use crate::state::{FilteredSnapshot, ViewerAppStateRef, ViewerSystemCommand};
+use crate::acknowledged;
+#[cfg(not(target_arch = "wasm32"))]
+use crate::confirm::{self, Change, ChangeKind};
+use crate::diff_precompute::DiffPrecompute;
+use crate::github::auth::GithubAuthCommand;
+use crate::loaders::gh_archive_loader::ArtifactAccessDenied;
+use crate::loaders::pinned_loader::BaselineSide;
+use crate::platform::split_platform;
+use crate::snapshot::Snapshot;
+use crate::state::{FilteredSnapshot, SystemCommand, ViewerAppStateRef, ViewerSystemCommand};
+use crate::thumbnail_loader::THUMBNAIL_SIZE;
+use crate::viewer::clipboard::{self, CopyImage};
+use crate::viewer::{batch_export, duplicates, popout, review_summary, set_diff, stats};
 use eframe::egui;
-use eframe::egui::{Id, OpenUrl, ScrollArea, TextEdit, Ui};
+use eframe::egui::{
+    Color32, DragValue, Grid, Id, Image, OpenUrl, Popup, ProgressBar, RichText, ScrollArea,
+    TextEdit, Ui, Vec2,
+};
+use kitdiff_core::diff::MinChange;
+use kitdiff_core::test_logs::FailedSnapshot;
 use re_ui::UiExt as _;
 use re_ui::alert::Alert;
 use re_ui::list_item::LabelContent;
+use std::collections::BTreeMap;
+use std::path::{Path, PathBuf};
 use std::task::Poll;
 
 fn is_github_permission_error(err: &anyhow::Error) -> bool {
@@ -26,7 +45,22 @@ pub fn file_tree(ui: &mut Ui, state: &ViewerAppStateRef<'_>) {
     state.loader.extra_ui(ui, state.app);
 
     if let Poll::Ready(Err(e)) = state.loader.state() {
-        if is_github_permission_error(e) {
+        if e.downcast_ref::<ArtifactAccessDenied>().is_some()
+            && state.app.github_auth.get_token().is_none()
+        {
+            Alert::warning().show(ui, |ui: &mut Ui| {
+                ui.vertical(|ui| {
+                    ui.label(
+                        "Sign in to access this artifact. GitHub only lets signed in users \
+                        download artifacts.",
+                    );
+                    if ui.button("Log in with GitHub").clicked() {
+                        // Signing in refreshes the loader, which retries the download.
+                        state.app.send(GithubAuthCommand::Login);
+                    }
+                });
+            });
+        } else if is_github_permission_error(e) {
             Alert::warning().show(ui, |ui: &mut Ui| {
                 ui.vertical(|ui| {
                     ui.label("kitdiff does not have access to this repository.");
@@ -44,12 +78,63 @@ pub fn file_tree(ui: &mut Ui, state: &ViewerAppStateRef<'_>) {
         }
     }
 
+    let cancelled = state.cancel.is_cancelled();
     ui.panel_title_bar_with_buttons(&state.loader.files_header(), None, |ui| {
-        if state.loader.state().is_pending() {
+        stats::stats_button(ui, state);
+        if cancelled {
+            if ui
+                .small_button("Reload")
+                .on_hover_text("Loading was cancelled, start over")
+                .clicked()
+            {
+                state.app.send(SystemCommand::Refresh);
+            }
+        } else if state.loader.state().is_pending() {
+            if ui
+                .small_button("Cancel")
+                .on_hover_text("Stop loading, keeping the snapshots found so far")
+                .clicked()
+            {
+                state.cancel.cancel();
+            }
             ui.spinner();
+        } else {
+            baseline_menu(ui, state);
+            workspace_menu(ui, state);
+            #[cfg(not(target_arch = "wasm32"))]
+            crate::viewer::test_run::run_button(ui, state);
         }
     });
 
+    if let crate::DiffSource::Pinned { baseline, .. } = &state.source {
+        ui.horizontal_wrapped(|ui| {
+            ui.label(format!(
+                "📌 Old side pinned to {} ({} images)",
+                baseline.name,
+                baseline.len()
+            ));
+            if ui.small_button("Unpin").clicked() {
+                state.app.send(SystemCommand::PinBaseline(None));
+            }
+        });
+    }
+
+    let progress = state
+        .loader
+        .progress()
+        .or_else(|| state.precompute.progress(state.loader.snapshots()));
+    if let Some(progress) = progress.filter(|_| !cancelled) {
+        ui.add(
+            ProgressBar::new(progress.fraction.unwrap_or(0.0))
+                .text(progress.to_string())
+                .animate(progress.fraction.is_none()),
+        );
+    }
+
+    if let Some(scope) = &state.scope {
+        breadcrumbs(ui, state, scope);
+    }
+
     let mut filter = state.filter.clone();
     TextEdit::singleline(&mut filter)
         .hint_text("Filter")
@@ -59,44 +144,615 @@ pub fn file_tree(ui: &mut Ui, state: &ViewerAppStateRef<'_>) {
         state.app.send(ViewerSystemCommand::SetFilter(filter));
     }
 
+    let has_unchanged = state
+        .loader
+        .snapshots()
+        .iter()
+        .any(|snapshot| snapshot.unchanged);
+    ui.horizontal(|ui| {
+        if has_unchanged {
+            let mut show = state.app.settings.show_unchanged;
+            if ui
+                .checkbox(&mut show, "Show unchanged")
+                .on_hover_text("Snapshots whose old and new images are identical")
+                .changed()
+            {
+                let mut settings = state.app.settings.clone();
+                settings.show_unchanged = show;
+                state.app.send(SystemCommand::UpdateSettings(settings));
+            }
+        }
+        min_change_button(ui, state);
+        if !state.flaky.is_empty() || state.flaky_only {
+            if ui
+                .selectable_label(
+                    state.flaky_only,
+                    format!("🎲 Flaky ({})", state.flaky.len()),
+                )
+                .on_hover_text(
+                    "Only list snapshots that flipped between changed and unchanged across \
+                    loads of this source",
+                )
+                .clicked()
+            {
+                state
+                    .app
+                    .send(ViewerSystemCommand::SetFlakyOnly(!state.flaky_only));
+            }
+        }
+        if !state.acknowledged.is_empty() || state.hide_acknowledged {
+            if ui
+                .selectable_label(
+                    state.hide_acknowledged,
+                    format!("🔕 Hide acknowledged ({})", state.acknowledged.len()),
+                )
+                .on_hover_text("Leave out known diffs whose new image didn't change since")
+                .clicked()
+            {
+                state.app.send(ViewerSystemCommand::SetHideAcknowledged(
+                    !state.hide_acknowledged,
+                ));
+            }
+        }
+        if !state.filtered_snapshots.is_empty() {
+            batch_export::export_button(ui, state);
+        }
+        if !state.reviews.is_empty() {
+            review_summary::summary_button(ui, state);
+        }
+        if state.loader.snapshots().iter().any(|snapshot| {
+            snapshot.added() || snapshot.deleted() || snapshot.renamed_from.is_some()
+        }) {
+            set_diff::set_diff_button(ui, state);
+        }
+        if state.loader.state().is_ready() && state.loader.snapshots().len() > 1 {
+            duplicates::duplicates_button(ui, state);
+        }
+    });
+
+    let has_platforms = state
+        .filtered_snapshots
+        .iter()
+        .any(|(_, snapshot)| split_platform(&snapshot.path).is_some());
+    if has_platforms {
+        let mut group = state.app.settings.group_platforms;
+        if ui.checkbox(&mut group, "Group platforms").changed() {
+            let mut settings = state.app.settings.clone();
+            settings.group_platforms = group;
+            state.app.send(SystemCommand::UpdateSettings(settings));
+        }
+    }
+
     ScrollArea::vertical().show(ui, |ui| {
         ui.list_item_scope("file_tree", |ui| {
-            let mut tree: Vec<(Option<&str>, Vec<FilteredSnapshot<'_>>)> = Vec::new();
+            if has_platforms && state.app.settings.group_platforms {
+                platform_tree(ui, state);
+            } else {
+                flat_tree(ui, state);
+            }
 
-            // Snapshots should already be sorted, so we only need to group them
-            for filtered_snapshot in state.filtered_snapshots.iter().copied() {
-                let prefix = filtered_snapshot.1.path.parent().and_then(|p| p.to_str());
-                if let Some((current_prefix, snapshots)) = tree.last_mut()
-                    && *current_prefix == prefix
-                {
-                    snapshots.push(filtered_snapshot);
-                    continue;
+            if state.loader.snapshots().is_empty() {
+                if state.loader.state().is_ready() {
+                    ui.label("No snapshots were found.");
                 }
-                tree.push((prefix, vec![filtered_snapshot]));
+            } else if state.filtered_snapshots.is_empty() {
+                if state.scope.is_some() {
+                    ui.label("No snapshots in this directory match the filter.");
+                } else {
+                    ui.label("No snapshots match the filter.");
+                }
+            }
+        });
+    });
+}
+
+fn baseline_menu(ui: &mut Ui, state: &ViewerAppStateRef<'_>) {
+    let response = ui
+        .small_button("📌")
+        .on_hover_text("Pin the old side, so it stays the same while the new side is reloaded");
+    Popup::menu(&response).show(|ui| {
+        if ui
+            .button("Pin old side")
+            .on_hover_text("Keep comparing against the current old images")
+            .clicked()
+        {
+            state
+                .app
+                .send(SystemCommand::PinBaseline(Some(BaselineSide::Old)));
+        }
+        if ui
+            .button("Pin new side as baseline")
+            .on_hover_text(
+                "Compare against the current new images from now on, e.g. the output of this CI \
+                run. Open another source to compare it against them.",
+            )
+            .clicked()
+        {
+            state
+                .app
+                .send(SystemCommand::PinBaseline(Some(BaselineSide::New)));
+        }
+        if matches!(state.source, crate::DiffSource::Pinned { .. }) && ui.button("Unpin").clicked()
+        {
+            state.app.send(SystemCommand::PinBaseline(None));
+        }
+
+        #[cfg(not(target_arch = "wasm32"))]
+        {
+            ui.separator();
+            saved_baselines_ui(ui, state);
+        }
+    });
+}
+
+/// Adds the open source to a new or existing workspace, see [`crate::workspace`].
+fn workspace_menu(ui: &mut Ui, state: &ViewerAppStateRef<'_>) {
+    let response = ui
+        .small_button("🗂")
+        .on_hover_text("Add this source to a workspace, to open it together with others");
+    Popup::menu(&response).show(|ui| {
+        let id = ui.id().with("new_workspace_name");
+        let mut name: String = ui.data(|d| d.get_temp(id)).unwrap_or_default();
+        let mut target = None;
+        ui.horizontal(|ui| {
+            ui.add(
+                TextEdit::singleline(&mut name)
+                    .hint_text("Workspace name")
+                    .desired_width(120.0),
+            );
+            if ui
+                .add_enabled(!name.trim().is_empty(), egui::Button::new("New workspace"))
+                .clicked()
+            {
+                target = Some(name.trim().to_owned());
+                name.clear();
             }
+        });
+        ui.data_mut(|d| d.insert_temp(id, name));
+
+        for existing in state.app.settings.workspaces.keys() {
+            if ui.button(format!("Add to {existing}")).clicked() {
+                target = Some(existing.clone());
+            }
+        }
+
+        if let Some(target) = target {
+            let mut settings = state.app.settings.clone();
+            let workspace = settings.workspaces.entry(target).or_default();
+            if workspace.add(state.loader.files_header(), &state.source) {
+                state.app.send(SystemCommand::UpdateSettings(settings));
+            } else {
+                state.app.send(crate::notifications::Notification::error(
+                    "Failed to add to the workspace",
+                    "this source can't be opened again later",
+                ));
+            }
+        }
+    });
+}
+
+/// Saving the current images as a named baseline, and comparing against saved ones.
+#[cfg(not(target_arch = "wasm32"))]
+fn saved_baselines_ui(ui: &mut Ui, state: &ViewerAppStateRef<'_>) {
+    use crate::baselines;
+    use crate::notifications::Notification;
 
-            for (prefix, snapshots) in tree {
-                if let Some(prefix) = prefix {
-                    ui.list_item().show_hierarchical_with_children(
-                        ui,
-                        Id::new(prefix),
-                        true,
-                        LabelContent::new(prefix),
-                        |ui| show_prefix(ui, state, &snapshots),
+    let id = ui.id().with("new_baseline_name");
+    let mut name: String = ui.data(|d| d.get_temp(id)).unwrap_or_default();
+    ui.horizontal(|ui| {
+        ui.add(
+            TextEdit::singleline(&mut name)
+                .hint_text("Baseline name")
+                .desired_width(120.0),
+        );
+        if ui
+            .add_enabled(
+                !name.trim().is_empty(),
+                egui::Button::new("Save as baseline"),
+            )
+            .on_hover_text(confirm::hover_text(
+                state.app,
+                "Keep the current image of every snapshot, to compare later versions against it",
+            ))
+            .clicked()
+        {
+            let name = name.trim().to_owned();
+            let source = state.loader.files_header();
+            let snapshots = state.loader.snapshots().to_vec();
+            let changes = baseline_changes(&name, &snapshots);
+            let tx = state.app.tx.clone();
+            confirm::confirm(
+                ui.ctx(),
+                format!("Save baseline {name}"),
+                format!("in baseline {name}"),
+                changes,
+                move || {
+                    tokio::spawn(async move {
+                        if let Err(err) = baselines::save(&name, source, snapshots).await {
+                            tx.send(Notification::error("Failed to save the baseline", err).into())
+                                .ok();
+                        }
+                    });
+                },
+            );
+            name.clear();
+        }
+    });
+    ui.data_mut(|d| d.insert_temp(id, name));
+
+    ui.menu_button("Compare against baseline", |ui| {
+        let saved = baselines::list();
+        if saved.is_empty() {
+            ui.label("No baselines saved yet");
+        }
+        for baseline in saved {
+            ui.horizontal(|ui| {
+                if ui
+                    .button(&baseline.manifest.name)
+                    .on_hover_text(format!(
+                        "{} images from {}, saved {}",
+                        baseline.manifest.images.len(),
+                        baseline.manifest.source,
+                        baseline.created()
+                    ))
+                    .clicked()
+                {
+                    state
+                        .app
+                        .send(SystemCommand::Open(crate::DiffSource::Compare {
+                            old: Box::new(crate::DiffSource::Baseline(baseline.clone())),
+                            new: Box::new(state.source.clone()),
+                        }));
+                    ui.close();
+                }
+                if ui
+                    .small_button("🗑")
+                    .on_hover_text(confirm::hover_text(state.app, "Delete"))
+                    .clicked()
+                {
+                    let changes = baseline
+                        .manifest
+                        .images
+                        .iter()
+                        .map(|path| Change::new(ChangeKind::Delete, path.display().to_string()))
+                        .collect();
+                    let name = baseline.manifest.name.clone();
+                    let tx = state.app.tx.clone();
+                    confirm::confirm(
+                        ui.ctx(),
+                        format!("Delete baseline {name}"),
+                        format!("in baseline {name}"),
+                        changes,
+                        move || {
+                            if let Err(err) = baselines::delete(&baseline) {
+                                tx.send(
+                                    Notification::error("Failed to delete the baseline", err)
+                                        .into(),
+                                )
+                                .ok();
+                            }
+                        },
                     );
-                } else {
-                    show_prefix(ui, state, &snapshots);
+                    ui.close();
                 }
+            });
+        }
+    });
+}
+
+/// What saving `snapshots` as baseline `name` changes, replacing a baseline of that name.
+#[cfg(not(target_arch = "wasm32"))]
+fn baseline_changes(name: &str, snapshots: &[Snapshot]) -> Vec<Change> {
+    let existing: Vec<PathBuf> = crate::baselines::find(name)
+        .map(|baseline| baseline.manifest.images)
+        .unwrap_or_default();
+    let saved: Vec<&Snapshot> = snapshots
+        .iter()
+        .filter(|snapshot| snapshot.new.is_some() || snapshot.old.is_some())
+        .collect();
+    let mut changes: Vec<Change> = saved
+        .iter()
+        .map(|snapshot| {
+            let kind = if existing.contains(&snapshot.path) {
+                ChangeKind::Overwrite
+            } else {
+                ChangeKind::Add
+            };
+            Change::new(kind, snapshot.path.display().to_string())
+        })
+        .collect();
+    changes.extend(
+        existing
+            .iter()
+            .filter(|path| !saved.iter().any(|snapshot| snapshot.path == **path))
+            .map(|path| Change::new(ChangeKind::Delete, path.display().to_string())),
+    );
+    changes
+}
+
+fn flat_tree(ui: &mut Ui, state: &ViewerAppStateRef<'_>) {
+    let mut tree: Vec<(Option<&str>, Vec<FilteredSnapshot<'_>>)> = Vec::new();
+
+    // Snapshots should already be sorted, so we only need to group them
+    for filtered_snapshot in state.filtered_snapshots.iter().copied() {
+        let prefix = filtered_snapshot.1.path.parent().and_then(|p| p.to_str());
+        if let Some((current_prefix, snapshots)) = tree.last_mut()
+            && *current_prefix == prefix
+        {
+            snapshots.push(filtered_snapshot);
+            continue;
+        }
+        tree.push((prefix, vec![filtered_snapshot]));
+    }
+
+    for (prefix, snapshots) in tree {
+        directory(ui, state, prefix, |ui| show_prefix(ui, state, &snapshots));
+    }
+}
+
+/// Same as [`flat_tree`], but `linux/…`, `macos/…` and `windows/…` variants of a snapshot are
+/// shown as one entry.
+fn platform_tree(ui: &mut Ui, state: &ViewerAppStateRef<'_>) {
+    let mut groups: BTreeMap<PathBuf, Vec<PlatformVariant<'_>>> = BTreeMap::new();
+    for filtered_snapshot in state.filtered_snapshots.iter().copied() {
+        let (platform, path) = match split_platform(&filtered_snapshot.1.path) {
+            Some((platform, path)) => (Some(platform), path),
+            None => (None, filtered_snapshot.1.path.clone()),
+        };
+        groups
+            .entry(path)
+            .or_default()
+            .push((platform, filtered_snapshot));
+    }
+
+    let mut tree: Vec<(Option<&str>, Vec<(&Path, &[PlatformVariant<'_>])>)> = Vec::new();
+    for (path, variants) in &groups {
+        let prefix = path.parent().and_then(|p| p.to_str());
+        if let Some((current_prefix, entries)) = tree.last_mut()
+            && *current_prefix == prefix
+        {
+            entries.push((path.as_path(), variants.as_slice()));
+            continue;
+        }
+        tree.push((prefix, vec![(path.as_path(), variants.as_slice())]));
+    }
+
+    for (prefix, entries) in tree {
+        directory(ui, state, prefix, |ui| {
+            for (path, variants) in entries {
+                show_platform_group(ui, state, path, variants);
             }
+        });
+    }
+}
 
-            if state.loader.snapshots().is_empty() {
-                if state.loader.state().is_ready() {
-                    ui.label("No snapshots were found.");
+/// Shows `add_contents` below a collapsible directory entry, or directly if there's no directory.
+fn directory(
+    ui: &mut Ui,
+    state: &ViewerAppStateRef<'_>,
+    prefix: Option<&str>,
+    add_contents: impl FnOnce(&mut Ui),
+) {
+    let Some(prefix) = prefix else {
+        add_contents(ui);
+        return;
+    };
+    let response = ui.list_item().show_hierarchical_with_children(
+        ui,
+        Id::new(prefix),
+        true,
+        LabelContent::new(prefix),
+        add_contents,
+    );
+    response.item_response.context_menu(|ui| {
+        if ui.button("Only show this directory").clicked() {
+            state
+                .app
+                .send(ViewerSystemCommand::SetScope(Some(prefix.into())));
+            ui.close();
+        }
+    });
+}
+
+/// A snapshot and the platform it belongs to, if any.
+type PlatformVariant<'a> = (Option<&'static str>, FilteredSnapshot<'a>);
+
+fn show_platform_group(
+    ui: &mut Ui,
+    state: &ViewerAppStateRef<'_>,
+    path: &Path,
+    variants: &[PlatformVariant<'_>],
+) {
+    if let [(None, filtered_snapshot)] = variants {
+        show_prefix(ui, state, std::slice::from_ref(filtered_snapshot));
+        return;
+    }
+
+    let statuses: Vec<PlatformStatus> = variants
+        .iter()
+        .map(|(_, (_, snapshot))| PlatformStatus::new(state, snapshot))
+        .collect();
+    let has_same = statuses.contains(&PlatformStatus::Same);
+    let has_changes = statuses
+        .iter()
+        .any(|status| !matches!(status, PlatformStatus::Same | PlatformStatus::Pending));
+
+    let name = path
+        .file_name()
+        .map_or_else(|| path.to_string_lossy(), |name| name.to_string_lossy());
+    let label = if has_same && has_changes {
+        RichText::new(format!("⚠ {name}")).color(ui.visuals().warn_fg_color)
+    } else {
+        RichText::new(name)
+    };
+
+    let selected = variants.iter().any(|(_, (index, _))| *index == state.index);
+    let response = ui
+        .list_item()
+        .selected(selected)
+        .show_hierarchical_with_children(ui, Id::new(path), true, LabelContent::new(label), |ui| {
+            for ((platform, (index, snapshot)), status) in variants.iter().zip(&statuses) {
+                let mut text = format!("{} {}", platform.unwrap_or("other"), status.label());
+                if let Some(review) = state.reviews.get(&snapshot.path) {
+                    text = format!("{} {text}", review.icon());
+                }
+                let failure = state.loader.failed_snapshot(&snapshot.path);
+                let color = if failure.is_some() {
+                    text = format!("✖ {text}");
+                    ui.visuals().error_fg_color
+                } else {
+                    status.color(ui)
+                };
+                let selected = *index == state.index;
+                let response = ui
+                    .list_item()
+                    .selected(selected)
+                    .show_hierarchical(ui, LabelContent::new(RichText::new(text).color(color)))
+                    .on_hover_ui(|ui| {
+                        if let Some(failure) = failure {
+                            failure_ui(ui, failure);
+                        }
+                        thumbnail_preview(ui, snapshot);
+                    });
+
+                if response.clicked() {
+                    state.app.send(ViewerSystemCommand::SelectSnapshot(*index));
+                }
+
+                response.context_menu(|ui| snapshot_context_menu(ui, state, snapshot));
+
+                if selected && state.index_just_selected {
+                    response.scroll_to_me(None);
                 }
-            } else if state.filtered_snapshots.is_empty() {
-                ui.label("No snapshots match the filter.");
             }
         });
+
+    let item_response = if has_same && has_changes {
+        response
+            .item_response
+            .on_hover_text("Only changed on some platforms")
+    } else {
+        response.item_response
+    };
+    if item_response.clicked()
+        && !selected
+        && let Some((_, (index, _))) = variants.first()
+    {
+        state.app.send(ViewerSystemCommand::SelectSnapshot(*index));
+    }
+}
+
+/// Diff status of one platform variant, shown as a badge in the grouped tree.
+#[derive(Clone, Copy, PartialEq, Eq)]
+enum PlatformStatus {
+    Pending,
+    Same,
+    Changed(i32),
+    Failed,
+    Added,
+    Deleted,
+    Unchanged,
+}
+
+impl PlatformStatus {
+    fn new(state: &ViewerAppStateRef<'_>, snapshot: &Snapshot) -> Self {
+        if snapshot.added() {
+            return Self::Added;
+        }
+        if snapshot.deleted() {
+            return Self::Deleted;
+        }
+        if snapshot.unchanged {
+            return Self::Unchanged;
+        }
+        let loader = &state.app.diff_image_loader;
+        match DiffPrecompute::diff_uri(snapshot, state.app.settings.options) {
+            Some(uri) if loader.is_finished(&uri) => match loader.diff_pixels(&uri) {
+                Some(0) => Self::Same,
+                Some(pixels) => Self::Changed(pixels),
+                None => Self::Failed,
+            },
+            _ => Self::Pending,
+        }
+    }
+
+    fn label(self) -> String {
+        match self {
+            Self::Pending => "…".to_owned(),
+            Self::Same => "below threshold".to_owned(),
+            Self::Changed(pixels) => format!("{pixels} px"),
+            Self::Failed => "diff failed".to_owned(),
+            Self::Added => "added".to_owned(),
+            Self::Deleted => "deleted".to_owned(),
+            Self::Unchanged => "unchanged".to_owned(),
+        }
+    }
+
+    fn color(self, ui: &Ui) -> Color32 {
+        match self {
+            Self::Pending | Self::Unchanged => ui.visuals().weak_text_color(),
+            Self::Same => ui.visuals().text_color(),
+            Self::Changed(_) | Self::Added | Self::Deleted => ui.visuals().warn_fg_color,
+            Self::Failed => ui.visuals().error_fg_color,
+        }
+    }
+}
+
+/// Hides snapshots with small diffs, e.g. a few anti-aliased pixels, see
+/// [`crate::settings::Settings::min_change`].
+fn min_change_button(ui: &mut Ui, state: &ViewerAppStateRef<'_>) {
+    let mut min_change = state.app.settings.min_change;
+    let response = ui
+        .selectable_label(min_change.is_active(), "Min. diff")
+        .on_hover_text("Hide snapshots where fewer pixels changed");
+    Popup::menu(&response)
+        .close_behavior(egui::PopupCloseBehavior::CloseOnClickOutside)
+        .show(|ui| {
+            Grid::new("min_change").num_columns(2).show(ui, |ui| {
+                ui.label("Changed pixels");
+                ui.add(DragValue::new(&mut min_change.pixels));
+                ui.end_row();
+                ui.label("Share of the image");
+                ui.add(
+                    DragValue::new(&mut min_change.percent)
+                        .range(0.0..=100.0)
+                        .speed(0.01)
+                        .suffix(" %"),
+                );
+                ui.end_row();
+            });
+            if min_change.is_active() && ui.button("Show all").clicked() {
+                min_change = MinChange::default();
+            }
+        });
+
+    if min_change != state.app.settings.min_change {
+        let mut settings = state.app.settings.clone();
+        settings.min_change = min_change;
+        state.app.send(SystemCommand::UpdateSettings(settings));
+    }
+}
+
+/// The components of the current scope. Clicking one widens the scope to it.
+fn breadcrumbs(ui: &mut Ui, state: &ViewerAppStateRef<'_>, scope: &Path) {
+    ui.horizontal_wrapped(|ui| {
+        ui.spacing_mut().item_spacing.x = 2.0;
+        if ui.link("All").clicked() {
+            state.app.send(ViewerSystemCommand::SetScope(None));
+        }
+        let mut path = PathBuf::new();
+        for component in scope.components() {
+            path.push(component);
+            ui.weak("/");
+            let name = component.as_os_str().to_string_lossy();
+            if path == scope {
+                ui.strong(name);
+            } else if ui.link(name).clicked() {
+                state
+                    .app
+                    .send(ViewerSystemCommand::SetScope(Some(path.clone())));
+            }
+        }
     });
 }
 
@@ -107,17 +763,221 @@ fn show_prefix(
 ) {
     for (index, snapshot) in filtered_snapshots {
         let selected = *index == state.index;
-        let content = LabelContent::new(snapshot.file_name());
+        let mut name = if let Some(status) = state.reviews.get(&snapshot.path) {
+            format!("{} {}", status.icon(), snapshot.file_name())
+        } else {
+            snapshot.file_name().into_owned()
+        };
+        if snapshot.renamed_from.is_some() {
+            name.push_str(" (renamed)");
+        }
+        let duplicate_of = duplicates::duplicate_of(ui, state, &snapshot.path);
+        if duplicate_of.is_some() {
+            name = format!("⧉ {name}");
+        }
+        let flaky = state.flaky.is_flaky(&snapshot.path);
+        if flaky {
+            name = format!("🎲 {name}");
+        }
+        let acknowledged = state.acknowledged.is_acknowledged(&snapshot.path);
+        if acknowledged {
+            name = format!("🔕 {name}");
+        }
+        let failure = state.loader.failed_snapshot(&snapshot.path);
+        let content = if snapshot.unchanged {
+            LabelContent::new(RichText::new(format!("{name} (unchanged)")).weak())
+        } else if failure.is_some() {
+            let color = ui.visuals().error_fg_color;
+            LabelContent::new(RichText::new(format!("✖ {name}")).color(color))
+        } else {
+            LabelContent::new(name)
+        };
         let item = ui.list_item().selected(selected);
 
-        let response = item.show_hierarchical(ui, content);
+        let response = item.show_hierarchical(ui, content).on_hover_ui(|ui| {
+            if let Some(failure) = failure {
+                failure_ui(ui, failure);
+            }
+            if let Some(renamed_from) = &snapshot.renamed_from {
+                ui.label(format!("Renamed from {}", renamed_from.display()));
+            }
+            if let Some(duplicate_of) = &duplicate_of {
+                ui.label(duplicate_of);
+            }
+            if flaky && let Some(history) = state.flaky.history(&snapshot.path) {
+                ui.label(format!(
+                    "Flaky, changed (●) or not (○) in recent loads: {history}"
+                ));
+            }
+            if acknowledged {
+                ui.label("Acknowledged, the new image didn't change since");
+            }
+            thumbnail_preview(ui, snapshot);
+        });
 
         if response.clicked() {
             state.app.send(ViewerSystemCommand::SelectSnapshot(*index));
         }
 
+        response.context_menu(|ui| snapshot_context_menu(ui, state, snapshot));
+
         if selected && state.index_just_selected {
             response.scroll_to_me(None);
         }
     }
 }
+
+/// Which test failed on the snapshot in CI, and how.
+fn failure_ui(ui: &mut Ui, failure: &FailedSnapshot) {
+    let title = match &failure.test {
+        Some(test) => format!("Failed in CI: {test}"),
+        None => "Failed in CI".to_owned(),
+    };
+    ui.colored_label(ui.visuals().error_fg_color, title);
+    ui.weak(&failure.message);
+}
+
+fn snapshot_context_menu(ui: &mut Ui, state: &ViewerAppStateRef<'_>, snapshot: &Snapshot) {
+    if ui.button("Copy new image").clicked() {
+        clipboard::copy_image(ui.ctx(), state, snapshot, CopyImage::New);
+        ui.close();
+    }
+    if ui.button("Copy diff image").clicked() {
+        clipboard::copy_image(ui.ctx(), state, snapshot, CopyImage::Diff);
+        ui.close();
+    }
+    if ui.button("Copy path").clicked() {
+        clipboard::copy_path(ui.ctx(), snapshot);
+        ui.close();
+    }
+    if popout::supported(ui) && ui.button("Open in new window").clicked() {
+        state
+            .app
+            .send(ViewerSystemCommand::PopOut(snapshot.path.clone()));
+        ui.close();
+    }
+    acknowledge_button(ui, state, snapshot);
+
+    ui.separator();
+
+    match state.loader.blame(snapshot) {
+        Poll::Pending => {
+            ui.horizontal(|ui| {
+                ui.spinner();
+                ui.label("Finding last commit…");
+            });
+        }
+        Poll::Ready(Some(blame)) => {
+            ui.label(format!(
+                "{} {}",
+                blame.sha.get(..7).unwrap_or(&blame.sha),
+                blame.summary
+            ));
+            ui.weak(&blame.author);
+            if let Some(url) = &blame.url
+                && ui.button("Open commit on GitHub").clicked()
+            {
+                ui.ctx().open_url(OpenUrl::new_tab(url));
+                ui.close();
+            }
+        }
+        Poll::Ready(None) => {
+            ui.weak("No commit information");
+        }
+    }
+
+    #[cfg(not(target_arch = "wasm32"))]
+    file_actions(ui, state, snapshot);
+}
+
+/// Acknowledge a known diff until the new image changes, or take that back.
+fn acknowledge_button(ui: &mut Ui, state: &ViewerAppStateRef<'_>, snapshot: &Snapshot) {
+    if state.acknowledged.is_acknowledged(&snapshot.path) {
+        if ui.button("Remove acknowledgement").clicked() {
+            acknowledged::set_acknowledged(state, snapshot, false);
+            ui.close();
+        }
+    } else if snapshot.new.is_some()
+        && !snapshot.unchanged
+        && ui
+            .button("Acknowledge diff")
+            .on_hover_text("Mark the diff as known until the new image changes again")
+            .clicked()
+    {
+        acknowledged::set_acknowledged(state, snapshot, true);
+        ui.close();
+    }
+}
+
+/// Actions for snapshots that are files on disk.
+#[cfg(not(target_arch = "wasm32"))]
+fn file_actions(ui: &mut Ui, state: &ViewerAppStateRef<'_>, snapshot: &Snapshot) {
+    use crate::external;
+    use crate::notifications::Notification;
+    use crate::snapshot::FileReference;
+
+    let path = |file: &Option<FileReference>| match file {
+        Some(FileReference::Path(path)) => Some(path.clone()),
+        _ => None,
+    };
+    let old = path(&snapshot.old);
+    let new = path(&snapshot.new);
+    if old.is_none() && new.is_none() {
+        return;
+    }
+
+    ui.separator();
+
+    let mut result = Ok(());
+    if let Some(file) = new.as_ref().or(old.as_ref())
+        && ui.button("Reveal in file manager").clicked()
+    {
+        result = external::reveal(file);
+        ui.close();
+    }
+    for (label, file) in [("Open old", &old), ("Open new", &new)] {
+        if let Some(file) = file
+            && ui.button(label).clicked()
+        {
+            result = external::open(file);
+            ui.close();
+        }
+    }
+
+    let diff_tool = &state.app.settings.external_diff_tool;
+    if let (Some(old), Some(new)) = (&old, &new)
+        && ui
+            .add_enabled(
+                !diff_tool.is_empty(),
+                egui::Button::new("Open in diff tool"),
+            )
+            .on_disabled_hover_text("Set a diff tool in the options panel")
+            .clicked()
+    {
+        result = external::open_diff_tool(diff_tool, old, new);
+        ui.close();
+    }
+
+    if let Err(err) = result {
+        state
+            .app
+            .send(Notification::error("External action failed", err));
+    }
+}
+
+fn thumbnail_preview(ui: &mut Ui, snapshot: &Snapshot) {
+    let max_size = Vec2::splat(THUMBNAIL_SIZE as f32 / ui.pixels_per_point());
+    ui.horizontal(|ui| {
+        for (label, uri) in [
+            ("Old", snapshot.old_thumbnail_uri()),
+            ("New", snapshot.new_thumbnail_uri()),
+        ] {
+            if let Some(uri) = uri {
+                ui.vertical(|ui| {
+                    ui.label(label);
+                    ui.add(Image::new(uri).max_size(max_size));
+                });
+            }
+        }
+    });
+}
@@ -0,0 +1,232 @@
+use crate::snapshot::Snapshot;
+use crate::state::ViewerAppStateRef;
+use eframe::egui::load::ImagePoll;
+use eframe::egui::{
+    self, Color32, ColorImage, Id, Pos2, SizeHint, Stroke, StrokeKind, Ui, pos2, vec2,
+};
+use std::sync::Arc;
+
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+enum Channel {
+    #[default]
+    Luma,
+    Red,
+    Green,
+    Blue,
+}
+
+impl Channel {
+    const ALL: [Self; 4] = [Self::Luma, Self::Red, Self::Green, Self::Blue];
+
+    fn index(self) -> usize {
+        match self {
+            Self::Red => 0,
+            Self::Green => 1,
+            Self::Blue => 2,
+            Self::Luma => 3,
+        }
+    }
+
+    fn name(self) -> &'static str {
+        match self {
+            Self::Luma => "Luma",
+            Self::Red => "R",
+            Self::Green => "G",
+            Self::Blue => "B",
+        }
+    }
+
+    fn color(self, ui: &Ui) -> Color32 {
+        match self {
+            Self::Luma => ui.visuals().strong_text_color(),
+            Self::Red => Color32::from_rgb(230, 80, 80),
+            Self::Green => Color32::from_rgb(80, 200, 100),
+            Self::Blue => Color32::from_rgb(90, 140, 240),
+        }
+    }
+}
+
+/// Pixel counts per value of the red, green, blue and luma channels.
+struct Histogram {
+    bins: [[u32; 256]; 4],
+    count: u32,
+}
+
+impl Histogram {
+    /// Fully transparent pixels are skipped, their color is meaningless.
+    fn compute(image: &ColorImage) -> Self {
+        let mut bins = [[0; 256]; 4];
+        let mut count = 0;
+        for pixel in &image.pixels {
+            let [r, g, b, a] = pixel.to_srgba_unmultiplied();
+            if a == 0 {
+                continue;
+            }
+            let luma = 0.2126 * f32::from(r) + 0.7152 * f32::from(g) + 0.0722 * f32::from(b);
+            bins[0][r as usize] += 1;
+            bins[1][g as usize] += 1;
+            bins[2][b as usize] += 1;
+            bins[3][(luma.round() as usize).min(255)] += 1;
+            count += 1;
+        }
+        Self { bins, count }
+    }
+
+    fn mean(&self, channel: Channel) -> f32 {
+        let sum: f64 = self.bins[channel.index()]
+            .iter()
+            .enumerate()
+            .map(|(value, n)| value as f64 * f64::from(*n))
+            .sum();
+        (sum / f64::from(self.count.max(1))) as f32
+    }
+
+    /// The standard deviation, a rough measure of contrast.
+    fn std_dev(&self, channel: Channel) -> f32 {
+        let mean = f64::from(self.mean(channel));
+        let variance: f64 = self.bins[channel.index()]
+            .iter()
+            .enumerate()
+            .map(|(value, n)| (value as f64 - mean).powi(2) * f64::from(*n))
+            .sum::<f64>()
+            / f64::from(self.count.max(1));
+        variance.sqrt() as f32
+    }
+}
+
+/// Old and new histograms overlaid, to understand changes that touch every pixel, like a shift in
+/// brightness, where the diff image is solid red.
+pub fn histogram_ui(ui: &mut Ui, state: &ViewerAppStateRef<'_>, snapshot: &Snapshot) {
+    let id = Id::new("histogram_options");
+    let (mut channel, mut log_scale): (Channel, bool) = ui
+        .data(|d| d.get_temp(id))
+        .unwrap_or((Channel::default(), true));
+
+    ui.horizontal(|ui| {
+        for c in Channel::ALL {
+            ui.selectable_value(&mut channel, c, c.name());
+        }
+        ui.checkbox(&mut log_scale, "Log scale");
+    });
+    ui.data_mut(|d| d.insert_temp(id, (channel, log_scale)));
+
+    let old = snapshot
+        .old_uri()
+        .and_then(|uri| histogram(ui, state, &uri));
+    let new = snapshot
+        .new_uri()
+        .and_then(|uri| histogram(ui, state, &uri));
+    if old.is_none() && new.is_none() {
+        ui.weak("Loading images…");
+        return;
+    }
+
+    let old_color = ui.visuals().weak_text_color();
+    let new_color = channel.color(ui);
+    plot(
+        ui,
+        channel,
+        log_scale,
+        [(old.as_deref(), old_color), (new.as_deref(), new_color)],
+    );
+
+    ui.horizontal(|ui| {
+        ui.colored_label(old_color, "— old");
+        ui.colored_label(new_color, "— new");
+    });
+    if let (Some(old), Some(new)) = (&old, &new) {
+        let stat = |ui: &mut Ui, label: &str, old: f32, new: f32| {
+            ui.label(format!("{label}: {old:.1} → {new:.1} ({:+.1})", new - old));
+        };
+        stat(ui, "Mean", old.mean(channel), new.mean(channel));
+        stat(
+            ui,
+            "Contrast (σ)",
+            old.std_dev(channel),
+            new.std_dev(channel),
+        );
+    }
+}
+
+/// The histogram of a decoded image, computed once per decode.
+fn histogram(ui: &Ui, state: &ViewerAppStateRef<'_>, uri: &str) -> Option<Arc<Histogram>> {
+    let Ok(ImagePoll::Ready { image }) =
+        state
+            .app
+            .diff_image_loader
+            .load_source(ui.ctx(), uri, SizeHint::default())
+    else {
+        return None;
+    };
+    // The pointer changes when the image is decoded again, e.g. after a refresh.
+    let id = Id::new(("histogram", uri, Arc::as_ptr(&image)));
+    Some(ui.data_mut(|d| {
+        d.get_temp_mut_or_insert_with(id, || Arc::new(Histogram::compute(&image)))
+            .clone()
+    }))
+}
+
+fn plot(
+    ui: &mut Ui,
+    channel: Channel,
+    log_scale: bool,
+    histograms: [(Option<&Histogram>, Color32); 2],
+) {
+    let (rect, _) = ui.allocate_exact_size(vec2(ui.available_width(), 120.0), egui::Sense::hover());
+    let painter = ui.painter_at(rect);
+    painter.rect_stroke(
+        rect,
+        2.0,
+        ui.visuals().widgets.noninteractive.bg_stroke,
+        StrokeKind::Inside,
+    );
+
+    let scale = |n: u32| {
+        if log_scale {
+            (n as f32).ln_1p()
+        } else {
+            n as f32
+        }
+    };
+    // Both on the same scale, as fractions of their pixel count, so differently sized images
+    // compare.
+    let fraction = |histogram: &Histogram, n: u32| scale(n) / scale(histogram.count.max(1));
+    let max = histograms
+        .iter()
+        .filter_map(|(histogram, _)| *histogram)
+        .flat_map(|histogram| {
+            histogram.bins[channel.index()]
+                .iter()
+                .map(move |n| fraction(histogram, *n))
+        })
+        .fold(f32::EPSILON, f32::max);
+
+    let plot_rect = rect.shrink(2.0);
+    for (histogram, color) in histograms {
+        let Some(histogram) = histogram else {
+            continue;
+        };
+        let points: Vec<Pos2> = histogram.bins[channel.index()]
+            .iter()
+            .enumerate()
+            .map(|(value, n)| {
+                let x = plot_rect.left() + plot_rect.width() * value as f32 / 255.0;
+                let y = plot_rect.bottom() - plot_rect.height() * fraction(histogram, *n) / max;
+                pos2(x, y)
+            })
+            .collect();
+        painter.line(points, Stroke::new(1.5, color));
+    }
+
+    // Mark the means, shifts show up as the lines moving apart.
+    for (histogram, color) in histograms {
+        if let Some(histogram) = histogram {
+            let x = plot_rect.left() + plot_rect.width() * histogram.mean(channel) / 255.0;
+            painter.vline(
+                x,
+                plot_rect.y_range(),
+                Stroke::new(1.0, color.gamma_multiply(0.6)),
+            );
+        }
+    }
+}
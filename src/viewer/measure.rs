@@ -0,0 +1,209 @@
+use crate::snapshot::Snapshot;
+use crate::state::{MeasureTool, ViewerAppStateRef, ViewerSystemCommand};
+use eframe::egui::emath::RectTransform;
+use eframe::egui::load::ImagePoll;
+use eframe::egui::{
+    Align2, Color32, CursorIcon, FontId, Id, Key, Pos2, Rect, Sense, SizeHint, Stroke, StrokeKind,
+    Ui, Vec2, pos2, vec2,
+};
+
+/// A pixel picked with the color sampler, with its color in each layer.
+#[derive(Clone)]
+struct Sample {
+    pixel: [usize; 2],
+    colors: Vec<(&'static str, Color32)>,
+}
+
+fn ruler_id() -> Id {
+    Id::new("measure_ruler")
+}
+
+fn sample_id() -> Id {
+    Id::new("measure_sample")
+}
+
+pub fn toolbar(ui: &mut Ui, state: &ViewerAppStateRef<'_>) {
+    ui.horizontal_wrapped(|ui| {
+        ui.label("Measure:");
+        for tool in MeasureTool::ALL {
+            let selected = state.measure_tool == Some(tool);
+            if ui
+                .selectable_label(selected, tool.icon())
+                .on_hover_text(tool.to_string())
+                .clicked()
+            {
+                state.app.send(ViewerSystemCommand::SetMeasureTool(
+                    (!selected).then_some(tool),
+                ));
+            }
+        }
+
+        if let Some(sample) = ui.data(|d| d.get_temp::<Sample>(sample_id())) {
+            ui.separator();
+            ui.label(format!("{}, {}", sample.pixel[0], sample.pixel[1]));
+            for (layer, color) in sample.colors {
+                let hex = hex(color);
+                let (rect, _) = ui.allocate_exact_size(Vec2::splat(12.0), Sense::hover());
+                ui.painter().rect_filled(rect, 2.0, color);
+                if ui
+                    .small_button(format!("{layer} {hex}"))
+                    .on_hover_text("Copy")
+                    .clicked()
+                {
+                    ui.ctx().copy_text(hex);
+                }
+            }
+        }
+    });
+}
+
+/// Draw the last ruler measurement, and let the active tool measure.
+///
+/// Takes the same arguments as [`super::annotate::annotation_layer`].
+pub fn measure_layer(
+    ui: &Ui,
+    state: &ViewerAppStateRef<'_>,
+    snapshot: &Snapshot,
+    image_rect: Rect,
+    clip_rect: Rect,
+    image_size: Vec2,
+) {
+    let to_screen = RectTransform::from_to(Rect::from_min_size(Pos2::ZERO, image_size), image_rect);
+    let from_screen = to_screen.inverse();
+    let visible = image_rect.intersect(clip_rect);
+    let painter = ui.painter_at(visible);
+
+    // Pixels rather than positions, so distances count whole pixels, like in an image editor.
+    let pixel_at = |pos: Pos2| {
+        let pos = from_screen * pos;
+        (pos.x >= 0.0 && pos.y >= 0.0 && pos.x < image_size.x && pos.y < image_size.y)
+            .then(|| [pos.x as usize, pos.y as usize])
+    };
+    let pixel_center = |[x, y]: [usize; 2]| to_screen * pos2(x as f32 + 0.5, y as f32 + 0.5);
+
+    if let Some(tool) = state.measure_tool {
+        let response = ui
+            .interact(visible, ui.id().with("measure"), Sense::click_and_drag())
+            .on_hover_cursor(CursorIcon::Crosshair);
+        let hovered = response.hover_pos().and_then(pixel_at);
+
+        if ui.input(|i| i.key_pressed(Key::Escape)) {
+            ui.data_mut(|d| {
+                d.remove::<[[usize; 2]; 2]>(ruler_id());
+                d.remove::<Sample>(sample_id());
+            });
+            state.app.send(ViewerSystemCommand::SetMeasureTool(None));
+        }
+
+        match tool {
+            MeasureTool::Ruler => {
+                if response.drag_started()
+                    && let Some(pixel) = hovered
+                {
+                    ui.data_mut(|d| d.insert_temp(ruler_id(), [pixel, pixel]));
+                }
+                if response.dragged()
+                    && let Some(pixel) = response.interact_pointer_pos().and_then(pixel_at)
+                    && let Some([from, _]) = ui.data(|d| d.get_temp::<[[usize; 2]; 2]>(ruler_id()))
+                {
+                    ui.data_mut(|d| d.insert_temp(ruler_id(), [from, pixel]));
+                }
+            }
+            MeasureTool::ColorPicker => {
+                if let Some(pixel) = hovered {
+                    let sample = sample(ui, state, snapshot, pixel);
+                    painter.rect_stroke(
+                        Rect::from_center_size(pixel_center(pixel), Vec2::splat(8.0)),
+                        0.0,
+                        Stroke::new(1.0, Color32::WHITE),
+                        StrokeKind::Outside,
+                    );
+                    if response.clicked() {
+                        ui.data_mut(|d| d.insert_temp(sample_id(), sample.clone()));
+                    }
+                    response.on_hover_ui_at_pointer(|ui| sample_ui(ui, &sample));
+                }
+            }
+        }
+    }
+
+    if let Some([from, to]) = ui.data(|d| d.get_temp::<[[usize; 2]; 2]>(ruler_id())) {
+        let (a, b) = (pixel_center(from), pixel_center(to));
+        let stroke = Stroke::new(1.5, Color32::YELLOW);
+        painter.line_segment([a, b], Stroke::new(3.5, Color32::from_black_alpha(160)));
+        painter.line_segment([a, b], stroke);
+        for end in [a, b] {
+            painter.circle_filled(end, 3.0, Color32::YELLOW);
+        }
+
+        let dx = from[0].abs_diff(to[0]);
+        let dy = from[1].abs_diff(to[1]);
+        let length = vec2(dx as f32, dy as f32).length();
+        let label = format!("Δx {dx}  Δy {dy}  {length:.1} px");
+        let galley = painter.layout_no_wrap(label, FontId::monospace(12.0), Color32::YELLOW);
+        let pos = b + vec2(8.0, 8.0);
+        painter.rect_filled(
+            Align2::LEFT_TOP.anchor_size(pos, galley.size()).expand(3.0),
+            2.0,
+            Color32::from_black_alpha(180),
+        );
+        painter.galley(pos, galley, Color32::YELLOW);
+    }
+}
+
+/// The color of `pixel` in the old, new and diff image, as far as they are loaded.
+fn sample(
+    ui: &Ui,
+    state: &ViewerAppStateRef<'_>,
+    snapshot: &Snapshot,
+    pixel: [usize; 2],
+) -> Sample {
+    let settings = &state.app.settings;
+    let layers = [
+        ("old", snapshot.old_uri()),
+        ("new", snapshot.new_uri()),
+        (
+            "diff",
+            snapshot.diff_uri(settings.use_original_diff, settings.options),
+        ),
+    ];
+    let colors = layers
+        .into_iter()
+        .filter_map(|(layer, uri)| {
+            let poll = state
+                .app
+                .diff_image_loader
+                .load_source(ui.ctx(), &uri?, SizeHint::default())
+                .ok()?;
+            let ImagePoll::Ready { image } = poll else {
+                return None;
+            };
+            let [width, height] = image.size;
+            // Old and new may have different sizes.
+            (pixel[0] < width && pixel[1] < height)
+                .then(|| (layer, image.pixels[pixel[1] * width + pixel[0]]))
+        })
+        .collect();
+    Sample { pixel, colors }
+}
+
+fn sample_ui(ui: &mut Ui, sample: &Sample) {
+    ui.label(format!("x {}, y {}", sample.pixel[0], sample.pixel[1]));
+    for (layer, color) in &sample.colors {
+        ui.horizontal(|ui| {
+            let (rect, _) = ui.allocate_exact_size(Vec2::splat(12.0), Sense::hover());
+            ui.painter().rect_filled(rect, 2.0, *color);
+            ui.monospace(format!("{layer:<4} {}", hex(*color)));
+        });
+    }
+    ui.weak("Click to keep");
+}
+
+fn hex(color: Color32) -> String {
+    let [r, g, b, a] = color.to_srgba_unmultiplied();
+    if a == u8::MAX {
+        format!("#{r:02x}{g:02x}{b:02x}")
+    } else {
+        format!("#{r:02x}{g:02x}{b:02x}{a:02x}")
+    }
+}
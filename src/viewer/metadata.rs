@@ -0,0 +1,217 @@
+use crate::snapshot::Snapshot;
+use crate::state::ViewerAppStateRef;
+use eframe::egui::load::BytesPoll;
+use eframe::egui::{Grid, Label, RichText, Sense, Ui};
+use image::{ImageDecoder as _, ImageReader};
+use std::io::Cursor;
+
+/// What the header of an image file says about it.
+struct Metadata {
+    file_size: usize,
+    format: Option<image::ImageFormat>,
+    size: Option<[u32; 2]>,
+    color_type: Option<image::ColorType>,
+
+    /// Pixels per inch, horizontally and vertically.
+    dpi: Option<[f32; 2]>,
+}
+
+impl Metadata {
+    /// Only the header is decoded, not the pixels.
+    fn read(bytes: &[u8]) -> Self {
+        let reader = ImageReader::new(Cursor::new(bytes)).with_guessed_format();
+        let format = reader.as_ref().ok().and_then(ImageReader::format);
+        let decoder = reader.ok().and_then(|reader| reader.into_decoder().ok());
+        Self {
+            file_size: bytes.len(),
+            format,
+            size: decoder.as_ref().map(|decoder| decoder.dimensions().into()),
+            color_type: decoder.as_ref().map(|decoder| decoder.color_type()),
+            dpi: png_dpi(bytes),
+        }
+    }
+}
+
+/// Reads the `pHYs` chunk of a png, which says how large the pixels are meant to be.
+fn png_dpi(bytes: &[u8]) -> Option<[f32; 2]> {
+    const SIGNATURE: &[u8] = b"\x89PNG\r\n\x1a\n";
+    const INCH_IN_METERS: f32 = 0.0254;
+
+    let mut rest = bytes.strip_prefix(SIGNATURE)?;
+    while rest.len() >= 8 {
+        let length = u32::from_be_bytes(rest[0..4].try_into().ok()?) as usize;
+        let kind = &rest[4..8];
+        let data = rest.get(8..8 + length)?;
+        match kind {
+            b"pHYs" if data.len() == 9 => {
+                let x = u32::from_be_bytes(data[0..4].try_into().ok()?);
+                let y = u32::from_be_bytes(data[4..8].try_into().ok()?);
+                // Unit 0 only gives the aspect ratio.
+                let per_meter = data[8] == 1;
+                return per_meter.then(|| [x as f32 * INCH_IN_METERS, y as f32 * INCH_IN_METERS]);
+            }
+            // The header chunks come before the image data.
+            b"IDAT" | b"IEND" => return None,
+            _ => {}
+        }
+        // Skip the data and the crc.
+        rest = rest.get(8 + length + 4..)?;
+    }
+    None
+}
+
+/// Dimensions, file sizes, color types and uris of old, new and diff, e.g. to find out whether
+/// a CI run accidentally produced screenshots at a different scale.
+pub fn metadata_ui(ui: &mut Ui, state: &ViewerAppStateRef<'_>, snapshot: &Snapshot) {
+    let diff_uri = snapshot.diff_uri(
+        state.app.settings.use_original_diff,
+        state.app.settings.options,
+    );
+    let entries = [
+        ("Old", snapshot.old_uri()),
+        ("New", snapshot.new_uri()),
+        ("Diff", diff_uri),
+    ];
+
+    let mut sizes = [None, None];
+    Grid::new("snapshot_metadata")
+        .num_columns(2)
+        .striped(true)
+        .show(ui, |ui| {
+            for (i, (name, uri)) in entries.into_iter().enumerate() {
+                let Some(uri) = uri else {
+                    continue;
+                };
+                ui.strong(name);
+                ui.end_row();
+
+                let size = if uri.starts_with("diff://") {
+                    // Computed by kitdiff, there is no file to read a header from.
+                    let info = state.app.diff_image_loader.diff_info(&uri);
+                    let size = info.map(|info| {
+                        let [width, height] = info.image.size;
+                        [width as u32, height as u32]
+                    });
+                    row(ui, "Dimensions", size.map_or_else(loading, dimensions));
+                    size
+                } else {
+                    file_rows(ui, &uri)
+                };
+                if let Some(slot) = sizes.get_mut(i) {
+                    *slot = size;
+                }
+
+                ui.label("Uri");
+                if ui
+                    .add(Label::new(uri_label(&uri)).sense(Sense::click()))
+                    .on_hover_text(format!("{uri}\n\nClick to copy"))
+                    .clicked()
+                {
+                    ui.ctx().copy_text(uri.clone());
+                }
+                ui.end_row();
+            }
+        });
+
+    if let [Some(old), Some(new)] = sizes
+        && old != new
+    {
+        let scale = new[0] as f32 / old[0].max(1) as f32;
+        let text = if (scale - scale.round()).abs() < 0.01 && scale.round() >= 2.0 {
+            format!("New is {}× the size of old", scale.round())
+        } else {
+            "Old and new have different dimensions".to_owned()
+        };
+        ui.label(RichText::new(text).color(ui.visuals().warn_fg_color));
+    }
+}
+
+/// The rows read from the file at `uri`, returns its dimensions once loaded.
+fn file_rows(ui: &mut Ui, uri: &str) -> Option<[u32; 2]> {
+    let bytes = match ui.ctx().try_load_bytes(uri) {
+        Ok(BytesPoll::Ready { bytes, .. }) => bytes,
+        Ok(BytesPoll::Pending { .. }) => {
+            row(ui, "File", loading());
+            return None;
+        }
+        Err(err) => {
+            row(
+                ui,
+                "File",
+                RichText::new(err.to_string()).color(ui.visuals().error_fg_color),
+            );
+            return None;
+        }
+    };
+
+    let metadata = Metadata::read(&bytes);
+    let format = metadata.format.map_or_else(
+        || "Unknown format".to_owned(),
+        |format| format!("{format:?}"),
+    );
+    row(
+        ui,
+        "File",
+        format!("{format}, {}", file_size(metadata.file_size)).into(),
+    );
+    row(
+        ui,
+        "Dimensions",
+        metadata.size.map_or_else(unknown, dimensions),
+    );
+    row(
+        ui,
+        "Color",
+        metadata
+            .color_type
+            .map_or_else(unknown, |color_type| format!("{color_type:?}").into()),
+    );
+    if let Some([x, y]) = metadata.dpi {
+        let dpi = if (x - y).abs() < 0.5 {
+            format!("{x:.0}")
+        } else {
+            format!("{x:.0} × {y:.0}")
+        };
+        row(ui, "DPI", dpi.into());
+    }
+    metadata.size
+}
+
+fn row(ui: &mut Ui, label: &str, value: RichText) {
+    ui.label(label);
+    ui.label(value);
+    ui.end_row();
+}
+
+fn uri_label(uri: &str) -> RichText {
+    const MAX_LEN: usize = 48;
+    let shown = if uri.chars().count() > MAX_LEN {
+        let tail: String = uri.chars().rev().take(MAX_LEN).collect();
+        format!("…{}", tail.chars().rev().collect::<String>())
+    } else {
+        uri.to_owned()
+    };
+    RichText::new(shown).monospace().small()
+}
+
+fn dimensions([width, height]: [u32; 2]) -> RichText {
+    format!("{width} × {height}").into()
+}
+
+fn file_size(bytes: usize) -> String {
+    if bytes < 1024 {
+        format!("{bytes} B")
+    } else if bytes < 1024 * 1024 {
+        format!("{:.1} KiB", bytes as f64 / 1024.0)
+    } else {
+        format!("{:.1} MiB", bytes as f64 / (1024.0 * 1024.0))
+    }
+}
+
+fn loading() -> RichText {
+    RichText::new("…").weak()
+}
+
+fn unknown() -> RichText {
+    RichText::new("unknown").weak()
+}
@@ -1,5 +1,23 @@
+mod annotate;
+mod background;
+mod batch_export;
+mod clipboard;
 mod diff_view;
+mod duplicates;
+mod export_view;
 mod file_tree;
+mod histogram;
+mod measure;
+mod metadata;
+mod palette;
+mod popout;
+mod review_summary;
+mod set_diff;
+mod stats;
+#[cfg(not(target_arch = "wasm32"))]
+mod test_run;
+mod threshold_tuning;
+mod tiled_image;
 mod viewer_options;
 
 use crate::state::ViewerAppStateRef;
@@ -7,6 +25,16 @@ use eframe::egui;
 use eframe::egui::Ui;
 
 pub fn viewer_ui(ui: &mut Ui, state: &ViewerAppStateRef<'_>) {
+    palette::palette(ui, state);
+    review_summary::summary_modal(ui, state);
+    set_diff::set_diff_modal(ui, state);
+    duplicates::duplicates_modal(ui, state);
+    stats::stats_modal(ui, state);
+    clipboard::handle_shortcuts(ui, state);
+    popout::popouts(ui, state);
+    #[cfg(not(target_arch = "wasm32"))]
+    test_run::output_panel(ui);
+
     egui::Panel::left("files").show_inside(ui, |ui| {
         file_tree::file_tree(ui, state);
     });
@@ -16,6 +44,18 @@ pub fn viewer_ui(ui: &mut Ui, state: &ViewerAppStateRef<'_>) {
 
         viewer_options::viewer_options(ui, state);
 
+        if let Some(snapshot) = state.active_snapshot {
+            egui::CollapsingHeader::new("Histogram").show(ui, |ui| {
+                histogram::histogram_ui(ui, state, snapshot);
+            });
+
+            egui::CollapsingHeader::new("Metadata").show(ui, |ui| {
+                metadata::metadata_ui(ui, state, snapshot);
+            });
+
+            state.loader.snapshot_ui(ui, state.app, snapshot);
+        }
+
         // // GitHub Authentication Section (WASM only)
         // #[cfg(target_arch = "wasm32")]
         // ui.group(|ui| {
@@ -0,0 +1,263 @@
+use crate::acknowledged;
+use crate::state::{ReviewStatus, SystemCommand, View, ViewerAppStateRef, ViewerSystemCommand};
+use crate::viewer::clipboard::{self, CopyImage};
+use eframe::egui::{Id, Key, KeyboardShortcut, Modal, Modifiers, ScrollArea, TextEdit, Ui};
+
+const SHORTCUT: KeyboardShortcut = KeyboardShortcut::new(Modifiers::COMMAND, Key::P);
+
+/// Only this many matches are shown, the rest is noise anyway.
+const MAX_RESULTS: usize = 100;
+
+#[derive(Clone, Default)]
+struct PaletteState {
+    open: bool,
+    query: String,
+    selected: usize,
+}
+
+#[derive(Clone, Copy)]
+enum PaletteCommand {
+    SetView(View),
+    Review(Option<ReviewStatus>),
+    ToggleAcknowledged,
+    CopyImage(CopyImage),
+    CopyPath,
+    Refresh,
+    Undo,
+    Redo,
+}
+
+impl PaletteCommand {
+    fn all() -> impl Iterator<Item = Self> {
+        View::ALL.into_iter().map(Self::SetView).chain([
+            Self::Review(Some(ReviewStatus::Accepted)),
+            Self::Review(Some(ReviewStatus::Rejected)),
+            Self::Review(Some(ReviewStatus::Flagged)),
+            Self::Review(None),
+            Self::ToggleAcknowledged,
+            Self::CopyImage(CopyImage::New),
+            Self::CopyImage(CopyImage::Diff),
+            Self::CopyPath,
+            Self::Refresh,
+            Self::Undo,
+            Self::Redo,
+        ])
+    }
+
+    fn label(self) -> String {
+        match self {
+            Self::SetView(view) => format!("Set view: {view}"),
+            Self::Review(Some(ReviewStatus::Accepted)) => "Accept snapshot".to_owned(),
+            Self::Review(Some(ReviewStatus::Rejected)) => "Reject snapshot".to_owned(),
+            Self::Review(Some(ReviewStatus::Flagged)) => "Flag snapshot for discussion".to_owned(),
+            Self::Review(None) => "Clear review".to_owned(),
+            Self::ToggleAcknowledged => "Acknowledge diff or remove acknowledgement".to_owned(),
+            Self::CopyImage(CopyImage::New) => "Copy new image".to_owned(),
+            Self::CopyImage(CopyImage::Diff) => "Copy diff image".to_owned(),
+            Self::CopyPath => "Copy path".to_owned(),
+            Self::Refresh => "Refresh".to_owned(),
+            Self::Undo => "Undo review change (Ctrl+Z)".to_owned(),
+            Self::Redo => "Redo review change (Ctrl+Shift+Z)".to_owned(),
+        }
+    }
+
+    fn run(self, state: &ViewerAppStateRef<'_>) {
+        match self {
+            Self::SetView(view) => state.app.send(ViewerSystemCommand::SetView(view)),
+            Self::Review(status) => {
+                if let Some(snapshot) = state.active_snapshot {
+                    state.app.send(ViewerSystemCommand::SetReview(
+                        snapshot.path.clone(),
+                        status,
+                    ));
+                }
+            }
+            Self::ToggleAcknowledged => {
+                if let Some(snapshot) = state.active_snapshot {
+                    let acknowledged = state.acknowledged.is_acknowledged(&snapshot.path);
+                    acknowledged::set_acknowledged(state, snapshot, !acknowledged);
+                }
+            }
+            Self::CopyImage(which) => {
+                if let Some(snapshot) = state.active_snapshot {
+                    clipboard::copy_image(state.app.egui_ctx, state, snapshot, which);
+                }
+            }
+            Self::CopyPath => {
+                if let Some(snapshot) = state.active_snapshot {
+                    clipboard::copy_path(state.app.egui_ctx, snapshot);
+                }
+            }
+            Self::Refresh => state.app.send(SystemCommand::Refresh),
+            Self::Undo => state.app.send(ViewerSystemCommand::Undo),
+            Self::Redo => state.app.send(ViewerSystemCommand::Redo),
+        }
+    }
+}
+
+enum Entry {
+    /// Index into all snapshots.
+    Snapshot(usize),
+    Command(PaletteCommand),
+}
+
+/// Ctrl+P palette to jump to any snapshot or run a command by typing a few characters of it.
+pub fn palette(ui: &Ui, state: &ViewerAppStateRef<'_>) {
+    let ctx = ui.ctx();
+    let id = Id::new("command_palette");
+    let mut palette: PaletteState = ctx.data_mut(|d| d.get_temp(id)).unwrap_or_default();
+
+    if ctx.input_mut(|i| i.consume_shortcut(&SHORTCUT)) {
+        palette = PaletteState {
+            open: !palette.open,
+            ..Default::default()
+        };
+    }
+
+    if palette.open {
+        let modal = Modal::new(id.with("modal")).show(ctx, |ui| {
+            ui.set_width(500.0);
+
+            // Handle navigation before the text edit gets to see the keys.
+            let (down, up, enter) = ui.input_mut(|i| {
+                (
+                    i.consume_key(Modifiers::NONE, Key::ArrowDown),
+                    i.consume_key(Modifiers::NONE, Key::ArrowUp),
+                    i.consume_key(Modifiers::NONE, Key::Enter),
+                )
+            });
+
+            let response = TextEdit::singleline(&mut palette.query)
+                .hint_text("Go to snapshot or run a command…")
+                .desired_width(f32::INFINITY)
+                .show(ui)
+                .response;
+            response.request_focus();
+            if response.changed() {
+                palette.selected = 0;
+            }
+
+            let entries = matching_entries(&palette.query, state);
+            let mut chosen = None;
+
+            if down {
+                palette.selected = (palette.selected + 1).min(entries.len().saturating_sub(1));
+            }
+            if up {
+                palette.selected = palette.selected.saturating_sub(1);
+            }
+            if enter {
+                chosen = entries.get(palette.selected).map(|(entry, _)| entry);
+            }
+
+            ui.separator();
+
+            ScrollArea::vertical().max_height(400.0).show(ui, |ui| {
+                for (index, (entry, label)) in entries.iter().enumerate() {
+                    let selected = index == palette.selected;
+                    let response = ui.horizontal(|ui| {
+                        let response = ui.selectable_label(selected, label);
+                        if matches!(entry, Entry::Command(_)) {
+                            ui.weak("command");
+                        }
+                        response
+                    });
+                    if response.inner.clicked() {
+                        chosen = Some(entry);
+                    }
+                    if selected && (up || down) {
+                        response.inner.scroll_to_me(None);
+                    }
+                }
+
+                if entries.is_empty() {
+                    ui.weak("No matches");
+                }
+            });
+
+            chosen.map(|entry| match entry {
+                Entry::Snapshot(index) => {
+                    // Make sure the snapshot is visible in the file tree.
+                    if !state.filtered_snapshots.iter().any(|(i, _)| i == index) {
+                        state
+                            .app
+                            .send(ViewerSystemCommand::SetFilter(String::new()));
+                        state.app.send(ViewerSystemCommand::SetScope(None));
+                    }
+                    state.app.send(ViewerSystemCommand::SelectSnapshot(*index));
+                }
+                Entry::Command(command) => command.run(state),
+            })
+        });
+
+        if modal.inner.is_some() || modal.should_close() {
+            palette = PaletteState::default();
+        }
+    }
+
+    ctx.data_mut(|d| d.insert_temp(id, palette));
+}
+
+/// Matching entries with their labels, best match first.
+fn matching_entries(query: &str, state: &ViewerAppStateRef<'_>) -> Vec<(Entry, String)> {
+    let commands = PaletteCommand::all().map(|command| (Entry::Command(command), command.label()));
+    let snapshots = state
+        .loader
+        .snapshots()
+        .iter()
+        .enumerate()
+        .map(|(index, snapshot)| {
+            (
+                Entry::Snapshot(index),
+                snapshot.path.to_string_lossy().into_owned(),
+            )
+        });
+
+    let mut entries: Vec<(i32, (Entry, String))> = commands
+        .chain(snapshots)
+        .filter_map(|(entry, label)| Some((fuzzy_score(query, &label)?, (entry, label))))
+        .collect();
+
+    // Stable sort, so equally good matches keep their order.
+    entries.sort_by_key(|(score, _)| -score);
+    entries.truncate(MAX_RESULTS);
+    entries.into_iter().map(|(_, entry)| entry).collect()
+}
+
+/// How well `query` matches `candidate`, or `None` if it doesn't match at all.
+///
+/// All characters of the query need to appear in the candidate in order. Consecutive characters
+/// and characters at the start of a path segment or word score higher, and shorter candidates win
+/// ties.
+fn fuzzy_score(query: &str, candidate: &str) -> Option<i32> {
+    let candidate: Vec<char> = candidate.to_lowercase().chars().collect();
+
+    let mut score = 0;
+    let mut next = 0;
+    let mut previous: Option<usize> = None;
+    for query_char in query.to_lowercase().chars().filter(|c| !c.is_whitespace()) {
+        let index = next
+            + candidate
+                .get(next..)?
+                .iter()
+                .position(|c| *c == query_char)?;
+
+        score += 10;
+        if previous.is_some_and(|previous| previous + 1 == index) {
+            score += 15;
+        }
+        if index == 0
+            || matches!(
+                candidate[index - 1],
+                '/' | '\\' | '_' | '-' | '.' | ' ' | ':'
+            )
+        {
+            score += 10;
+        }
+
+        previous = Some(index);
+        next = index + 1;
+    }
+
+    Some(score * 1000 - candidate.len().min(999) as i32)
+}
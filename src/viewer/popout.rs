@@ -0,0 +1,108 @@
+use crate::snapshot::Snapshot;
+use crate::state::{Popout, PopoutView, ViewerAppStateRef, ViewerSystemCommand};
+use eframe::egui::{self, Image, TextureOptions, Ui, ViewportBuilder, ViewportId};
+
+/// Whether snapshots can get their own OS window. Not on the web, where every viewport would be
+/// embedded in the page anyway.
+pub fn supported(ui: &Ui) -> bool {
+    !ui.ctx().embed_viewports()
+}
+
+/// Show the windows of all popped out snapshots.
+pub fn popouts(ui: &Ui, state: &ViewerAppStateRef<'_>) {
+    for popout in &state.popouts {
+        let snapshot = state
+            .loader
+            .snapshots()
+            .iter()
+            .find(|snapshot| snapshot.path == popout.path);
+
+        let id = ViewportId::from_hash_of(("popout", &popout.path));
+        let builder = ViewportBuilder::default()
+            .with_title(format!("kitdiff - {}", popout.path.display()))
+            .with_inner_size([900.0, 700.0]);
+        ui.ctx().show_viewport_immediate(id, builder, |ui, _class| {
+            egui::CentralPanel::default().show_inside(ui, |ui| match snapshot {
+                Some(snapshot) => popout_ui(ui, state, popout, snapshot),
+                // E.g. while refreshing, or if the snapshot is gone.
+                None => {
+                    ui.weak(format!("{} isn't loaded", popout.path.display()));
+                }
+            });
+
+            if ui.input(|i| i.viewport().close_requested()) {
+                state
+                    .app
+                    .send(ViewerSystemCommand::ClosePopout(popout.path.clone()));
+            }
+        });
+    }
+}
+
+fn popout_ui(ui: &mut Ui, state: &ViewerAppStateRef<'_>, popout: &Popout, snapshot: &Snapshot) {
+    ui.horizontal(|ui| {
+        ui.strong(snapshot.file_name());
+        ui.separator();
+        for view in PopoutView::ALL {
+            if ui
+                .selectable_label(popout.view == view, view.to_string())
+                .clicked()
+            {
+                state.app.send(ViewerSystemCommand::SetPopoutView(
+                    popout.path.clone(),
+                    view,
+                ));
+            }
+        }
+    });
+
+    let settings = &state.app.settings;
+    let image = |uri: Option<String>| {
+        uri.map(|uri| {
+            Image::new(uri)
+                .texture_options(TextureOptions {
+                    magnification: settings.texture_magnification,
+                    ..TextureOptions::default()
+                })
+                .shrink_to_fit()
+        })
+    };
+    let missing = |ui: &mut Ui, label: &str| {
+        ui.centered_and_justified(|ui| ui.weak(format!("No {label} image")));
+    };
+
+    match popout.view {
+        PopoutView::SideBySide => {
+            ui.columns(2, |columns| {
+                for (ui, (label, uri)) in columns
+                    .iter_mut()
+                    .zip([("old", snapshot.old_uri()), ("new", snapshot.new_uri())])
+                {
+                    ui.label(label);
+                    match image(uri) {
+                        Some(image) => {
+                            ui.add(image);
+                        }
+                        None => missing(ui, label),
+                    }
+                }
+            });
+        }
+        view => {
+            let (label, uri) = match view {
+                PopoutView::Old => ("old", snapshot.old_uri()),
+                PopoutView::New => ("new", snapshot.new_uri()),
+                PopoutView::SideBySide | PopoutView::Diff => (
+                    "diff",
+                    snapshot.diff_uri(settings.use_original_diff, settings.options),
+                ),
+            };
+            match image(uri) {
+                Some(image) => {
+                    ui.centered_and_justified(|ui| ui.add(image));
+                }
+                None => missing(ui, label),
+            }
+        }
+    }
+}
@@ -0,0 +1,192 @@
+//! A markdown summary of the review decisions, to paste or post on the pull request, so design
+//! sign-off is written down somewhere.
+
+use crate::diff_precompute::DiffPrecompute;
+use crate::github::octokit::RepoClient;
+use crate::notifications::Notification;
+use crate::snapshot::Snapshot;
+use crate::state::{ReviewStatus, ViewerAppStateRef};
+use eframe::egui::{Id, Modal, OpenUrl, ScrollArea, TextEdit, Ui};
+use std::fmt::Write as _;
+use std::task::Poll;
+
+/// Unreviewed snapshots are listed too, but only this many of them.
+const MAX_UNREVIEWED: usize = 50;
+
+fn id() -> Id {
+    Id::new("review_summary")
+}
+
+pub fn summary_button(ui: &mut Ui, state: &ViewerAppStateRef<'_>) {
+    if ui
+        .button("Summary…")
+        .on_hover_text(
+            "Generate a markdown summary of the accepted, rejected and flagged snapshots",
+        )
+        .clicked()
+    {
+        let markdown = markdown(state);
+        ui.data_mut(|d| d.insert_temp(id(), markdown));
+    }
+}
+
+/// The summary window, while it is open.
+pub fn summary_modal(ui: &Ui, state: &ViewerAppStateRef<'_>) {
+    let Some(mut markdown) = ui.data(|d| d.get_temp::<String>(id())) else {
+        return;
+    };
+
+    let mut close = false;
+    let modal = Modal::new(id().with("modal")).show(ui.ctx(), |ui| {
+        ui.set_width(600.0);
+        ui.heading("Review summary");
+        ui.label("Edit the text as you like before copying or posting it.");
+
+        ScrollArea::vertical().max_height(400.0).show(ui, |ui| {
+            TextEdit::multiline(&mut markdown)
+                .code_editor()
+                .desired_width(f32::INFINITY)
+                .show(ui);
+        });
+
+        ui.horizontal(|ui| {
+            if ui.button("Copy").clicked() {
+                ui.ctx().copy_text(markdown.clone());
+                close = true;
+            }
+            if let Some(link) = state.loader.pull_request()
+                && ui
+                    .button("Post as PR comment")
+                    .on_hover_text(format!("Comment on {}", link.short_name()))
+                    .clicked()
+            {
+                let repo = RepoClient::new(state.app.github_auth.client(), link.repo.clone());
+                let pr_number = link.pr_number;
+                let body = markdown.clone();
+                let tx = state.app.tx.clone();
+                let ctx = ui.ctx().clone();
+                hello_egui_utils::spawn(async move {
+                    match repo.issues().create_comment(pr_number, body).await {
+                        Ok(comment) => {
+                            tracing::info!("Posted the review summary");
+                            ctx.open_url(OpenUrl::new_tab(comment.html_url));
+                        }
+                        Err(err) => {
+                            tx.send(Notification::error("Failed to post the summary", err).into())
+                                .ok();
+                        }
+                    }
+                });
+                close = true;
+            }
+            if ui.button("Close").clicked() {
+                close = true;
+            }
+        });
+    });
+
+    if close || modal.should_close() {
+        ui.data_mut(|d| d.remove::<String>(id()));
+    } else {
+        ui.data_mut(|d| d.insert_temp(id(), markdown));
+    }
+}
+
+fn markdown(state: &ViewerAppStateRef<'_>) -> String {
+    let snapshots = state.loader.snapshots();
+    let decided = |status: ReviewStatus| -> Vec<&Snapshot> {
+        snapshots
+            .iter()
+            .filter(|snapshot| state.reviews.get(&snapshot.path) == Some(&status))
+            .collect()
+    };
+    let accepted = decided(ReviewStatus::Accepted);
+    let rejected = decided(ReviewStatus::Rejected);
+    let flagged = decided(ReviewStatus::Flagged);
+    let unreviewed: Vec<&Snapshot> = snapshots
+        .iter()
+        .filter(|snapshot| !snapshot.unchanged && !state.reviews.contains_key(&snapshot.path))
+        .collect();
+
+    let mut text = String::from("## Snapshot review\n\n");
+    let reviewed = accepted.len() + rejected.len() + flagged.len();
+    writeln!(
+        text,
+        "Reviewed {reviewed} of {} snapshots of {} in kitdiff: \
+        {} accepted, {} rejected, {} flagged.",
+        reviewed + unreviewed.len(),
+        state.loader.files_header(),
+        accepted.len(),
+        rejected.len(),
+        flagged.len(),
+    )
+    .ok();
+
+    for (status, title, list) in [
+        (ReviewStatus::Rejected, "Rejected", &rejected),
+        (ReviewStatus::Flagged, "Flagged", &flagged),
+        (ReviewStatus::Accepted, "Accepted", &accepted),
+    ] {
+        if list.is_empty() {
+            continue;
+        }
+        writeln!(text, "\n### {} {title} ({})\n", status.icon(), list.len()).ok();
+        for snapshot in list {
+            writeln!(text, "{}", line(state, snapshot)).ok();
+        }
+    }
+
+    if !unreviewed.is_empty() {
+        writeln!(
+            text,
+            "\n<details><summary>Not reviewed ({})</summary>\n",
+            unreviewed.len()
+        )
+        .ok();
+        for snapshot in unreviewed.iter().take(MAX_UNREVIEWED) {
+            writeln!(text, "{}", line(state, snapshot)).ok();
+        }
+        if unreviewed.len() > MAX_UNREVIEWED {
+            writeln!(text, "- …and {} more", unreviewed.len() - MAX_UNREVIEWED).ok();
+        }
+        writeln!(text, "\n</details>").ok();
+    }
+    text
+}
+
+/// A list item with the path, linked to the new image if it's online, and what we know about it.
+fn line(state: &ViewerAppStateRef<'_>, snapshot: &Snapshot) -> String {
+    let path = snapshot.path.display();
+    let mut line = match snapshot.new_uri().filter(|uri| uri.starts_with("https://")) {
+        Some(url) => format!("- [`{path}`]({url})"),
+        None => format!("- `{path}`"),
+    };
+
+    if snapshot.added() {
+        line.push_str(" (added)");
+    } else if snapshot.deleted() {
+        line.push_str(" (deleted)");
+    } else {
+        if let Some(renamed_from) = &snapshot.renamed_from {
+            write!(line, " (renamed from `{}`)", renamed_from.display()).ok();
+        }
+        if let Some(pixels) = DiffPrecompute::diff_uri(snapshot, state.app.settings.options)
+            .and_then(|uri| state.app.diff_image_loader.diff_pixels(&uri))
+        {
+            write!(line, " ({pixels} px changed)").ok();
+        }
+    }
+
+    if let Poll::Ready(Some(blame)) = state.loader.blame(snapshot) {
+        match &blame.url {
+            Some(url) => write!(line, ", changed in [{}]({url})", short_sha(&blame.sha)),
+            None => write!(line, ", changed in {}", short_sha(&blame.sha)),
+        }
+        .ok();
+    }
+    line
+}
+
+fn short_sha(sha: &str) -> &str {
+    sha.get(..7).unwrap_or(sha)
+}
@@ -0,0 +1,219 @@
+//! Which snapshot files a source added and removed, rather than how the images changed. A renamed
+//! test can show up as one file removed and another added, so those with identical contents are
+//! listed as renames, next to the ones the source already paired up.
+
+use crate::snapshot::{FileReference, Snapshot};
+use crate::state::{ViewerAppStateRef, ViewerSystemCommand};
+use eframe::egui::mutex::Mutex;
+use eframe::egui::{Id, Modal, ScrollArea, Ui};
+use std::collections::HashMap;
+use std::fmt::Write as _;
+use std::hash::{DefaultHasher, Hash as _, Hasher as _};
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+use std::task::Poll;
+
+type Report = Arc<Mutex<Poll<Result<SetDiff, String>>>>;
+
+#[derive(Debug, Default)]
+struct SetDiff {
+    added: Vec<PathBuf>,
+    removed: Vec<PathBuf>,
+
+    /// Old and new path of files that were removed and added with the same contents.
+    renamed: Vec<(PathBuf, PathBuf)>,
+
+    /// Snapshots on both sides.
+    kept: usize,
+}
+
+impl SetDiff {
+    fn markdown(&self, header: &str) -> String {
+        let mut text = format!(
+            "## Snapshot files of {header}\n\n\
+            {} added, {} removed, {} renamed, {} on both sides.\n",
+            self.added.len(),
+            self.removed.len(),
+            self.renamed.len(),
+            self.kept
+        );
+        if !self.renamed.is_empty() {
+            writeln!(text, "\n### Renamed\n").ok();
+            for (old, new) in &self.renamed {
+                writeln!(text, "- `{}` → `{}`", old.display(), new.display()).ok();
+            }
+        }
+        for (title, paths) in [("Added", &self.added), ("Removed", &self.removed)] {
+            if !paths.is_empty() {
+                writeln!(text, "\n### {title}\n").ok();
+                for path in paths {
+                    writeln!(text, "- `{}`", path.display()).ok();
+                }
+            }
+        }
+        text
+    }
+}
+
+fn id() -> Id {
+    Id::new("set_diff")
+}
+
+pub fn set_diff_button(ui: &mut Ui, state: &ViewerAppStateRef<'_>) {
+    if ui
+        .button("Files…")
+        .on_hover_text("Which snapshot files were added, removed or renamed")
+        .clicked()
+    {
+        let report = Report::new(Mutex::new(Poll::Pending));
+        let snapshots = state.loader.snapshots().to_vec();
+        let ctx = ui.ctx().clone();
+        {
+            let report = report.clone();
+            hello_egui_utils::spawn(async move {
+                let result = set_diff(snapshots).await.map_err(|err| format!("{err:#}"));
+                *report.lock() = Poll::Ready(result);
+                ctx.request_repaint();
+            });
+        }
+        ui.data_mut(|d| d.insert_temp(id(), report));
+    }
+}
+
+/// The report window, while it is open.
+pub fn set_diff_modal(ui: &Ui, state: &ViewerAppStateRef<'_>) {
+    let Some(report) = ui.data(|d| d.get_temp::<Report>(id())) else {
+        return;
+    };
+
+    let mut close = false;
+    let modal = Modal::new(id().with("modal")).show(ui.ctx(), |ui| {
+        ui.set_width(600.0);
+        ui.heading("Snapshot files");
+
+        let report = report.lock();
+        let set_diff = match &*report {
+            Poll::Pending => {
+                ui.horizontal(|ui| {
+                    ui.spinner();
+                    ui.label("Comparing the contents of added and removed files…");
+                });
+                None
+            }
+            Poll::Ready(Err(err)) => {
+                ui.colored_label(ui.visuals().error_fg_color, err);
+                None
+            }
+            Poll::Ready(Ok(set_diff)) => Some(set_diff),
+        };
+
+        if let Some(set_diff) = set_diff {
+            ui.label(format!(
+                "{} added, {} removed, {} renamed, {} on both sides.",
+                set_diff.added.len(),
+                set_diff.removed.len(),
+                set_diff.renamed.len(),
+                set_diff.kept
+            ));
+            ScrollArea::vertical().max_height(400.0).show(ui, |ui| {
+                if !set_diff.renamed.is_empty() {
+                    ui.strong("Renamed");
+                    for (old, new) in &set_diff.renamed {
+                        ui.horizontal(|ui| {
+                            path_link(ui, state, old);
+                            ui.label("→");
+                            path_link(ui, state, new);
+                        });
+                    }
+                }
+                for (title, paths) in [("Added", &set_diff.added), ("Removed", &set_diff.removed)] {
+                    if !paths.is_empty() {
+                        ui.strong(title);
+                        for path in paths {
+                            path_link(ui, state, path);
+                        }
+                    }
+                }
+            });
+        }
+
+        ui.horizontal(|ui| {
+            if let Some(set_diff) = set_diff
+                && ui.button("Copy as markdown").clicked()
+            {
+                ui.ctx()
+                    .copy_text(set_diff.markdown(&state.loader.files_header()));
+            }
+            if ui.button("Close").clicked() {
+                close = true;
+            }
+        });
+    });
+
+    if close || modal.should_close() {
+        ui.data_mut(|d| d.remove::<Report>(id()));
+    }
+}
+
+/// Selects the snapshot when clicked.
+pub(super) fn path_link(ui: &mut Ui, state: &ViewerAppStateRef<'_>, path: &Path) {
+    let index = state
+        .loader
+        .snapshots()
+        .iter()
+        .position(|snapshot| snapshot.path == path);
+    let clicked = ui
+        .add_enabled(
+            index.is_some(),
+            eframe::egui::Link::new(path.display().to_string()),
+        )
+        .clicked();
+    if clicked && let Some(index) = index {
+        state.app.send(ViewerSystemCommand::SelectSnapshot(index));
+    }
+}
+
+async fn set_diff(snapshots: Vec<Snapshot>) -> anyhow::Result<SetDiff> {
+    let mut set_diff = SetDiff::default();
+    let mut removed: HashMap<u64, Vec<PathBuf>> = HashMap::new();
+    let mut added = Vec::new();
+    for snapshot in snapshots {
+        match (&snapshot.old, &snapshot.new) {
+            (Some(_), Some(_)) => match snapshot.renamed_from {
+                Some(old_path) => set_diff.renamed.push((old_path, snapshot.path)),
+                None => set_diff.kept += 1,
+            },
+            (Some(old), None) => match content_hash(old).await? {
+                Some(hash) => removed.entry(hash).or_default().push(snapshot.path),
+                None => set_diff.removed.push(snapshot.path),
+            },
+            (None, Some(new)) => added.push((content_hash(new).await?, snapshot.path)),
+            (None, None) => {}
+        }
+    }
+
+    for (hash, path) in added {
+        let old_path = hash
+            .and_then(|hash| removed.get_mut(&hash))
+            .and_then(Vec::pop);
+        match old_path {
+            Some(old_path) => set_diff.renamed.push((old_path, path)),
+            None => set_diff.added.push(path),
+        }
+    }
+    set_diff.removed.extend(removed.into_values().flatten());
+
+    set_diff.added.sort();
+    set_diff.removed.sort();
+    set_diff.renamed.sort();
+    Ok(set_diff)
+}
+
+/// Hash of the encoded file, so only byte-identical files match.
+async fn content_hash(file: &FileReference) -> anyhow::Result<Option<u64>> {
+    Ok(file.read().await?.map(|bytes| {
+        let mut hasher = DefaultHasher::new();
+        bytes.hash(&mut hasher);
+        hasher.finish()
+    }))
+}
@@ -0,0 +1,269 @@
+//! Aggregate numbers of the loaded source: how many snapshots changed, by how much, and how big
+//! the images are. Answers "how big is this visual change" without clicking through every diff.
+
+use crate::diff_precompute::DiffPrecompute;
+use crate::loaders::download::format_bytes;
+use crate::snapshot::{FileReference, Snapshot};
+use crate::state::ViewerAppStateRef;
+use eframe::egui::{self, Align2, FontId, Grid, Id, ImageSource, Modal, Rect, Sense, Ui, vec2};
+use std::fmt::Write as _;
+use std::path::PathBuf;
+use std::sync::Arc;
+
+/// How many of the largest snapshots are listed.
+const LARGEST: usize = 10;
+
+/// Bins of the diff size distribution, by order of magnitude of changed pixels.
+const BINS: [&str; 7] = ["1+", "10+", "100+", "1k+", "10k+", "100k+", "1M+"];
+
+fn id() -> Id {
+    Id::new("source_stats")
+}
+
+pub fn stats_button(ui: &mut Ui, state: &ViewerAppStateRef<'_>) {
+    if ui
+        .small_button("📊")
+        .on_hover_text("Statistics of the loaded snapshots")
+        .clicked()
+    {
+        let sizes = Sizes::new(state.loader.snapshots());
+        ui.data_mut(|d| d.insert_temp(id(), Arc::new(sizes)));
+    }
+}
+
+/// File sizes, read once when the window is opened.
+struct Sizes {
+    total: u64,
+
+    /// Remote images, whose size we don't know without downloading them.
+    unknown: usize,
+
+    /// Largest current image first.
+    largest: Vec<(PathBuf, u64)>,
+}
+
+impl Sizes {
+    fn new(snapshots: &[Snapshot]) -> Self {
+        let mut total = 0;
+        let mut unknown = 0;
+        let mut largest = Vec::new();
+        for snapshot in snapshots {
+            let mut current = None;
+            for file in [&snapshot.old, &snapshot.new].into_iter().flatten() {
+                match file_size(file) {
+                    Some(size) => {
+                        total += size;
+                        current = Some(size);
+                    }
+                    None => unknown += 1,
+                }
+            }
+            if let Some(size) = current {
+                largest.push((snapshot.path.clone(), size));
+            }
+        }
+        largest.sort_by(|(_, a), (_, b)| b.cmp(a));
+        largest.truncate(LARGEST);
+        Self {
+            total,
+            unknown,
+            largest,
+        }
+    }
+}
+
+fn file_size(file: &FileReference) -> Option<u64> {
+    match file {
+        FileReference::Path(path) => std::fs::metadata(path).ok().map(|meta| meta.len()),
+        FileReference::Source(ImageSource::Bytes { bytes, .. }) => Some(bytes.len() as u64),
+        FileReference::Source(ImageSource::Uri(uri)) => {
+            let path = uri.strip_prefix("file://")?;
+            std::fs::metadata(path).ok().map(|meta| meta.len())
+        }
+        FileReference::Source(ImageSource::Texture(_)) => None,
+        FileReference::Archive(entry) => Some(entry.size()),
+    }
+}
+
+/// Snapshot counts, computed every frame as diffs come in.
+#[derive(Default)]
+struct Counts {
+    total: usize,
+    changed: usize,
+    below_threshold: usize,
+    unchanged: usize,
+    added: usize,
+    deleted: usize,
+    renamed: usize,
+
+    /// Diffs that aren't computed yet, or failed.
+    unknown: usize,
+    changed_pixels: u64,
+
+    /// Number of changed snapshots per entry of [`BINS`].
+    distribution: [usize; BINS.len()],
+}
+
+impl Counts {
+    fn new(state: &ViewerAppStateRef<'_>) -> Self {
+        let mut counts = Self::default();
+        for snapshot in state.loader.snapshots() {
+            counts.total += 1;
+            if snapshot.renamed_from.is_some() {
+                counts.renamed += 1;
+            }
+            if snapshot.added() {
+                counts.added += 1;
+            } else if snapshot.deleted() {
+                counts.deleted += 1;
+            } else if snapshot.unchanged {
+                counts.unchanged += 1;
+            } else {
+                match DiffPrecompute::diff_uri(snapshot, state.app.settings.options)
+                    .and_then(|uri| state.app.diff_image_loader.diff_pixels(&uri))
+                {
+                    Some(0) => counts.below_threshold += 1,
+                    Some(pixels) => {
+                        let pixels = pixels.unsigned_abs();
+                        counts.changed += 1;
+                        counts.changed_pixels += u64::from(pixels);
+                        let bin = (pixels.ilog10() as usize).min(BINS.len() - 1);
+                        counts.distribution[bin] += 1;
+                    }
+                    None => counts.unknown += 1,
+                }
+            }
+        }
+        counts
+    }
+
+    fn rows(&self) -> Vec<(&'static str, String)> {
+        vec![
+            ("Snapshots", self.total.to_string()),
+            ("Changed", self.changed.to_string()),
+            ("Below the threshold", self.below_threshold.to_string()),
+            ("Identical files", self.unchanged.to_string()),
+            ("Added", self.added.to_string()),
+            ("Removed", self.deleted.to_string()),
+            ("Renamed", self.renamed.to_string()),
+            ("Changed pixels", self.changed_pixels.to_string()),
+        ]
+    }
+}
+
+/// The statistics window, while it is open.
+pub fn stats_modal(ui: &Ui, state: &ViewerAppStateRef<'_>) {
+    let Some(sizes) = ui.data(|d| d.get_temp::<Arc<Sizes>>(id())) else {
+        return;
+    };
+    let counts = Counts::new(state);
+
+    let mut close = false;
+    let modal = Modal::new(id().with("modal")).show(ui.ctx(), |ui| {
+        ui.set_width(500.0);
+        ui.heading("Statistics");
+        ui.weak(state.loader.files_header());
+
+        Grid::new("stats_counts").num_columns(2).show(ui, |ui| {
+            for (label, value) in counts.rows() {
+                ui.label(label);
+                ui.strong(value);
+                ui.end_row();
+            }
+            ui.label("Total size");
+            ui.strong(format_bytes(sizes.total));
+            ui.end_row();
+        });
+        if counts.unknown > 0 {
+            ui.weak(format!(
+                "{} diffs aren't computed yet or failed",
+                counts.unknown
+            ));
+        }
+        if sizes.unknown > 0 {
+            ui.weak(format!(
+                "{} remote images aren't included in the size",
+                sizes.unknown
+            ));
+        }
+
+        ui.add_space(8.0);
+        ui.strong("Changed snapshots by number of changed pixels");
+        distribution_plot(ui, &counts.distribution);
+
+        if !sizes.largest.is_empty() {
+            ui.add_space(8.0);
+            ui.strong("Largest images");
+            Grid::new("stats_largest").num_columns(2).show(ui, |ui| {
+                for (path, size) in &sizes.largest {
+                    ui.label(path.display().to_string());
+                    ui.label(format_bytes(*size));
+                    ui.end_row();
+                }
+            });
+        }
+
+        ui.horizontal(|ui| {
+            if ui.button("Copy as markdown").clicked() {
+                ui.ctx()
+                    .copy_text(markdown(&state.loader.files_header(), &counts, &sizes));
+            }
+            if ui.button("Close").clicked() {
+                close = true;
+            }
+        });
+    });
+
+    if close || modal.should_close() {
+        ui.data_mut(|d| d.remove::<Arc<Sizes>>(id()));
+    }
+}
+
+/// A bar per bin, labeled with its count.
+fn distribution_plot(ui: &mut Ui, distribution: &[usize; BINS.len()]) {
+    let (rect, _) = ui.allocate_exact_size(vec2(ui.available_width(), 120.0), Sense::hover());
+    let painter = ui.painter_at(rect);
+    let max = distribution.iter().copied().max().unwrap_or(0).max(1);
+    let label_height = 16.0;
+    let bar_area = Rect::from_min_max(rect.min, rect.max - vec2(0.0, label_height));
+    let slot = bar_area.width() / BINS.len() as f32;
+    let font = FontId::proportional(11.0);
+
+    for (index, (label, count)) in BINS.iter().zip(distribution).enumerate() {
+        let left = bar_area.left() + slot * index as f32;
+        let height = (bar_area.height() - label_height) * *count as f32 / max as f32;
+        let bar = Rect::from_min_max(
+            egui::pos2(left + slot * 0.15, bar_area.bottom() - height),
+            egui::pos2(left + slot * 0.85, bar_area.bottom()),
+        );
+        painter.rect_filled(bar, 2.0, ui.visuals().selection.bg_fill);
+        painter.text(
+            bar.center_top() - vec2(0.0, 2.0),
+            Align2::CENTER_BOTTOM,
+            count.to_string(),
+            font.clone(),
+            ui.visuals().text_color(),
+        );
+        painter.text(
+            egui::pos2(left + slot * 0.5, rect.bottom()),
+            Align2::CENTER_BOTTOM,
+            *label,
+            font.clone(),
+            ui.visuals().weak_text_color(),
+        );
+    }
+}
+
+fn markdown(header: &str, counts: &Counts, sizes: &Sizes) -> String {
+    let mut text = format!("## Snapshot statistics of {header}\n\n| | |\n|---|---|\n");
+    for (label, value) in counts.rows() {
+        writeln!(text, "| {label} | {value} |").ok();
+    }
+    writeln!(text, "| Total size | {} |", format_bytes(sizes.total)).ok();
+
+    writeln!(text, "\nChanged snapshots by number of changed pixels:\n").ok();
+    for (label, count) in BINS.iter().zip(&counts.distribution) {
+        writeln!(text, "- {label}: {count}").ok();
+    }
+    text
+}
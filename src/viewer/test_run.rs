@@ -0,0 +1,223 @@
+//! Runs the snapshot tests of a local folder and reloads it once they are done, so editing,
+//! testing and reviewing happen in one window.
+
+use crate::DiffSource;
+use crate::notifications::Notification;
+use crate::state::{SystemCommand, ViewerAppStateRef};
+use anyhow::Context as _;
+use eframe::egui::mutex::Mutex;
+use eframe::egui::{self, Context, Id, RichText, ScrollArea, TextStyle, Ui};
+use egui_inbox::UiInboxSender;
+use std::collections::VecDeque;
+use std::io::{BufRead as _, BufReader, Read};
+use std::path::Path;
+use std::process::{Child, Command, ExitStatus, Stdio};
+use std::sync::Arc;
+use std::time::Duration;
+
+/// Only the most recent output lines are kept.
+const MAX_LINES: usize = 10_000;
+
+struct TestRun {
+    command: String,
+    child: Mutex<Child>,
+    output: Mutex<VecDeque<String>>,
+
+    /// Set once the process exited.
+    status: Mutex<Option<std::io::Result<ExitStatus>>>,
+}
+
+impl TestRun {
+    fn running(&self) -> bool {
+        self.status.lock().is_none()
+    }
+
+    fn push(&self, line: String) {
+        let mut output = self.output.lock();
+        if output.len() == MAX_LINES {
+            output.pop_front();
+        }
+        output.push_back(line);
+    }
+}
+
+fn id() -> Id {
+    Id::new("test_run")
+}
+
+/// The folder the tests of a source run in, for sources on this machine.
+fn directory(source: &DiffSource) -> Option<&Path> {
+    match source {
+        DiffSource::Files(path) | DiffSource::Git(path) => Some(path),
+        DiffSource::Pinned { live, .. } => directory(live),
+        _ => None,
+    }
+}
+
+/// `cargo test` for the crate or workspace the folder is in.
+fn default_command(directory: &Path) -> String {
+    let directory = directory
+        .canonicalize()
+        .unwrap_or_else(|_| directory.to_path_buf());
+    let manifest = directory.ancestors().find_map(|dir| {
+        let text = std::fs::read_to_string(dir.join("Cargo.toml")).ok()?;
+        toml::from_str::<toml::Table>(&text).ok()
+    });
+    let package = manifest
+        .as_ref()
+        .filter(|manifest| !manifest.contains_key("workspace"))
+        .and_then(|manifest| manifest.get("package")?.get("name")?.as_str());
+    match package {
+        Some(name) => format!("cargo test -p {name}"),
+        None => "cargo test".to_owned(),
+    }
+}
+
+/// Starts the tests, unless the source isn't a local folder.
+pub fn run_button(ui: &mut Ui, state: &ViewerAppStateRef<'_>) {
+    let Some(directory) = directory(&state.source) else {
+        return;
+    };
+    let running = ui
+        .data(|d| d.get_temp::<Arc<TestRun>>(id()))
+        .is_some_and(|run| run.running());
+    let configured = state.app.source_config().test_command.clone();
+    let hover = match &configured {
+        Some(command) => format!("Run `{command}` and reload the snapshots when it's done"),
+        None => "Run the tests of this crate and reload the snapshots when they're done. \
+            Set `test_command` in kitdiff.toml to run something else"
+            .to_owned(),
+    };
+    if ui
+        .add_enabled(!running, egui::Button::new("▶").small())
+        .on_hover_text(hover)
+        .clicked()
+    {
+        let command = configured.unwrap_or_else(|| default_command(directory));
+        if let Err(err) = start(ui.ctx(), command, directory, state.app.tx.clone()) {
+            state
+                .app
+                .send(Notification::error("Failed to run the tests", err));
+        }
+    }
+}
+
+fn start(
+    ctx: &Context,
+    command: String,
+    directory: &Path,
+    tx: UiInboxSender<SystemCommand>,
+) -> anyhow::Result<()> {
+    let mut args = command.split_whitespace();
+    let program = args.next().context("The test command is empty")?;
+    let mut child = Command::new(program)
+        .args(args)
+        .current_dir(directory)
+        .stdin(Stdio::null())
+        .stdout(Stdio::piped())
+        .stderr(Stdio::piped())
+        .spawn()
+        .with_context(|| format!("Failed to run {command}"))?;
+    let pipes: Vec<Box<dyn Read + Send>> = [
+        child
+            .stdout
+            .take()
+            .map(|pipe| Box::new(pipe) as Box<dyn Read + Send>),
+        child
+            .stderr
+            .take()
+            .map(|pipe| Box::new(pipe) as Box<dyn Read + Send>),
+    ]
+    .into_iter()
+    .flatten()
+    .collect();
+
+    let run = Arc::new(TestRun {
+        command,
+        child: Mutex::new(child),
+        output: Mutex::default(),
+        status: Mutex::new(None),
+    });
+    ctx.data_mut(|d| d.insert_temp(id(), run.clone()));
+
+    let ctx = ctx.clone();
+    std::thread::Builder::new()
+        .name("test run".to_owned())
+        .spawn(move || {
+            std::thread::scope(|scope| {
+                for pipe in pipes {
+                    let (run, ctx) = (&run, &ctx);
+                    scope.spawn(move || {
+                        for line in BufReader::new(pipe).lines().map_while(Result::ok) {
+                            run.push(line);
+                            ctx.request_repaint();
+                        }
+                    });
+                }
+            });
+
+            // Polled instead of `wait`, so the lock is free for the Stop button.
+            let status = loop {
+                match run.child.lock().try_wait() {
+                    Ok(Some(status)) => break Ok(status),
+                    Ok(None) => {}
+                    Err(err) => break Err(err),
+                }
+                std::thread::sleep(Duration::from_millis(100));
+            };
+            *run.status.lock() = Some(status);
+            tx.send(SystemCommand::Refresh).ok();
+            ctx.request_repaint();
+        })?;
+    Ok(())
+}
+
+/// The output of the last run, until it's closed.
+pub fn output_panel(ui: &mut Ui) {
+    let Some(run) = ui.data(|d| d.get_temp::<Arc<TestRun>>(id())) else {
+        return;
+    };
+    egui::Panel::bottom("test_output")
+        .resizable(true)
+        .show_inside(ui, |ui| {
+            ui.horizontal(|ui| {
+                ui.strong(&run.command);
+                match &*run.status.lock() {
+                    None => {
+                        ui.spinner();
+                        if ui.small_button("Stop").clicked() {
+                            run.child.lock().kill().ok();
+                        }
+                    }
+                    Some(Ok(status)) if status.success() => {
+                        ui.label("✔ Passed");
+                    }
+                    Some(Ok(status)) => {
+                        ui.colored_label(ui.visuals().error_fg_color, format!("✖ {status}"));
+                    }
+                    Some(Err(err)) => {
+                        ui.colored_label(ui.visuals().error_fg_color, err.to_string());
+                    }
+                }
+                if ui.small_button("Copy").clicked() {
+                    let output = run.output.lock();
+                    ui.ctx()
+                        .copy_text(output.iter().cloned().collect::<Vec<_>>().join("\n"));
+                }
+                if !run.running() && ui.small_button("Close").clicked() {
+                    ui.data_mut(|d| d.remove::<Arc<TestRun>>(id()));
+                }
+            });
+
+            let output = run.output.lock();
+            let row_height = ui.text_style_height(&TextStyle::Monospace);
+            ScrollArea::vertical()
+                .auto_shrink(false)
+                .stick_to_bottom(true)
+                .show_rows(ui, row_height, output.len(), |ui, rows| {
+                    for line in output.range(rows) {
+                        ui.label(RichText::new(line).monospace());
+                    }
+                });
+        });
+}
@@ -0,0 +1,287 @@
+//! How many snapshots a diff threshold would report as changed, over the whole range of the
+//! threshold slider. Helps to pick a threshold for a project instead of guessing one.
+
+use crate::diff_image_loader::DiffOptions;
+use crate::loaders::CancelFlag;
+use crate::snapshot::Snapshot;
+use crate::state::ViewerAppStateRef;
+use eframe::egui::mutex::Mutex;
+use eframe::egui::{
+    Align2, Button, Context, FontId, Id, Pos2, Sense, Stroke, StrokeKind, Ui, vec2,
+};
+use futures::StreamExt as _;
+use kitdiff_core::diff::{self, Algorithm};
+use std::ops::RangeInclusive;
+use std::sync::Arc;
+use std::sync::atomic::{AtomicUsize, Ordering};
+
+/// The range of the threshold slider.
+pub const THRESHOLD_RANGE: RangeInclusive<f32> = 0.01..=1000.0;
+
+/// Thresholds the snapshots are checked at, evenly spaced on the logarithmic slider.
+const SAMPLES: usize = 25;
+
+/// How many snapshots are read and diffed at once.
+const CONCURRENT_DIFFS: usize = 8;
+
+/// The options a sweep was made with, it's outdated once they change.
+#[derive(Clone, PartialEq)]
+struct Key {
+    files_header: String,
+    algorithm: Algorithm,
+    detect_aa_pixels: bool,
+}
+
+struct Sweep {
+    key: Key,
+    thresholds: Vec<f32>,
+    checked: AtomicUsize,
+    total: usize,
+
+    /// Per entry of `thresholds`, how many of the checked snapshots still differ at it.
+    differing: Mutex<Vec<usize>>,
+    cancel: CancelFlag,
+}
+
+fn id() -> Id {
+    Id::new("threshold_sweep")
+}
+
+fn thresholds() -> Vec<f32> {
+    let start = THRESHOLD_RANGE.start().log10();
+    let end = THRESHOLD_RANGE.end().log10();
+    (0..SAMPLES)
+        .map(|index| 10_f32.powf(start + (end - start) * index as f32 / (SAMPLES - 1) as f32))
+        .collect()
+}
+
+/// A button to start the preview, then a plot of the number of changed snapshots per threshold.
+/// Clicking the plot picks that threshold.
+pub fn threshold_preview(ui: &mut Ui, state: &ViewerAppStateRef<'_>, options: &mut DiffOptions) {
+    let key = Key {
+        files_header: state.loader.files_header(),
+        algorithm: options.algorithm,
+        detect_aa_pixels: options.detect_aa_pixels,
+    };
+    let sweep = ui
+        .data(|d| d.get_temp::<Arc<Sweep>>(id()))
+        .filter(|sweep| sweep.key == key);
+
+    let Some(sweep) = sweep else {
+        let ready = state.loader.state().is_ready();
+        if ui
+            .add_enabled(ready, Button::new("Preview thresholds"))
+            .on_hover_text("Count how many snapshots every threshold would report as changed")
+            .clicked()
+        {
+            start(ui, state, key, *options);
+        }
+        return;
+    };
+
+    let differing = sweep.differing.lock().clone();
+    let checked = sweep.checked.load(Ordering::Relaxed);
+    if let Some(threshold) = plot(ui, &sweep.thresholds, &differing, sweep.total, options) {
+        options.threshold = threshold;
+    }
+
+    ui.horizontal(|ui| {
+        let nearest = nearest_sample(&sweep.thresholds, options.threshold);
+        ui.label(format!(
+            "{} of {} snapshots differ at ≈{:.2}",
+            differing[nearest], sweep.total, sweep.thresholds[nearest],
+        ));
+        if checked < sweep.total {
+            ui.weak(format!("({checked} checked)"));
+        }
+        if ui.small_button("Close").clicked() {
+            sweep.cancel.cancel();
+            ui.data_mut(|d| d.remove::<Arc<Sweep>>(id()));
+        }
+    });
+}
+
+fn start(ui: &Ui, state: &ViewerAppStateRef<'_>, key: Key, options: DiffOptions) {
+    if let Some(previous) = ui.data(|d| d.get_temp::<Arc<Sweep>>(id())) {
+        previous.cancel.cancel();
+    }
+
+    // Identical files don't differ at any threshold, there's no need to diff them.
+    let compared: Vec<&Snapshot> = state
+        .loader
+        .snapshots()
+        .iter()
+        .filter(|snapshot| snapshot.old.is_some() && snapshot.new.is_some())
+        .collect();
+    let snapshots: Vec<Snapshot> = compared
+        .iter()
+        .filter(|snapshot| !snapshot.unchanged)
+        .map(|snapshot| (*snapshot).clone())
+        .collect();
+    let thresholds = thresholds();
+    let sweep = Arc::new(Sweep {
+        key,
+        differing: Mutex::new(vec![0; thresholds.len()]),
+        thresholds,
+        checked: AtomicUsize::new(compared.len() - snapshots.len()),
+        total: compared.len(),
+        cancel: CancelFlag::default(),
+    });
+    ui.data_mut(|d| d.insert_temp(id(), sweep.clone()));
+
+    let ctx = ui.ctx().clone();
+    hello_egui_utils::spawn(async move {
+        run(snapshots, options, &sweep, &ctx).await;
+    });
+}
+
+async fn run(snapshots: Vec<Snapshot>, options: DiffOptions, sweep: &Arc<Sweep>, ctx: &Context) {
+    futures::stream::iter(snapshots)
+        .take_while(|_| std::future::ready(!sweep.cancel.is_cancelled()))
+        .map(|snapshot| async move {
+            let result = differing_thresholds(&snapshot, options, sweep.clone()).await;
+            (snapshot, result)
+        })
+        .buffer_unordered(CONCURRENT_DIFFS)
+        .for_each(|(snapshot, result)| async move {
+            match result {
+                Ok(count) => {
+                    let mut differing = sweep.differing.lock();
+                    for entry in &mut differing[..count] {
+                        *entry += 1;
+                    }
+                }
+                Err(err) => {
+                    tracing::warn!(
+                        "Skipping {} in the preview: {err:#}",
+                        snapshot.path.display()
+                    );
+                }
+            }
+            sweep.checked.fetch_add(1, Ordering::Relaxed);
+            ctx.request_repaint();
+        })
+        .await;
+}
+
+/// How many of the sweep's thresholds still find the snapshot changed.
+async fn differing_thresholds(
+    snapshot: &Snapshot,
+    options: DiffOptions,
+    sweep: Arc<Sweep>,
+) -> anyhow::Result<usize> {
+    let (Some(old), Some(new)) = (&snapshot.old, &snapshot.new) else {
+        return Ok(0);
+    };
+    let (Some(old), Some(new)) = (old.read().await?, new.read().await?) else {
+        anyhow::bail!("The images can't be read");
+    };
+
+    #[cfg(not(target_arch = "wasm32"))]
+    let count = tokio::task::spawn_blocking(move || {
+        count_differing(&old, &new, &options, &sweep.thresholds)
+    })
+    .await??;
+    #[cfg(target_arch = "wasm32")]
+    let count = count_differing(&old, &new, &options, &sweep.thresholds)?;
+    Ok(count)
+}
+
+fn count_differing(
+    old: &[u8],
+    new: &[u8],
+    options: &DiffOptions,
+    thresholds: &[f32],
+) -> anyhow::Result<usize> {
+    let old = image::load_from_memory(old)?.to_rgba8();
+    let new = image::load_from_memory(new)?.to_rgba8();
+    if old.dimensions() != new.dimensions() {
+        // A resized snapshot is changed whatever the threshold.
+        return Ok(thresholds.len());
+    }
+    diff::differing_thresholds(&old, &new, options, thresholds)
+}
+
+/// The sample closest to `threshold` on the logarithmic scale.
+fn nearest_sample(thresholds: &[f32], threshold: f32) -> usize {
+    let target = threshold.log10();
+    thresholds
+        .iter()
+        .enumerate()
+        .min_by(|(_, a), (_, b)| {
+            (a.log10() - target)
+                .abs()
+                .total_cmp(&(b.log10() - target).abs())
+        })
+        .map_or(0, |(index, _)| index)
+}
+
+/// The curve on a logarithmic threshold axis, with the current threshold marked. Returns the
+/// threshold that was clicked or dragged to.
+fn plot(
+    ui: &mut Ui,
+    thresholds: &[f32],
+    differing: &[usize],
+    total: usize,
+    options: &DiffOptions,
+) -> Option<f32> {
+    let (rect, response) =
+        ui.allocate_exact_size(vec2(ui.available_width(), 100.0), Sense::click_and_drag());
+    let painter = ui.painter_at(rect);
+    painter.rect_stroke(
+        rect,
+        2.0,
+        ui.visuals().widgets.noninteractive.bg_stroke,
+        StrokeKind::Inside,
+    );
+
+    let start = THRESHOLD_RANGE.start().log10();
+    let end = THRESHOLD_RANGE.end().log10();
+    let plot_rect = rect.shrink(2.0);
+    let x = |threshold: f32| {
+        plot_rect.left() + plot_rect.width() * (threshold.log10() - start) / (end - start)
+    };
+    let y =
+        |count: usize| plot_rect.bottom() - plot_rect.height() * count as f32 / total.max(1) as f32;
+
+    let points: Vec<Pos2> = thresholds
+        .iter()
+        .zip(differing)
+        .map(|(threshold, count)| Pos2::new(x(*threshold), y(*count)))
+        .collect();
+    painter.line(points, Stroke::new(1.5, ui.visuals().selection.bg_fill));
+    painter.vline(
+        x(options.threshold),
+        plot_rect.y_range(),
+        Stroke::new(1.0, ui.visuals().warn_fg_color),
+    );
+
+    let font = FontId::proportional(11.0);
+    let weak = ui.visuals().weak_text_color();
+    painter.text(
+        plot_rect.left_top(),
+        Align2::LEFT_TOP,
+        total.to_string(),
+        font.clone(),
+        weak,
+    );
+    for (pos, align, threshold) in [
+        (
+            plot_rect.left_bottom(),
+            Align2::LEFT_BOTTOM,
+            *THRESHOLD_RANGE.start(),
+        ),
+        (
+            plot_rect.right_bottom(),
+            Align2::RIGHT_BOTTOM,
+            *THRESHOLD_RANGE.end(),
+        ),
+    ] {
+        painter.text(pos, align, threshold.to_string(), font.clone(), weak);
+    }
+
+    // Only set while the plot is clicked or dragged.
+    let pointer = response.interact_pointer_pos()?;
+    let fraction = ((pointer.x - plot_rect.left()) / plot_rect.width()).clamp(0.0, 1.0);
+    Some(10_f32.powf(start + (end - start) * fraction))
+}
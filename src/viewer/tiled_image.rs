@@ -0,0 +1,98 @@
+use crate::settings::ImageMode;
+use crate::thumbnail_loader::thumbnail_uri;
+use crate::tile_loader::{TILE_SIZE, tile_uri};
+use eframe::egui::load::{SizedTexture, TexturePoll};
+use eframe::egui::{Color32, Pos2, Rect, SizeHint, TextureOptions, Ui, pos2, vec2};
+
+/// Images with a side longer than this are drawn in tiles.
+const MAX_SINGLE_TEXTURE_SIDE: usize = 4096;
+
+const UV: Rect = Rect::from_min_max(Pos2::ZERO, pos2(1.0, 1.0));
+
+pub fn needs_tiling(ui: &Ui, size: [usize; 2]) -> bool {
+    let limit = ui
+        .input(|i| i.max_texture_side)
+        .min(MAX_SINGLE_TEXTURE_SIDE);
+    size[0] > limit || size[1] > limit
+}
+
+/// Paint a large image in `rect`, only loading the tiles that are visible.
+///
+/// A low resolution thumbnail is drawn first, so something shows up immediately and gets refined
+/// as the tiles come in. Returns where the whole image is shown.
+pub fn paint_tiled(
+    ui: &Ui,
+    rect: Rect,
+    uri: &str,
+    image_size: [usize; 2],
+    tint: Color32,
+    mode: &ImageMode,
+    texture_options: TextureOptions,
+) -> Rect {
+    let size = vec2(image_size[0] as f32, image_size[1] as f32);
+    let target = display_rect(ui, rect, image_size, mode);
+    let painter = ui.painter_at(rect);
+
+    paint_preview(ui, rect, target, uri, tint, texture_options);
+
+    let scale = target.width() / size.x;
+    let visible = painter.clip_rect().intersect(target);
+    let tiles_x = image_size[0].div_ceil(TILE_SIZE);
+    let tiles_y = image_size[1].div_ceil(TILE_SIZE);
+
+    for y in 0..tiles_y {
+        for x in 0..tiles_x {
+            let min = vec2((x * TILE_SIZE) as f32, (y * TILE_SIZE) as f32);
+            let max = vec2(
+                ((x + 1) * TILE_SIZE).min(image_size[0]) as f32,
+                ((y + 1) * TILE_SIZE).min(image_size[1]) as f32,
+            );
+            let tile_rect = Rect::from_min_max(target.min + min * scale, target.min + max * scale);
+            if !visible.intersects(tile_rect) {
+                continue;
+            }
+            if let Some(tile) = load_texture(ui, &tile_uri(uri, x, y), texture_options) {
+                painter.image(tile.id, tile_rect, UV, tint);
+            }
+        }
+    }
+
+    target
+}
+
+/// Where an image of `image_size` ends up in `rect`, like [`eframe::egui::Image`] places it.
+pub fn display_rect(ui: &Ui, rect: Rect, image_size: [usize; 2], mode: &ImageMode) -> Rect {
+    let size = vec2(image_size[0] as f32, image_size[1] as f32);
+    let display_size = match mode {
+        ImageMode::Pixel => size / ui.pixels_per_point(),
+        ImageMode::Fit => size * (rect.width() / size.x).min(rect.height() / size.y),
+    };
+    Rect::from_min_size(rect.min, display_size)
+}
+
+/// Paint the thumbnail of the image at `uri` scaled up to `target`, clipped to `rect`. Returns
+/// whether the thumbnail was loaded yet.
+pub fn paint_preview(
+    ui: &Ui,
+    rect: Rect,
+    target: Rect,
+    uri: &str,
+    tint: Color32,
+    texture_options: TextureOptions,
+) -> bool {
+    let preview = load_texture(ui, &thumbnail_uri(uri), texture_options);
+    if let Some(preview) = &preview {
+        ui.painter_at(rect).image(preview.id, target, UV, tint);
+    }
+    preview.is_some()
+}
+
+fn load_texture(ui: &Ui, uri: &str, texture_options: TextureOptions) -> Option<SizedTexture> {
+    match ui
+        .ctx()
+        .try_load_texture(uri, texture_options, SizeHint::default())
+    {
+        Ok(TexturePoll::Ready { texture }) => Some(texture),
+        _ => None,
+    }
+}
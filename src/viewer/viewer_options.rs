@@ -1,6 +1,12 @@
+use crate::loaders::download::format_bytes;
+use crate::path_filter::PathFilterSettings;
+use crate::settings::{ImageMode, PrefetchSettings};
+use crate::state::View;
 use crate::state::{SystemCommand, ViewerAppStateRef, ViewerSystemCommand};
-use crate::{settings::ImageMode, state::View};
+use crate::viewer::threshold_tuning::{THRESHOLD_RANGE, threshold_preview};
+use crate::viewer::{background, export_view};
 use eframe::egui::{self, Slider, TextureFilter, Ui};
+use kitdiff_core::diff::{Algorithm, DiffPalette};
 
 pub fn viewer_options(ui: &mut Ui, state: &ViewerAppStateRef<'_>) {
     let mut settings = state.app.settings.clone();
@@ -29,8 +35,20 @@ pub fn viewer_options(ui: &mut Ui, state: &ViewerAppStateRef<'_>) {
         if new_view != state.view {
             state.app.send(ViewerSystemCommand::SetView(new_view));
         }
+
+        if let Some(snapshot) = state.active_snapshot {
+            export_view::export_menu(ui, state, snapshot);
+        }
     });
 
+    if state.view == View::Flip {
+        ui.add(
+            Slider::new(&mut settings.flip_interval, 0.1..=2.0)
+                .suffix(" s")
+                .text("Flip interval"),
+        );
+    }
+
     ui.add_enabled_ui(state.view == View::BlendAll, |ui| {
         ui.add(Slider::new(&mut settings.new_opacity, 0.0..=1.0).text("New Opacity"));
         ui.add(Slider::new(&mut settings.diff_opacity, 0.0..=1.0).text("Diff Opacity"));
@@ -54,6 +72,8 @@ pub fn viewer_options(ui: &mut Ui, state: &ViewerAppStateRef<'_>) {
         ui.selectable_value(&mut settings.mode, ImageMode::Fit, "Fit");
     });
 
+    background::background_ui(ui, &mut settings.background);
+
     ui.horizontal_wrapped(|ui| {
         ui.label("Filtering:");
         ui.selectable_value(
@@ -70,21 +90,100 @@ pub fn viewer_options(ui: &mut Ui, state: &ViewerAppStateRef<'_>) {
 
     ui.group(|ui| {
         ui.heading("Diff Options");
+        profile_ui(ui, state);
         ui.checkbox(
             &mut settings.use_original_diff,
             "Use original diff if available",
         );
 
         ui.add_enabled_ui(!settings.use_original_diff, |ui| {
+            let algorithm = settings.options.algorithm;
+            algorithm_ui(ui, &mut settings.options.algorithm);
+            if settings.options.algorithm != algorithm {
+                settings.options.threshold = settings.options.algorithm.default_threshold();
+            }
+            let hint = settings
+                .options
+                .algorithm
+                .get()
+                .map(|algorithm| algorithm.threshold_hint().to_owned())
+                .unwrap_or_default();
             ui.add(
-                Slider::new(&mut settings.options.threshold, 0.01..=1000.0)
+                Slider::new(&mut settings.options.threshold, THRESHOLD_RANGE)
                     .logarithmic(true)
                     .text("Diff Threshold"),
+            )
+            .on_hover_text(hint);
+            threshold_preview(ui, state, &mut settings.options);
+            let detects_aa = matches!(
+                settings.options.algorithm,
+                Algorithm::Pixelmatch | Algorithm::PixelmatchJs
+            );
+            ui.add_enabled_ui(detects_aa, |ui| {
+                ui.checkbox(&mut settings.options.detect_aa_pixels, "Detect AA Pixels");
+            });
+            ui.add(
+                Slider::new(&mut settings.options.min_cluster_size, 0..=25)
+                    .text("Min. Cluster Size"),
+            )
+            .on_hover_text(
+                "Ignore changed pixels in smaller connected clusters, e.g. speckles from \
+                nondeterministic GPU rendering",
             );
-            ui.checkbox(&mut settings.options.detect_aa_pixels, "Detect AA Pixels");
         });
     });
 
+    ui.group(|ui| {
+        ui.heading("Accessibility");
+        egui::ComboBox::from_label("Diff colors")
+            .selected_text(settings.options.palette.name())
+            .show_ui(ui, |ui| {
+                for palette in DiffPalette::ALL {
+                    ui.selectable_value(&mut settings.options.palette, palette, palette.name());
+                }
+            })
+            .response
+            .on_hover_text("Original diff images are only used with the red / yellow colors");
+        ui.checkbox(&mut settings.high_contrast, "High contrast theme");
+    });
+
+    ui.group(|ui| {
+        ui.heading("Discovery");
+        path_filter_ui(ui, &mut settings.path_filter);
+    });
+
+    #[cfg(not(target_arch = "wasm32"))]
+    ui.group(|ui| {
+        ui.heading("External tools");
+        ui.label("Diff tool");
+        egui::TextEdit::singleline(&mut settings.external_diff_tool)
+            .hint_text("code --diff {old} {new}")
+            .show(ui);
+    });
+
+    ui.group(|ui| {
+        ui.heading("Memory");
+        ui.add(
+            Slider::new(&mut settings.memory_budget_mb, 256..=16_384)
+                .logarithmic(true)
+                .suffix(" MB")
+                .text("Memory budget"),
+        );
+        let usage = state.memory.usage();
+        ui.label(format!(
+            "Using {} (files {}, images {}, textures {})",
+            format_bytes(usage.total() as u64),
+            format_bytes(usage.bytes as u64),
+            format_bytes(usage.images as u64),
+            format_bytes(usage.textures as u64),
+        ));
+    });
+
+    ui.group(|ui| {
+        ui.heading("Prefetch");
+        prefetch_ui(ui, state, &mut settings.prefetch);
+    });
+
     if settings != state.app.settings {
         state
             .app
@@ -93,3 +192,141 @@ pub fn viewer_options(ui: &mut Ui, state: &ViewerAppStateRef<'_>) {
             .ok();
     }
 }
+
+fn prefetch_ui(ui: &mut Ui, state: &ViewerAppStateRef<'_>, prefetch: &mut PrefetchSettings) {
+    ui.add(
+        Slider::new(&mut prefetch.window, 0..=50)
+            .text("Snapshots ahead")
+            .suffix(" each way"),
+    )
+    .on_hover_text("Load this many snapshots before and after the selected one ahead of time");
+    ui.add(Slider::new(&mut prefetch.max_in_flight, 1..=32).text("Loading at once"));
+    if cfg!(target_arch = "wasm32") {
+        ui.checkbox(&mut prefetch.on_web, "Prefetch in the browser");
+        ui.add_enabled(
+            prefetch.on_web,
+            egui::Checkbox::new(&mut prefetch.on_metered, "Also on metered connections"),
+        )
+        .on_hover_text("When the browser reports a cellular connection or data saver");
+    }
+
+    ui.collapsing("Debug", |ui| {
+        let stats = state.prefetch.stats();
+        egui::Grid::new("prefetch_stats").show(ui, |ui| {
+            ui.label("Cache hits");
+            ui.label(stats.hits.to_string());
+            ui.end_row();
+            ui.label("Cache misses");
+            ui.label(stats.misses.to_string());
+            ui.end_row();
+            ui.label("Hit rate");
+            ui.label(
+                stats
+                    .hit_rate()
+                    .map_or_else(|| "-".to_owned(), |rate| format!("{:.0}%", rate * 100.0)),
+            );
+            ui.end_row();
+            ui.label("Images prefetched");
+            ui.label(stats.prefetched.to_string());
+            ui.end_row();
+            ui.label("Loading now");
+            ui.label(state.prefetch.in_flight().to_string());
+            ui.end_row();
+        });
+    });
+}
+
+fn algorithm_ui(ui: &mut Ui, selected: &mut Algorithm) {
+    egui::ComboBox::from_label("Algorithm")
+        .selected_text(selected.name())
+        .show_ui(ui, |ui| {
+            for algorithm in Algorithm::all() {
+                ui.selectable_value(selected, algorithm, algorithm.name());
+            }
+        });
+}
+
+fn profile_ui(ui: &mut Ui, state: &ViewerAppStateRef<'_>) {
+    let active = state.app.active_profile.as_deref();
+
+    ui.horizontal(|ui| {
+        ui.label("Profile:");
+        egui::ComboBox::from_id_salt("profile")
+            .selected_text(active.unwrap_or("Defaults"))
+            .show_ui(ui, |ui| {
+                if ui.selectable_label(active.is_none(), "Defaults").clicked() {
+                    state.app.send(SystemCommand::SetProfile(None));
+                }
+                for name in state.app.profile_names() {
+                    if ui.selectable_label(active == Some(name), name).clicked() {
+                        state
+                            .app
+                            .send(SystemCommand::SetProfile(Some(name.to_owned())));
+                    }
+                }
+            });
+    });
+
+    let id = ui.id().with("new_profile_name");
+    let mut name: String = ui.data(|d| d.get_temp(id)).unwrap_or_default();
+    ui.horizontal(|ui| {
+        ui.add(
+            egui::TextEdit::singleline(&mut name)
+                .hint_text("Profile name")
+                .desired_width(120.0),
+        );
+        if ui
+            .add_enabled(
+                !name.trim().is_empty(),
+                egui::Button::new("Save as profile"),
+            )
+            .clicked()
+        {
+            state
+                .app
+                .send(SystemCommand::SaveProfile(name.trim().to_owned()));
+            name.clear();
+        }
+    });
+    ui.data_mut(|d| d.insert_temp(id, name));
+}
+
+/// Edits the globs as text, one per line. They are only applied on request, since every change
+/// restarts discovery.
+fn path_filter_ui(ui: &mut Ui, filter: &mut PathFilterSettings) {
+    let id = ui.id().with("path_filter_draft");
+    let (mut include, mut exclude): (String, String) = ui
+        .data(|d| d.get_temp(id))
+        .unwrap_or_else(|| (filter.include.join("\n"), filter.exclude.join("\n")));
+
+    ui.label("Include (one glob per line)");
+    egui::TextEdit::multiline(&mut include)
+        .hint_text("**/snapshots/**")
+        .desired_rows(2)
+        .show(ui);
+    ui.label("Exclude");
+    egui::TextEdit::multiline(&mut exclude)
+        .hint_text("target/**")
+        .desired_rows(2)
+        .show(ui);
+
+    let lines = |text: &str| -> Vec<String> {
+        text.lines()
+            .map(str::trim)
+            .filter(|line| !line.is_empty())
+            .map(ToOwned::to_owned)
+            .collect()
+    };
+    let draft = PathFilterSettings {
+        include: lines(&include),
+        exclude: lines(&exclude),
+    };
+    if ui
+        .add_enabled(draft != *filter, egui::Button::new("Apply"))
+        .clicked()
+    {
+        *filter = draft;
+    }
+
+    ui.data_mut(|d| d.insert_temp(id, (include, exclude)));
+}
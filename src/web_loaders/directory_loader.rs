@@ -0,0 +1,237 @@
+use crate::loaders::{CancelFlag, LoadSnapshots, Phase, Progress, sort_snapshots};
+use crate::path_filter::PathFilter;
+use crate::snapshot::Snapshot;
+use crate::web_loaders::{call_method, js_error};
+use anyhow::{Context as _, Error, Result};
+use eframe::egui::Context;
+use egui_inbox::UiInbox;
+use js_sys::{Function, Promise, Reflect, Uint8Array};
+use kitdiff_core::archive::Files;
+use std::collections::HashMap;
+use std::path::{Path, PathBuf};
+use std::task::Poll;
+use wasm_bindgen::JsCast as _;
+use wasm_bindgen_futures::JsFuture;
+use web_sys::{
+    DomException, File, FileSystemDirectoryHandle, FileSystemFileHandle, FileSystemHandle,
+    FileSystemHandleKind,
+};
+
+/// Folders that never contain snapshots worth walking into.
+const SKIPPED_DIRECTORIES: &[&str] = &["target", "node_modules"];
+
+/// Whether the browser supports picking local folders (currently only Chromium based ones).
+pub fn is_supported() -> bool {
+    web_sys::window().is_some_and(|window| {
+        Reflect::get(&window, &"showDirectoryPicker".into())
+            .is_ok_and(|picker| picker.is_function())
+    })
+}
+
+/// Ask the user for a folder. `None` if they closed the picker.
+pub async fn pick_directory() -> Result<Option<FileSystemDirectoryHandle>> {
+    let window = web_sys::window().context("No window")?;
+    let picker = Reflect::get(&window, &"showDirectoryPicker".into()).map_err(js_error)?;
+    let picker = picker
+        .dyn_ref::<Function>()
+        .context("This browser can't open local folders")?;
+    let promise = Promise::from(picker.call0(&window).map_err(js_error)?);
+    match JsFuture::from(promise).await {
+        Ok(handle) => Ok(Some(handle.unchecked_into())),
+        Err(err)
+            if err
+                .dyn_ref::<DomException>()
+                .is_some_and(|err| err.name() == "AbortError") =>
+        {
+            Ok(None)
+        }
+        Err(err) => Err(js_error(err)),
+    }
+}
+
+/// Like the native file loader, but for a folder picked with the File System Access API.
+pub struct DirectoryLoader {
+    handle: FileSystemDirectoryHandle,
+    filter: PathFilter,
+    inbox: UiInbox<Event>,
+    data: Poll<Result<Vec<Snapshot>>>,
+
+    /// Number of png files found so far.
+    found: usize,
+}
+
+enum Event {
+    Found(usize),
+    Done(Result<Vec<Snapshot>>),
+}
+
+impl DirectoryLoader {
+    pub fn new(handle: FileSystemDirectoryHandle, filter: PathFilter, cancel: CancelFlag) -> Self {
+        let mut inbox = UiInbox::new();
+        {
+            let handle = handle.clone();
+            let filter = filter.clone();
+            inbox.spawn(|tx| async move {
+                let result = async {
+                    let files = list_pngs(handle, &cancel, |found| {
+                        tx.send(Event::Found(found)).ok();
+                    })
+                    .await?;
+                    let files = read_variants(&files, &cancel).await?;
+
+                    let mut snapshots: Vec<Snapshot> =
+                        kitdiff_core::archive::snapshots_from_files(&files)
+                            .into_iter()
+                            .filter(|snapshot| filter.matches(&snapshot.path))
+                            .map(Snapshot::from)
+                            .collect();
+                    sort_snapshots(&mut snapshots);
+                    tracing::info!("Found {} snapshots", snapshots.len());
+                    Ok::<_, Error>(snapshots)
+                }
+                .await;
+                tx.send(Event::Done(result)).ok();
+            });
+        }
+
+        Self {
+            handle,
+            filter,
+            inbox,
+            data: Poll::Pending,
+            found: 0,
+        }
+    }
+}
+
+impl LoadSnapshots for DirectoryLoader {
+    fn update(&mut self, ctx: &Context) {
+        for event in self.inbox.read(ctx) {
+            match event {
+                Event::Found(found) => self.found = found,
+                Event::Done(result) => self.data = Poll::Ready(result),
+            }
+        }
+    }
+
+    fn refresh(&mut self, _client: octocrab::Octocrab, cancel: CancelFlag) {
+        *self = Self::new(self.handle.clone(), self.filter.clone(), cancel);
+    }
+
+    fn snapshots(&self) -> &[Snapshot] {
+        match &self.data {
+            Poll::Ready(Ok(snapshots)) => snapshots,
+            _ => &[],
+        }
+    }
+
+    fn state(&self) -> Poll<Result<(), &Error>> {
+        match &self.data {
+            Poll::Ready(Ok(_)) => Poll::Ready(Ok(())),
+            Poll::Ready(Err(e)) => Poll::Ready(Err(e)),
+            Poll::Pending => Poll::Pending,
+        }
+    }
+
+    fn progress(&self) -> Option<Progress> {
+        self.data.is_pending().then(|| {
+            Progress::new(Phase::Discovering).with_detail(format!("{} files found", self.found))
+        })
+    }
+
+    fn files_header(&self) -> String {
+        format!("Folder: {}", self.handle.name())
+    }
+}
+
+/// Every png file below `root`, by path relative to it.
+#[tracing::instrument(name = "discover", skip_all)]
+async fn list_pngs(
+    root: FileSystemDirectoryHandle,
+    cancel: &CancelFlag,
+    mut on_found: impl FnMut(usize),
+) -> Result<HashMap<PathBuf, FileSystemFileHandle>> {
+    let mut files = HashMap::new();
+    let mut pending = vec![(PathBuf::new(), root)];
+
+    while let Some((path, directory)) = pending.pop() {
+        let entries = call_method(&directory, "values")?;
+        loop {
+            anyhow::ensure!(!cancel.is_cancelled(), "Cancelled");
+            let next = JsFuture::from(Promise::from(call_method(&entries, "next")?))
+                .await
+                .map_err(js_error)?;
+            if Reflect::get(&next, &"done".into())
+                .map_err(js_error)?
+                .is_truthy()
+            {
+                break;
+            }
+            let handle: FileSystemHandle = Reflect::get(&next, &"value".into())
+                .map_err(js_error)?
+                .unchecked_into();
+            let name = handle.name();
+            let entry_path = path.join(&name);
+
+            match handle.kind() {
+                FileSystemHandleKind::Directory => {
+                    if !name.starts_with('.') && !SKIPPED_DIRECTORIES.contains(&name.as_str()) {
+                        pending.push((entry_path, handle.unchecked_into()));
+                    }
+                }
+                FileSystemHandleKind::File => {
+                    if entry_path.extension().is_some_and(|ext| ext == "png") {
+                        files.insert(entry_path, handle.unchecked_into());
+                        on_found(files.len());
+                    }
+                }
+                _ => {}
+            }
+        }
+    }
+
+    tracing::debug!("Found {} png files", files.len());
+    Ok(files)
+}
+
+/// Reads the files that are part of a snapshot with variants. Unchanged snapshots only have the
+/// base file, so most of a large folder is never read.
+async fn read_variants(
+    handles: &HashMap<PathBuf, FileSystemFileHandle>,
+    cancel: &CancelFlag,
+) -> Result<Files> {
+    let mut files = Files::new();
+
+    #[expect(clippy::iter_over_hash_type)]
+    for (path, handle) in handles {
+        if !has_variant(path, handles) {
+            continue;
+        }
+        anyhow::ensure!(!cancel.is_cancelled(), "Cancelled");
+        let file: File = JsFuture::from(handle.get_file())
+            .await
+            .map_err(js_error)?
+            .unchecked_into();
+        let buffer = JsFuture::from(file.array_buffer())
+            .await
+            .map_err(js_error)?;
+        files.insert(path.clone(), Uint8Array::new(&buffer).to_vec().into());
+    }
+    Ok(files)
+}
+
+/// Whether `path` is a variant, or the base file of one.
+fn has_variant(path: &Path, handles: &HashMap<PathBuf, FileSystemFileHandle>) -> bool {
+    let Some(stem) = path.file_stem().and_then(|stem| stem.to_str()) else {
+        return false;
+    };
+    if [".old", ".new", ".diff"]
+        .iter()
+        .any(|suffix| stem.ends_with(suffix))
+    {
+        return true;
+    }
+    ["old", "new"]
+        .iter()
+        .any(|variant| handles.contains_key(&path.with_file_name(format!("{stem}.{variant}.png"))))
+}
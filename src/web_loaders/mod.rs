@@ -0,0 +1,45 @@
+//! Loaders that only work in the browser.
+
+use js_sys::{Function, Reflect};
+use wasm_bindgen::{JsCast as _, JsValue};
+
+pub mod directory_loader;
+pub mod opfs_cache;
+
+/// Call a method web-sys doesn't have bindings for (yet), e.g. async iterators.
+fn call_method(target: &JsValue, name: &str) -> anyhow::Result<JsValue> {
+    let method: Function = Reflect::get(target, &name.into())
+        .map_err(js_error)?
+        .dyn_into()
+        .map_err(js_error)?;
+    method.call0(target).map_err(js_error)
+}
+
+pub(crate) fn js_error(err: JsValue) -> anyhow::Error {
+    anyhow::anyhow!("{err:?}")
+}
+
+/// Save `data` through the browser's downloads.
+pub(crate) fn download(name: &str, mime: &str, data: &[u8]) -> anyhow::Result<()> {
+    let parts = js_sys::Array::of1(&js_sys::Uint8Array::from(data));
+    let options = web_sys::BlobPropertyBag::new();
+    options.set_type(mime);
+    let blob = web_sys::Blob::new_with_u8_array_sequence_and_options(&parts, &options)
+        .map_err(js_error)?;
+    let url = web_sys::Url::create_object_url_with_blob(&blob).map_err(js_error)?;
+
+    let document = web_sys::window()
+        .and_then(|window| window.document())
+        .ok_or_else(|| anyhow::anyhow!("No document"))?;
+    let link: web_sys::HtmlAnchorElement = document
+        .create_element("a")
+        .map_err(js_error)?
+        .dyn_into()
+        .map_err(|element| js_error(element.into()))?;
+    link.set_href(&url);
+    link.set_download(name);
+    link.click();
+
+    web_sys::Url::revoke_object_url(&url).ok();
+    Ok(())
+}
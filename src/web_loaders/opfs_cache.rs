@@ -0,0 +1,254 @@
+//! Downloaded artifacts and archives, kept in the Origin Private File System.
+//!
+//! Reloading the page (or signing in again) would otherwise download hundreds of megabytes again.
+//! Failures are only logged, the cache is never required for loading to work.
+
+use crate::web_loaders::{call_method, js_error};
+use anyhow::Result;
+use bytes::Bytes;
+use js_sys::{Promise, Reflect, Uint8Array};
+use wasm_bindgen::JsCast as _;
+use wasm_bindgen_futures::JsFuture;
+use web_sys::{
+    File, FileSystemDirectoryHandle, FileSystemFileHandle, FileSystemGetDirectoryOptions,
+    FileSystemGetFileOptions, FileSystemWritableFileStream,
+};
+
+const DIRECTORY: &str = "downloads";
+
+/// Shown for a cached download, stored next to it as `{key}.json`.
+#[derive(Debug, Clone, serde::Serialize, serde::Deserialize)]
+pub struct CachedDownload {
+    #[serde(skip)]
+    pub key: String,
+    pub name: String,
+    #[serde(skip)]
+    pub size: f64,
+}
+
+/// Once the cache is larger than this, the least recently written downloads are removed.
+const MAX_BYTES: f64 = 2_000_000_000.0;
+
+/// The cache key of a GitHub artifact. Artifacts never change, so the id is enough.
+pub fn artifact_key(owner: &str, repo: &str, artifact_id: &str) -> String {
+    sanitize(&format!("artifact-{owner}-{repo}-{artifact_id}"))
+}
+
+/// The cache key of an archive url.
+pub fn url_key(url: &str) -> String {
+    // FNV-1a, so keys stay the same across releases.
+    let hash = url.bytes().fold(0xcbf2_9ce4_8422_2325_u64, |hash, byte| {
+        (hash ^ u64::from(byte)).wrapping_mul(0x0100_0000_01b3)
+    });
+    format!("url-{hash:016x}")
+}
+
+pub async fn get(key: &str) -> Option<Bytes> {
+    match read(key).await {
+        Ok(data) => {
+            if let Some(data) = &data {
+                tracing::info!(
+                    "Loaded {key} from the download cache ({} bytes)",
+                    data.len()
+                );
+            }
+            data
+        }
+        Err(err) => {
+            tracing::debug!("Failed to read {key} from the download cache: {err}");
+            None
+        }
+    }
+}
+
+/// `name` is what the download is listed as in [`list`].
+pub async fn put(key: &str, name: &str, data: &Bytes) {
+    let meta = CachedDownload {
+        key: key.to_owned(),
+        name: name.to_owned(),
+        size: 0.0,
+    };
+    let meta = serde_json::to_vec(&meta).unwrap_or_default();
+    let written = async {
+        write(&meta_name(key), &meta).await?;
+        write(key, data).await
+    };
+    if let Err(err) = written.await {
+        tracing::debug!("Failed to write {key} to the download cache: {err}");
+    }
+    if let Err(err) = evict().await {
+        tracing::debug!("Failed to clean up the download cache: {err}");
+    }
+}
+
+async fn directory() -> Result<FileSystemDirectoryHandle> {
+    let window = web_sys::window().ok_or_else(|| anyhow::anyhow!("No window"))?;
+    let root: FileSystemDirectoryHandle =
+        JsFuture::from(window.navigator().storage().get_directory())
+            .await
+            .map_err(js_error)?
+            .unchecked_into();
+    let options = FileSystemGetDirectoryOptions::new();
+    options.set_create(true);
+    Ok(
+        JsFuture::from(root.get_directory_handle_with_options(DIRECTORY, &options))
+            .await
+            .map_err(js_error)?
+            .unchecked_into(),
+    )
+}
+
+async fn read(key: &str) -> Result<Option<Bytes>> {
+    let directory = directory().await?;
+    // Missing entries reject with a `NotFoundError`.
+    let Ok(handle) = JsFuture::from(directory.get_file_handle(key)).await else {
+        return Ok(None);
+    };
+    let handle: FileSystemFileHandle = handle.unchecked_into();
+    let file: File = JsFuture::from(handle.get_file())
+        .await
+        .map_err(js_error)?
+        .unchecked_into();
+    let buffer = JsFuture::from(file.array_buffer())
+        .await
+        .map_err(js_error)?;
+    Ok(Some(Uint8Array::new(&buffer).to_vec().into()))
+}
+
+/// Everything in the cache, most recent first. Used to reopen sources offline.
+pub async fn list() -> Vec<CachedDownload> {
+    match entries().await {
+        Ok(mut entries) => {
+            entries.sort_by(|a, b| b.modified.total_cmp(&a.modified));
+            let mut downloads = Vec::new();
+            for entry in entries {
+                if entry.name.ends_with(".json") {
+                    continue;
+                }
+                let Ok(Some(meta)) = read(&meta_name(&entry.name)).await else {
+                    continue;
+                };
+                let Ok(meta) = serde_json::from_slice::<CachedDownload>(&meta) else {
+                    continue;
+                };
+                downloads.push(CachedDownload {
+                    key: entry.name,
+                    size: entry.size,
+                    ..meta
+                });
+            }
+            downloads
+        }
+        Err(err) => {
+            tracing::debug!("Failed to list the download cache: {err}");
+            Vec::new()
+        }
+    }
+}
+
+fn meta_name(key: &str) -> String {
+    format!("{key}.json")
+}
+
+async fn write(key: &str, data: &[u8]) -> Result<()> {
+    let directory = directory().await?;
+    let options = FileSystemGetFileOptions::new();
+    options.set_create(true);
+    let handle: FileSystemFileHandle =
+        JsFuture::from(directory.get_file_handle_with_options(key, &options))
+            .await
+            .map_err(js_error)?
+            .unchecked_into();
+    let stream: FileSystemWritableFileStream = JsFuture::from(handle.create_writable())
+        .await
+        .map_err(js_error)?
+        .unchecked_into();
+    JsFuture::from(
+        stream
+            .write_with_buffer_source(&Uint8Array::from(data))
+            .map_err(js_error)?,
+    )
+    .await
+    .map_err(js_error)?;
+    JsFuture::from(stream.close()).await.map_err(js_error)?;
+    Ok(())
+}
+
+struct Entry {
+    name: String,
+    size: f64,
+
+    /// Milliseconds since the epoch.
+    modified: f64,
+}
+
+async fn entries() -> Result<Vec<Entry>> {
+    let directory = directory().await?;
+    let entries = call_method(&directory, "values")?;
+
+    let mut files = Vec::new();
+    loop {
+        let next = JsFuture::from(Promise::from(call_method(&entries, "next")?))
+            .await
+            .map_err(js_error)?;
+        if Reflect::get(&next, &"done".into())
+            .map_err(js_error)?
+            .is_truthy()
+        {
+            break;
+        }
+        let Ok(handle) = Reflect::get(&next, &"value".into())
+            .map_err(js_error)?
+            .dyn_into::<FileSystemFileHandle>()
+        else {
+            continue;
+        };
+        let file: File = JsFuture::from(handle.get_file())
+            .await
+            .map_err(js_error)?
+            .unchecked_into();
+        files.push(Entry {
+            name: handle.name(),
+            size: file.size(),
+            modified: file.last_modified(),
+        });
+    }
+    Ok(files)
+}
+
+/// Remove the oldest downloads until the cache fits in [`MAX_BYTES`].
+async fn evict() -> Result<()> {
+    let directory = directory().await?;
+    let mut entries = entries().await?;
+
+    let mut total: f64 = entries.iter().map(|entry| entry.size).sum();
+    entries.sort_by(|a, b| a.modified.total_cmp(&b.modified));
+    for entry in entries {
+        if total <= MAX_BYTES {
+            break;
+        }
+        if entry.name.ends_with(".json") {
+            continue;
+        }
+        tracing::debug!("Removing {} from the download cache", entry.name);
+        for name in [meta_name(&entry.name), entry.name] {
+            JsFuture::from(directory.remove_entry(&name))
+                .await
+                .map_err(js_error)?;
+        }
+        total -= entry.size;
+    }
+    Ok(())
+}
+
+fn sanitize(key: &str) -> String {
+    key.chars()
+        .map(|c| {
+            if c.is_ascii_alphanumeric() || c == '-' {
+                c
+            } else {
+                '_'
+            }
+        })
+        .collect()
+}
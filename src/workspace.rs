@@ -0,0 +1,68 @@
+//! Named sets of sources that are reviewed together, e.g. a PR, the artifacts of each platform
+//! and a local checkout. Opening a workspace loads all of them merged into one list.
+
+use crate::DiffSource;
+use crate::session::SavedSource;
+
+#[derive(Clone, Debug, Default, PartialEq, serde::Serialize, serde::Deserialize)]
+#[serde(default)]
+pub struct Workspace {
+    /// The names prefix the snapshot paths of their source, like in
+    /// [`crate::loaders::merged_loader::MergedLoader`].
+    pub sources: Vec<(String, SavedSource)>,
+}
+
+impl Workspace {
+    /// Adds the parts of a merged source one by one, so a workspace can be opened and saved
+    /// under another name. `false` if the source can't be saved, e.g. a dropped archive.
+    pub fn add(&mut self, name: String, source: &DiffSource) -> bool {
+        if let DiffSource::Merged(parts) = source {
+            return parts.iter().fold(true, |added, (name, part)| {
+                self.add(name.clone(), part) && added
+            });
+        }
+        let Some(saved) = SavedSource::new(source) else {
+            return false;
+        };
+        if !self.sources.iter().any(|(_, other)| *other == saved) {
+            self.sources.push((unique_name(&self.sources, name), saved));
+        }
+        true
+    }
+
+    /// The sources that can still be opened, merged if there are several.
+    pub fn source(&self) -> Option<DiffSource> {
+        let mut parts: Vec<(String, DiffSource)> = self
+            .sources
+            .iter()
+            .filter_map(|(name, saved)| {
+                let source = saved.source();
+                if source.is_none() {
+                    tracing::warn!("Skipping {name}, it can't be opened anymore");
+                }
+                Some((name.clone(), source?))
+            })
+            .collect();
+        match parts.len() {
+            0 => None,
+            1 => parts.pop().map(|(_, source)| source),
+            _ => Some(DiffSource::Merged(parts)),
+        }
+    }
+}
+
+/// Two parts with one name would end up in the same directory of the merged list.
+fn unique_name(sources: &[(String, SavedSource)], name: String) -> String {
+    let taken = |candidate: &str| sources.iter().any(|(other, _)| other == candidate);
+    if !taken(&name) {
+        return name;
+    }
+    let mut number = 2;
+    loop {
+        let candidate = format!("{name} ({number})");
+        if !taken(&candidate) {
+            return candidate;
+        }
+        number += 1;
+    }
+}